@@ -0,0 +1,180 @@
+//! Validating the discriminants of `#[repr(C)]` enums which arrive from foreign code, before
+//! matching on them.
+//!
+//! An enum value received from C may have any discriminant at all, and it is undefined behavior
+//! even to hold a value of a Rust enum whose discriminant is not that of one of its variants, let
+//! alone to match on it. The [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html)
+//! derive, given `#[vesta(ffi)]`, implements [`FfiEnum`] for an enum with a `#[repr(C)]` or
+//! primitive representation, which can check the discriminant of a raw value before it is
+//! converted to the enum, using [`from_raw_validated`](FfiEnum::from_raw_validated).
+//!
+//! Alternatively, a raw value can be wrapped in an [`Unvalidated`], which is matched like the enum
+//! itself, except that its [`Range`](Match::Range) is [`Nonexhaustive`]: values whose discriminants
+//! are unknown have no tag, so they are handled by the `_` arm.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::mem::MaybeUninit;
+//! use vesta::{case, ffi::{FfiEnum, Unvalidated}, Match};
+//!
+//! #[derive(Match, Debug, PartialEq)]
+//! #[vesta(ffi)]
+//! #[repr(C)]
+//! enum Status {
+//!     Ok = 0,
+//!     Busy = 7,
+//!     Failed,
+//! }
+//!
+//! // A status as it might be written by C code, which has since added a new status
+//! fn from_c(discriminant: i32) -> MaybeUninit<Status> {
+//!     let mut raw = MaybeUninit::<Status>::uninit();
+//!     unsafe { raw.as_mut_ptr().cast::<i32>().write(discriminant) };
+//!     raw
+//! }
+//!
+//! assert_eq!(Status::validate_tag(8), Some(2));
+//! assert_eq!(unsafe { Status::from_raw_validated(from_c(7)) }, Some(Status::Busy));
+//! assert_eq!(unsafe { Status::from_raw_validated(from_c(9)) }, None);
+//!
+//! let retry = |raw| {
+//!     case!(unsafe { Unvalidated::new(raw) } {
+//!         1 => true,
+//!         _ => false,
+//!     })
+//! };
+//! assert!(retry(from_c(7)));
+//! assert!(!retry(from_c(9)));
+//! ```
+
+use std::{fmt, mem::MaybeUninit};
+
+use crate::{Case, CaseRef, Match, Nonexhaustive};
+
+/// An enum with a `#[repr(C)]` or primitive representation, whose discriminant can be checked in a
+/// raw value before it is converted to the enum, as described in the [module
+/// documentation](self). This is implemented by the
+/// [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html) derive when given
+/// `#[vesta(ffi)]`.
+///
+/// # Safety
+///
+/// The enum must have a `#[repr(C)]` or primitive representation, so that every value of it begins
+/// with its discriminant, of type [`Discriminant`](FfiEnum::Discriminant). The
+/// [`validate_tag`](FfiEnum::validate_tag) of a discriminant must be `Some(n)` exactly when it is
+/// the discriminant of a variant whose [`tag`](Match::tag) is `Some(n)`, and `None` otherwise.
+pub unsafe trait FfiEnum: Match + Sized {
+    /// The integer type of the discriminant at the start of every value of this enum.
+    type Discriminant: Copy + Eq + fmt::Debug;
+
+    /// The tag of the variant with this discriminant, if there is one.
+    fn validate_tag(discriminant: Self::Discriminant) -> Option<usize>;
+
+    /// Convert a raw value of this enum, which may have come from foreign code, to the enum, if its
+    /// discriminant is that of one of the enum's variants.
+    ///
+    /// # Safety
+    ///
+    /// The discriminant of the raw value must be initialized. If it is the discriminant of a
+    /// variant, the raw value must be a valid value of that variant.
+    unsafe fn from_raw_validated(raw: MaybeUninit<Self>) -> Option<Self> {
+        Unvalidated::new(raw).validate().ok()
+    }
+}
+
+/// A raw value of an [`FfiEnum`] whose discriminant has not been checked, matched like the enum
+/// itself, except that values whose discriminants are unknown have no tag, as described in the
+/// [module documentation](self).
+///
+/// Dropping an [`Unvalidated`] never drops the value it holds, since it may not be a valid value at
+/// all.
+pub struct Unvalidated<T>(MaybeUninit<T>);
+
+impl<T: FfiEnum> Unvalidated<T> {
+    /// Wrap a raw value of the enum, whose discriminant is to be checked when it is matched.
+    ///
+    /// # Safety
+    ///
+    /// The discriminant of the raw value must be initialized. If it is the discriminant of a
+    /// variant, the raw value must be a valid value of that variant.
+    pub unsafe fn new(raw: MaybeUninit<T>) -> Self {
+        Unvalidated(raw)
+    }
+
+    /// The discriminant of the raw value, whether or not it is that of any variant.
+    pub fn discriminant(&self) -> T::Discriminant {
+        // It is safe to read the discriminant because every value of an `FfiEnum` begins with it,
+        // `new` requires it to be initialized, and its alignment is no greater than the enum's
+        unsafe { self.0.as_ptr().cast::<T::Discriminant>().read() }
+    }
+
+    /// Convert the raw value to the enum, if its discriminant is that of one of its variants, or
+    /// otherwise return it unchanged.
+    pub fn validate(self) -> Result<T, Self> {
+        if T::validate_tag(self.discriminant()).is_some() {
+            // It is safe to assume the raw value is initialized because its discriminant is known,
+            // and `new` requires such a raw value to be valid
+            Ok(unsafe { self.0.assume_init() })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// The raw value itself.
+    pub fn into_raw(self) -> MaybeUninit<T> {
+        self.0
+    }
+}
+
+impl<T: Copy> Clone for Unvalidated<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Copy> Copy for Unvalidated<T> {}
+
+impl<T: FfiEnum> fmt::Debug for Unvalidated<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Unvalidated")
+            .field("discriminant", &self.discriminant())
+            .finish()
+    }
+}
+
+unsafe impl<T: FfiEnum> Match for Unvalidated<T> {
+    type Range = Nonexhaustive;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        T::validate_tag(self.discriminant())
+    }
+}
+
+impl<T: FfiEnum + Case<N>, const N: usize> Case<N> for Unvalidated<T> {
+    type Case = T::Case;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        // The tag is `N`, so the discriminant is known and the raw value is valid
+        Case::<N>::case(this.0.assume_init())
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        Unvalidated(MaybeUninit::new(Case::<N>::uncase(case)))
+    }
+}
+
+impl<T: FfiEnum + CaseRef<N>, const N: usize> CaseRef<N> for Unvalidated<T> {
+    type Ref<'a>
+        = T::Ref<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        CaseRef::<N>::case_ref(&*this.0.as_ptr())
+    }
+}
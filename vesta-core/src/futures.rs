@@ -0,0 +1,72 @@
+//! Implementations of [`Match`] and [`Case`] for the enums returned by the combinators of
+//! [`futures-util`](https://docs.rs/futures-util), so that their outcomes can be routed through
+//! [`case!`] without a wrapper enum for each of them.
+//!
+//! This module is only available when the `futures` feature is enabled. It covers:
+//!
+//! | Type                  | Cases                                                         |
+//! |-----------------------|---------------------------------------------------------------|
+//! | [`Either<A, B>`]      | 0: `Left`: `A`, 1: `Right`: `B`                               |
+//! | [`MaybeDone<Fut>`]    | 0: `Future`: `Fut`, 1: `Done`: `Fut::Output`, 2: `Gone`: `()` |
+//! | [`TryMaybeDone<Fut>`] | 0: `Future`: `Fut`, 1: `Done`: `Fut::Ok`, 2: `Gone`: `()`     |
+//! | [`PollNext`]          | 0: `Left`: `()`, 1: `Right`: `()`                             |
+//!
+//! The output of [`select`](futures_util::future::select) is an [`Either`] of the output of
+//! whichever future finished first, paired with the other future, so its two cases are the two ways
+//! the race can end. The outcomes of fallible futures and streams, such as the output of a
+//! [`TryFuture`] or the items of a [`TryStream`](futures_util::stream::TryStream), are [`Result`]s,
+//! and the items of any stream are [`Option`]s once taken, which are already matched like any other
+//! enum.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures_util::future::{ready, select, FutureExt};
+//! use vesta::case;
+//!
+//! let raced = select(ready(1), ready("late")).now_or_never().unwrap();
+//! let winner = case!(raced {
+//!     0((n, _late)) => format!("left won with {}", n),
+//!     1((s, _early)) => format!("right won with {}", s),
+//! });
+//! assert_eq!(winner, "left won with 1");
+//! ```
+//!
+//! [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+
+use futures_util::{
+    future::{Either, Future, MaybeDone, TryFuture, TryMaybeDone},
+    stream::PollNext,
+};
+
+use crate::{unreachable, Case, CaseMut, CaseRef, Exhaustive, Match, MatchMeta};
+
+impl_match! {
+    impl[A, B] Either<A, B> {
+        Left(left: A),
+        Right(right: B),
+    }
+}
+
+impl_match! {
+    impl[Fut: Future] MaybeDone<Fut> {
+        Future(future: Fut),
+        Done(output: <Fut as Future>::Output),
+        Gone,
+    }
+}
+
+impl_match! {
+    impl[Fut: TryFuture] TryMaybeDone<Fut> {
+        Future(future: Fut),
+        Done(output: <Fut as TryFuture>::Ok),
+        Gone,
+    }
+}
+
+impl_match! {
+    impl[] PollNext {
+        Left,
+        Right,
+    }
+}
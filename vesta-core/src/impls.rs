@@ -0,0 +1,539 @@
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    fmt::Alignment,
+    io::{ErrorKind, SeekFrom},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6},
+    num::FpCategory,
+    ops::Bound,
+    path::{Component, Prefix, PrefixComponent},
+    sync::{
+        mpsc::{RecvTimeoutError, TryRecvError, TrySendError},
+        PoisonError, TryLockError,
+    },
+    task::Poll,
+};
+
+use crate::{
+    unknown::{self, CaseOrUnknown, KnownCases, Unknown},
+    unreachable, Case, CaseMut, CaseRef, Exhaustive, Match, MatchMeta, Nonexhaustive,
+};
+
+/// Implement `Match`, `MatchMeta`, `Case`, `CaseRef`, and `CaseMut` for a "foreign" enum, given its
+/// variants in order, just as `#[derive(Match)]` would (including `KnownCases` and `CaseOrUnknown`
+/// if it is `#[non_exhaustive]`).
+///
+/// This is a declarative macro so that this crate needn't depend on `vesta-macro`, which means it
+/// can't parse generics or make up names: the parameters of the impls are given in brackets after
+/// `impl`, and each field is given the name to bind it to.
+macro_rules! impl_match {
+    (#[non_exhaustive] impl $params:tt $name:ident $(<$($arg:tt),*>)? { $($variants:tt)* }) => {
+        impl_match!(@impl Nonexhaustive, [_ => None], $params $params $name [$name $(<$($arg),*>)?] {
+            $($variants)*
+        } {
+            impl_match!(@unknown $params [$name $(<$($arg),*>)?] { $($variants)* });
+        });
+    };
+    (impl $params:tt $name:ident $(<$($arg:tt),*>)? { $($variants:tt)* }) => {
+        impl_match!(@impl Exhaustive<{ CASE_NAMES.len() }>, [], $params $params $name [$name $(<$($arg),*>)?] {
+            $($variants)*
+        } {});
+    };
+    (@impl $range:ty, [$($default:tt)*], [$($param:tt)*] $params:tt $name:ident [$ty:ty] {
+        $($variant:ident $(($($field:ident: $field_ty:ty),*))?),* $(,)?
+    } { $($extra:tt)* }) => {
+        const _: () = {
+            /// The names of the variants, in order of their tags.
+            const CASE_NAMES: &[&str] = &[$(stringify!($variant)),*];
+
+            /// The tags of the variants, by name.
+            #[allow(dead_code, clippy::upper_case_acronyms)]
+            enum Tag {
+                $($variant),*
+            }
+
+            unsafe impl<$($param)*> Match for $ty {
+                type Range = $range;
+
+                #[inline]
+                fn tag(&self) -> Option<usize> {
+                    match *self {
+                        $(Self::$variant { .. } => Some(Tag::$variant as usize),)*
+                        $($default)*
+                    }
+                }
+            }
+
+            impl<$($param)*> MatchMeta for $ty {
+                const NAME: &'static str = stringify!($name);
+                const CASE_NAMES: &'static [&'static str] = CASE_NAMES;
+            }
+
+            $(impl_match!(@case $params [$ty] $variant $(($($field: $field_ty),*))?);)*
+
+            $($extra)*
+        };
+    };
+    (@case $params:tt [$ty:ty] $variant:ident $(($($field:ident: $field_ty:ty),*))?) => {
+        impl_match!(@case_impls $params [$ty] $variant [$(($($field),*))?] [$($($field),*)?] [$($($field_ty),*)?]);
+    };
+    (@case_impls [$($param:tt)*] [$ty:ty] $variant:ident [$($fields:tt)*] [$($field:ident),*] [$($field_ty:ty),*]) => {
+        #[allow(unused_parens, clippy::unused_unit)]
+        impl<$($param)*> Case<{ Tag::$variant as usize }> for $ty {
+            type Case = ($($field_ty),*);
+
+            #[inline]
+            unsafe fn case(this: Self) -> Self::Case {
+                if let Self::$variant $($fields)* = this {
+                    ($($field),*)
+                } else {
+                    unreachable()
+                }
+            }
+
+            #[inline]
+            fn uncase(case: Self::Case) -> Self {
+                let ($($field),*) = case;
+                Self::$variant $($fields)*
+            }
+
+            #[inline]
+            fn try_case(this: Self) -> Result<Self::Case, Self> {
+                if let Self::$variant $($fields)* = this {
+                    Ok(($($field),*))
+                } else {
+                    Err(this)
+                }
+            }
+        }
+
+        #[allow(unused_parens, clippy::unused_unit)]
+        impl<$($param)*> CaseRef<{ Tag::$variant as usize }> for $ty {
+            type Ref<'__vesta> = ($(&'__vesta $field_ty),*) where Self: '__vesta;
+
+            #[inline]
+            unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+                if let Self::$variant $($fields)* = this {
+                    ($($field),*)
+                } else {
+                    unreachable()
+                }
+            }
+
+            #[inline]
+            fn try_case_ref(this: &Self) -> Option<Self::Ref<'_>> {
+                if let Self::$variant $($fields)* = this {
+                    Some(($($field),*))
+                } else {
+                    None
+                }
+            }
+        }
+
+        #[allow(unused_parens, clippy::unused_unit)]
+        impl<$($param)*> CaseMut<{ Tag::$variant as usize }> for $ty {
+            type Mut<'__vesta> = ($(&'__vesta mut $field_ty),*) where Self: '__vesta;
+
+            #[inline]
+            unsafe fn case_mut(this: &mut Self) -> Self::Mut<'_> {
+                if let Self::$variant $($fields)* = this {
+                    ($($field),*)
+                } else {
+                    unreachable()
+                }
+            }
+
+            #[inline]
+            fn try_case_mut(this: &mut Self) -> Option<Self::Mut<'_>> {
+                if let Self::$variant $($fields)* = this {
+                    Some(($($field),*))
+                } else {
+                    None
+                }
+            }
+        }
+    };
+    (@unknown $params:tt [$ty:ty] { $($variant:ident $(($($field:ident: $field_ty:ty),*))?),* $(,)? }) => {
+        impl_match!(@unknown_impls $params $params [$ty] { $($variant),* });
+    };
+    (@unknown_impls [$($param:tt)*] $params:tt [$ty:ty] { $($variant:ident),* }) => {
+        unsafe impl<$($param)*> KnownCases for $ty {
+            const KNOWN: usize = CASE_NAMES.len();
+            type WithUnknown = Exhaustive<{ CASE_NAMES.len() + 1 }>;
+        }
+
+        $(impl_match!(@known $params [$ty] $variant);)*
+
+        unsafe impl<$($param)*> CaseOrUnknown<{ CASE_NAMES.len() }> for $ty {
+            type Case = Unknown<Self>;
+            type Ref<'__vesta> = Unknown<&'__vesta Self> where Self: '__vesta;
+
+            #[inline]
+            unsafe fn case(this: Self) -> Self::Case {
+                unknown::unknown(this)
+            }
+
+            #[inline]
+            fn uncase(case: Self::Case) -> Self {
+                case.into_value()
+            }
+
+            #[inline]
+            unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+                unknown::unknown_ref(this)
+            }
+        }
+    };
+    (@known [$($param:tt)*] [$ty:ty] $variant:ident) => {
+        unsafe impl<$($param)*> CaseOrUnknown<{ Tag::$variant as usize }> for $ty {
+            type Case = <Self as Case<{ Tag::$variant as usize }>>::Case;
+            type Ref<'__vesta> = <Self as CaseRef<{ Tag::$variant as usize }>>::Ref<'__vesta> where Self: '__vesta;
+
+            #[inline]
+            unsafe fn case(this: Self) -> Self::Case {
+                Case::<{ Tag::$variant as usize }>::case(this)
+            }
+
+            #[inline]
+            fn uncase(case: Self::Case) -> Self {
+                Case::<{ Tag::$variant as usize }>::uncase(case)
+            }
+
+            #[inline]
+            unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+                CaseRef::<{ Tag::$variant as usize }>::case_ref(this)
+            }
+        }
+    };
+}
+
+impl_match! {
+    impl[] Infallible {}
+}
+
+impl_match! {
+    impl[T] Option<T> {
+        None,
+        Some(value: T),
+    }
+}
+
+impl_match! {
+    impl[T, E] Result<T, E> {
+        Ok(value: T),
+        Err(error: E),
+    }
+}
+
+impl_match! {
+    impl[T] Poll<T> {
+        Ready(value: T),
+        Pending,
+    }
+}
+
+impl_match! {
+    impl['a, B: 'a + ToOwned + ?Sized] Cow<'a, B> {
+        Borrowed(borrowed: &'a B),
+        Owned(owned: <B as ToOwned>::Owned),
+    }
+}
+
+impl_match! {
+    impl[] VarError {
+        NotPresent,
+        NotUnicode(string: OsString),
+    }
+}
+
+impl_match! {
+    impl[] SeekFrom {
+        Start(offset: u64),
+        End(offset: i64),
+        Current(offset: i64),
+    }
+}
+
+impl_match! {
+    impl[T] Bound<T> {
+        Included(bound: T),
+        Excluded(bound: T),
+        Unbounded,
+    }
+}
+
+impl_match! {
+    impl[] IpAddr {
+        V4(addr: Ipv4Addr),
+        V6(addr: Ipv6Addr),
+    }
+}
+
+impl_match! {
+    impl[] SocketAddr {
+        V4(addr: SocketAddrV4),
+        V6(addr: SocketAddrV6),
+    }
+}
+
+impl_match! {
+    impl[] Shutdown {
+        Read,
+        Write,
+        Both,
+    }
+}
+
+impl_match! {
+    impl[T] TryLockError<T> {
+        Poisoned(error: PoisonError<T>),
+        WouldBlock,
+    }
+}
+
+impl_match! {
+    impl[] TryRecvError {
+        Empty,
+        Disconnected,
+    }
+}
+
+impl_match! {
+    impl[] RecvTimeoutError {
+        Timeout,
+        Disconnected,
+    }
+}
+
+impl_match! {
+    impl[T] TrySendError<T> {
+        Full(value: T),
+        Disconnected(value: T),
+    }
+}
+
+impl_match! {
+    impl[] FpCategory {
+        Nan,
+        Infinite,
+        Zero,
+        Subnormal,
+        Normal,
+    }
+}
+
+impl_match! {
+    impl[] Alignment {
+        Left,
+        Right,
+        Center,
+    }
+}
+
+impl_match! {
+    impl['a] Prefix<'a> {
+        Verbatim(prefix: &'a OsStr),
+        VerbatimUNC(server: &'a OsStr, share: &'a OsStr),
+        VerbatimDisk(drive: u8),
+        DeviceNS(device: &'a OsStr),
+        UNC(server: &'a OsStr, share: &'a OsStr),
+        Disk(drive: u8),
+    }
+}
+
+impl_match! {
+    impl['a] Component<'a> {
+        Prefix(prefix: PrefixComponent<'a>),
+        RootDir,
+        CurDir,
+        ParentDir,
+        Normal(name: &'a OsStr),
+    }
+}
+
+impl_match! {
+    #[non_exhaustive]
+    impl[] ErrorKind {
+        NotFound,
+        PermissionDenied,
+        ConnectionRefused,
+        ConnectionReset,
+        ConnectionAborted,
+        NotConnected,
+        AddrInUse,
+        AddrNotAvailable,
+        BrokenPipe,
+        AlreadyExists,
+        WouldBlock,
+        InvalidInput,
+        InvalidData,
+        TimedOut,
+        WriteZero,
+        Interrupted,
+        Other,
+        UnexpectedEof,
+    }
+}
+
+mod cmp {
+    use super::*;
+    use std::cmp::Ordering;
+
+    impl_match! {
+        impl[] Ordering {
+            Less,
+            Equal,
+            Greater,
+        }
+    }
+}
+
+mod atomic {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    impl_match! {
+        #[non_exhaustive]
+        impl[] Ordering {
+            Relaxed,
+            Release,
+            Acquire,
+            AcqRel,
+            SeqCst,
+        }
+    }
+}
+
+mod btree_map {
+    use super::*;
+    use std::collections::btree_map::*;
+
+    impl_match! {
+        impl['a, K: 'a, V: 'a] Entry<'a, K, V> {
+            Vacant(entry: VacantEntry<'a, K, V>),
+            Occupied(entry: OccupiedEntry<'a, K, V>),
+        }
+    }
+}
+
+mod hash_map {
+    use super::*;
+    use std::collections::hash_map::*;
+
+    impl_match! {
+        impl['a, K: 'a, V: 'a] Entry<'a, K, V> {
+            Vacant(entry: VacantEntry<'a, K, V>),
+            Occupied(entry: OccupiedEntry<'a, K, V>),
+        }
+    }
+}
+
+/// A string is matched by whether it is empty (case 0), or else by its first character and the
+/// remainder of the string (case 1).
+unsafe impl Match for str {
+    type Range = Exhaustive<2>;
+
+    fn tag(&self) -> Option<usize> {
+        Some(if self.is_empty() { 0 } else { 1 })
+    }
+}
+
+impl CaseRef<0> for str {
+    type Ref<'a> = ();
+
+    unsafe fn case_ref(_: &Self) -> Self::Ref<'_> {}
+}
+
+impl CaseRef<1> for str {
+    type Ref<'a> = (char, &'a str);
+
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        let mut chars = this.chars();
+        match chars.next() {
+            Some(first) => (first, chars.as_str()),
+            None => unreachable(),
+        }
+    }
+}
+
+/// A slice is matched by whether it is empty (case 0), or else by its first element and the
+/// remainder of the slice (case 1).
+unsafe impl<T> Match for [T] {
+    type Range = Exhaustive<2>;
+
+    fn tag(&self) -> Option<usize> {
+        Some(if self.is_empty() { 0 } else { 1 })
+    }
+}
+
+impl<T> CaseRef<0> for [T] {
+    type Ref<'a>
+        = ()
+    where
+        T: 'a;
+
+    unsafe fn case_ref(_: &Self) -> Self::Ref<'_> {}
+}
+
+impl<T> CaseRef<1> for [T] {
+    type Ref<'a>
+        = (&'a T, &'a [T])
+    where
+        T: 'a;
+
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        match this.split_first() {
+            Some((first, rest)) => (first, rest),
+            None => unreachable(),
+        }
+    }
+}
+
+impl<T> CaseMut<0> for [T] {
+    type Mut<'a>
+        = ()
+    where
+        T: 'a;
+
+    unsafe fn case_mut(_: &mut Self) -> Self::Mut<'_> {}
+}
+
+impl<T> CaseMut<1> for [T] {
+    type Mut<'a>
+        = (&'a mut T, &'a mut [T])
+    where
+        T: 'a;
+
+    unsafe fn case_mut(this: &mut Self) -> Self::Mut<'_> {
+        match this.split_first_mut() {
+            Some((first, rest)) => (first, rest),
+            None => unreachable(),
+        }
+    }
+}
+
+/// A reference is matched by reference in the same way as the value it refers to.
+unsafe impl<T: Match + ?Sized> Match for &T {
+    type Range = T::Range;
+
+    fn tag(&self) -> Option<usize> {
+        T::tag(self)
+    }
+}
+
+impl<T: CaseRef<N> + ?Sized, const N: usize> CaseRef<N> for &T {
+    type Ref<'a>
+        = T::Ref<'a>
+    where
+        Self: 'a;
+
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        T::case_ref(this)
+    }
+
+    fn try_case_ref(this: &Self) -> Option<Self::Ref<'_>> {
+        T::try_case_ref(this)
+    }
+}
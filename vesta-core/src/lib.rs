@@ -0,0 +1,1253 @@
+//! The traits underlying [Vesta](https://crates.io/crates/vesta)'s extensible pattern matching,
+//! along with their implementations for types in the standard library, without any of its macros.
+//!
+//! This crate has no dependencies, so it suits embedded and compile-time-sensitive projects which
+//! only need to implement or be generic over [`Match`] and [`Case`], without pulling procedural
+//! macro machinery into their dependency graph. Everything here is re-exported by `vesta`, whose
+//! [`case!`] macro and [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html) derive
+//! macro work with these traits; use that crate if you want to match on values.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta_core::{CaseExt, Exhaustive, Match};
+//!
+//! /// The payload of the last case of any exhaustive type with two cases, if it has that case.
+//! fn last<T>(value: T) -> Option<T::Case>
+//! where
+//!     T: Match<Range = Exhaustive<2>> + vesta_core::Case<1>,
+//! {
+//!     value.try_case::<1>().ok()
+//! }
+//!
+//! assert_eq!(last(Some(3)), Some(3));
+//! assert_eq!(last(Err::<(), _>("oops")), Some("oops"));
+//! assert_eq!(last(Ok::<_, ()>(1)), None);
+//! ```
+//!
+//! [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+
+#![warn(missing_docs)]
+#![warn(missing_copy_implementations, missing_debug_implementations)]
+#![warn(unused_qualifications, unused_results)]
+#![warn(future_incompatible)]
+#![warn(unused)]
+// Documentation configuration
+#![forbid(broken_intra_doc_links)]
+
+use std::{borrow::Cow, fmt};
+
+/// A type which is [`Match`] can be pattern-matched using the [`case!`] macro and the methods of
+/// [`CaseExt`]/[`Case`].
+///
+/// In order for a type to be matched, it must implement [`Match`], as well as [`Case`] for each
+/// distinct case it can be matched against.
+///
+/// Types which are not [`Sized`], such as `str` and `[T]`, cannot implement [`Case`], because it
+/// moves values in and out of cases. Instead, they can implement [`CaseRef`], which allows them to
+/// be matched by reference using [`case!(ref ...)`][`case!`].
+///
+/// # Safety
+///
+/// The [`tag`](Match::tag) of a value must agree with its [`Range`](Match::Range) and with the
+/// implementations of [`Case`] for the type, as described in the documentation for each of those
+/// items. Generated code relies on these guarantees to skip checks, so violating them may result in
+/// undefined behavior.
+///
+/// [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+pub unsafe trait Match {
+    /// The range of [`tag`](Match::tag) for this type: either [`Nonexhaustive`], or
+    /// [`Exhaustive<N>`](Exhaustive) for some `N`.
+    ///
+    /// No other types are permissible for this associated type; it is constrained by the sealed
+    /// `Range` trait, which is only implemented for these two options. (For experimentation, the
+    /// `unstable-ranges` feature unseals it: see `unstable_ranges`.)
+    ///
+    /// # Safety
+    ///
+    /// If the [`Range`](Match::Range) is [`Exhaustive<N>`](Exhaustive), then [`tag`](Match::tag)
+    /// must *never* return `None`. For all `Some(m)` it returns, `m` must be *strictly less than*
+    /// `N`. Undefined behavior may result if this guarantee is violated.
+    type Range: sealed::Range;
+
+    /// The tag of this value.
+    ///
+    /// # Safety
+    ///
+    /// If this function returns `Some(n)`, this is a *guarantee* that it is safe to call
+    /// [`case`](Case::case) for this value at the type level tag `N = n`. It is undefined behavior
+    /// for this function to return `Some(n)` if `<Self as Case<N>>::case(self)` would be unsafe.
+    ///
+    /// If the [`Range`](Match::Range) is [`Exhaustive<N>`](Exhaustive), then this function must
+    /// *never* return `None`. For all `Some(m)` it returns, `m` must be *strictly less than* `N`.
+    /// Undefined behavior may result if this guarantee is violated.
+    ///
+    /// Only if the [`Range`](Match::Range) is [`Nonexhaustive`] is it safe for this function to
+    /// return `None`. Returning `None` will cause all pattern matches on this value to take the
+    /// default case.
+    ///
+    /// This function should always return the same result. In general, it is impossible to safely
+    /// implement [`Match`] for types with interior mutability, unless that interior mutability has
+    /// no ability to change the tag. When pattern-matching occurs, there is no guarantee that
+    /// `self.tag()` is checked and `self.case()` subsequently called (if applicable) in a single
+    /// atomic action, which may lead to undefined behavior if the tag changes between these two
+    /// moments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::Match;
+    ///
+    /// assert_eq!(Some(0), None::<bool>.tag());
+    /// assert_eq!(Some(1), Some(true).tag());
+    /// ```
+    fn tag(&self) -> Option<usize>;
+
+    /// The tag of this value, for a type whose [`Range`](Match::Range) is
+    /// [`Exhaustive<N>`](Exhaustive), without wrapping it in an [`Option`].
+    ///
+    /// The [`tag`](Match::tag) of such a value is never `None`, so this is `self.tag()` unwrapped
+    /// without any check, which saves a branch in generic inner loops. The [`case!`] macro uses
+    /// this whenever it has no default arm. The default implementation unwraps [`tag`](Match::tag);
+    /// `#[derive(Match)]` overrides it to read the variant directly.
+    ///
+    /// # Safety
+    ///
+    /// An overriding implementation must always return the same tag as [`tag`](Match::tag), and
+    /// so it must be *strictly less than* `N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::Match;
+    ///
+    /// assert_eq!(0, None::<bool>.tag_exhaustive());
+    /// assert_eq!(1, Err::<(), _>("oops").tag_exhaustive());
+    /// ```
+    ///
+    /// [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+    #[inline(always)]
+    fn tag_exhaustive<const N: usize>(&self) -> usize
+    where
+        Self: Match<Range = Exhaustive<N>>,
+    {
+        match self.tag() {
+            Some(tag) => tag,
+            // The range is exhaustive, so the tag is never `None`
+            None => unsafe { unreachable() },
+        }
+    }
+}
+
+/// An extension trait providing methods analogous to those in [`Case`], but which take `self` and
+/// type parameters.<br>💡 Prefer using these to directly calling the methods in [`Case`],
+/// [`CaseRef`], and [`CaseMut`].
+pub trait CaseExt {
+    /// The [`tag`](Match::tag) of the value, as a [`Tag`] rather than a plain number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{CaseExt, Tag};
+    ///
+    /// assert_eq!(Ok::<_, ()>(1).typed_tag(), Some(Tag(0)));
+    /// ```
+    #[inline(always)]
+    fn typed_tag(&self) -> Option<Tag>
+    where
+        Self: Match,
+    {
+        self.tag().map(Tag)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, CaseExt};
+    ///
+    /// let option = Some("hello");
+    /// assert_eq!(option.tag(), Some(1));
+    /// let string = unsafe { option.case::<1>() };
+    /// assert_eq!(string, "hello");
+    /// ```
+    #[inline(always)]
+    unsafe fn case<const N: usize>(self) -> Self::Case
+    where
+        Self: Case<N>,
+    {
+        Case::case(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return that case; otherwise, return `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseExt;
+    ///
+    /// let result = Some("hello").try_case::<1>();
+    /// assert_eq!(result, Ok("hello"));
+    /// ```
+    #[inline(always)]
+    fn try_case<const N: usize>(self) -> Result<Self::Case, Self>
+    where
+        Self: Case<N>,
+    {
+        Case::try_case(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return that case; otherwise, panic with the
+    /// message given, followed by the case expected and the case the value was actually in, named
+    /// using [`MatchMeta`].
+    ///
+    /// This is like [`Option::expect`], for tests and code relying on invariants, where
+    /// `try_case::<N>().unwrap()` would say nothing about what the value was instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseExt;
+    ///
+    /// let port = Some(8080).expect_case::<1>("the port should be configured");
+    /// assert_eq!(port, 8080);
+    /// ```
+    ///
+    /// ```should_panic
+    /// use vesta::CaseExt;
+    ///
+    /// // Panics with "the port should be configured: expected `Some(1)` of `Option`, found
+    /// // `None(0)`"
+    /// let port = None::<u16>.expect_case::<1>("the port should be configured");
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn expect_case<const N: usize>(self, msg: &str) -> Self::Case
+    where
+        Self: Case<N> + MatchMeta,
+    {
+        match Case::try_case(self) {
+            Ok(case) => case,
+            Err(this) => expect_case_failed(msg, N, &this),
+        }
+    }
+
+    /// The inverse of [`case`](CaseExt::case): inject this case back into the matched type.
+    ///
+    /// This operation must not panic or otherwise fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseExt;
+    ///
+    /// let option: Option<_> = "hello".uncase::<_, 1>();
+    /// assert_eq!(option, Some("hello"));
+    /// ```
+    #[inline(always)]
+    fn uncase<T, const N: usize>(self) -> T
+    where
+        Self: Sized,
+        T: Case<N, Case = Self>,
+    {
+        Case::uncase(self)
+    }
+
+    /// The inverse of [`try_case`](CaseExt::try_case) for types with invariants: inject this case
+    /// back into the matched type using [`TryUncase`], if the result upholds those invariants.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::num::NonZeroU8;
+    /// use vesta::{CaseExt, Match};
+    ///
+    /// #[derive(Match, Debug, PartialEq)]
+    /// #[vesta(validate = "Batch::is_valid")]
+    /// enum Batch {
+    ///     Empty,
+    ///     Items(Vec<u8>, NonZeroU8),
+    /// }
+    ///
+    /// impl Batch {
+    ///     fn is_valid(&self) -> bool {
+    ///         match self {
+    ///             Batch::Empty => true,
+    ///             Batch::Items(items, size) => items.len() == size.get() as usize,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let size = NonZeroU8::new(2).unwrap();
+    /// let batch: Result<Batch, _> = (vec![1, 2], size).try_uncase::<_, 1>();
+    /// assert_eq!(batch, Ok(Batch::Items(vec![1, 2], size)));
+    ///
+    /// let batch: Result<Batch, _> = (vec![1], size).try_uncase::<_, 1>();
+    /// assert!(batch.is_err());
+    /// ```
+    #[inline(always)]
+    fn try_uncase<T, const N: usize>(self) -> Result<T, UncaseError<Self>>
+    where
+        Self: Sized,
+        T: TryUncase<N, Case = Self>,
+    {
+        TryUncase::try_uncase(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a reference to that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, CaseExt};
+    ///
+    /// let option = Some(String::from("hello"));
+    /// assert_eq!(option.tag(), Some(1));
+    /// let string: &String = unsafe { option.case_ref::<1>() };
+    /// assert_eq!(string, "hello");
+    /// ```
+    #[inline(always)]
+    unsafe fn case_ref<const N: usize>(&self) -> Self::Ref<'_>
+    where
+        Self: CaseRef<N>,
+    {
+        CaseRef::case_ref(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a reference to that case; otherwise,
+    /// return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseExt;
+    ///
+    /// let slice: &[u8] = b"hello";
+    /// assert_eq!(slice.try_case_ref::<0>(), None);
+    /// assert_eq!(slice.try_case_ref::<1>(), Some((&b'h', &b"ello"[..])));
+    /// ```
+    #[inline(always)]
+    fn try_case_ref<const N: usize>(&self) -> Option<Self::Ref<'_>>
+    where
+        Self: CaseRef<N>,
+    {
+        CaseRef::try_case_ref(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return its case as a [`Cow`], borrowed from the
+    /// value, which is only cloned if it is turned into an owned case; otherwise, return `None`.
+    ///
+    /// This suits code which usually only reads a payload but sometimes needs to own it, without
+    /// matching the value once by reference and again by value. It applies to the cases which are
+    /// borrowed as a reference to the whole case, as are those of [`Option`] and [`Result`] and
+    /// those of derived enums whose variants have a single field. The cases of variants with
+    /// several fields are borrowed as tuples of references to each field, and there is no tuple of
+    /// the fields themselves in the value to borrow, so `case_cow` can't be called for them: use
+    /// [`try_case_ref`](CaseExt::try_case_ref) and clone the fields needed instead.
+    ///
+    /// The [`case!`] macro borrows every case as a `Cow` in the same way when `cow` is written
+    /// before its scrutinee, as in `case!(cow value { ... })`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use vesta::{CaseExt, Match};
+    ///
+    /// #[derive(Match)]
+    /// enum Name {
+    ///     Anonymous,
+    ///     Given(String),
+    /// }
+    ///
+    /// fn shout(name: &Name) -> Option<Cow<'_, String>> {
+    ///     let mut given = name.case_cow::<1>()?;
+    ///     if !given.ends_with('!') {
+    ///         // Only a name without a '!' is cloned
+    ///         given.to_mut().push('!');
+    ///     }
+    ///     Some(given)
+    /// }
+    ///
+    /// assert!(matches!(shout(&Name::Given("Ada!".into())), Some(Cow::Borrowed(_))));
+    /// assert_eq!(shout(&Name::Given("Ada".into())).unwrap().as_str(), "Ada!");
+    /// assert_eq!(shout(&Name::Anonymous), None);
+    ///
+    /// // The value needn't be `'static`, so its cases may borrow from elsewhere
+    /// let s = String::from("borrowed");
+    /// assert_eq!(Some(s.as_str()).case_cow::<1>(), Some(Cow::Borrowed(&"borrowed")));
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use vesta::{CaseExt, Match};
+    ///
+    /// #[derive(Match)]
+    /// enum Point {
+    ///     Flat(u32, u32),
+    /// }
+    ///
+    /// // Error: the case of `Flat` is borrowed as `(&u32, &u32)`, not `&(u32, u32)`
+    /// Point::Flat(1, 2).case_cow::<0>();
+    /// ```
+    ///
+    /// [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+    #[inline(always)]
+    fn case_cow<'a, const N: usize>(&'a self) -> Option<Cow<'a, Self::Case>>
+    where
+        Self: Case<N> + CaseRef<N, Ref<'a> = &'a <Self as Case<N>>::Case>,
+        Self::Case: Clone,
+    {
+        CaseRef::try_case_ref(self).map(Cow::Borrowed)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a mutable reference to that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, CaseExt};
+    ///
+    /// let mut option = Some(1);
+    /// assert_eq!(option.tag(), Some(1));
+    /// *unsafe { option.case_mut::<1>() } += 1;
+    /// assert_eq!(option, Some(2));
+    /// ```
+    #[inline(always)]
+    unsafe fn case_mut<const N: usize>(&mut self) -> Self::Mut<'_>
+    where
+        Self: CaseMut<N>,
+    {
+        CaseMut::case_mut(self)
+    }
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a mutable reference to that case;
+    /// otherwise, return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseExt;
+    ///
+    /// let mut result: Result<u8, String> = Err("oops".into());
+    /// assert_eq!(result.try_case_mut::<0>(), None);
+    /// if let Some(error) = result.try_case_mut::<1>() {
+    ///     error.push('!');
+    /// }
+    /// assert_eq!(result, Err("oops!".into()));
+    /// ```
+    #[inline(always)]
+    fn try_case_mut<const N: usize>(&mut self) -> Option<Self::Mut<'_>>
+    where
+        Self: CaseMut<N>,
+    {
+        CaseMut::try_case_mut(self)
+    }
+}
+
+impl<T: ?Sized> CaseExt for T {}
+
+/// The names of a type implementing [`Match`] and of its cases, for use in diagnostics and logs.
+///
+/// This is implemented by the [`Match` derive], using the names of the type and its variants as
+/// written in its declaration (the only case of a struct is named after the struct).
+///
+/// # Examples
+///
+/// ```
+/// use vesta::MatchMeta;
+///
+/// assert_eq!(<Option<()> as MatchMeta>::NAME, "Option");
+/// assert_eq!(Some(1).case_name(), Some("Some"));
+/// ```
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub trait MatchMeta: Match {
+    /// The name of the type.
+    const NAME: &'static str;
+
+    /// The names of the cases of the type, in order of their tags.
+    const CASE_NAMES: &'static [&'static str];
+
+    /// The former names of the cases of the type, in order of their tags, so that names saved
+    /// before a case was renamed can still be looked up. Cases without former names may be left
+    /// off the end, so this is empty for a type none of whose cases have been renamed.
+    ///
+    /// `#[derive(Match)]` lists the names given by `#[vesta(alias = "...")]` attributes on each
+    /// variant.
+    const CASE_ALIASES: &'static [&'static [&'static str]] = &[];
+
+    /// The name of the case of this value, or `None` if its [`tag`](Match::tag) is unknown.
+    #[inline]
+    fn case_name(&self) -> Option<&'static str> {
+        self.tag()
+            .and_then(|tag| Self::CASE_NAMES.get(tag).copied())
+    }
+
+    /// The tag of the case with the given name, or with the given former name, or `None` if no case
+    /// has ever had that name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::MatchMeta;
+    ///
+    /// assert_eq!(<Result<(), ()> as MatchMeta>::tag_from_name("Err"), Some(1));
+    /// assert_eq!(<Result<(), ()> as MatchMeta>::tag_from_name("Error"), None);
+    /// ```
+    fn tag_from_name(name: &str) -> Option<usize> {
+        Self::CASE_NAMES
+            .iter()
+            .position(|&case| case == name)
+            .or_else(|| {
+                Self::CASE_ALIASES
+                    .iter()
+                    .position(|aliases| aliases.contains(&name))
+            })
+    }
+}
+
+/// Write the case of a value as its name followed by its tag in parentheses, such as `Some(1)`,
+/// without allocating.
+///
+/// A value whose [`tag`](Match::tag) is unknown, as may happen for non-exhaustive types, is written
+/// as `_`, like the pattern which would match it.
+///
+/// # Examples
+///
+/// ```
+/// use std::fmt;
+/// use vesta::fmt_case;
+///
+/// struct Handled<'a>(&'a Result<u8, String>);
+///
+/// impl fmt::Display for Handled<'_> {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         f.write_str("handled ")?;
+///         fmt_case(self.0, f)
+///     }
+/// }
+///
+/// assert_eq!(Handled(&Err("oops".into())).to_string(), "handled Err(1)");
+/// ```
+#[inline]
+pub fn fmt_case<T: MatchMeta + ?Sized>(value: &T, f: &mut fmt::Formatter) -> fmt::Result {
+    match value.tag() {
+        Some(tag) => match T::CASE_NAMES.get(tag) {
+            Some(name) => write!(f, "{}({})", name, tag),
+            None => write!(f, "_({})", tag),
+        },
+        None => f.write_str("_"),
+    }
+}
+
+/// An adapter which displays the case of a value using [`fmt_case`], for cheaply recording which
+/// case was handled in log lines.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::DisplayCase;
+///
+/// let message: Option<&str> = None;
+/// assert_eq!(format!("dispatched {}", DisplayCase(&message)), "dispatched None(0)");
+/// ```
+pub struct DisplayCase<'a, T: ?Sized>(pub &'a T);
+
+impl<T: MatchMeta + ?Sized> fmt::Display for DisplayCase<'_, T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_case(self.0, f)
+    }
+}
+
+impl<T: MatchMeta + ?Sized> fmt::Debug for DisplayCase<'_, T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_case(self.0, f)
+    }
+}
+
+/// Panic for [`CaseExt::expect_case`], kept out of line so that the successful path stays small.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn expect_case_failed<T: MatchMeta>(msg: &str, expected: usize, value: &T) -> ! {
+    let expected_name = T::CASE_NAMES.get(expected).copied().unwrap_or("_");
+    panic!(
+        "{}: expected `{}({})` of `{}`, found `{}`",
+        msg,
+        expected_name,
+        expected,
+        T::NAME,
+        DisplayCase(value),
+    )
+}
+
+/// Statically assert that the type of the given value is exhaustive for `N`.
+///
+/// This function can only be called if `T: Match<Range = Exhaustive<N>>`. It does nothing
+/// when called.
+///
+/// # Examples
+///
+/// ```
+/// vesta::assert_exhaustive::<_, 2>(&Some(true));
+/// ```
+#[inline(always)]
+pub fn assert_exhaustive<T, const N: usize>(_: &T)
+where
+    T: Match<Range = Exhaustive<N>> + ?Sized,
+{
+}
+
+/// Eliminate a value of an exhaustive type with no cases, which can never exist, as a value of any
+/// type at all.
+///
+/// This lets generic code dispose of impossible branches, such as the error case of a `Result`
+/// whose error type is empty, without writing `unsafe` code or `unreachable!()`. It does the same
+/// thing as a `case!` with no arms. To convert such a type to and from [`Infallible`], derive
+/// [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html) for it with the
+/// `#[vesta(absurd)]` option.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// fn get<T>(result: Result<T, Infallible>) -> T {
+///     match result {
+///         Ok(value) => value,
+///         Err(never) => vesta::absurd(never),
+///     }
+/// }
+///
+/// assert_eq!(get(Ok(3)), 3);
+/// ```
+///
+/// [`Infallible`]: std::convert::Infallible
+#[inline(always)]
+pub fn absurd<T, R>(value: T) -> R
+where
+    T: Match<Range = Exhaustive<0>>,
+{
+    match value.tag() {
+        // It is safe to assume this is unreachable because the type has no cases, so its tag would
+        // have to be less than zero
+        Some(_) | None => unsafe { unreachable() },
+    }
+}
+
+/// Statically assert that the type of the given value implements [`Case<N>`](Case).
+///
+/// The `case!` macro calls this for each of its arms, so that a missing implementation of
+/// [`Case`] is reported at the arm which needs it. This function does nothing when called.
+#[doc(hidden)]
+#[inline(always)]
+pub fn assert_case<T: Case<N>, const N: usize>(_: &T) {}
+
+/// Statically assert that the type of the given value implements [`CaseRef<N>`](CaseRef).
+///
+/// The `case!` macro calls this for each of its arms when matching by reference, so that a
+/// missing implementation of [`CaseRef`] is reported at the arm which needs it. This function does
+/// nothing when called.
+#[doc(hidden)]
+#[inline(always)]
+pub fn assert_case_ref<T: CaseRef<N> + ?Sized, const N: usize>(_: &T) {}
+
+/// Statically assert that the case `N` of the type of the given value can be borrowed as a
+/// [`Cow`], because it is borrowed as a reference to the whole case, or has no contents.
+///
+/// The `case!` macro calls this for each of its arms when matching with `cow`, so that a case
+/// which can't be borrowed as a `Cow` is reported at the arm which needs it. This function does
+/// nothing when called.
+#[doc(hidden)]
+#[inline(always)]
+pub fn assert_case_cow<'a, T: CaseRef<N> + ?Sized, const N: usize>(_: &'a T)
+where
+    T::Ref<'a>: CowCase<'a>,
+{
+}
+
+/// A case borrowed by [`CaseRef`] which the `case!` macro can bind as a [`Cow`] when matching with
+/// `cow`: a reference to the whole case, which becomes [`Cow::Borrowed`], or the `()` of a case
+/// with no contents, which stays as it is.
+#[doc(hidden)]
+pub trait CowCase<'a> {
+    /// The case as bound by the `case!` macro.
+    type Cow;
+
+    /// Wrap the borrowed case.
+    fn cow_case(self) -> Self::Cow;
+}
+
+impl<'a, T: Clone + 'a> CowCase<'a> for &'a T {
+    type Cow = Cow<'a, T>;
+
+    #[inline(always)]
+    fn cow_case(self) -> Self::Cow {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a> CowCase<'a> for () {
+    type Cow = ();
+
+    #[inline(always)]
+    fn cow_case(self) -> Self::Cow {}
+}
+
+/// Mark an unreachable location in generated code.
+///
+/// # Panics
+///
+/// In debug mode, panics immediately when this function is called.
+///
+/// # Safety
+///
+/// In release mode, undefined behavior may occur if this function is ever called.
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn unreachable<T>() -> T {
+    #[cfg(release)]
+    {
+        core::hint::unreachable_unchecked()
+    }
+    #[cfg(not(release))]
+    {
+        core::unreachable!("invariant violation in `vesta::Match` or `vesta::Case` implementation")
+    }
+}
+
+/// Check that a value has the tag of the case being extracted from it or reconstructed as it, in
+/// the code generated by `#[derive(Match)]` with `#[vesta(check_tags)]`.
+///
+/// # Panics
+///
+/// In debug mode, panics if the tag of `value` is not `Some(n)`, naming the `method` at fault.
+#[doc(hidden)]
+#[track_caller]
+#[inline(always)]
+pub fn debug_assert_tag<T: Match + ?Sized>(value: &T, n: usize, method: &str) {
+    debug_assert!(
+        value.tag() == Some(n),
+        "`{}` for case {} of `{}` was used with a value whose tag is {:?}",
+        method,
+        n,
+        core::any::type_name::<T>(),
+        value.tag(),
+    );
+}
+
+/// A marker type indicating that the [`tag`](Match::tag) for some type will always be *strictly
+/// less than* `N`.
+///
+/// Use this to mark the [`Range`](Match::Range) of exhaustive enumerations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Exhaustive<const N: usize> {}
+
+/// A marker type indicating that the [`tag`](Match::tag) for some type is not fixed to some known
+/// upper bound.
+///
+/// Use this to mark the [`Range`](Match::Range) of non-exhaustive enumerations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Nonexhaustive {}
+
+/// The [`tag`](Match::tag) of a case, as a number distinct from the indices, lengths, and counts it
+/// would otherwise be mixed up with.
+///
+/// [`Match::tag`] gives plain `usize`s, which [`CaseExt::typed_tag`] wraps in this type. A tag of a
+/// particular case of a type can be named using [`Tag::of_case`], which checks that the type has
+/// that case. Offsetting a tag, as when the tags of several types share one numbering, is checked
+/// for overflow. A `Tag` can be turned back into a `usize`, but is only made from one explicitly,
+/// by writing `Tag(n)`, so that an index or a length is never taken for a tag by accident.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{CaseExt, Tag};
+///
+/// let some = Tag::of_case::<Option<u8>, 1>();
+/// assert_eq!(Some(3).typed_tag(), Some(some));
+/// assert_eq!(some.to_string(), "1");
+///
+/// let global = some.checked_add(10).unwrap();
+/// assert_eq!(global, Tag(11));
+/// assert_eq!(global.offset_from(Tag(10)), Some(1));
+/// assert_eq!(some.checked_sub(2), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Tag(pub usize);
+
+impl Tag {
+    /// The tag `N` of a case of the type `T`, which must have a case with that tag.
+    ///
+    /// ```compile_fail
+    /// use vesta::Tag;
+    ///
+    /// // Error: `Option` has no case with the tag 2
+    /// let tag = Tag::of_case::<Option<u8>, 2>();
+    /// ```
+    #[inline(always)]
+    pub const fn of_case<T: Case<N>, const N: usize>() -> Tag {
+        Tag(N)
+    }
+
+    /// The tag as a plain number.
+    #[inline(always)]
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    /// The tag `offset` places after this one, or `None` if that would overflow.
+    #[inline(always)]
+    pub const fn checked_add(self, offset: usize) -> Option<Tag> {
+        match self.0.checked_add(offset) {
+            Some(tag) => Some(Tag(tag)),
+            None => None,
+        }
+    }
+
+    /// The tag `offset` places before this one, or `None` if there is no such tag.
+    #[inline(always)]
+    pub const fn checked_sub(self, offset: usize) -> Option<Tag> {
+        match self.0.checked_sub(offset) {
+            Some(tag) => Some(Tag(tag)),
+            None => None,
+        }
+    }
+
+    /// The number of places this tag is after `base`, or `None` if it comes before `base`.
+    #[inline(always)]
+    pub const fn offset_from(self, base: Tag) -> Option<usize> {
+        self.0.checked_sub(base.0)
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<Tag> for usize {
+    #[inline(always)]
+    fn from(tag: Tag) -> usize {
+        tag.0
+    }
+}
+
+/// An implementation of [`Case`] defines a particular case of a pattern match for a type.<br> ℹ️
+/// Prefer using the methods of [`CaseExt`] to directly calling these methods.
+pub trait Case<const N: usize>: Match + Sized {
+    /// The type of the data contained in the `N`th case of the matched type.
+    type Case;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, Case};
+    ///
+    /// let option = Some("hello");
+    /// assert_eq!(option.tag(), Some(1));
+    /// let string = unsafe { <_ as Case<1>>::case(option) };
+    /// assert_eq!(string, "hello");
+    /// ```
+    unsafe fn case(this: Self) -> Self::Case;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return that case; otherwise, return `self`.
+    ///
+    /// In its default implementation, this method checks that `self.tag() == N` and then calls
+    /// [`case`](Case::case) only if so.
+    ///
+    /// In the case where this method can be more efficiently implemented than the composition of
+    /// [`tag`](Match::tag) with [`case`](Case::case), this method can be overloaded. The
+    /// implementations generated by the [`Match` derive] do so with a single pattern match, so that
+    /// for types with a niche, such as `Option<&T>` or `Option<NonZeroU32>`, it compiles to a
+    /// single comparison, just like a hand-written `match`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::Case;
+    ///
+    /// let result = <_ as Case<1>>::try_case(Some("hello"));
+    /// assert_eq!(result, Ok("hello"));
+    /// ```
+    ///
+    /// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+    fn try_case(this: Self) -> Result<Self::Case, Self> {
+        if this.tag() == Some(N) {
+            // It is safe to call `self.case()` because we have checked the tag
+            Ok(unsafe { Case::case(this) })
+        } else {
+            Err(this)
+        }
+    }
+
+    /// The inverse of [`case`](Case::case): inject this case back into the matched type.
+    ///
+    /// This operation must not panic or otherwise fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::Case;
+    ///
+    /// let option: Option<_> = <_ as Case<1>>::uncase("hello");
+    /// assert_eq!(option, Some("hello"));
+    /// ```
+    fn uncase(case: Self::Case) -> Self;
+}
+
+/// An implementation of [`TryUncase`] reconstructs a value from its `N`th case when that can fail,
+/// because the type maintains an invariant which not every case upholds.
+///
+/// [`Case::uncase`] must not fail, so a type which is non-empty or sorted, for instance, can only
+/// restore its invariant there by panicking. Implementing this trait instead lets construction
+/// check the invariant and give back the case if it does not hold. It is implemented for every case
+/// of a type by `#[vesta(validate = "...")]` along with the
+/// [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html) derive macro, and code which
+/// builds values from their cases, such as the `TryFrom` conversion generated by
+/// `#[vesta(raw_form)]`, uses it when it is present.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{Case, Match, TryUncase};
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(validate = "Sorted::is_sorted")]
+/// struct Sorted(Vec<i32>);
+///
+/// impl Sorted {
+///     fn is_sorted(&self) -> bool {
+///         self.0.windows(2).all(|pair| pair[0] <= pair[1])
+///     }
+/// }
+///
+/// assert_eq!(Sorted::try_uncase(vec![1, 2, 3]), Ok(Sorted(vec![1, 2, 3])));
+///
+/// let error = Sorted::try_uncase(vec![3, 1, 2]).unwrap_err();
+/// assert_eq!(error.into_case(), vec![3, 1, 2]);
+/// ```
+pub trait TryUncase<const N: usize>: Case<N> {
+    /// Inject this case back into the matched type if the result upholds the invariants of the
+    /// type, or give back the case unchanged otherwise.
+    fn try_uncase(case: Self::Case) -> Result<Self, UncaseError<Self::Case>>;
+}
+
+/// The error returned by [`TryUncase::try_uncase`] when a case cannot be injected back into the
+/// matched type, holding the case which was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UncaseError<C>(C);
+
+impl<C> UncaseError<C> {
+    /// Reject a case, because it does not uphold the invariants of the matched type.
+    pub fn new(case: C) -> Self {
+        UncaseError(case)
+    }
+
+    /// Get back the case which was rejected.
+    pub fn into_case(self) -> C {
+        self.0
+    }
+}
+
+impl<C> fmt::Display for UncaseError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("case does not uphold the invariants of the matched type")
+    }
+}
+
+impl<C: fmt::Debug> std::error::Error for UncaseError<C> {}
+
+/// An implementation of [`CaseRef`] defines a particular case of a pattern match for a type, when
+/// that type is matched by reference.<br> ℹ️ Prefer using the methods of [`CaseExt`] to
+/// directly calling these methods.
+///
+/// Unlike [`Case`], this can be implemented for types which are not [`Sized`], because it never
+/// moves the matched value.
+pub trait CaseRef<const N: usize>: Match {
+    /// The type of references to the data contained in the `N`th case of the matched type.
+    type Ref<'a>
+    where
+        Self: 'a;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a reference to that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, CaseRef};
+    ///
+    /// let string = "hello";
+    /// assert_eq!(string.tag(), Some(1));
+    /// let (first, rest) = unsafe { <_ as CaseRef<1>>::case_ref(string) };
+    /// assert_eq!((first, rest), ('h', "ello"));
+    /// ```
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_>;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a reference to that case; otherwise,
+    /// return `None`.
+    ///
+    /// In its default implementation, this method checks that `self.tag() == N` and then calls
+    /// [`case_ref`](CaseRef::case_ref) only if so.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseRef;
+    ///
+    /// let result = <_ as CaseRef<1>>::try_case_ref(&Some(true));
+    /// assert_eq!(result, Some(&true));
+    /// ```
+    fn try_case_ref(this: &Self) -> Option<Self::Ref<'_>> {
+        if this.tag() == Some(N) {
+            // It is safe to call `case_ref` because we have checked the tag
+            Some(unsafe { CaseRef::case_ref(this) })
+        } else {
+            None
+        }
+    }
+}
+
+/// An implementation of [`CaseMut`] defines a particular case of a pattern match for a type, when
+/// that type is matched by mutable reference.<br> ℹ️ Prefer using the methods of [`CaseExt`] to
+/// directly calling these methods.
+///
+/// Mutating the case through the reference this gives must not change the
+/// [`tag`](Match::tag) of the value.
+pub trait CaseMut<const N: usize>: Match {
+    /// The type of mutable references to the data contained in the `N`th case of the matched type.
+    type Mut<'a>
+    where
+        Self: 'a;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a mutable reference to that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
+    /// anything other than `Some(n)`, where `n = N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::{Match, CaseMut};
+    ///
+    /// let mut slice = [1, 2, 3];
+    /// let (first, rest) = unsafe { <[_] as CaseMut<1>>::case_mut(&mut slice) };
+    /// *first = rest.len();
+    /// assert_eq!(slice, [2, 2, 3]);
+    /// ```
+    unsafe fn case_mut(this: &mut Self) -> Self::Mut<'_>;
+
+    /// If the value's [`tag`](Match::tag) is `N`, return a mutable reference to that case;
+    /// otherwise, return `None`.
+    ///
+    /// In its default implementation, this method checks that `self.tag() == N` and then calls
+    /// [`case_mut`](CaseMut::case_mut) only if so.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::CaseMut;
+    ///
+    /// let mut option = Some(1);
+    /// if let Some(n) = <_ as CaseMut<1>>::try_case_mut(&mut option) {
+    ///     *n += 1;
+    /// }
+    /// assert_eq!(option, Some(2));
+    /// ```
+    fn try_case_mut(this: &mut Self) -> Option<Self::Mut<'_>> {
+        if this.tag() == Some(N) {
+            // It is safe to call `case_mut` because we have checked the tag
+            Some(unsafe { CaseMut::case_mut(this) })
+        } else {
+            None
+        }
+    }
+}
+
+/// An implementation of [`TagBase`] gives the cases of a type global tags, offset from their tags
+/// by a constant, so that each subsystem of a large program can be given its own block of tags,
+/// unique across the program.
+///
+/// It is implemented by `#[vesta(tag_base = "...")]` along with the [`Match` derive], which also
+/// implements [`GlobalCase`] for each case, so that the `case!` macro can match on global tags.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{Match, Tag, TagBase};
+///
+/// pub const AUTH_BASE: usize = 100;
+///
+/// #[derive(Match)]
+/// #[vesta(tag_base = "AUTH_BASE")]
+/// pub enum Auth {
+///     Login(String),
+///     Logout,
+/// }
+///
+/// assert_eq!(Auth::TAG_BASE, Tag(100));
+/// assert_eq!(Auth::Logout.global_tag(), Some(Tag(101)));
+/// assert_eq!(Auth::local_tag(Tag(101)), Some(Tag(1)));
+/// assert_eq!(Auth::local_tag(Tag(102)), None);
+/// ```
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub trait TagBase: Match {
+    /// The global tag of the case with tag `0`. The case with tag `n` has the global tag
+    /// `TAG_BASE + n`.
+    const TAG_BASE: Tag;
+
+    /// The global tag of this value, which is its [`tag`](Match::tag) offset by
+    /// [`TAG_BASE`](TagBase::TAG_BASE), or `None` if its tag is unknown.
+    #[inline]
+    fn global_tag(&self) -> Option<Tag> {
+        self.tag().map(|tag| Tag(Self::TAG_BASE.get() + tag))
+    }
+
+    /// The tag of the case with the given global tag, or `None` if no case has it.
+    fn local_tag(global: Tag) -> Option<Tag>;
+}
+
+/// An implementation of [`GlobalCase`] defines the case of a type with the global tag `G`, which is
+/// the case `G - TAG_BASE` of the type, as given by its implementation of [`TagBase`].
+///
+/// The `case!` macro uses this to extract the case for an arm whose tag is written as a constant
+/// expression giving a global tag, such as `AUTH_BASE + 1`, rather than as a literal tag.
+pub trait GlobalCase<const G: usize>: TagBase + Sized {
+    /// The type of the data contained in the case with the global tag `G`.
+    type Case;
+
+    /// If the value's [`global_tag`](TagBase::global_tag) is `G`, return that case.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function when
+    /// [`self.global_tag()`](TagBase::global_tag) would return anything other than `Some(Tag(G))`.
+    unsafe fn global_case(this: Self) -> Self::Case;
+}
+
+mod sealed {
+    /// The kinds of [`Range`](crate::Match::Range) a type implementing [`Match`](crate::Match)
+    /// may have.
+    pub trait Range {}
+    impl<const N: usize> Range for super::Exhaustive<N> {}
+    impl Range for super::Nonexhaustive {}
+}
+
+#[macro_use]
+mod impls;
+
+pub mod ffi;
+
+#[cfg(feature = "futures")]
+pub mod futures;
+
+pub mod once;
+
+pub mod open;
+
+pub mod option;
+
+pub mod result;
+
+pub mod time;
+
+pub mod unknown;
+
+#[cfg(feature = "unstable-ranges")]
+pub mod unstable_ranges {
+    //! An escape hatch for defining new kinds of [`Range`](crate::Match::Range), for research into
+    //! richer notions of exhaustiveness, such as sparse sets of tags or tags which change with the
+    //! state of a protocol.
+    //!
+    //! This module is only available when the `unstable-ranges` feature is enabled. It is
+    //! permanently unstable: it is exempt from semantic versioning, and may change or disappear in
+    //! any release. Don't depend on it in anything but prototypes.
+    //!
+    //! # Obligations
+    //!
+    //! A type implementing [`UnstableRange`] should be uninhabited, like [`Exhaustive`] and
+    //! [`Nonexhaustive`], since it is only ever used as a marker. Vesta itself knows nothing about
+    //! new ranges: [`case!`] treats a type whose range is neither [`Exhaustive`] nor
+    //! [`Nonexhaustive`] as non-exhaustive, requiring a `_` arm, and nothing in this crate relies
+    //! on what a new range means. Any code which *does* rely on it to skip checks (for instance, by
+    //! calling [`unreachable`](crate::unreachable) for tags outside the range) must treat the range
+    //! as part of the safety contract of [`Match`](crate::Match): an implementation of
+    //! [`Match`](crate::Match) whose [`tag`](crate::Match::tag) disagrees with its range is
+    //! unsound.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use vesta::{case, Match, unstable_ranges::UnstableRange};
+    //!
+    //! /// A range whose tags are always among the bits set in `MASK`.
+    //! pub enum Sparse<const MASK: u64> {}
+    //!
+    //! impl<const MASK: u64> UnstableRange for Sparse<MASK> {}
+    //!
+    //! /// All the tags a value of the type `T` could have.
+    //! fn possible_tags<T: Match<Range = Sparse<MASK>>, const MASK: u64>() -> Vec<usize> {
+    //!     (0..64).filter(|tag| MASK & (1 << tag) != 0).collect()
+    //! }
+    //!
+    //! /// A number, whose tag is `0` if it's even and `2` if it's odd.
+    //! struct Parity(u8);
+    //!
+    //! unsafe impl Match for Parity {
+    //!     type Range = Sparse<0b101>;
+    //!
+    //!     fn tag(&self) -> Option<usize> {
+    //!         Some(if self.0 % 2 == 0 { 0 } else { 2 })
+    //!     }
+    //! }
+    //!
+    //! assert_eq!(possible_tags::<Parity, _>(), [0, 2]);
+    //!
+    //! // `case!` doesn't know about the new range, so it needs a `_` arm
+    //! assert_eq!(case!(ref Parity(3) { _ => "any" }), "any");
+    //! assert_eq!(Parity(3).tag(), Some(2));
+    //! ```
+    //!
+    //! [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+    //! [`Exhaustive`]: crate::Exhaustive
+    //! [`Nonexhaustive`]: crate::Nonexhaustive
+
+    /// The sealed trait constraining [`Match::Range`](crate::Match::Range), unsealed under a
+    /// different name so that new kinds of range can be defined, subject to the obligations
+    /// described in the [module documentation](self).
+    pub use crate::sealed::Range as UnstableRange;
+}
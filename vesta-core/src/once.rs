@@ -0,0 +1,162 @@
+//! Matching on whether lazily-initialized values, such as globals in a [`OnceLock`], have been
+//! initialized yet, without racing other threads.
+//!
+//! Startup and teardown code often needs to do something different depending on whether a global
+//! has been initialized. A [`OnceLock`] can't implement [`Match`] itself: another thread could
+//! initialize it between reading its tag and extracting its case, so that a value found to be
+//! uninitialized is initialized by the time its case is used. Instead, [`OnceExt::state`] takes a
+//! [`OnceState`], a snapshot of the state of the cell at a single moment, which can then be matched
+//! like any other enum:
+//!
+//! | Tag | `OnceState<'a, T>`      |
+//! |-----|-------------------------|
+//! | 0   | `Uninit`: `()`          |
+//! | 1   | `Init`: `&'a T`         |
+//!
+//! A snapshot of an initialized cell stays accurate, since the cell can't be uninitialized while it
+//! is borrowed. A snapshot of an uninitialized cell may be out of date as soon as it is taken, if
+//! other threads may initialize the cell, so it only says that the cell *was* uninitialized.
+//!
+//! With the `lazy-lock` feature, which needs Rust 1.94, snapshots can also be taken of a
+//! `LazyLock`, without initializing it, using `OnceState::of_lazy` or [`OnceExt::state`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::OnceLock;
+//! use vesta::{case, once::OnceExt};
+//!
+//! static CONFIG: OnceLock<String> = OnceLock::new();
+//!
+//! let describe = || {
+//!     case!(CONFIG.state() {
+//!         0 => "not configured yet".to_string(),
+//!         1(config) => format!("configured with {}", config),
+//!     })
+//! };
+//!
+//! assert_eq!(describe(), "not configured yet");
+//! CONFIG.set("defaults".to_string()).unwrap();
+//! assert_eq!(describe(), "configured with defaults");
+//! ```
+
+use std::{fmt, sync::OnceLock};
+
+#[cfg(feature = "lazy-lock")]
+use std::sync::LazyLock;
+
+use crate::{unreachable, Case, CaseMut, CaseRef, Exhaustive, Match, MatchMeta};
+
+/// A snapshot of whether a lazily-initialized value had been initialized, and of the value if so,
+/// as described in the [module documentation](self).
+pub enum OnceState<'a, T> {
+    /// The value had not been initialized.
+    Uninit,
+    /// The value had been initialized, and is this.
+    Init(&'a T),
+}
+
+impl<'a, T> OnceState<'a, T> {
+    /// Take a snapshot of the state of a [`OnceLock`].
+    pub fn of(cell: &'a OnceLock<T>) -> Self {
+        match cell.get() {
+            Some(value) => OnceState::Init(value),
+            None => OnceState::Uninit,
+        }
+    }
+
+    /// Take a snapshot of the state of a [`LazyLock`], without initializing it.
+    ///
+    /// This is only available with the `lazy-lock` feature, since it needs Rust 1.94.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::LazyLock;
+    /// use vesta::{case, once::OnceExt, MatchMeta};
+    ///
+    /// static TABLE: LazyLock<Vec<u32>> = LazyLock::new(|| (0..4).collect());
+    ///
+    /// // Checking the state doesn't initialize the table
+    /// assert!(TABLE.state().get().is_none());
+    /// assert_eq!(TABLE.len(), 4);
+    /// assert_eq!(case!(TABLE.state() { 0 => 0, 1(table) => table.len() }), 4);
+    /// assert_eq!(TABLE.state().case_name(), Some("Init"));
+    /// ```
+    #[cfg(feature = "lazy-lock")]
+    pub fn of_lazy<F>(lazy: &'a LazyLock<T, F>) -> Self {
+        match LazyLock::get(lazy) {
+            Some(value) => OnceState::Init(value),
+            None => OnceState::Uninit,
+        }
+    }
+
+    /// The value, if it had been initialized.
+    pub fn get(self) -> Option<&'a T> {
+        match self {
+            OnceState::Uninit => None,
+            OnceState::Init(value) => Some(value),
+        }
+    }
+}
+
+impl<T> Clone for OnceState<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for OnceState<'_, T> {}
+
+impl<T: fmt::Debug> fmt::Debug for OnceState<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnceState::Uninit => f.write_str("Uninit"),
+            OnceState::Init(value) => f.debug_tuple("Init").field(value).finish(),
+        }
+    }
+}
+
+impl_match! {
+    impl['a, T] OnceState<'a, T> {
+        Uninit,
+        Init(value: &'a T),
+    }
+}
+
+/// Taking a [`OnceState`] snapshot of a lazily-initialized value, as described in the [module
+/// documentation](self).
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::OnceLock;
+/// use vesta::{case, once::OnceExt, MatchMeta};
+///
+/// let cell = OnceLock::new();
+/// assert!(cell.state().get().is_none());
+/// assert_eq!(cell.state().case_name(), Some("Uninit"));
+///
+/// cell.get_or_init(|| vec![1, 2, 3]);
+/// assert_eq!(case!(cell.state() { 0 => 0, 1(v) => v.len() }), 3);
+/// assert_eq!(cell.state().case_name(), Some("Init"));
+/// ```
+pub trait OnceExt<T> {
+    /// Take a snapshot of whether this value has been initialized, and of the value if so.
+    fn state(&self) -> OnceState<'_, T>;
+}
+
+impl<T> OnceExt<T> for OnceLock<T> {
+    #[inline]
+    fn state(&self) -> OnceState<'_, T> {
+        OnceState::of(self)
+    }
+}
+
+#[cfg(feature = "lazy-lock")]
+impl<T, F> OnceExt<T> for LazyLock<T, F> {
+    #[inline]
+    fn state(&self) -> OnceState<'_, T> {
+        OnceState::of_lazy(self)
+    }
+}
@@ -0,0 +1,139 @@
+//! Matching on exhaustive types as though they were non-exhaustive, for call sites which should
+//! keep compiling if the type gains cases.
+//!
+//! A type whose [`Range`](Match::Range) is [`Exhaustive`](crate::Exhaustive) can be matched without
+//! a `_` arm, so a match on it stops compiling as soon as its author adds a case, even in a minor
+//! release. Wrapping the value in an [`Open`] gives it the same tag and the same cases, but makes
+//! its range [`Nonexhaustive`], so every match on it needs a `_` arm, just as if the type were
+//! already `#[non_exhaustive]`. Nothing else changes: the wrapper has the same representation as
+//! the value, and its tag and cases are those of the value.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta::{case, open::Open};
+//!
+//! // A library type which may gain cases in a later version
+//! #[derive(vesta::Match)]
+//! pub enum Shape {
+//!     Circle(f64),
+//!     Square(f64),
+//! }
+//!
+//! fn area(shape: Shape) -> Option<f64> {
+//!     case!(Open(shape) {
+//!         0(r) => Some(3.0 * r * r),
+//!         1(side) => Some(side * side),
+//!         _ => None,
+//!     })
+//! }
+//!
+//! assert_eq!(area(Shape::Square(2.0)), Some(4.0));
+//! ```
+//!
+//! Without the `_` arm, the match doesn't compile, even though every current case is covered:
+//!
+//! ```compile_fail
+//! # use vesta::{case, open::Open};
+//! # #[derive(vesta::Match)]
+//! # pub enum Shape {
+//! #     Circle(f64),
+//! #     Square(f64),
+//! # }
+//! # let shape = Shape::Circle(1.0);
+//! case!(Open(shape) {
+//!     0(r) => 3.0 * r * r,
+//!     1(side) => side * side,
+//! });
+//! ```
+
+use crate::{Case, CaseMut, CaseRef, Match, MatchMeta, Nonexhaustive};
+
+/// A wrapper for a value which is matched with the same tag and cases as the value itself, but
+/// as though its type were non-exhaustive, as described in the [module documentation](self).
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{case, open::Open};
+///
+/// let value = Open::from(Some(1));
+/// let n = case!(ref value {
+///     1(n) => *n,
+///     _ => 0,
+/// });
+/// assert_eq!(n, 1);
+/// assert_eq!(value.into_inner(), Some(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct Open<T>(pub T);
+
+impl<T> Open<T> {
+    /// The value itself.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Open<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Open(value)
+    }
+}
+
+// The tag of the value is within any range, since every range is within `Nonexhaustive`
+unsafe impl<T: Match> Match for Open<T> {
+    type Range = Nonexhaustive;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        self.0.tag()
+    }
+}
+
+impl<T: MatchMeta> MatchMeta for Open<T> {
+    const NAME: &'static str = T::NAME;
+    const CASE_NAMES: &'static [&'static str] = T::CASE_NAMES;
+    const CASE_ALIASES: &'static [&'static [&'static str]] = T::CASE_ALIASES;
+}
+
+impl<T: Case<N>, const N: usize> Case<N> for Open<T> {
+    type Case = T::Case;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        Case::<N>::case(this.0)
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        Open(Case::<N>::uncase(case))
+    }
+}
+
+impl<T: CaseRef<N>, const N: usize> CaseRef<N> for Open<T> {
+    type Ref<'a>
+        = T::Ref<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        CaseRef::<N>::case_ref(&this.0)
+    }
+}
+
+impl<T: CaseMut<N>, const N: usize> CaseMut<N> for Open<T> {
+    type Mut<'a>
+        = T::Mut<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_mut(this: &mut Self) -> Self::Mut<'_> {
+        CaseMut::<N>::case_mut(&mut this.0)
+    }
+}
@@ -0,0 +1,167 @@
+//! Flattened matching on optional values.
+//!
+//! An [`Option`] of a matchable type is matched like any other enum, with `None` as case 0 and
+//! `Some` as case 1, so getting at the case of the value inside needs a second, nested [`case!`].
+//! Wrapping the option in a [`FlatOption`] instead gives it one case for `None`, followed by one
+//! for each case of the value inside, so it can be matched in one level:
+//!
+//! | Tag     | `FlatOption<T>`                       |
+//! |---------|---------------------------------------|
+//! | 0       | `None`: `()`                          |
+//! | `n + 1` | `Some(t)`, where `t` has tag `n`      |
+//!
+//! This requires the type inside to implement [`OptionCases`] and [`CaseInOption`], which are
+//! implemented by the [`Match` derive] when given the `#[vesta(flat_option)]` option. All the
+//! methods involved are inlined, so matching on a [`FlatOption`] optimizes to the same checks as
+//! nested `match`es would, including of the niche of the option.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta::{case, option::FlatOption, Match};
+//!
+//! #[derive(Match)]
+//! #[vesta(flat_option)]
+//! enum Message {
+//!     Ping,
+//!     Data(Vec<u8>),
+//! }
+//!
+//! fn describe(message: Option<Message>) -> String {
+//!     case!(FlatOption(message) {
+//!         0 => "nothing".to_string(),
+//!         1 => "ping".to_string(),
+//!         2(data) => format!("{} bytes", data.len()),
+//!     })
+//! }
+//!
+//! assert_eq!(describe(None), "nothing");
+//! assert_eq!(describe(Some(Message::Ping)), "ping");
+//! assert_eq!(describe(Some(Message::Data(vec![1, 2]))), "2 bytes");
+//! ```
+//!
+//! [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+//! [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+
+use crate::{sealed, Case, CaseRef, Match};
+
+/// An [`Option`] of a matchable type, matched with one case for `None` followed by the cases of
+/// the value inside, as described in the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlatOption<T>(pub Option<T>);
+
+impl<T> From<Option<T>> for FlatOption<T> {
+    fn from(option: Option<T>) -> Self {
+        FlatOption(option)
+    }
+}
+
+impl<T> From<FlatOption<T>> for Option<T> {
+    fn from(FlatOption(option): FlatOption<T>) -> Self {
+        option
+    }
+}
+
+/// An exhaustive type whose options can be flattened into a [`FlatOption`]. This is implemented by
+/// the [`Match` derive] when given the `#[vesta(flat_option)]` option.
+///
+/// # Safety
+///
+/// This type must be exhaustive, with a [`Range`](Match::Range) of
+/// [`Exhaustive<N>`](crate::Exhaustive) for some `N`, and [`WithNone`](OptionCases::WithNone)
+/// must be [`Exhaustive<{ N + 1 }>`](crate::Exhaustive). This type must implement
+/// [`CaseInOption<M>`](CaseInOption) for every `M` up to and including `N`.
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub unsafe trait OptionCases: Match {
+    /// The [`Range`](Match::Range) of a [`FlatOption`] of this type, which has one more case than
+    /// this type does.
+    type WithNone: sealed::Range;
+}
+
+/// The `N`th case of a [`FlatOption`] of this type: `()` if `N` is 0, or the case `N - 1` of this
+/// type otherwise. This is implemented by the [`Match` derive] when given the
+/// `#[vesta(flat_option)]` option.
+///
+/// # Safety
+///
+/// The methods of this trait must behave like those of [`Case`] and [`CaseRef`] for `Option<Self>`,
+/// with the tags described in the [module documentation](self).
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub unsafe trait CaseInOption<const N: usize>: OptionCases + Sized {
+    /// The type of the `N`th case of a [`FlatOption`] of this type.
+    type Case;
+
+    /// The type of a reference to the `N`th case of a [`FlatOption`] of this type.
+    type Ref<'a>
+    where
+        Self: 'a;
+
+    /// Extract the `N`th case of a [`FlatOption`] of this type from the option inside it.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function unless the [`tag`](Match::tag) of a
+    /// [`FlatOption`] of this option would be `Some(n)`, where `n = N`.
+    unsafe fn case(this: Option<Self>) -> Self::Case;
+
+    /// Put the `N`th case of a [`FlatOption`] of this type back together into an option.
+    fn uncase(case: Self::Case) -> Option<Self>;
+
+    /// Extract the `N`th case of a [`FlatOption`] of this type from the option inside it, if the
+    /// option has that case, or return the option otherwise.
+    fn try_case(this: Option<Self>) -> Result<Self::Case, Option<Self>>;
+
+    /// Extract a reference to the `N`th case of a [`FlatOption`] of this type from the option
+    /// inside it.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function unless the [`tag`](Match::tag) of a
+    /// [`FlatOption`] of this option would be `Some(n)`, where `n = N`.
+    unsafe fn case_ref(this: &Option<Self>) -> Self::Ref<'_>;
+}
+
+unsafe impl<T: OptionCases> Match for FlatOption<T> {
+    type Range = T::WithNone;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        match &self.0 {
+            None => Some(0),
+            Some(value) => value.tag().map(|tag| tag + 1),
+        }
+    }
+}
+
+impl<T: CaseInOption<N>, const N: usize> Case<N> for FlatOption<T> {
+    type Case = T::Case;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        CaseInOption::<N>::case(this.0)
+    }
+
+    #[inline]
+    fn try_case(this: Self) -> Result<Self::Case, Self> {
+        CaseInOption::<N>::try_case(this.0).map_err(FlatOption)
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        FlatOption(CaseInOption::<N>::uncase(case))
+    }
+}
+
+impl<T: CaseInOption<N>, const N: usize> CaseRef<N> for FlatOption<T> {
+    type Ref<'a>
+        = T::Ref<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        CaseInOption::<N>::case_ref(&this.0)
+    }
+}
@@ -0,0 +1,231 @@
+//! Matching on results with two kinds of error in one level.
+//!
+//! Layered error handling often produces a [`Result`] whose error is itself one of two kinds, such
+//! as an I/O error or a parse error, which should be handled differently. Matching on it directly
+//! needs a second, nested `case!` to tell the kinds of error apart. Wrapping it in a [`SplitErr`]
+//! instead gives it one case for success and one for each kind of error, so it can be matched in
+//! one level:
+//!
+//! | Tag | `SplitErr<T, E1, E2>`   |
+//! |-----|-------------------------|
+//! | 0   | `Ok(t)`: `T`            |
+//! | 1   | `Err(Ok(e))`: `E1`      |
+//! | 2   | `Err(Err(e))`: `E2`     |
+//!
+//! The two kinds of error are represented as a `Result<E1, E2>`, which the standard library uses
+//! in place of an `Either` type. A [`SplitErr`] can also be made from a result nested the other
+//! way, as returned by a fallible operation which produces another result, using
+//! [`SplitErr::nested`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::num::ParseIntError;
+//! use vesta::{case, result::SplitErr};
+//!
+//! /// Read a number, failing if there is no input, or if it isn't a number.
+//! fn read(input: Option<&str>) -> Result<Result<u8, ParseIntError>, &'static str> {
+//!     input.map(str::parse).ok_or("no input")
+//! }
+//!
+//! fn describe(input: Option<&str>) -> String {
+//!     case!(SplitErr::nested(read(input)) {
+//!         0(n) => format!("read {}", n),
+//!         1(missing) => format!("failed: {}", missing),
+//!         2(error) => format!("invalid: {}", error),
+//!     })
+//! }
+//!
+//! assert_eq!(describe(Some("3")), "read 3");
+//! assert_eq!(describe(None), "failed: no input");
+//! assert_eq!(describe(Some("x")), "invalid: invalid digit found in string");
+//! ```
+
+use crate::{unreachable, Case, CaseRef, Exhaustive, Match};
+
+/// A [`Result`] whose error is one of two kinds, matched with one case for success followed by one
+/// for each kind of error, as described in the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SplitErr<T, E1, E2>(pub Result<T, Result<E1, E2>>);
+
+impl<T, E1, E2> SplitErr<T, E1, E2> {
+    /// Split a result whose error is of the first kind.
+    #[inline]
+    pub fn first(result: Result<T, E1>) -> Self {
+        SplitErr(result.map_err(Ok))
+    }
+
+    /// Split a result whose error is of the second kind.
+    #[inline]
+    pub fn second(result: Result<T, E2>) -> Self {
+        SplitErr(result.map_err(Err))
+    }
+
+    /// Split a result produced by a fallible operation whose own errors are of the first kind, and
+    /// whose successful result holds an error of the second kind.
+    #[inline]
+    pub fn nested(result: Result<Result<T, E2>, E1>) -> Self {
+        SplitErr(match result {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(error)) => Err(Err(error)),
+            Err(error) => Err(Ok(error)),
+        })
+    }
+
+    /// Merge both kinds of error into a single type which can be converted from either of them.
+    #[inline]
+    pub fn merge<E>(self) -> Result<T, E>
+    where
+        E: From<E1> + From<E2>,
+    {
+        self.0.map_err(|error| match error {
+            Ok(error) => E::from(error),
+            Err(error) => E::from(error),
+        })
+    }
+}
+
+impl<T, E1, E2> From<Result<T, Result<E1, E2>>> for SplitErr<T, E1, E2> {
+    fn from(result: Result<T, Result<E1, E2>>) -> Self {
+        SplitErr(result)
+    }
+}
+
+impl<T, E1, E2> From<SplitErr<T, E1, E2>> for Result<T, Result<E1, E2>> {
+    fn from(SplitErr(result): SplitErr<T, E1, E2>) -> Self {
+        result
+    }
+}
+
+unsafe impl<T, E1, E2> Match for SplitErr<T, E1, E2> {
+    type Range = Exhaustive<3>;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        Some(match self.0 {
+            Ok(_) => 0,
+            Err(Ok(_)) => 1,
+            Err(Err(_)) => 2,
+        })
+    }
+}
+
+impl<T, E1, E2> Case<0> for SplitErr<T, E1, E2> {
+    type Case = T;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Ok(value) => value,
+            Err(_) => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn try_case(this: Self) -> Result<Self::Case, Self> {
+        match this.0 {
+            Ok(value) => Ok(value),
+            Err(error) => Err(SplitErr(Err(error))),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        SplitErr(Ok(case))
+    }
+}
+
+impl<T, E1, E2> Case<1> for SplitErr<T, E1, E2> {
+    type Case = E1;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Err(Ok(error)) => error,
+            _ => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn try_case(this: Self) -> Result<Self::Case, Self> {
+        match this.0 {
+            Err(Ok(error)) => Ok(error),
+            result => Err(SplitErr(result)),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        SplitErr(Err(Ok(case)))
+    }
+}
+
+impl<T, E1, E2> Case<2> for SplitErr<T, E1, E2> {
+    type Case = E2;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Err(Err(error)) => error,
+            _ => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn try_case(this: Self) -> Result<Self::Case, Self> {
+        match this.0 {
+            Err(Err(error)) => Ok(error),
+            result => Err(SplitErr(result)),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        SplitErr(Err(Err(case)))
+    }
+}
+
+impl<T, E1, E2> CaseRef<0> for SplitErr<T, E1, E2> {
+    type Ref<'a>
+        = &'a T
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        match &this.0 {
+            Ok(value) => value,
+            Err(_) => unreachable(),
+        }
+    }
+}
+
+impl<T, E1, E2> CaseRef<1> for SplitErr<T, E1, E2> {
+    type Ref<'a>
+        = &'a E1
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        match &this.0 {
+            Err(Ok(error)) => error,
+            _ => unreachable(),
+        }
+    }
+}
+
+impl<T, E1, E2> CaseRef<2> for SplitErr<T, E1, E2> {
+    type Ref<'a>
+        = &'a E2
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        match &this.0 {
+            Err(Err(error)) => error,
+            _ => unreachable(),
+        }
+    }
+}
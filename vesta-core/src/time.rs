@@ -0,0 +1,151 @@
+//! Matching on whether things happened in time, without defining a new enum for each timeout.
+//!
+//! Code with deadlines keeps branching on the same question: did the work finish in time, and if
+//! not, by how much did it overshoot? An [`Elapsed`] answers it, holding either the result of work
+//! finished within its deadline, or the [`Duration`] by which the deadline was missed. Likewise, an
+//! [`Offset`] holds the result of [`SystemTime::duration_since`] as two cases, depending on whether
+//! a time is after or before another, rather than as a [`Result`] whose error hides the duration:
+//!
+//! | Tag | `Elapsed<T>`                 | `Offset`                       |
+//! |-----|------------------------------|--------------------------------|
+//! | 0   | `Within`: `T`                | `After`: [`Duration`]          |
+//! | 1   | `TimedOut`: [`Duration`]     | `Before`: [`Duration`]         |
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::{Duration, Instant};
+//! use vesta::{case, time::Elapsed};
+//!
+//! let start = Instant::now();
+//! let deadline = start + Duration::from_millis(10);
+//!
+//! let describe = |finished| {
+//!     case!(Elapsed::at(finished, deadline, "done") {
+//!         0(result) => result.to_string(),
+//!         1(overshoot) => format!("timed out by {:?}", overshoot),
+//!     })
+//! };
+//!
+//! assert_eq!(describe(start), "done");
+//! assert_eq!(describe(deadline + Duration::from_millis(5)), "timed out by 5ms");
+//! ```
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{unreachable, Case, CaseMut, CaseRef, Exhaustive, Match, MatchMeta};
+
+/// The result of work with a deadline: either its result, if it finished within the deadline, or
+/// how long after the deadline it finished, as described in the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Elapsed<T> {
+    /// The work finished within its deadline, with this result.
+    Within(T),
+    /// The work finished this long after its deadline.
+    TimedOut(Duration),
+}
+
+impl<T> Elapsed<T> {
+    /// Check whether work which finished at `finished`, with the given result, finished within
+    /// `deadline`. Finishing exactly at the deadline counts as finishing within it.
+    pub fn at(finished: Instant, deadline: Instant, result: T) -> Self {
+        if finished > deadline {
+            Elapsed::TimedOut(finished - deadline)
+        } else {
+            Elapsed::Within(result)
+        }
+    }
+
+    /// Check whether work which has just finished, with the given result, finished within
+    /// `deadline`.
+    pub fn now(deadline: Instant, result: T) -> Self {
+        Elapsed::at(Instant::now(), deadline, result)
+    }
+
+    /// Check whether work which started at `start` and has just finished, with the given result,
+    /// took no longer than `timeout`.
+    pub fn since(start: Instant, timeout: Duration, result: T) -> Self {
+        match start.checked_add(timeout) {
+            Some(deadline) => Elapsed::now(deadline, result),
+            // A deadline too far in the future to represent can't have passed
+            None => Elapsed::Within(result),
+        }
+    }
+
+    /// The result of the work, if it finished within its deadline, or otherwise how long after the
+    /// deadline it finished.
+    pub fn into_result(self) -> Result<T, Duration> {
+        match self {
+            Elapsed::Within(result) => Ok(result),
+            Elapsed::TimedOut(overshoot) => Err(overshoot),
+        }
+    }
+}
+
+impl<T> From<Elapsed<T>> for Result<T, Duration> {
+    fn from(elapsed: Elapsed<T>) -> Self {
+        elapsed.into_result()
+    }
+}
+
+impl_match! {
+    impl[T] Elapsed<T> {
+        Within(result: T),
+        TimedOut(overshoot: Duration),
+    }
+}
+
+/// How far one [`SystemTime`] is from another, in either direction, as described in the [module
+/// documentation](self).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use vesta::{case, time::Offset, CaseExt, MatchMeta};
+///
+/// let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+/// let describe = |now| {
+///     case!(Offset::between(now, modified) {
+///         0(age) => format!("modified {}s ago", age.as_secs()),
+///         1(skew) => format!("modified {}s in the future", skew.as_secs()),
+///     })
+/// };
+///
+/// assert_eq!(describe(modified + Duration::from_secs(5)), "modified 5s ago");
+/// assert_eq!(describe(SystemTime::UNIX_EPOCH), "modified 60s in the future");
+///
+/// let offset = Offset::between(SystemTime::UNIX_EPOCH, modified);
+/// assert_eq!(offset.case_name(), Some("Before"));
+/// assert_eq!(offset.try_case_ref::<1>(), Some(&Duration::from_secs(60)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Offset {
+    /// The time is this long after the reference time, or the same as it.
+    After(Duration),
+    /// The time is this long before the reference time.
+    Before(Duration),
+}
+
+impl Offset {
+    /// How far `time` is from `reference`, as found by [`SystemTime::duration_since`].
+    pub fn between(time: SystemTime, reference: SystemTime) -> Self {
+        match time.duration_since(reference) {
+            Ok(after) => Offset::After(after),
+            Err(error) => Offset::Before(error.duration()),
+        }
+    }
+
+    /// How far the current time is from `reference`, which may be before it if the system clock
+    /// has been adjusted.
+    pub fn since(reference: SystemTime) -> Self {
+        Offset::between(SystemTime::now(), reference)
+    }
+}
+
+impl_match! {
+    impl[] Offset {
+        After(after: Duration),
+        Before(before: Duration),
+    }
+}
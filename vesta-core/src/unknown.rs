@@ -0,0 +1,221 @@
+//! Exhaustive matching on non-exhaustive types, with an extra case for values whose tags are
+//! unknown.
+//!
+//! A value of a [`Nonexhaustive`](crate::Nonexhaustive) type may have no known tag, such as a
+//! variant added to a foreign `#[non_exhaustive]` enum after it was matched, or a message decoded
+//! from the wire with a tag from a newer version of a protocol. Matching on such a value needs a
+//! `_` arm, which can't tell these unknown values apart from any other unmatched case. Wrapping
+//! the value in an [`OrUnknown`] instead gives it one extra case, after all its known ones, whose
+//! payload is an [`Unknown`] holding the value and whatever tag it reported, so that it can be
+//! handled explicitly and the match can be exhaustive:
+//!
+//! | Tag                   | `OrUnknown<T>`                                        |
+//! |-----------------------|-------------------------------------------------------|
+//! | `n < T::KNOWN`        | The `n`th case of `T`                                 |
+//! | `T::KNOWN`            | [`Unknown<T>`], for every value without a known tag   |
+//!
+//! The [`Match` derive] implements [`KnownCases`] and [`CaseOrUnknown`] for every non-exhaustive
+//! enum, which is all that's needed to wrap it in an [`OrUnknown`].
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta::{case, unknown::OrUnknown, Match};
+//!
+//! #[derive(Match)]
+//! #[non_exhaustive]
+//! pub enum Message {
+//!     Ping,
+//!     Data(Vec<u8>),
+//! }
+//!
+//! fn size(message: Message) -> Result<usize, Option<usize>> {
+//!     case!(OrUnknown(message) {
+//!         0 => Ok(0),
+//!         1(data) => Ok(data.len()),
+//!         2(unknown) => Err(unknown.tag()),
+//!     })
+//! }
+//!
+//! assert_eq!(size(Message::Data(vec![1, 2])), Ok(2));
+//! ```
+//!
+//! [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+
+use crate::{sealed, Case, CaseRef, Match};
+
+/// A value of a non-exhaustive type whose [`tag`](Match::tag) is unknown, as extracted from the
+/// last case of an [`OrUnknown`].
+///
+/// This can only be obtained by extracting it from an [`OrUnknown`], so putting it back is
+/// guaranteed to give a value whose tag is still unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unknown<T> {
+    tag: Option<usize>,
+    value: T,
+}
+
+impl<T> Unknown<T> {
+    /// The tag reported by the value, if it reported one at all. This is never less than the
+    /// number of known cases.
+    pub fn tag(&self) -> Option<usize> {
+        self.tag
+    }
+
+    /// A reference to the value itself.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The value itself.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+/// A wrapper for a value of a non-exhaustive type, matched exhaustively with one extra case for
+/// values whose tags are unknown, as described in the [module documentation](self).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::ErrorKind;
+/// use vesta::{case, unknown::OrUnknown};
+///
+/// fn retry(kind: ErrorKind) -> bool {
+///     case!(OrUnknown(kind) {
+///         10 => true, // `WouldBlock`
+///         13 => true, // `TimedOut`
+///         18(unknown) => {
+///             // Kinds of error added to the standard library since Vesta last listed them
+///             assert_eq!(unknown.tag(), None);
+///             false
+///         }
+///         _ => false,
+///     })
+/// }
+///
+/// assert!(retry(ErrorKind::TimedOut));
+/// assert!(!retry(ErrorKind::OutOfMemory));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrUnknown<T>(pub T);
+
+/// A non-exhaustive type with a fixed number of known cases, which can therefore be wrapped in an
+/// [`OrUnknown`]. This is implemented by the [`Match` derive] for every non-exhaustive enum.
+///
+/// # Safety
+///
+/// [`WithUnknown`](KnownCases::WithUnknown) must be
+/// [`Exhaustive<{ KNOWN + 1 }>`](crate::Exhaustive), and this type must implement
+/// [`CaseOrUnknown<N>`](CaseOrUnknown) for every `N` up to and including `KNOWN`.
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub unsafe trait KnownCases: Match {
+    /// The number of known cases of this type.
+    const KNOWN: usize;
+
+    /// The [`Range`](Match::Range) of an [`OrUnknown`] of this type, which has one more case than
+    /// this type has known cases.
+    type WithUnknown: sealed::Range;
+}
+
+/// The `N`th case of an [`OrUnknown`] of this type: either the `N`th case of this type, if `N` is
+/// less than the number of known cases, or an [`Unknown`] of this type, if it is equal to it.
+/// This is implemented by the [`Match` derive] for every non-exhaustive enum.
+///
+/// # Safety
+///
+/// For `N` less than [`KNOWN`](KnownCases::KNOWN), each item must be the same as the
+/// corresponding item of [`Case<N>`](Case) and [`CaseRef<N>`](CaseRef). For `N` equal to
+/// [`KNOWN`](KnownCases::KNOWN), the [`Case`](CaseOrUnknown::Case) and
+/// [`Ref`](CaseOrUnknown::Ref) must be [`Unknown<Self>`](Unknown) and
+/// [`Unknown<&Self>`](Unknown), made using [`unknown`] and [`unknown_ref`].
+///
+/// [`Match` derive]: https://docs.rs/vesta/latest/vesta/derive.Match.html
+pub unsafe trait CaseOrUnknown<const N: usize>: KnownCases + Sized {
+    /// The type of the `N`th case of an [`OrUnknown`] of this type.
+    type Case;
+
+    /// The type of a reference to the `N`th case of an [`OrUnknown`] of this type.
+    type Ref<'a>
+    where
+        Self: 'a;
+
+    /// Extract the `N`th case of an [`OrUnknown`] of this value.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function unless the [`tag`](Match::tag) of
+    /// [`OrUnknown`] of this value would be `Some(n)`, where `n = N`.
+    unsafe fn case(this: Self) -> Self::Case;
+
+    /// Put the `N`th case of an [`OrUnknown`] of this type back together.
+    fn uncase(case: Self::Case) -> Self;
+
+    /// Extract a reference to the `N`th case of an [`OrUnknown`] of this value.
+    ///
+    /// # Safety
+    ///
+    /// It is undefined behavior to call this function unless the [`tag`](Match::tag) of
+    /// [`OrUnknown`] of this value would be `Some(n)`, where `n = N`.
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_>;
+}
+
+/// Capture a value whose tag is unknown, for implementing [`CaseOrUnknown`].
+#[doc(hidden)]
+#[inline]
+pub fn unknown<T: Match>(value: T) -> Unknown<T> {
+    Unknown {
+        tag: value.tag(),
+        value,
+    }
+}
+
+/// Capture a reference to a value whose tag is unknown, for implementing [`CaseOrUnknown`].
+#[doc(hidden)]
+#[inline]
+pub fn unknown_ref<T: Match + ?Sized>(value: &T) -> Unknown<&T> {
+    Unknown {
+        tag: value.tag(),
+        value,
+    }
+}
+
+unsafe impl<T: KnownCases> Match for OrUnknown<T> {
+    type Range = T::WithUnknown;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        match self.0.tag() {
+            Some(tag) if tag < T::KNOWN => Some(tag),
+            _ => Some(T::KNOWN),
+        }
+    }
+}
+
+impl<T: CaseOrUnknown<N>, const N: usize> Case<N> for OrUnknown<T> {
+    type Case = T::Case;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        CaseOrUnknown::<N>::case(this.0)
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        OrUnknown(CaseOrUnknown::<N>::uncase(case))
+    }
+}
+
+impl<T: CaseOrUnknown<N>, const N: usize> CaseRef<N> for OrUnknown<T> {
+    type Ref<'a>
+        = T::Ref<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    unsafe fn case_ref(this: &Self) -> Self::Ref<'_> {
+        CaseOrUnknown::<N>::case_ref(&this.0)
+    }
+}
@@ -0,0 +1,30 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Generics, Ident};
+
+use vesta_syntax::vesta_path;
+
+/// Generate conversions between the empty enum `ident` and `Infallible` in both directions, so that
+/// errors of either type can be propagated as the other using `?`.
+pub fn derive_absurd(ident: &Ident, generics: &Generics) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let never_ident = Ident::new("never", Span::mixed_site());
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::std::convert::Infallible #where_clause {
+            #[inline]
+            fn from(#never_ident: #ident #ty_generics) -> Self {
+                #vesta_path::absurd(#never_ident)
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics ::std::convert::From<::std::convert::Infallible> for #ident #ty_generics #where_clause {
+            #[inline]
+            fn from(#never_ident: ::std::convert::Infallible) -> Self {
+                match #never_ident {}
+            }
+        }
+    }
+}
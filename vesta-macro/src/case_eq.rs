@@ -0,0 +1,63 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_quote, Generics, Ident, Type};
+
+use vesta_syntax::{audited_case, vesta_path};
+
+/// Implement `CaseEq` for the type `ident`, whose cases have fields of the given types, comparing
+/// the payloads of two values in the same case by reference. Each field type is required to
+/// implement `PartialEq` and `Debug`, rather than the type itself.
+pub fn derive_case_eq(
+    ident: &Ident,
+    generics: &Generics,
+    num_cases: usize,
+    field_types: &[Type],
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in field_types {
+        where_clause.predicates.push(parse_quote! {
+            #ty: ::std::cmp::PartialEq + ::std::fmt::Debug
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let other_ident = Ident::new("other", Span::mixed_site());
+    let arms = (0..num_cases).map(|n| {
+        let this_case = audited_case(
+            quote!(self),
+            n,
+            quote!(#vesta_path::CaseRef::<#n>::case_ref(self)),
+        );
+        let other_case = audited_case(
+            &other_ident,
+            n,
+            quote!(#vesta_path::CaseRef::<#n>::case_ref(#other_ident)),
+        );
+        quote! {
+            (::std::option::Option::Some(#n), ::std::option::Option::Some(#n)) => {
+                // Both values have the tag `n`, so the case can be borrowed from each of them
+                #vesta_path::assert::compare_payloads(
+                    unsafe #this_case,
+                    unsafe #other_case,
+                )
+            }
+        }
+    });
+
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::assert::CaseEq for #ident #ty_generics #where_clause {
+            fn compare_cases(&self, #other_ident: &Self) -> #vesta_path::assert::CaseComparison {
+                match (
+                    #vesta_path::Match::tag(self),
+                    #vesta_path::Match::tag(#other_ident),
+                ) {
+                    #(#arms)*
+                    _ => #vesta_path::assert::CaseComparison::DifferentCases,
+                }
+            }
+        }
+    }
+}
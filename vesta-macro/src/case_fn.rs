@@ -0,0 +1,58 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_quote, Generics, Ident};
+
+use vesta_syntax::{audited_case, vesta_path};
+
+/// Implement `Apply` for the exhaustive type `ident` with `num_cases` cases, for every `CaseFn`
+/// which handles each of its cases, by dispatching on the tag of a value to the handler of its case
+/// and converting the output into the unified type.
+pub fn derive_apply(ident: &Ident, generics: &Generics, num_cases: usize) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let f_ident = Ident::new("f", Span::mixed_site());
+    let tags: Vec<usize> = (0..num_cases).collect();
+
+    let mut apply_generics = generics.clone();
+    apply_generics.params.push(parse_quote!(__VestaCaseFn));
+    apply_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote! {
+            __VestaCaseFn: #vesta_path::case_fn::CaseFn<Self>
+                #(+ #vesta_path::case_fn::CaseFnAt<Self, #tags>)*
+        });
+    let (impl_generics, _, where_clause) = apply_generics.split_for_impl();
+
+    let extract_cases = tags.iter().map(|n| {
+        audited_case(
+            quote!(&self),
+            n,
+            quote!(#vesta_path::Case::<#n>::case(self)),
+        )
+    });
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::case_fn::Apply<__VestaCaseFn> for #ident #ty_generics
+        #where_clause
+        {
+            #[inline]
+            fn apply(
+                self,
+                #[allow(unused_mut)] mut #f_ident: __VestaCaseFn,
+            ) -> <__VestaCaseFn as #vesta_path::case_fn::CaseFn<Self>>::Unified {
+                match #vesta_path::Match::tag(&self) {
+                    #(::std::option::Option::Some(#tags) => ::std::convert::Into::into(
+                        #vesta_path::case_fn::CaseFnAt::<Self, #tags>::call(
+                            &mut #f_ident,
+                            // The tag is `n`, so the case can be extracted
+                            unsafe #extract_cases,
+                        ),
+                    ),)*
+                    // The derived tag of a value is always that of one of its cases
+                    _ => unsafe { #vesta_path::unreachable() },
+                }
+            }
+        }
+    }
+}
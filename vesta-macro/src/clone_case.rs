@@ -0,0 +1,189 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{ext::IdentExt, parse_quote, DataEnum, Generics, Ident, Index, Member, Path, Variant};
+
+use vesta_syntax::vesta_path;
+
+/// The path of a variant of the enum `ident`, the members of its fields, and a fresh name for each
+/// of them, for binding them all with `path { member: name, ... }`.
+fn variant_bindings(ident: &Ident, variant: &Variant) -> (TokenStream, Vec<Member>, Vec<Ident>) {
+    let constructor = &variant.ident;
+    let names = (0..variant.fields.len())
+        .map(|i| Ident::new(&format!("x_{}", i), Span::mixed_site()))
+        .collect();
+    let members = variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+    (quote!(#ident::#constructor), members, names)
+}
+
+/// The generics of an impl for case `n`, which require `bound` of the type of each of its fields.
+/// The bounds are higher-ranked, so that a field type which doesn't satisfy them, and which doesn't
+/// mention any generic parameter, leaves the impl unusable rather than being an error.
+fn case_generics(generics: &Generics, variant: &Variant, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in &variant.fields {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(for<'__vesta> #ty: #bound));
+    }
+    generics
+}
+
+/// Implement `CloneCase` for each case of the enum `ident`, requiring only the fields of each case
+/// to be `Clone`, along with an inherent method `clone_case::<N>()` calling it. If the type is
+/// validated by `check`, the clone is rebuilt into a value which `check` must accept, just as
+/// `try_uncase` would, so that a `Clone` impl which breaks the invariant can't hand out its
+/// payload.
+pub fn derive_clone_case(
+    ident: &Ident,
+    generics: &Generics,
+    data: &DataEnum,
+    check: Option<&Path>,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let impls = data.variants.iter().enumerate().map(|(n, variant)| {
+        let case_generics = case_generics(generics, variant, quote!(::std::clone::Clone));
+        let (impl_generics, _, where_clause) = case_generics.split_for_impl();
+        let (path, members, names) = variant_bindings(ident, variant);
+        let constructor = &variant.ident;
+        let value_ident = Ident::new("value", Span::mixed_site());
+        let checked = match check {
+            Some(check) => quote! {
+                if #check(&#value_ident) {
+                    ::std::result::Result::ok(<Self as #vesta_path::Case<#n>>::try_case(#value_ident))
+                } else {
+                    ::std::option::Option::None
+                }
+            },
+            None => quote! {
+                ::std::result::Result::ok(<Self as #vesta_path::Case<#n>>::try_case(#value_ident))
+            },
+        };
+        quote! {
+            #[allow(unused_qualifications)]
+            impl #impl_generics #vesta_path::CloneCase<#n> for #ident #ty_generics #where_clause {
+                #[inline]
+                fn clone_case(&self) -> ::std::option::Option<Self::Case> {
+                    match self {
+                        #path { #(#members: #names),* } => {
+                            let #value_ident = Self::#constructor {
+                                #(#members: ::std::clone::Clone::clone(#names)),*
+                            };
+                            #checked
+                        }
+                        #[allow(unreachable_patterns)]
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// A clone of the payload of this value, if it has the tag `N`, which requires only the
+            /// fields of that case to be `Clone`.
+            ///
+            /// This is generated by `#[vesta(clone_case)]`.
+            #[inline]
+            pub fn clone_case<const N: ::std::primitive::usize>(
+                &self,
+            ) -> ::std::option::Option<<Self as #vesta_path::Case<N>>::Case>
+            where
+                Self: #vesta_path::CloneCase<N>,
+            {
+                <Self as #vesta_path::CloneCase<N>>::clone_case(self)
+            }
+        }
+    }
+}
+
+/// Implement `DebugCase` for each case of the enum `ident`, requiring only the fields of each case
+/// to be `Debug`, along with an inherent method `debug_case::<N>()` returning a `DebugPayload`.
+pub fn derive_debug_case(ident: &Ident, generics: &Generics, data: &DataEnum) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let f_ident = Ident::new("f", Span::mixed_site());
+
+    let impls = data.variants.iter().enumerate().map(|(n, variant)| {
+        let case_generics = case_generics(generics, variant, quote!(::std::fmt::Debug));
+        let (impl_generics, _, where_clause) = case_generics.split_for_impl();
+        let (path, members, names) = variant_bindings(ident, variant);
+
+        // Format the variant just as `#[derive(Debug)]` would
+        let name = variant.ident.unraw().to_string();
+        let format = match variant.fields.iter().next() {
+            None => quote!(::std::fmt::Formatter::write_str(#f_ident, #name)),
+            Some(field) if field.ident.is_none() => quote! {
+                ::std::fmt::Formatter::debug_tuple(#f_ident, #name)
+                    #(.field(#names))*
+                    .finish()
+            },
+            Some(_) => {
+                let field_names = variant
+                    .fields
+                    .iter()
+                    .filter_map(|field| field.ident.as_ref())
+                    .map(|ident| ident.unraw().to_string());
+                quote! {
+                    ::std::fmt::Formatter::debug_struct(#f_ident, #name)
+                        #(.field(#field_names, #names))*
+                        .finish()
+                }
+            }
+        };
+
+        quote! {
+            #[allow(unused_qualifications)]
+            impl #impl_generics #vesta_path::DebugCase<#n> for #ident #ty_generics #where_clause {
+                fn fmt_case(
+                    &self,
+                    #f_ident: &mut ::std::fmt::Formatter,
+                ) -> ::std::option::Option<::std::fmt::Result> {
+                    match self {
+                        #path { #(#members: #names),* } => {
+                            ::std::option::Option::Some(#format)
+                        }
+                        #[allow(unreachable_patterns)]
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// The payload of this value, if it has the tag `N`, formatted for debugging in the
+            /// same way as its variant, which requires only the fields of that case to be `Debug`.
+            ///
+            /// This is generated by `#[vesta(debug_case)]`.
+            #[inline]
+            pub fn debug_case<const N: ::std::primitive::usize>(
+                &self,
+            ) -> ::std::option::Option<#vesta_path::DebugPayload<'_, Self, N>>
+            where
+                Self: #vesta_path::DebugCase<N>,
+            {
+                #vesta_path::DebugPayload::new(self)
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::Parser, parse_quote, spanned::Spanned, token::Brace, Block, Error, FnArg, Ident, ItemFn,
+    Pat, PatIdent,
+};
+
+use vesta_syntax::{CaseArm, CaseInput};
+
+/// Wrap the body of the function in a `case!` on each of its parameters marked
+/// `#[destructure(N(...))]`, with the single arm given in the attribute, so that the arm's bindings
+/// are in scope in the original body.
+pub fn destructure(attr: TokenStream, mut item: ItemFn) -> Result<TokenStream, Error> {
+    if !attr.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            "`#[destructure]` takes no arguments; mark each parameter to destructure with \
+            `#[destructure(N(...))]` instead",
+        ));
+    }
+
+    // Take the arm to use for each marked parameter, replacing the parameter's pattern
+    let mut destructured = Vec::new();
+    for (n, input) in item.sig.inputs.iter_mut().enumerate() {
+        let typed = match input {
+            FnArg::Typed(typed) => typed,
+            FnArg::Receiver(receiver) => {
+                if let Some(attr) = receiver
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("destructure"))
+                {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "`self` can't be destructured; destructure it using `case!` in the body \
+                        instead",
+                    ));
+                }
+                continue;
+            }
+        };
+        let mut arm = None;
+        let mut error = None;
+        typed.attrs.retain(|attr| {
+            if !attr.path.is_ident("destructure") {
+                return true;
+            }
+            if arm.is_some() {
+                error = Some(Error::new_spanned(
+                    attr,
+                    "a parameter can only be destructured once",
+                ));
+            }
+            arm = Some(attr.parse_args::<TokenStream>());
+            false
+        });
+        if let Some(error) = error {
+            return Err(error);
+        }
+        if let Some(arm) = arm {
+            // The parameter is moved into the `case!`, so it can only be named by an identifier,
+            // or by `_`, in which case we name it ourselves
+            let ident = match &*typed.pat {
+                Pat::Ident(PatIdent {
+                    by_ref: None,
+                    subpat: None,
+                    ident,
+                    ..
+                }) => ident.clone(),
+                Pat::Wild(_) => {
+                    let ident = Ident::new(&format!("param_{}", n), Span::mixed_site());
+                    *typed.pat = parse_quote!(#ident);
+                    ident
+                }
+                pat => {
+                    return Err(Error::new_spanned(
+                        pat,
+                        "a destructured parameter must be named by an identifier or `_`",
+                    ))
+                }
+            };
+            destructured.push((ident, arm?));
+        }
+    }
+    if destructured.is_empty() {
+        return Err(Error::new(
+            item.sig.ident.span(),
+            "`#[destructure]` needs at least one parameter marked `#[destructure(N(...))]`",
+        ));
+    }
+
+    // Nest the `case!`s so that the first parameter is matched first
+    for (ident, arm) in destructured.into_iter().rev() {
+        let span = arm.span();
+        let block = &item.block;
+        let arms = CaseArm::parse_all.parse2(quote!(#arm => #block,))?;
+        let case = CaseInput {
+            attrs: Vec::new(),
+            ref_token: None,
+            cow_token: None,
+            scrutinee: parse_quote!(#ident),
+            output_ty: None,
+            brace_token: Brace(span),
+            close_span: span,
+            arms,
+        }
+        .compile()?;
+        let body: Block = parse_quote!({ #case });
+        *item.block = body;
+    }
+
+    Ok(item.into_token_stream())
+}
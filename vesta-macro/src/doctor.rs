@@ -0,0 +1,161 @@
+use proc_macro2::{Literal, Span, TokenStream};
+use proc_macro_crate::FoundCrate;
+use quote::quote;
+use std::env;
+use syn::{
+    parse::{Parse, ParseStream},
+    Error, Ident,
+};
+
+use vesta_syntax::{messages, vesta_path};
+
+/// The features of `vesta` which `vesta::doctor!()` reports other than `macros`, in order, along
+/// with the bit of `vesta::__FEATURES` set when each is enabled, or `None` for `message-catalog`,
+/// which `vesta` passes on to this crate instead.
+const FEATURES: &[(&str, Option<u8>)] = &[
+    ("crossbeam", Some(0)),
+    ("usage", Some(1)),
+    ("audit-unsafe", Some(2)),
+    ("message-catalog", None),
+    ("unstable-ranges", Some(3)),
+    ("strum", Some(4)),
+    ("futures", Some(5)),
+];
+
+/// The number of bits used by `vesta::__FEATURES`.
+const FEATURE_BITS: u8 = 6;
+
+/// The input to `vesta::__doctor!`, as invoked by `vesta::doctor!()`, which is empty.
+pub struct DoctorInput;
+
+impl Parse for DoctorInput {
+    fn parse(_: ParseStream) -> syn::Result<Self> {
+        Ok(DoctorInput)
+    }
+}
+
+impl DoctorInput {
+    /// Report how the macros are configured for the crate being compiled, as a warning, and
+    /// generate code which only compiles if they work in it. If `vesta` can't be found among the
+    /// dependencies of the crate, the report is an error instead.
+    ///
+    /// Whether `vesta` is built with `--cfg release`, and which of its features are enabled, are
+    /// only known once `vesta::__RELEASE` and `vesta::__FEATURES` are evaluated, so a report is
+    /// written for every case, and the right one is selected by const generics, just as for the
+    /// size checks of `#[vesta(warn_large)]`.
+    pub fn expand(self) -> TokenStream {
+        let message_catalog = cfg!(feature = "message-catalog");
+        let crate_name = env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "this crate".to_string());
+        let vesta_path = vesta_path();
+
+        let mut report = format!("`vesta::doctor!()` report for `{}`:\n", crate_name);
+        let found = match proc_macro_crate::crate_name("vesta") {
+            Ok(FoundCrate::Itself) => {
+                "this is `vesta` itself, or one of its tests or examples".to_string()
+            }
+            Ok(FoundCrate::Name(name)) if name == "vesta" => {
+                "a dependency of this package, named `vesta`".to_string()
+            }
+            Ok(FoundCrate::Name(name)) => format!(
+                "a dependency of this package, renamed to `{}` in its `Cargo.toml`",
+                name
+            ),
+            Err(error) => {
+                let message = format!(
+                    "{}cannot find `vesta` among the dependencies of this package: {}\n\
+                    help: the code generated by the macros refers to `{}`, so `vesta` must be a \
+                    direct dependency of every package using them, even one which only uses them \
+                    through another crate's re-exports",
+                    report,
+                    error,
+                    quote!(#vesta_path).to_string().replace(' ', ""),
+                );
+                return Error::new(Span::call_site(), message).to_compile_error();
+            }
+        };
+        report.push_str(&format!(
+            "  path:            `{}`, found as {}\n",
+            quote!(#vesta_path).to_string().replace(' ', ""),
+            found
+        ));
+
+        // Everything after this line is the same for either report
+        let mut rest = String::new();
+
+        let catalog = match (message_catalog, env::var_os("VESTA_MESSAGE_CATALOG")) {
+            (false, _) => "not used, since the `message-catalog` feature is disabled".to_string(),
+            (true, None) => "not used, since `VESTA_MESSAGE_CATALOG` is not set".to_string(),
+            (true, Some(path)) => match messages::catalog_error() {
+                Some(error) => format!("could not be read: {}", error),
+                None => format!("read from `{}`", path.to_string_lossy()),
+            },
+        };
+        rest.push_str(&format!("  message catalog: {}\n", catalog));
+
+        let debug_expansion = match env::var_os("VESTA_DEBUG_EXPANSION") {
+            Some(value) if !value.is_empty() && value != "0" => "printed for every `case!`",
+            _ => "printed only for a `case!` with a `#[debug_expansion]` arm",
+        };
+        rest.push_str(&format!("  expansions:      {}\n", debug_expansion));
+        let stable_expansion = if vesta_syntax::stable_expansion() {
+            "in their stable form, for snapshot tests, since `VESTA_STABLE_EXPANSION` is set"
+        } else {
+            "in their usual form, since `VESTA_STABLE_EXPANSION` is not set"
+        };
+        rest.push_str(&format!("  generated as:    {}\n", stable_expansion));
+        rest.push_str(
+            "  const generics:  checked by compiling a match on a type with an exhaustive range",
+        );
+
+        let report_ident = Ident::new("__VestaDoctorReport", Span::call_site());
+        let mut reports = Vec::new();
+        for bits in 0..1u8 << FEATURE_BITS {
+            let mut features = vec!["macros"];
+            features.extend(
+                FEATURES
+                    .iter()
+                    .filter(|(_, bit)| bit.map_or(message_catalog, |bit| bits & 1 << bit != 0))
+                    .map(|(feature, _)| feature),
+            );
+            let features = format!("  features:        {}\n", features.join(", "));
+            let bits = Literal::u8_unsuffixed(bits);
+            for &release in &[false, true] {
+                let unreachable = if release {
+                    "assumed never to be reached, since `vesta` is built with `--cfg release`"
+                } else {
+                    "panics if reached, since `vesta` is built without `--cfg release`"
+                };
+                let note = format!(
+                    "{}{}  unreachable:     {}\n{}",
+                    report, features, unreachable, rest
+                );
+                reports.push(quote! {
+                    impl #report_ident<#release, #bits> {
+                        #[deprecated(note = #note)]
+                        const fn report() {}
+                    }
+                });
+            }
+        }
+
+        // This only compiles if the path to `vesta` resolves, and const generics are supported
+        quote! {
+            #[allow(unused_qualifications, dead_code)]
+            const _: () = {
+                fn exhaustive<T, const N: ::std::primitive::usize>()
+                where
+                    T: #vesta_path::Match<Range = #vesta_path::Exhaustive<N>>,
+                {
+                }
+                let _: fn() = exhaustive::<::std::option::Option<()>, 2>;
+
+                struct #report_ident<
+                    const RELEASE: ::std::primitive::bool,
+                    const FEATURES: ::std::primitive::u8,
+                >;
+                #(#reports)*
+                #report_ident::<{ #vesta_path::__RELEASE }, { #vesta_path::__FEATURES }>::report();
+            };
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Attribute, DataEnum, Error, Generics, Ident, Meta, NestedMeta, Type};
+
+use vesta_syntax::vesta_path;
+
+/// The primitive representations an enum may have, whose discriminants are of that type.
+const PRIMITIVE_REPRS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// The type of the discriminant of an enum with the given attributes, if it has a `#[repr(C)]` or
+/// primitive representation. A primitive representation takes precedence, as in `#[repr(C, u8)]`,
+/// and otherwise the discriminant of a `#[repr(C)]` enum is a C `int`.
+fn discriminant_type(attrs: &[Attribute]) -> Option<Type> {
+    let mut c = false;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("repr")) {
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let NestedMeta::Meta(Meta::Path(path)) = nested {
+                    if path.is_ident("C") {
+                        c = true;
+                    } else if let Some(repr) = PRIMITIVE_REPRS.iter().find(|r| path.is_ident(r)) {
+                        let repr = Ident::new(repr, Span::call_site());
+                        return Some(syn::parse_quote!(::std::primitive::#repr));
+                    }
+                }
+            }
+        }
+    }
+    if c {
+        Some(syn::parse_quote!(::std::os::raw::c_int))
+    } else {
+        None
+    }
+}
+
+/// Implement `FfiEnum` for the enum `ident`, checking raw discriminants against those of its
+/// variants, which are computed just as the compiler does: each is either given explicitly, or is
+/// one more than that of the previous variant, starting from zero. The variants of `data` must be
+/// in the order they were declared, which is what their discriminants follow, and each is given
+/// the tag at which its name appears in `case_names`.
+pub fn derive_ffi(
+    ident: &Ident,
+    generics: &Generics,
+    attrs: &[Attribute],
+    data: &DataEnum,
+    case_names: &[Ident],
+) -> TokenStream {
+    let discriminant_ty = match discriminant_type(attrs) {
+        Some(ty) => ty,
+        None => {
+            return Error::new(
+                ident.span(),
+                format!(
+                    "cannot check the discriminants of `{}` unless it is `#[repr(C)]` or has a \
+                    primitive representation such as `#[repr(u8)]`",
+                    ident
+                ),
+            )
+            .to_compile_error()
+        }
+    };
+
+    let vesta_path = vesta_path();
+    let discriminant_ident = Ident::new("discriminant", Span::mixed_site());
+    let mut consts = Vec::new();
+    let mut arms = Vec::new();
+    let mut previous: Option<Ident> = None;
+    for (i, variant) in data.variants.iter().enumerate() {
+        let const_ident = format_ident!("DISCRIMINANT_{}", i, span = Span::mixed_site());
+        let tag = case_names
+            .iter()
+            .position(|name| *name == variant.ident)
+            .expect("every variant has a tag");
+        let value = match (&variant.discriminant, &previous) {
+            (Some((_, expr)), _) => quote!(#expr),
+            (None, Some(previous)) => quote!(#previous + 1),
+            (None, None) => quote!(0),
+        };
+        consts.push(quote!(const #const_ident: #discriminant_ty = #value;));
+        arms.push(quote!(#const_ident => ::std::option::Option::Some(#tag),));
+        previous = Some(const_ident);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        #[allow(unused_qualifications)]
+        unsafe impl #impl_generics #vesta_path::ffi::FfiEnum for #ident #ty_generics #where_clause {
+            type Discriminant = #discriminant_ty;
+
+            #[inline]
+            fn validate_tag(
+                #discriminant_ident: #discriminant_ty,
+            ) -> ::std::option::Option<::std::primitive::usize> {
+                #(#consts)*
+                match #discriminant_ident {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    }
+}
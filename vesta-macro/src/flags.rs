@@ -0,0 +1,96 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, Path, Token,
+};
+
+use vesta_syntax::vesta_path;
+
+/// The input to `match_flags!`: a flags type followed by its flags, in priority order.
+pub struct FlagsInput {
+    ty: Path,
+    flags: Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for FlagsInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        let content;
+        let _ = braced!(content in input);
+        let flags = content.parse_terminated(Ident::parse)?;
+        Ok(FlagsInput { ty, flags })
+    }
+}
+
+impl FlagsInput {
+    /// Generate `Match` and `Case` for the flags type, where the tag of a value is the position of
+    /// its highest-priority flag, and the tag one past the last flag means no listed flag is set.
+    pub fn expand(self) -> TokenStream {
+        let vesta_path = vesta_path();
+        let FlagsInput { ty, flags } = self;
+        let none_tag = flags.len();
+        let num_cases = none_tag + 1;
+        let this_ident = Ident::new("this", Span::mixed_site());
+        let case_ident = Ident::new("case", Span::mixed_site());
+
+        // Check each flag in order, so the first one present wins
+        let tag_checks = flags.iter().enumerate().map(|(n, flag)| {
+            quote! {
+                if #ty::contains(self, #ty::#flag) {
+                    return ::std::option::Option::Some(#n);
+                }
+            }
+        });
+
+        // Each flag's case is the remaining flags once it has been removed; putting it back also
+        // removes any higher-priority flags, so that the result has the tag it came from
+        let case_impls = flags.iter().enumerate().map(|(n, flag)| {
+            let higher = flags.iter().take(n);
+            quote! {
+                #[allow(unused_qualifications)]
+                impl #vesta_path::Case<#n> for #ty {
+                    type Case = #ty;
+                    unsafe fn case(#this_ident: Self) -> Self::Case {
+                        #ty::difference(#this_ident, #ty::#flag)
+                    }
+                    fn uncase(#case_ident: Self::Case) -> Self {
+                        #(let #case_ident = #ty::difference(#case_ident, #ty::#higher);)*
+                        #ty::union(#case_ident, #ty::#flag)
+                    }
+                }
+            }
+        });
+
+        // The case for no listed flag has all of them removed when put back
+        let all_flags = flags.iter();
+
+        quote! {
+            #[allow(unused_qualifications)]
+            unsafe impl #vesta_path::Match for #ty {
+                type Range = #vesta_path::Exhaustive<#num_cases>;
+
+                fn tag(&self) -> ::std::option::Option<::std::primitive::usize> {
+                    #(#tag_checks)*
+                    ::std::option::Option::Some(#none_tag)
+                }
+            }
+
+            #(#case_impls)*
+
+            #[allow(unused_qualifications)]
+            impl #vesta_path::Case<#none_tag> for #ty {
+                type Case = #ty;
+                unsafe fn case(#this_ident: Self) -> Self::Case {
+                    #this_ident
+                }
+                fn uncase(#case_ident: Self::Case) -> Self {
+                    #(let #case_ident = #ty::difference(#case_ident, #ty::#all_flags);)*
+                    #case_ident
+                }
+            }
+        }
+    }
+}
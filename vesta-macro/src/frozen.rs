@@ -0,0 +1,129 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{ext::IdentExt, Error, Generics, Ident, LitStr};
+
+/// The offset basis and prime of the 64-bit FNV-1a hash.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Continue the FNV-1a hash `hash` of a list of case names with the next name, followed by a zero
+/// byte.
+fn hash_name(mut hash: u64, name: &str) -> u64 {
+    for byte in name.bytes().chain(Some(0)) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Write a hash as a fingerprint: 16 hexadecimal digits.
+fn hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// The fingerprint of a list of case names, in order: the 64-bit FNV-1a hash of the names, each
+/// followed by a zero byte, written as 16 hexadecimal digits. This must never change, since users
+/// write these fingerprints into their code.
+fn fingerprint(cases: &[Ident]) -> String {
+    hex(cases.iter().fold(FNV_OFFSET, |hash, case| {
+        hash_name(hash, &case.unraw().to_string())
+    }))
+}
+
+/// The length of the longest prefix of `cases` which has the fingerprint `frozen`, when the cases
+/// are named either all by their current names, or all by their earliest names: the first of their
+/// former names in `aliases`, if they have any. This way renaming frozen cases while keeping their
+/// old names as aliases doesn't unfreeze them, and the check stays linear in the number of cases.
+fn frozen_len(cases: &[Ident], aliases: &[Vec<LitStr>], frozen: &str) -> Option<usize> {
+    // The hashes of each prefix of the cases under their current and their earliest names
+    let (mut current, mut earliest) = (FNV_OFFSET, FNV_OFFSET);
+    let mut len = if hex(FNV_OFFSET) == frozen {
+        Some(0)
+    } else {
+        None
+    };
+    for (n, case) in cases.iter().enumerate() {
+        let name = case.unraw().to_string();
+        current = hash_name(current, &name);
+        earliest = match aliases.get(n).and_then(|aliases| aliases.first()) {
+            Some(alias) => hash_name(earliest, &alias.value()),
+            None => hash_name(earliest, &name),
+        };
+        if hex(current) == frozen || hex(earliest) == frozen {
+            len = Some(n + 1);
+        }
+    }
+    len
+}
+
+/// Check that the cases of the type `ident` still begin with the cases whose fingerprint is
+/// `frozen`, under either their current names or their earliest names in `aliases`, so that none
+/// of those cases have been renumbered, and generate a public constant listing the frozen cases,
+/// documented as a table of their tags.
+pub fn derive_frozen(
+    ident: &Ident,
+    generics: &Generics,
+    cases: &[Ident],
+    aliases: &[Vec<LitStr>],
+    frozen: &LitStr,
+) -> TokenStream {
+    let current = fingerprint(cases);
+    let frozen_len = match frozen_len(cases, aliases, &frozen.value()) {
+        Some(len) => len,
+        None if frozen.value().is_empty() => {
+            return Error::new(
+                frozen.span(),
+                format!(
+                    "to freeze the tags of the cases of `{}` as they are, write \
+                    `#[vesta(frozen = \"{}\")]`",
+                    ident, current
+                ),
+            )
+            .to_compile_error()
+        }
+        None => {
+            return Error::new(
+                frozen.span(),
+                format!(
+                    "the tags of the cases of `{}` have changed since they were frozen, which is a \
+                    breaking change: cases may only be added after all the frozen ones\n\
+                    if this is intentional, write `#[vesta(frozen = \"{}\")]` to freeze the tags \
+                    as they are now",
+                    ident, current
+                ),
+            )
+            .to_compile_error()
+        }
+    };
+
+    // Document the frozen cases as a table, and how to freeze any newer ones
+    let frozen_cases = &cases[..frozen_len];
+    let mut doc = format!(
+        "The cases of [`{}`] whose tags are frozen by `#[vesta(frozen = \"{}\")]`, in order of \
+        their tags. Renumbering any of these cases is a breaking change.\n\n\
+        | Tag | Case |\n|-----|------|\n",
+        ident,
+        frozen.value()
+    );
+    for (tag, case) in frozen_cases.iter().enumerate() {
+        doc.push_str(&format!("| {} | `{}` |\n", tag, case.unraw()));
+    }
+    if frozen_len < cases.len() {
+        doc.push_str(&format!(
+            "\nThe cases added since then can be frozen as well using \
+            `#[vesta(frozen = \"{}\")]`.",
+            current
+        ));
+    }
+
+    let names = frozen_cases.iter().map(|case| case.unraw().to_string());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let frozen_ident = Ident::new("FROZEN_CASES", Span::call_site());
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[doc = #doc]
+            pub const #frozen_ident: &'static [&'static ::std::primitive::str] = &[#(#names),*];
+        }
+    }
+}
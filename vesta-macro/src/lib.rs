@@ -1,6 +1,6 @@
 //! This crate defines the [`case!`] macro and [`Match`] derive macro exported by the
-//! [Vesta](https://crates.io/crates/vesta) crate, as well as the
-//! [`derive_match!`](derive_match!) macro that it uses internally.
+//! [Vesta](https://crates.io/crates/vesta) crate, as well as the [`match_flags!`] macro for bit
+//! flags.
 //!
 //! You cannot use this crate directly, because it depends on Vesta. Instead, use the `vesta` crate
 //! to use these macros.
@@ -15,105 +15,2674 @@
 
 use proc_macro::TokenStream;
 use proc_macro2::Span;
-use quote::{format_ident, quote, ToTokens};
-use std::iter::FromIterator;
+use quote::{quote, ToTokens};
+use std::collections::BTreeSet;
 use syn::{
-    parse_macro_input, parse_quote, punctuated::Punctuated, spanned::Spanned, Arm, Data, DataEnum,
-    DataStruct, DeriveInput, Error, Field, Fields, FieldsNamed, FieldsUnnamed, Generics, Ident,
-    Item, Path, Token, Type, Variant,
+    parse_macro_input, parse_quote, Data, DataEnum, DataStruct, DeriveInput, Error, Field, Ident,
+    ItemFn, LitStr, Type,
 };
 
-use vesta_syntax::{vesta_path, CaseInput};
+use vesta_syntax::{
+    derive::{
+        case_aliases, derive_match_enum, derive_match_struct, order_conditional_variants,
+        register_usage, replace_self, ImplOptions, NonExhaustive,
+    },
+    messages, CaseInput,
+};
+
+mod absurd;
+mod case_eq;
+mod case_fn;
+mod clone_case;
+mod destructure;
+mod doctor;
+mod ffi;
+mod flags;
+mod frozen;
+mod map_payloads;
+mod newtype_variants;
+mod option;
+mod options;
+mod pair;
+mod raw_form;
+mod router;
+mod select;
+mod size;
+mod strum;
+mod table;
+mod tag_base;
+mod type_list;
+mod unfold;
+mod validate;
+use absurd::derive_absurd;
+use case_eq::derive_case_eq;
+use case_fn::derive_apply;
+use clone_case::{derive_clone_case, derive_debug_case};
+use doctor::DoctorInput;
+use ffi::derive_ffi;
+use flags::FlagsInput;
+use frozen::derive_frozen;
+use map_payloads::derive_map_payloads;
+use newtype_variants::{derive_newtype_variants, payload_structs};
+use option::derive_flat_option;
+use options::Options;
+use pair::PairInput;
+use raw_form::{derive_raw_form, raw_form_idents};
+use router::{derive_router, router_idents};
+use select::SelectInput;
+use size::derive_size_check;
+use strum::derive_strum_check;
+use table::TableInput;
+use tag_base::derive_tag_base;
+use type_list::derive_type_list;
+use unfold::{derive_unfold, unfold_ident};
+use validate::derive_try_uncase;
 
 /// Match on the cases of a value implementing [`Match`].
 ///
-/// This macro is the safe and efficient way to match on something; it is faster than using chains
-/// of [`try_case`], but safe because it ensures exhaustiveness when required.
+/// This macro is the safe and efficient way to match on something; it is faster than using chains
+/// of [`try_case`], but safe because it ensures exhaustiveness when required.
+///
+/// Syntax for this macro is very similar to the syntax for `match` in Rust, except constructor
+/// names are replaced with their corresponding numerals, as defined by implementations of [`Case`].
+///
+/// Omitting a parenthesized pattern after a numeral `N` is equivalent to the pattern `N(_)`, i.e.
+/// the pattern matching all values tagged with `N`.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::case;
+///
+/// let option = Some("thing");
+///
+/// case!(option {
+///     0 => assert!(false),
+///     1(s) => assert_eq!(s, "thing"),
+/// });
+/// ```
+///
+/// # Macros in patterns
+///
+/// The pattern after a numeral is an ordinary Rust pattern, passed through unexpanded to the
+/// generated code, so it may invoke macros just like the pattern of a `match` arm can. The same
+/// goes for patterns given to a `case!` through a `$pattern:pat` fragment of a `macro_rules!`
+/// macro:
+///
+/// ```
+/// use vesta::case;
+///
+/// macro_rules! first {
+///     ($x:ident) => {
+///         ($x, _)
+///     };
+/// }
+///
+/// macro_rules! digit {
+///     () => {
+///         0..=9
+///     };
+/// }
+///
+/// macro_rules! is_error {
+///     ($value:expr, $error:pat) => {
+///         case!($value { 1($error) => true, _ => false })
+///     };
+/// }
+///
+/// fn describe(value: Result<(u8, u8), u8>) -> String {
+///     case!(value {
+///         0(first!(x)) => format!("first {}", x),
+///         1(digit!() | 100) => "small error".to_string(),
+///         1(e) => format!("error {}", e),
+///     })
+/// }
+///
+/// assert_eq!(describe(Ok((1, 2))), "first 1");
+/// assert_eq!(describe(Err(7)), "small error");
+/// assert_eq!(describe(Err(42)), "error 42");
+/// assert!(is_error!(Err::<(), u8>(3), digit!()));
+/// ```
+///
+/// # Matching by reference
+///
+/// Writing `ref` before the scrutinee matches on a reference to it, without moving it, using the
+/// implementations of [`CaseRef`] rather than [`Case`]. The patterns then bind references to the
+/// contents of each case. This also allows matching on types which are not `Sized`, such as `str`
+/// and slices:
+///
+/// ```
+/// use vesta::case;
+///
+/// fn describe(s: &str) -> String {
+///     case!(ref s {
+///         0 => "empty".to_string(),
+///         1('#', rest) => format!("comment: {}", rest),
+///         1(c, _) => format!("starts with {}", c),
+///     })
+/// }
+///
+/// assert_eq!(describe(""), "empty");
+/// assert_eq!(describe("#hi"), "comment: hi");
+/// assert_eq!(describe("hi"), "starts with h");
+///
+/// let option = Some(String::from("thing"));
+/// case!(ref option {
+///     0 => assert!(false),
+///     1(s) => assert_eq!(s, "thing"),
+/// });
+/// assert!(option.is_some());
+/// ```
+///
+/// Writing `cow` before the scrutinee instead also matches on a reference to it, but binds each
+/// case as a [`Cow`](std::borrow::Cow) borrowed from the scrutinee, just as
+/// [`CaseExt::case_cow`] returns it, so that an arm can take ownership of its case, cloning it
+/// only if it does. This applies to the cases which are borrowed as a reference to the whole case,
+/// such as those of [`Option`] and [`Result`] and those of derived enums whose variants have a
+/// single field; cases with no contents are bound as `()`, and cases with several fields, which
+/// are borrowed as tuples of references, can't be matched this way.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use vesta::{case, Match};
+///
+/// #[derive(Match)]
+/// enum Name {
+///     Anonymous,
+///     Given(String),
+/// }
+///
+/// fn shout(name: &Name) -> Cow<'_, String> {
+///     case!(cow *name {
+///         0 => Cow::Owned("nobody!".to_string()),
+///         1(given) if given.ends_with('!') => given,
+///         // Only a name without a '!' is cloned
+///         1(mut given) => {
+///             given.to_mut().push('!');
+///             given
+///         }
+///     })
+/// }
+///
+/// assert!(matches!(shout(&Name::Given("Ada!".into())), Cow::Borrowed(_)));
+/// assert_eq!(shout(&Name::Given("Ada".into())).as_str(), "Ada!");
+/// assert_eq!(shout(&Name::Anonymous).as_str(), "nobody!");
+/// ```
+///
+/// ```compile_fail
+/// use vesta::{case, Match};
+///
+/// #[derive(Match)]
+/// enum Point {
+///     Flat(u32, u32),
+/// }
+///
+/// // Error: the case of `Flat` is borrowed as `(&u32, &u32)`, not `&(u32, u32)`
+/// case!(cow Point::Flat(1, 2) {
+///     0(point) => drop(point),
+/// });
+/// ```
+///
+/// A scrutinee which is itself named `cow` is still matched by value, as in `case!(cow { ... })`.
+///
+/// # Result types
+///
+/// Writing `-> Type` after the scrutinee gives the type of the whole `case!`, which every arm must
+/// have. This pins down the type of arms which would otherwise be ambiguous, such as those which
+/// convert their results using `.into()` or `?`, without needing an intermediate `let` binding:
+///
+/// ```
+/// use std::num::ParseIntError;
+/// use vesta::case;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum ConfigError {
+///     Missing,
+///     Invalid(String),
+/// }
+///
+/// impl From<ParseIntError> for ConfigError {
+///     fn from(error: ParseIntError) -> Self {
+///         ConfigError::Invalid(error.to_string())
+///     }
+/// }
+///
+/// fn port(setting: Option<&str>) -> Result<u16, ConfigError> {
+///     let port = case!(setting -> Result<u16, ConfigError> {
+///         0 => Err(ConfigError::Missing),
+///         1(s) => Ok(s.parse()?),
+///     })?;
+///     Ok(port.max(1024))
+/// }
+///
+/// assert_eq!(port(Some("8080")), Ok(8080));
+/// assert_eq!(port(None), Err(ConfigError::Missing));
+/// assert!(matches!(port(Some("http")), Err(ConfigError::Invalid(_))));
+///
+/// let width = case!(ref Some(3u8) -> u64 { 0 => 0, 1(&n) => n.into() });
+/// assert_eq!(width, 3);
+/// ```
+///
+/// The scrutinee ends at the first `->` outside of any brackets, so a scrutinee which itself
+/// contains a `->`, such as a closure with a return type, must be wrapped in parentheses.
+///
+/// # Global tags
+///
+/// For a type whose cases have global tags, given by `#[vesta(tag_base = "...")]` with the
+/// [`Match`] derive, the tag of each arm may instead be a constant expression giving a global tag,
+/// such as `AUTH_BASE + 1` or the name of a constant. The arms then match on the global tag of the
+/// scrutinee, given by [`TagBase`], and extract its case using [`GlobalCase`]. A tag expression
+/// which contains parentheses must be wrapped in braces, as in `{ base(1) }(x) => ...`, so that
+/// they aren't mistaken for the pattern.
+///
+/// Since global tags aren't known until their constants are evaluated, these arms can't be checked
+/// for exhaustiveness, so they must be followed by a `_` arm, which is evaluated when no other arm
+/// matches. They can't be mixed with arms for literal tags, match only by value, and can't be used
+/// with any of the attributes described below. Arms are grouped by the expressions written for
+/// their tags, so all the arms for a case should use the same expression: those whose different
+/// expressions give the same global tag are never tried after the first.
+///
+/// ```
+/// use vesta::{case, Match};
+///
+/// pub const NET_BASE: usize = 200;
+///
+/// #[derive(Match)]
+/// #[vesta(tag_base = "NET_BASE")]
+/// pub enum Net {
+///     Connect(String, u16),
+///     Disconnect,
+/// }
+///
+/// fn describe(request: Net) -> String {
+///     case!(request {
+///         NET_BASE(host, 443) => format!("secure connection to {}", host),
+///         NET_BASE(host, port) => format!("connection to {}:{}", host, port),
+///         { NET_BASE + 1 } => "disconnect".to_string(),
+///         _ => unreachable!(),
+///     })
+/// }
+///
+/// assert_eq!(describe(Net::Connect("a".into(), 443)), "secure connection to a");
+/// assert_eq!(describe(Net::Connect("b".into(), 80)), "connection to b:80");
+/// assert_eq!(describe(Net::Disconnect), "disconnect");
+/// ```
+///
+/// # Shared guards
+///
+/// A guard can be attached to a whole group of arms for the same tag by writing
+/// `N(pattern) where guard { ... }`, where the arms inside the braces are written
+/// `(pattern) => ...` or `_ => ...`. The shared guard is checked once, with `pattern` bound to a
+/// reference to the case using [`CaseRef`], before any of the arms in the group are tried; if it
+/// fails, matching continues with the arms after the group. The pattern may be omitted, as in
+/// `N where guard`, if the guard does not need to look at the case.
+///
+/// Because the case is moved into the group's arms when its guard succeeds, those arms must cover
+/// every case themselves, and a group must come before any other arms for the same tag. A tag with
+/// only guarded arms is not considered covered.
+///
+/// ```
+/// use vesta::case;
+///
+/// fn describe(result: Result<i64, String>) -> String {
+///     case!(result {
+///         0(n) where *n > 0 {
+///             (1) => "one".to_string(),
+///             (n) if n % 2 == 0 => format!("positive even {}", n),
+///             (n) => format!("positive odd {}", n),
+///         }
+///         0(n) => format!("not positive {}", n),
+///         1(e) => format!("error {}", e),
+///     })
+/// }
+///
+/// assert_eq!(describe(Ok(1)), "one");
+/// assert_eq!(describe(Ok(4)), "positive even 4");
+/// assert_eq!(describe(Ok(7)), "positive odd 7");
+/// assert_eq!(describe(Ok(-2)), "not positive -2");
+/// assert_eq!(describe(Err("oops".into())), "error oops");
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case;
+/// # let result: Result<i64, String> = Ok(1);
+/// case!(result {
+///     0(n) => n,
+///     // This group comes too late to ever be tried
+///     0(n) where *n > 0 {
+///         (n) => n,
+///     }
+///     1(_) => 0,
+/// });
+/// ```
+///
+/// # Default arms for some tags
+///
+/// Before the final `_` arm, there may be any number of default arms which only handle some of the
+/// values not handled by the other arms: either `_ if guard => ...`, or `_(pattern) => ...`, whose
+/// pattern matches the tag of the value (as a `usize`), and which may also have a guard. These are
+/// tried in order, after all the arms for specific tags (which must come before them), and
+/// wherever the final `_` arm would otherwise be used. This lets the unknown tags of a
+/// non-exhaustive type be told apart by range, such as those reserved for vendor extensions of a
+/// protocol, without needing an implementation of [`Case`] for every one of them. They don't count
+/// towards exhaustiveness, so a final `_` arm is still needed for non-exhaustive types.
+///
+/// ```
+/// use vesta::{case, Case, Match, Nonexhaustive};
+///
+/// /// An opcode read from the wire, whose tag is its value.
+/// struct Opcode(u8);
+///
+/// unsafe impl Match for Opcode {
+///     type Range = Nonexhaustive;
+///
+///     fn tag(&self) -> Option<usize> {
+///         Some(self.0 as usize)
+///     }
+/// }
+///
+/// // Only the opcodes `0` and `1` are known
+/// # impl Case<0> for Opcode {
+/// #     type Case = ();
+/// #     unsafe fn case(_: Self) {}
+/// #     fn uncase(_: ()) -> Self { Opcode(0) }
+/// # }
+/// # impl Case<1> for Opcode {
+/// #     type Case = ();
+/// #     unsafe fn case(_: Self) {}
+/// #     fn uncase(_: ()) -> Self { Opcode(1) }
+/// # }
+///
+/// fn describe(opcode: Opcode) -> String {
+///     case!(opcode {
+///         0 => "nop".to_string(),
+///         1 => "halt".to_string(),
+///         _(tag) if tag >= 0xf0 => format!("vendor extension {:#x}", tag),
+///         _(0x80..=0xef) => "reserved".to_string(),
+///         _ => "invalid".to_string(),
+///     })
+/// }
+///
+/// assert_eq!(describe(Opcode(1)), "halt");
+/// assert_eq!(describe(Opcode(0xf3)), "vendor extension 0xf3");
+/// assert_eq!(describe(Opcode(0x81)), "reserved");
+/// assert_eq!(describe(Opcode(0x05)), "invalid");
+/// ```
+///
+/// # Arm order
+///
+/// The arms are grouped by tag, so arms for one tag may be written interleaved with arms for other
+/// tags. The arms for each tag are always tried in the order they are written, so this never
+/// changes which guards are evaluated for a value, or in what order: only the arms for the value's
+/// own tag are ever tried. Nonetheless, a guarded arm written after arms for other tags may read as
+/// though those arms were tried first. Adding a `#[warn_interleaved]` attribute before the
+/// scrutinee warns about the first such arm for each tag with any guards, pointing out the earlier
+/// arms for its tag that it is really tried after.
+///
+/// ```
+/// use vesta::case;
+///
+/// let steps = std::cell::RefCell::new(Vec::new());
+/// let mut check = |step, result| {
+///     steps.borrow_mut().push(step);
+///     result
+/// };
+/// let result = case!(Some(3) {
+///     1(n) if check("big", n > 5) => "big",
+///     0 => "none",
+///     1(n) if check("medium", n > 2) => "medium",
+///     1(_) => "small",
+/// });
+/// assert_eq!(result, "medium");
+/// assert_eq!(*steps.borrow(), ["big", "medium"]);
+/// ```
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// # use vesta::case;
+/// # fn main() {
+/// case!(#[warn_interleaved] Some(3) {
+///     1(n) if n > 5 => "big",
+///     0 => "none",
+///     // Warning: this arm for case `1` comes after arms for other cases, but is tried right after
+///     // the earlier arms for case `1`
+///     1(n) if n > 2 => "medium",
+///     1(_) => "small",
+/// });
+/// # }
+/// ```
+///
+/// # Opaque and generic scrutinees
+///
+/// The scrutinee may have any type implementing [`Match`], including a generic type parameter or
+/// an `impl Match` returned from a function, as long as it also implements [`Case`] (or
+/// [`CaseRef`], when matching by reference) for each tag mentioned in the arms. If one of these
+/// implementations is missing, the error points at the arm which needs it:
+///
+/// ```
+/// use vesta::{case, Case, Exhaustive, Match};
+///
+/// fn maybe() -> impl Match<Range = Exhaustive<2>> + Case<0, Case = ()> + Case<1, Case = u8> {
+///     Some(7)
+/// }
+///
+/// assert_eq!(case!(maybe() { 0 => 0, 1(n) => n }), 7);
+/// ```
+///
+/// ```compile_fail
+/// use vesta::{case, Case, Match};
+///
+/// fn maybe() -> impl Match + Case<0, Case = ()> {
+///     Some(7)
+/// }
+///
+/// // Error at `1(n)`: `Case<1>` is not implemented for the opaque type
+/// case!(maybe() {
+///     0 => 0,
+///     1(n) => n,
+///     _ => 1,
+/// });
+/// ```
+///
+/// # Evaluation order
+///
+/// However the arms are written, a `case!` evaluates them in an order which is guaranteed not to
+/// change, so guards with side effects run predictably:
+///
+/// 1. The scrutinee is evaluated exactly once, and then its tag is read exactly once.
+/// 2. Only the arms for that tag are tried, in the order they are written: the shared guard of a
+///    group is evaluated once, before the arms in the group, and each arm's guard is evaluated only
+///    if its pattern matches.
+/// 3. If none of them match, or there are none, the default arms for some tags are tried in the
+///    order they are written, and then the `_` arm.
+///
+/// Every guard is evaluated at most once, and exactly one body is evaluated. The body of the `_`
+/// arm is written only once in the expansion, however many tags can fall through to it, unless an
+/// arm contains a `break` or `continue` without a label which leaves the `case!` itself, rather
+/// than a loop or closure within the arm: the expansion can't jump to the `_` arm from within such
+/// an arm, so it is copied for each tag instead. This changes only the size of the code, not what
+/// it does, nor the order in which things are dropped.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use vesta::case;
+///
+/// let steps = RefCell::new(Vec::new());
+/// let step = |name: &'static str, result: bool| {
+///     steps.borrow_mut().push(name);
+///     result
+/// };
+/// let describe = |value: Option<i32>| {
+///     case!({ step("scrutinee", true); value } {
+///         1(n) if step("big", n > 5) => "big",
+///         0 => "none",
+///         1(n) if step("even", n % 2 == 0) => "even",
+///         _ if step("partial", false) => "partial",
+///         _ => "other",
+///     })
+/// };
+///
+/// assert_eq!(describe(Some(4)), "even");
+/// assert_eq!(steps.take(), ["scrutinee", "big", "even"]);
+///
+/// assert_eq!(describe(Some(3)), "other");
+/// assert_eq!(steps.take(), ["scrutinee", "big", "even", "partial"]);
+///
+/// assert_eq!(describe(None), "none");
+/// assert_eq!(steps.take(), ["scrutinee"]);
+/// ```
+///
+/// # Drop order
+///
+/// Unless it is matched by reference, the scrutinee of `case!` is always moved into it, even if it
+/// is a variable, and everything in it is dropped exactly once, no later than the end of the
+/// statement containing the `case!`. In detail:
+///
+/// - The case is only extracted once an arm for its tag is about to be tried, after any shared
+///   guard for that arm has succeeded; shared guards only borrow the case.
+/// - Once extracted, the case is never put back together. If an arm's guard fails, the following
+///   arms for the same tag (and then the `_` arm) are tried on the same extracted case, and the
+///   guard sees its bindings by reference, just as in `match`.
+/// - Parts of the case which are moved into bindings are dropped like any other variable in the
+///   chosen arm, and the remaining parts are dropped in order, after the arm's body.
+/// - If no arm for its tag is tried, the scrutinee is dropped whole, after the body of the `_` arm.
+///   With `#[borrow_guards]`, described below, the same happens when the last arm for its tag is
+///   guarded and none of them match.
+///
+/// This is the same as `match` on a temporary. When matching on a variable, `match` instead leaves
+/// the parts it does not move in the variable, to be dropped at the end of its scope.
+///
+/// ```
+/// use vesta::{case, drop_check::DropLog};
+///
+/// let log = DropLog::new();
+/// let pair = Some((log.tracked("left"), log.tracked("right")));
+/// case!(pair {
+///     1((left, _)) if left.name() == "middle" => unreachable!(),
+///     1((_, right)) => {
+///         // The guard failing didn't drop anything
+///         log.assert_dropped(&[]);
+///         drop(right);
+///         log.assert_dropped(&["right"]);
+///     }
+///     0 => unreachable!(),
+/// });
+/// // The unused part of the case was dropped at the end of the `case!`
+/// log.assert_dropped(&["right", "left"]);
+/// ```
+///
+/// ```
+/// # use vesta::{case, drop_check::DropLog};
+/// let log = DropLog::new();
+/// case!(Some(log.tracked("value")) {
+///     1(value) if value.name() == "other" => unreachable!(),
+///     // No arm for the case matched, but it is still held until after this one
+///     _ => log.assert_dropped(&[]),
+/// });
+/// log.assert_dropped(&["value"]);
+/// ```
+///
+/// ```
+/// # use vesta::{case, drop_check::DropLog};
+/// let log = DropLog::new();
+/// for name in ["first", "second"] {
+///     case!(Some(log.tracked(name)) {
+///         // Leaving the loop from an arm copies the `_` arm, without changing the drop order
+///         1(value) if value.name() == "second" => break,
+///         _ => log.assert_dropped(&[]),
+///     });
+///     log.assert_dropped(&["first"]);
+/// }
+/// log.assert_dropped(&["first", "second"]);
+/// ```
+///
+/// The module `vesta::drop_check` provides the [`DropLog`] used above, for checking the drops of
+/// values in tests, which can be run under [Miri](https://github.com/rust-lang/miri) to catch
+/// undefined behavior as well. For types deriving [`Match`], `#[vesta(check_tags)]` also checks
+/// every extracted case against its tag in debug builds.
+///
+/// # Denying tags
+///
+/// Some cases may be forbidden in certain places, such as messages which are illegal in some
+/// phase of a protocol. A `#[deny_tags(...)]` attribute before the scrutinee lists the tags of
+/// these cases: it is an error for any arm to mention one of them, and a value with one of them is
+/// handled by the expression after `=>` at the end of the attribute, or by panicking if there is
+/// none. The denied tags count as covered, so they need no arm of their own, and they are never
+/// matched by the `_` arm.
+///
+/// ```
+/// # use vesta::case;
+/// #[derive(Debug, PartialEq)]
+/// struct Illegal;
+///
+/// fn handshake(message: Result<u8, String>) -> Result<u8, Illegal> {
+///     case!(#[deny_tags(1 => return Err(Illegal))] message {
+///         0(n) => Ok(n),
+///     })
+/// }
+///
+/// assert_eq!(handshake(Ok(1)), Ok(1));
+/// assert_eq!(handshake(Err("hello".into())), Err(Illegal));
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case;
+/// # let message: Result<u8, String> = Ok(1);
+/// case!(#[deny_tags(1)] message {
+///     0(n) => n,
+///     // Error: case `1` is denied here
+///     1(_) => 0,
+/// });
+/// ```
+///
+/// # Fall-through
+///
+/// As with `match`, if arms for the tag of the scrutinee are tried but none of them match, because
+/// their guards or patterns fail, the `_` arm is used. To catch this instead, add an
+/// `#[on_fallthrough(...)]` attribute before the scrutinee, giving an expression to evaluate in
+/// place of the `_` arm whenever this happens. This also lets the arms for a tag be guarded
+/// without any `_` arm at all. A bare `#[on_fallthrough]` panics when this happens in debug builds,
+/// and falls through to the `_` arm as usual otherwise, or panics in every build if there is no `_`
+/// arm to fall through to.
+///
+/// ```
+/// # use vesta::case;
+/// fn check(reading: Option<i64>) -> Result<i64, String> {
+///     case!(#[on_fallthrough(Err("reading out of range".into()))] reading {
+///         1(n) if (0..100).contains(&n) => Ok(n),
+///         _ => Ok(0),
+///     })
+/// }
+///
+/// assert_eq!(check(Some(42)), Ok(42));
+/// assert_eq!(check(Some(-1)), Err("reading out of range".into()));
+/// assert_eq!(check(None), Ok(0));
+/// ```
+///
+/// ```should_panic
+/// # use vesta::case;
+/// # let reading = Some(-1);
+/// // Panics: no arm for case `1` matched
+/// case!(#[on_fallthrough] reading {
+///     1(n) if n >= 0 => n,
+///     _ => 0,
+/// });
+/// ```
+///
+/// ```should_panic
+/// # use vesta::case;
+/// # let reading = Some(-1);
+/// // Panics in every build: no arm for case `1` matched
+/// case!(#[on_fallthrough] reading {
+///     0 => 0,
+///     1(n) if n >= 0 => n,
+/// });
+/// ```
+///
+/// # Borrowing guards
+///
+/// Normally, the case is extracted from the scrutinee before any arm for its tag is tried, so if
+/// all their guards fail, the `_` arm runs after the scrutinee has already been taken apart. A
+/// `#[borrow_guards]` attribute before the scrutinee changes this: each guarded arm, and each arm
+/// before it, is first matched against a reference to the case, obtained using [`CaseRef`], and
+/// the case is only extracted using [`Case`] once an arm is known to match. The bindings of the
+/// pattern are then references within the guard, as when matching by reference, and if no arm for
+/// the tag matches, the scrutinee is left whole for the `_` arm or `#[on_fallthrough(...)]` to
+/// handle. Because of this, the last arm for a tag may only be guarded if one of these exists.
+///
+/// ```
+/// use vesta::{case, drop_check::DropLog};
+///
+/// let log = DropLog::new();
+/// let pair = Some((log.tracked("left"), log.tracked("right")));
+/// case!(#[borrow_guards] pair {
+///     1((left, _)) if left.name() == "middle" => unreachable!(),
+///     _ => log.assert_dropped(&[]),
+/// });
+/// // The scrutinee was dropped whole, after the `_` arm
+/// log.assert_dropped(&["left", "right"]);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case;
+/// # let reading = Some(-1);
+/// // Error: case `1` is not covered when the guard of its last arm fails
+/// case!(#[borrow_guards] reading {
+///     0 => 0,
+///     1(n) if *n >= 0 => n,
+/// });
+/// ```
+///
+/// # Profiling
+///
+/// A `#[profile(...)]` attribute before the scrutinee gives an expression for a counter, such as a
+/// `vesta::profile::TagCounter`, whose `observe` method is called with a reference to every value
+/// matched, before it is taken apart.
+///
+/// ```
+/// # use vesta::case;
+/// use vesta::profile::TagCounter;
+///
+/// let counter = TagCounter::<Option<u8>, 2>::new();
+/// for option in [None, Some(1), Some(2)].iter().copied() {
+///     case!(#[profile(counter)] option {
+///         0 => {}
+///         1(_) => {}
+///     });
+/// }
+/// assert_eq!(counter.counts(), [1, 2]);
+/// ```
+///
+/// The arms for each tag are tried in the order in which that tag is first written, so a profiled
+/// `case!` also counts the tags of its own arms, from which `vesta::profile::report` suggests
+/// putting the arms for the hottest tags first.
+///
+/// # Lookup tables
+///
+/// A `#[lookup_table(Type)]` attribute before the scrutinee compiles the whole `case!` into a
+/// single read from a `static` array of `Type`, indexed by tag, rather than a `match`. This
+/// guarantees constant-time dispatch for classification, such as finding the length of each opcode.
+/// Every arm must be of the form `N => ...` or `_ => ...`, with no pattern or guard, and its body
+/// must be a constant expression of type `Type`, which must be `Copy`. The table has an entry for
+/// every tag up to the largest one mentioned, filled in by the `_` arm for those without an arm of
+/// their own, so the type of the scrutinee must be exhaustive with exactly that many cases.
+///
+/// ```
+/// use vesta::{case, Match};
+///
+/// #[derive(Match)]
+/// enum Instruction {
+///     Nop,
+///     Push(u8),
+///     Pop,
+///     Jump(u16),
+/// }
+///
+/// fn encoded_len(instruction: &Instruction) -> usize {
+///     case!(#[lookup_table(usize)] ref instruction {
+///         1 => 2,
+///         3 => 3,
+///         _ => 1,
+///     })
+/// }
+///
+/// assert_eq!(encoded_len(&Instruction::Push(7)), 2);
+/// assert_eq!(encoded_len(&Instruction::Pop), 1);
+/// assert_eq!(encoded_len(&Instruction::Jump(0x100)), 3);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case;
+/// # let option = Some(1);
+/// // Error: an arm with a pattern can't be compiled into a lookup table
+/// case!(#[lookup_table(u8)] option {
+///     0 => 0,
+///     1(n) => n,
+/// });
+/// ```
+///
+/// # Fast paths
+///
+/// A `#[fast_path]` attribute before the scrutinee checks that the `case!` dispatches on the tag
+/// alone, so that it compiles to a single branch on the tag, with no further tests once the arm
+/// for that tag is chosen. Each tag may then have only one arm, with no guard, and with a pattern
+/// which can't fail to match, built only from bindings, `_`, `..`, references, and tuples, and not
+/// from macros, whose expansions can't be checked. Anything else is a compile error pointing at the
+/// offending arm, so that performance-critical code keeps this property as it changes.
+///
+/// ```
+/// use vesta::case;
+///
+/// fn length(result: Result<(u8, u8), String>) -> usize {
+///     case!(#[fast_path] result {
+///         0((_, len)) => len as usize,
+///         1(message) => message.len(),
+///     })
+/// }
+///
+/// assert_eq!(length(Ok((1, 2))), 2);
+/// assert_eq!(length(Err("oops".into())), 4);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case;
+/// # let option = Some(1);
+/// case!(#[fast_path] option {
+///     0 => 0,
+///     // Error: a pattern which may fail to match can't be dispatched on the tag alone
+///     1(0) => 0,
+///     1(n) => n,
+/// });
+/// ```
+///
+/// # Invariant violations
+///
+/// A `case!` trusts the [`Match`] implementation of its scrutinee: if it gives a tag outside the
+/// range of the type, which only an incorrect `unsafe` implementation can do, the generated code
+/// reaches a path which should be impossible. By default, this panics, or is undefined behavior
+/// when built with `--cfg release`. An `#[on_invariant(...)]` attribute before the scrutinee
+/// chooses what happens instead for that invocation alone: `panic` always panics with a message
+/// giving the tag, `abort` aborts the process, and `unreachable` assumes the path is never reached,
+/// even in debug builds, for the hottest loops.
+///
+/// ```should_panic
+/// use vesta::{case, Case, Exhaustive, Match};
+///
+/// struct Broken;
+///
+/// // Wrong: the tag is outside the range
+/// unsafe impl Match for Broken {
+///     type Range = Exhaustive<1>;
+///     fn tag(&self) -> Option<usize> {
+///         Some(1)
+///     }
+/// }
+///
+/// impl Case<0> for Broken {
+///     type Case = ();
+///     unsafe fn case(_: Self) {}
+///     fn uncase(_: ()) -> Self {
+///         Broken
+///     }
+/// }
+///
+/// // Panics with: invariant violation in `vesta::Match` implementation: `case!` was given the tag
+/// // Some(1), which is outside the range of the type
+/// case!(#[on_invariant(panic)] Broken {
+///     0 => (),
+/// });
+/// ```
+///
+/// The same holds for a `Match` implementation which gives no tag at all for an exhaustive type,
+/// and for a `case!` compiled into a lookup table:
+///
+/// ```
+/// use std::panic::catch_unwind;
+/// use vesta::{case, Case, Exhaustive, Match};
+///
+/// struct Untagged;
+///
+/// // Wrong: an exhaustive type always has a tag
+/// unsafe impl Match for Untagged {
+///     type Range = Exhaustive<1>;
+///     fn tag(&self) -> Option<usize> {
+///         None
+///     }
+/// }
+///
+/// impl Case<0> for Untagged {
+///     type Case = ();
+///     unsafe fn case(_: Self) {}
+///     fn uncase(_: ()) -> Self {
+///         Untagged
+///     }
+/// }
+///
+/// fn message(result: std::thread::Result<u8>) -> String {
+///     *result.unwrap_err().downcast::<String>().unwrap()
+/// }
+///
+/// let matched = catch_unwind(|| case!(#[on_invariant(panic)] Untagged { 0 => 0 }));
+/// assert!(message(matched).contains("given the tag None"));
+///
+/// let looked_up = catch_unwind(|| {
+///     case!(#[lookup_table(u8)] #[on_invariant(panic)] Untagged { 0 => 0 })
+/// });
+/// assert!(message(looked_up).contains("given the tag None"));
+/// ```
+///
+/// # Unused values
+///
+/// Like `match`, a `case!` whose value is discarded is warned about when that value is of a
+/// `#[must_use]` type, such as `Result`, whichever arm produced it:
+///
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// # use vesta::case;
+/// # fn parse(s: &str) -> Result<i64, std::num::ParseIntError> { s.parse() }
+/// # let input: Option<&str> = None;
+/// case!(input {
+///     0 => Ok(0),
+///     1(s) => parse(s),
+/// });
+/// ```
+///
+/// # Debugging
+///
+/// To see the code generated for a particular invocation, add a `#[debug_expansion]` attribute to
+/// any of its arms, and the formatted expansion will be printed to standard error at compile time.
+/// To print the expansion of every invocation, set the `VESTA_DEBUG_EXPANSION` environment variable
+/// to any value other than `0` while compiling.
+///
+/// ```
+/// # use vesta::case;
+/// # let option = Some("thing");
+/// case!(option {
+///     #[debug_expansion]
+///     0 => assert!(false),
+///     1(s) => assert_eq!(s, "thing"),
+/// });
+/// ```
+///
+/// To snapshot the expansions of particular invocations in tests, use [`CaseOutput::pretty`] from
+/// the `vesta-syntax` crate as a development dependency, which gives the same formatted text on
+/// every toolchain, unlike `cargo expand`.
+///
+/// To snapshot the expansions of a whole crate instead, using a tool like `macrotest`, set the
+/// `VESTA_STABLE_EXPANSION` environment variable to any value other than `0` while compiling it.
+/// This macro and the [`Match`] derive then leave out of their expansions everything which only
+/// affects diagnostics or performance, as described for [`stable_expansion`], so that the
+/// snapshots only change when the meaning of the generated code does.
+///
+/// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
+///
+/// [`Case`]: https://docs.rs/vesta/latest/vesta/trait.Case.html
+///
+/// [`CaseRef`]: https://docs.rs/vesta/latest/vesta/trait.CaseRef.html
+///
+/// [`try_case`]: https://docs.rs/vesta/latest/vesta/trait.CaseExt.html#method.try_case
+///
+/// [`CaseExt::case_cow`]: https://docs.rs/vesta/latest/vesta/trait.CaseExt.html#method.case_cow
+///
+/// [`TagBase`]: https://docs.rs/vesta/latest/vesta/trait.TagBase.html
+///
+/// [`GlobalCase`]: https://docs.rs/vesta/latest/vesta/trait.GlobalCase.html
+///
+/// [`DropLog`]: https://docs.rs/vesta/latest/vesta/drop_check/struct.DropLog.html
+///
+/// [`CaseOutput::pretty`]:
+/// https://docs.rs/vesta-syntax/latest/vesta_syntax/struct.CaseOutput.html#method.pretty
+///
+/// [`stable_expansion`]: https://docs.rs/vesta-syntax/latest/vesta_syntax/fn.stable_expansion.html
+#[proc_macro]
+pub fn case(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as CaseInput);
+    match input.compile() {
+        Ok(output) => output.to_token_stream().into(),
+        Err(e) => expression_error(e),
+    }
+}
+
+/// Match on the cases of a pair of values at once, using a single flat `match` on their packed
+/// tags.
+///
+/// This is useful for double dispatch in hot code: rather than a `case!` nested inside another, the
+/// tags of both values are packed into one index using [`pack_tags`], so the compiler can produce a
+/// single jump table. Whether this is faster than nested matches depends on the target and the
+/// distribution of the values, so measure: the `pair` benchmark in the `vesta` crate compares them.
+///
+/// Each arm matches a pair `(a, b)` of tags, where each tag may be followed by a parenthesized
+/// pattern for its case, as in [`case!`], or be `_` to match any tag. An arm may also be `_` alone,
+/// matching any pair. Arms are tried in order, and may have guards.
+///
+/// Both values must be of exhaustive types, and every case of each must be mentioned explicitly in
+/// at least one arm, so that the number of cases of each is known. Every pair of tags must be
+/// covered by some arm. Since each pair of tags gets its own branch, the body of an arm matching
+/// several pairs of tags is duplicated into each of them.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::case_pair;
+///
+/// fn combine(a: Option<u32>, b: Result<u32, &str>) -> String {
+///     case_pair!((a, b) {
+///         (1(x), 0(y)) => format!("sum {}", x + y),
+///         (1(_), 1(e)) => format!("error {}", e),
+///         (0, 0(y)) if y > 10 => "big".to_string(),
+///         (0, _) => "nothing".to_string(),
+///     })
+/// }
+///
+/// assert_eq!(combine(Some(1), Ok(2)), "sum 3");
+/// assert_eq!(combine(Some(1), Err("oops")), "error oops");
+/// assert_eq!(combine(None, Ok(20)), "big");
+/// assert_eq!(combine(None, Ok(2)), "nothing");
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::case_pair;
+/// # let (a, b) = (Some(1), Some(2));
+/// case_pair!((a, b) {
+///     (0, 0) => (),
+///     (1, 1) => (),
+/// });
+/// ```
+///
+/// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
+///
+/// [`pack_tags`]: https://docs.rs/vesta/latest/vesta/fn.pack_tags.html
+#[proc_macro]
+pub fn case_pair(input: TokenStream) -> TokenStream {
+    match parse_macro_input!(input as PairInput).expand() {
+        Ok(output) => output.into(),
+        Err(e) => expression_error(e),
+    }
+}
+
+/// Write the arms of a [`case!`] as a standalone handler, to be applied to a value later using the
+/// methods of [`Dispatch`], so that a long chain of method calls can end in a match.
+///
+/// The arms are written exactly as in [`case!`], and the result is a closure which matches on its
+/// argument using them. Writing `ref` before the arms makes a closure which takes a reference, and
+/// matches on it by reference, for use with [`dispatch_ref`].
+///
+/// The type of the value being matched must be known where the closure is created, so
+/// `case_table!` should be used directly as the argument of [`dispatch`] or [`dispatch_ref`].
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{case_table, Dispatch};
+///
+/// let words = "the quick brown fox";
+/// let description = words
+///     .split(' ')
+///     .map(str::len)
+///     .find(|&length| length > 3)
+///     .dispatch(case_table! {
+///         0 => "all short".to_string(),
+///         1(length) => format!("a word of length {}", length),
+///     });
+/// assert_eq!(description, "a word of length 5");
+///
+/// let first = words
+///     .split_once(' ')
+///     .dispatch_ref(case_table! {
+///         ref
+///         0 => "",
+///         1((first, _)) => *first,
+///     });
+/// assert_eq!(first, "the");
+/// ```
+///
+/// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
+///
+/// [`Dispatch`]: https://docs.rs/vesta/latest/vesta/trait.Dispatch.html
+///
+/// [`dispatch`]: https://docs.rs/vesta/latest/vesta/trait.Dispatch.html#method.dispatch
+///
+/// [`dispatch_ref`]: https://docs.rs/vesta/latest/vesta/trait.Dispatch.html#method.dispatch_ref
+#[proc_macro]
+pub fn case_table(input: TokenStream) -> TokenStream {
+    match parse_macro_input!(input as TableInput).expand() {
+        Ok(output) => output.into(),
+        Err(e) => expression_error(e),
+    }
+}
+
+/// Destructure parameters of a function using the patterns of [`case!`], as Rust does for
+/// parameters of struct and tuple types, but for any type with a single case.
+///
+/// Each parameter to destructure is marked `#[destructure(N(pattern))]`, giving the single arm of a
+/// [`case!`] on it without its body. The body of the function becomes the body of that arm, so the
+/// pattern's bindings are in scope throughout it. The parameter itself must be named by an
+/// identifier or `_`, and is moved by the [`case!`]. As with any [`case!`], the arm must cover
+/// every value of the parameter's type, so the type must have exactly one case, such as a struct,
+/// and the pattern must be irrefutable.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{destructure, Match};
+///
+/// #[derive(Match)]
+/// struct Point(i64, i64);
+///
+/// #[derive(Match)]
+/// struct Meters(f64);
+///
+/// #[destructure]
+/// fn distance(#[destructure(0(x1, y1))] a: Point, #[destructure(0(x2, y2))] b: Point) -> i64 {
+///     (x2 - x1).abs() + (y2 - y1).abs()
+/// }
+///
+/// #[destructure]
+/// fn to_feet(#[destructure(0(meters))] _: Meters) -> f64 {
+///     meters * 3.28084
+/// }
+///
+/// assert_eq!(distance(Point(1, 2), Point(4, 0)), 5);
+/// assert!((to_feet(Meters(1.0)) - 3.28084).abs() < 1e-9);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::destructure;
+/// // Error: non-exhaustive patterns: `0` not covered
+/// #[destructure]
+/// fn unwrap(#[destructure(1(x))] option: Option<i64>) -> i64 {
+///     x
+/// }
+/// ```
+///
+/// [`case!`]: https://docs.rs/vesta/latest/vesta/macro.case.html
+#[proc_macro_attribute]
+pub fn destructure(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    match destructure::destructure(attr.into(), item) {
+        Ok(output) => output.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Implement [`Match`] and [`Case`] for a set of bit flags, such as a type generated by the
+/// [`bitflags`](https://crates.io/crates/bitflags) crate, so that the highest-priority set flag
+/// determines the case.
+///
+/// The flags are listed in priority order, highest first, as the names of associated constants of
+/// the type. The tag of a value is the position of the first listed flag it
+/// [`contains`](https://docs.rs/bitflags/latest/bitflags/trait.Flags.html#method.contains), and the
+/// case for that tag is the value with that flag removed, i.e. the remaining flags. If none of the
+/// listed flags is set, the tag is one past the last flag, and the case is the whole value.
+///
+/// Going the other way, [`uncase`] for a flag's tag removes every higher-priority flag before
+/// adding that flag, and for the last tag removes every listed flag, so the value it builds always
+/// has the tag it was built for.
+///
+/// The type must have `contains`, `difference`, and `union` methods callable as
+/// `T::contains(&self, T)`, `T::difference(T, T)`, and `T::union(T, T)`, as all `bitflags` types
+/// do.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{case, match_flags};
+///
+/// bitflags::bitflags! {
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     struct Interest: u8 {
+///         const READABLE = 0b001;
+///         const WRITABLE = 0b010;
+///         const ERROR = 0b100;
+///     }
+/// }
+///
+/// // Errors take priority over everything else, then reads, then writes
+/// match_flags! {
+///     Interest { ERROR, READABLE, WRITABLE }
+/// }
+///
+/// fn handle(interest: Interest) -> &'static str {
+///     case!(interest {
+///         0 => "error",
+///         1(rest) if rest.contains(Interest::WRITABLE) => "read and write",
+///         1 => "read",
+///         2 => "write",
+///         3 => "nothing",
+///     })
+/// }
+///
+/// assert_eq!(handle(Interest::all()), "error");
+/// assert_eq!(handle(Interest::READABLE | Interest::WRITABLE), "read and write");
+/// assert_eq!(handle(Interest::READABLE), "read");
+/// assert_eq!(handle(Interest::WRITABLE), "write");
+/// assert_eq!(handle(Interest::empty()), "nothing");
+///
+/// // Putting a case back together gives a value with the same tag
+/// use vesta::{Case, Match};
+/// let read = <Interest as Case<1>>::uncase(Interest::all());
+/// assert_eq!(read, Interest::READABLE | Interest::WRITABLE);
+/// assert_eq!(read.tag(), Some(1));
+/// assert_eq!(<Interest as Case<2>>::uncase(Interest::all()).tag(), Some(2));
+/// assert_eq!(<Interest as Case<3>>::uncase(Interest::all()).tag(), Some(3));
+/// ```
+///
+/// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
+///
+/// [`Case`]: https://docs.rs/vesta/latest/vesta/trait.Case.html
+///
+/// [`uncase`]: https://docs.rs/vesta/latest/vesta/trait.Case.html#tymethod.uncase
+#[proc_macro]
+pub fn match_flags(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as FlagsInput).expand().into()
+}
+
+/// Report how the macros are configured for the crate being compiled, as gathered by
+/// `vesta::doctor!()`, which should be used instead.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __doctor(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as DoctorInput).expand().into()
+}
+
+/// Receive from whichever of several channels is ready first, and match on the received message
+/// using the arms given for that channel.
+///
+/// This macro is only available from `vesta` when its `crossbeam` feature is enabled, and works
+/// with [`crossbeam_channel`](https://docs.rs/crossbeam-channel) receivers.
+///
+/// Each `recv(receiver) { ... }` block contains the arms of a [`case!`] on messages received from
+/// that receiver, and is checked for exhaustiveness just like [`case!`]. The tags in each block
+/// refer to the message type of that channel only, so different channels may carry different
+/// types. An optional `closed => ...` arm is run when the selected channel is disconnected.
+///
+/// # Panics
+///
+/// If there is no `closed` arm, panics when the selected channel is disconnected.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{select_case, crossbeam_channel::unbounded};
+///
+/// let (numbers, numbers_rx) = unbounded::<Option<u32>>();
+/// let (results, results_rx) = unbounded::<Result<&str, &str>>();
+/// numbers.send(Some(3)).unwrap();
+/// results.send(Err("oops")).unwrap();
+///
+/// let receive = || {
+///     select_case! {
+///         recv(numbers_rx) {
+///             0 => "no number".to_string(),
+///             1(n) => format!("number {}", n),
+///         }
+///         recv(results_rx) {
+///             0(s) => format!("ok {}", s),
+///             1(e) => format!("err {}", e),
+///         }
+///         closed => "closed".to_string(),
+///     }
+/// };
+///
+/// let mut log = vec![receive(), receive()];
+/// log.sort();
+/// assert_eq!(log, ["err oops", "number 3"]);
+///
+/// drop((numbers, results));
+/// assert_eq!(receive(), "closed");
+/// ```
+#[proc_macro]
+pub fn select_case(input: TokenStream) -> TokenStream {
+    match parse_macro_input!(input as SelectInput).expand() {
+        Ok(output) => output.into(),
+        Err(e) => expression_error(e),
+    }
+}
+
+/// Report errors from a macro used in expression position, wrapping them in a block so there can be
+/// more than one of them.
+fn expression_error(errors: Error) -> TokenStream {
+    let errors = errors.to_compile_error();
+    TokenStream::from(quote!({ #errors }))
+}
+
+/// Derive correct and efficient instances of [`Match`] and [`Case`] for a given `struct` or `enum`.
+///
+/// This also implements [`MatchMeta`], naming the type and its cases, so that the case of a value
+/// can be logged, and [`CaseRef`] and [`CaseMut`] for every case, so that a case can be borrowed
+/// from a value without moving it.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{Match, case};
+///
+/// #[derive(Match)]
+/// enum T<'a, P> {
+///     A,
+///     B(i64),
+///     C { field: P },
+///     D(&'a str, bool),
+/// }
+///
+/// fn check<'a>(t: T<'a, usize>) -> bool {
+///     case!(t {
+///         0 => true,
+///         1(0) => true,
+///         1(n) => n != 0,
+///         2(u) if u == 6 => u % 2 == 0,
+///         2 => true,
+///         3(s, true) => s.chars().count() % 2 == 0,
+///         3(s, _) => true,
+///     })
+/// }
+///
+/// use T::*;
+///
+/// assert!(check(A));
+/// assert!(check(B(0)));
+/// assert!(check(B(1)));
+/// assert!(check(C { field: 0 }));
+/// assert!(check(C { field: 6 }));
+/// assert!(check(D("hello", false)));
+/// assert!(check(D("world!", true)));
+/// ```
+///
+/// # Non-exhaustive enums
+///
+/// An enum marked `#[non_exhaustive]` has [`Nonexhaustive`] as its range, so matching on it
+/// requires a default case. If the attribute only applies under some configuration, as in
+/// `#[cfg_attr(feature = "...", non_exhaustive)]`, the compiler has already decided whether it
+/// applies before deriving `Match`, so the range is [`Nonexhaustive`] exactly when that
+/// configuration holds:
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[cfg_attr(any(), non_exhaustive)]
+/// enum T {
+///     A,
+///     B,
+/// }
+///
+/// // The `cfg_attr` predicate is false, so `T` is exhaustive
+/// vesta::assert_exhaustive::<T, 2>(&T::A);
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[cfg_attr(all(), non_exhaustive)]
+/// enum T {
+///     A,
+///     B,
+/// }
+///
+/// // The `cfg_attr` predicate is true, so `T` is not exhaustive
+/// vesta::assert_exhaustive::<T, 2>(&T::A);
+/// ```
+///
+/// # Conditional variants
+///
+/// A variant with a `#[cfg(...)]` attribute, such as one only used by tests, exists in some
+/// configurations but not others. So that matches don't change meaning between them:
+///
+/// - The variants without a `#[cfg(...)]` are numbered first, in the order they are written, so
+///   their tags are the same in every configuration.
+/// - The variants with one are numbered after all the others, in the order they are written, so
+///   their tags depend on which of them exist, and they are best handled by a default arm.
+/// - The type is non-exhaustive in any configuration where some of them exist, so matches on it
+///   need a default arm there, and exhaustive otherwise.
+///
+/// Code which must compile in every configuration, such as the production code a test-only variant
+/// is injected into, should therefore match with a default arm:
+///
+/// ```
+/// use vesta::{case, Match};
+///
+/// #[derive(Match)]
+/// enum Event {
+///     // Stands in for `#[cfg(test)]`, whose predicate is true here
+///     #[cfg(all())]
+///     Injected(u8),
+///     Started,
+///     Stopped(u32),
+/// }
+///
+/// fn code(event: Event) -> u32 {
+///     case!(event {
+///         0 => 0,
+///         1(code) => code,
+///         _ => u32::MAX,
+///     })
+/// }
+///
+/// // `Injected` exists, so it comes after the others and `Event` is not exhaustive
+/// assert_eq!(Event::Injected(1).tag(), Some(2));
+/// assert_eq!(code(Event::Stopped(3)), 3);
+/// assert_eq!(code(Event::Injected(1)), u32::MAX);
+/// ```
+///
+/// In a configuration where none of them exist, the type is exhaustive as usual:
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// enum Event {
+///     // Stands in for `#[cfg(test)]`, whose predicate is false here
+///     #[cfg(any())]
+///     Injected(u8),
+///     Started,
+///     Stopped(u32),
+/// }
+///
+/// vesta::assert_exhaustive::<Event, 2>(&Event::Started);
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// enum Event {
+///     #[cfg(all())]
+///     Injected(u8),
+///     Started,
+///     Stopped(u32),
+/// }
+///
+/// // `Injected` exists, so `Event` is not exhaustive
+/// vesta::assert_exhaustive::<Event, 3>(&Event::Started);
+/// ```
+///
+/// The discriminants of the variants, as checked by [`ffi`](#ffi), still follow the order they are
+/// written in, whatever their tags:
+///
+/// ```
+/// use vesta::{ffi::FfiEnum, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(ffi)]
+/// #[repr(u8)]
+/// enum Event {
+///     #[cfg(all())]
+///     Injected,
+///     Started,
+///     Stopped,
+/// }
+///
+/// // `Injected` has the discriminant 0 but the tag 2
+/// assert_eq!(Event::validate_tag(0), Some(2));
+/// assert_eq!(Event::validate_tag(1), Some(0));
+/// assert_eq!(Event::validate_tag(2), Some(1));
+/// assert_eq!(Event::validate_tag(3), None);
+/// ```
+///
+/// # Unsized payloads
+///
+/// Fields may borrow unsized values, such as `&'a dyn Trait`, `&'a [T]`, or `&'a T` where
+/// `T: ?Sized`, and the generated impls carry over the type's generics and their bounds exactly as
+/// written, so they require nothing more to be [`Sized`] than the type itself does. Such cases can
+/// be matched, borrowed, and rebuilt using [`Case::uncase`] even in generic code:
+///
+/// ```
+/// use std::fmt::Debug;
+/// use vesta::{case, Case, Match};
+///
+/// #[derive(Match)]
+/// enum Field<'a, T: ?Sized> {
+///     Named(&'a str, &'a T),
+///     Opaque(&'a dyn Debug),
+///     Bytes(&'a [u8]),
+/// }
+///
+/// fn show<T: Debug + ?Sized>(field: &Field<'_, T>) -> String {
+///     case!(ref field {
+///         0(name, value) => format!("{} = {:?}", name, value),
+///         1(value) => format!("{:?}", value),
+///         2(bytes) => format!("{} bytes", bytes.len()),
+///     })
+/// }
+///
+/// fn opaque<'a, F>(value: &'a dyn Debug) -> F
+/// where
+///     F: Case<1, Case = &'a dyn Debug>,
+/// {
+///     F::uncase(value)
+/// }
+///
+/// let path: &str = "/tmp";
+/// assert_eq!(show(&Field::Named("path", path)), "path = \"/tmp\"");
+/// assert_eq!(show(&opaque::<Field<str>>(&[1, 2])), "[1, 2]");
+/// assert_eq!(show::<str>(&Field::Bytes(b"abc")), "3 bytes");
+/// ```
+///
+/// # Recursive types
+///
+/// Fields may refer to the type itself as `Self`. Each `Self` is replaced by the type, with its
+/// generics, wherever the types of the fields are used, so the cases are named by the type rather
+/// than `Self`, and mean the same thing outside its impls, such as in generic code or in the types
+/// generated by the [options](#options):
+///
+/// ```
+/// use vesta::{case, Case, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(newtype_variants)]
+/// enum Tree<T> {
+///     Leaf(T),
+///     Node(Box<Self>, Box<Self>),
+/// }
+///
+/// fn sum(tree: Tree<u32>) -> u32 {
+///     case!(tree {
+///         0(n) => n,
+///         1(NodePayload(left, right)) => sum(*left) + sum(*right),
+///     })
+/// }
+///
+/// fn branch<T, C: Case<1, Case = NodePayload<T>>>(left: Tree<T>, right: Tree<T>) -> C {
+///     C::uncase(NodePayload(Box::new(left), Box::new(right)))
+/// }
+///
+/// let tree: Tree<u32> = branch(Tree::Leaf(1), branch(Tree::Leaf(2), Tree::Leaf(3)));
+/// assert_eq!(sum(tree), 6);
+/// ```
+///
+/// # Types generated by other macros
+///
+/// The derived code refers to everything it uses by its full path, and binds the fields of each
+/// case to names of its own rather than to the names of the fields, so it means the same thing
+/// wherever the tokens of the type came from. This means `Match` can be derived for types generated
+/// by other macros, such as the enum of discriminants generated by
+/// [`strum`](https://docs.rs/strum)'s `EnumDiscriminants`:
+///
+/// ```
+/// use strum::EnumDiscriminants;
+/// use vesta::{case, Match};
+///
+/// #[derive(EnumDiscriminants)]
+/// #[strum_discriminants(derive(Match))]
+/// enum Request {
+///     Get(String),
+///     Put { key: String, value: Vec<u8> },
+/// }
+///
+/// let kind = RequestDiscriminants::from(&Request::Get("key".to_string()));
+/// assert_eq!(case!(kind { 0 => "get", 1 => "put" }), "get");
+/// ```
+///
+/// Code generators that emit constants alongside types, as binding generators do, may give a
+/// constant the same name as a field, which would turn a binding of that field into a comparison
+/// with the constant. The derived code never binds a field by its own name, so this is not a
+/// problem:
+///
+/// ```
+/// use vesta::{case, Match};
+///
+/// macro_rules! generate {
+///     ($name:ident { $field:ident: $ty:ty = $value:expr }) => {
+///         #[allow(non_upper_case_globals)]
+///         const $field: $ty = $value;
+///
+///         #[derive(Match)]
+///         enum $name {
+///             Empty,
+///             Full { $field: $ty },
+///         }
+///     };
+/// }
+///
+/// generate!(Buffer { len: usize = 64 });
+///
+/// let buffer = Buffer::Full { len: 3 };
+/// assert_eq!(case!(buffer { 0 => 0, 1(n) => n }), 3);
+/// ```
+///
+/// # Renaming cases
+///
+/// The names of the cases listed by [`MatchMeta`] may be stored outside the program, in logs,
+/// configuration files, or serialized data, and looked up again using
+/// [`MatchMeta::tag_from_name`]. To rename a variant without breaking such lookups, keep its old
+/// name as an alias using `#[vesta(alias = "...")]` on the variant, which may be repeated for a
+/// variant renamed more than once. Aliases are listed in [`MatchMeta::CASE_ALIASES`], and must not
+/// clash with the name or any alias of another case.
+///
+/// ```
+/// use vesta::{Match, MatchMeta};
+///
+/// #[derive(Match)]
+/// enum Level {
+///     #[vesta(alias = "Warn")]
+///     Warning,
+///     #[vesta(alias = "Err", alias = "Fatal")]
+///     Error,
+/// }
+///
+/// assert_eq!(Level::tag_from_name("Warning"), Some(0));
+/// assert_eq!(Level::tag_from_name("Warn"), Some(0));
+/// assert_eq!(Level::tag_from_name("Fatal"), Some(1));
+/// assert_eq!(Level::Error.case_name(), Some("Error"));
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// enum Level {
+///     Warning,
+///     // Error: `Warning` is already the name of `Level::Warning`
+///     #[vesta(alias = "Warning")]
+///     Error,
+/// }
+/// ```
+///
+/// # Snapshot testing
+///
+/// When the `VESTA_STABLE_EXPANSION` environment variable is set to any value other than `0` while
+/// compiling, the implementations of [`Match`], [`Case`], [`CaseRef`], and [`CaseMut`] generated by
+/// this macro leave out their overrides of provided methods, such as `Case::try_case`, which only
+/// make them faster, so that snapshots of them taken with a tool like `macrotest` only change when
+/// their meaning does. This is described further for [`stable_expansion`].
+///
+/// # Options
+///
+/// Further items can be generated alongside the instances by listing options in a `#[vesta(...)]`
+/// attribute on the type.
+///
+/// ## `router`
+///
+/// Generate a struct `{Type}Router<'h, ..., R>` holding one boxed handler for each case of the
+/// type, each returning `R`, and a `route` method which dispatches a value to the handler for its
+/// case. The router is constructed using `{Type}Router::builder()`, which has one method `on_N` per
+/// case `N` to set its handler; its `build` method only exists once every case has a handler, so
+/// forgetting a case is a compile-time error. The type must be exhaustive.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(router)]
+/// enum Command {
+///     Stop,
+///     Move(i64, i64),
+///     Say(String),
+/// }
+///
+/// let router = CommandRouter::builder()
+///     .on_0(|()| "stop".to_string())
+///     .on_1(|(x, y)| format!("move by {}", x + y))
+///     .on_2(|s| format!("say {}", s))
+///     .build();
+///
+/// assert_eq!(router.route(Command::Stop), "stop");
+/// assert_eq!(router.route(Command::Move(1, 2)), "move by 3");
+/// assert_eq!(router.route(Command::Say("hi".into())), "say hi");
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// # #[derive(Match)]
+/// # #[vesta(router)]
+/// # enum Command {
+/// #     Stop,
+/// #     Move(i64, i64),
+/// #     Say(String),
+/// # }
+/// // There is no handler for case 2, so this can't be built
+/// let router = CommandRouter::<()>::builder()
+///     .on_0(|()| ())
+///     .on_1(|_| ())
+///     .build();
+/// ```
+///
+/// The same option also generates a struct `{Type}StaticRouter<..., R>` holding a plain function
+/// pointer for each case, without boxing, and constructed by passing all of them at once to
+/// `{Type}StaticRouter::new`, which is a `const fn`. Non-capturing closures and functions can both
+/// be given as handlers, so the router can be a `static`, placed in read-only memory on targets
+/// such as microcontrollers, and used without any allocation.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(router)]
+/// enum Command {
+///     Stop,
+///     Move(i64, i64),
+///     Say(String),
+/// }
+///
+/// fn say(s: String) -> usize {
+///     s.len()
+/// }
+///
+/// static ROUTER: CommandStaticRouter<usize> =
+///     CommandStaticRouter::new(|()| 0, |(x, y)| (x + y) as usize, say);
+///
+/// assert_eq!(ROUTER.route(Command::Stop), 0);
+/// assert_eq!(ROUTER.route(Command::Move(1, 2)), 3);
+/// assert_eq!(ROUTER.route(Command::Say("hi".into())), 2);
+/// ```
+///
+/// The type's generic parameters, including any defaults, are carried over to both routers, after
+/// the lifetime of the handlers and before their output type. A type with no cases has nothing to
+/// route, so it can't have a router.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(router)]
+/// enum Packet<T = u8> {
+///     Byte(T),
+///     Empty,
+/// }
+///
+/// let router = PacketRouter::<u8, bool>::builder()
+///     .on_0(|byte| byte > 0)
+///     .on_1(|()| false)
+///     .build();
+/// assert!(router.route(Packet::Byte(1)));
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// #[derive(Match)]
+/// #[vesta(router)]
+/// // Error: cannot generate a router for `Never`, because it has no cases to route
+/// enum Never {}
+/// ```
+///
+/// For a router whose handlers can be replaced while it is running, see
+/// [`DynamicRouter`](https://docs.rs/vesta/latest/vesta/router/struct.DynamicRouter.html), which
+/// works for any type implementing [`Match`] and [`Case`].
+///
+/// ## `strum`
+///
+/// Assert at compile time that the type's implementation of
+/// [`strum::EnumCount`](https://docs.rs/strum/latest/strum/trait.EnumCount.html) counts the same
+/// number of cases as its implementation of [`Match`]. This requires the `strum` feature of
+/// `vesta`, and the type must be a non-generic enum. To also check that `EnumIter` yields the cases
+/// in the order of their tags, use `vesta::strum::tags_aligned`.
+///
+/// ```
+/// use vesta::Match;
+/// use strum::{EnumCount, EnumIter};
+///
+/// #[derive(Match, EnumCount, EnumIter)]
+/// #[vesta(strum)]
+/// enum Suit {
+///     Clubs,
+///     Diamonds,
+///     Hearts,
+///     Spades,
+/// }
+///
+/// assert!(vesta::strum::tags_aligned::<Suit>());
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+/// use strum::EnumCount;
+///
+/// #[derive(Match, EnumCount)]
+/// #[vesta(strum)]
+/// enum Suit {
+///     Clubs,
+///     Diamonds,
+///     Hearts,
+///     #[strum(disabled)]
+///     Spades,
+/// }
+/// ```
+///
+/// ## `check_tags`
+///
+/// Check in debug builds that `Case::case`, `CaseRef::case_ref`, and `CaseMut::case_mut` are only
+/// called on values with the right tag, and that `Case::uncase` reconstructs a value with the right
+/// tag, panicking with a message naming the type and case otherwise. This helps to track down
+/// unsafe code which extracts the wrong case, which would otherwise drop or leak payloads it does
+/// not own in release builds.
+///
+/// Only tags are checked: the drops themselves are not tracked. To check which payloads are
+/// dropped, and in what order, log them with a [`DropLog`] in tests.
+///
+/// ```should_panic
+/// use vesta::{Case, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(check_tags)]
+/// enum Shape {
+///     Point,
+///     Circle(f64),
+/// }
+///
+/// // Panics in debug builds, because a point is not a circle
+/// let radius = unsafe { <Shape as Case<1>>::case(Shape::Point) };
+/// ```
+///
+/// ## `boxed`
+///
+/// Writing `#[vesta(boxed(N, ...))]` makes the listed cases extract their payloads boxed: the
+/// [`Case`] type of each of those cases is `Box<...>` of what it would otherwise be, and
+/// reconstructing the case unboxes it again. The type itself is unchanged, but extracted payloads
+/// are cheap to move around, no matter how large they are.
+///
+/// ```
+/// use vesta::{case, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(boxed(1))]
+/// enum Packet {
+///     Empty,
+///     Full([u8; 1024], usize),
+/// }
+///
+/// let length = case!(Packet::Full([0; 1024], 3) {
+///     0 => 0,
+///     1(packet) => {
+///         let packet: Box<([u8; 1024], usize)> = packet;
+///         packet.1
+///     }
+/// });
+/// assert_eq!(length, 3);
+/// ```
+///
+/// ## `warn_large`
+///
+/// Warn about any case whose payload is at least 128 bytes, and at least four times the size of
+/// the payload of every other case, since every value of the type takes up as much space as the
+/// largest case. The fix is to box the fields of the variant itself: cases listed in
+/// [`boxed`](#boxed) are checked like any other, since `boxed` only boxes payloads once they are
+/// extracted, leaving the size of the type unchanged. This requires the type to be a non-generic
+/// enum.
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(warn_large)]
+/// enum Packet {
+///     Empty,
+///     // Warning: the payload of `Packet::Full` is at least 4 times as large as that of any other
+///     // case
+///     Full([u8; 1024], usize),
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// // Boxing the payload when it is extracted doesn't make `Packet` any smaller
+/// #[vesta(warn_large, boxed(1))]
+/// enum Packet {
+///     Empty,
+///     // Warning: the payload of `Packet::Full` is at least 4 times as large as that of any other
+///     // case
+///     Full([u8; 1024], usize),
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```
+/// #![deny(deprecated)]
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(warn_large)]
+/// enum Packet {
+///     Empty,
+///     Full(Box<[u8; 1024]>, usize),
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ## `module`
+///
+/// Writing `#[vesta(module = "name")]` places all the generated items in a `#[doc(hidden)]` module
+/// called `name`, next to the type, re-exporting any helper items (such as those generated by
+/// [`router`](#router)) from it. The module imports everything from its parent, so the type must
+/// be declared at module level, not inside a function body.
+///
+/// ## `unfold`
+///
+/// Implement `vesta::Unfold` for an enum, so that values can be built case by case from a seed.
+/// This generates an enum `{Type}Step<..., S>`, which has the same variants as the type, except
+/// that every field of the type's own type (directly, or within a `Box`, `Vec`, or `Option`) is
+/// replaced by a seed of type `S`. The step type also implements [`Match`] and [`Case`], so steps
+/// can be constructed by tag using `Case::uncase`.
+///
+/// ```
+/// use vesta::{Case, Match, Unfold};
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(unfold)]
+/// enum Expr {
+///     Number(i64),
+///     Sum(Vec<Expr>),
+///     Negate(Box<Expr>),
+/// }
+///
+/// // Build the sum of the numbers from 1 to 3, negating each even number: each seed is either
+/// // `None` for the whole sum, or a number and whether it still needs to be negated
+/// let expr = Expr::unfold(None, |seed| match seed {
+///     None => ExprStep::Sum((1..=3).map(|n| Some((n, n % 2 == 0))).collect()),
+///     Some((n, true)) => ExprStep::Negate(Some((n, false))),
+///     Some((n, false)) => <ExprStep<_> as Case<0>>::uncase(n),
+/// });
+///
+/// assert_eq!(
+///     expr,
+///     Expr::Sum(vec![
+///         Expr::Number(1),
+///         Expr::Negate(Box::new(Expr::Number(2))),
+///         Expr::Number(3),
+///     ]),
+/// );
+/// ```
+///
+/// If the type is also [`validate`](#validate)d, each value built is checked: `Unfold::unfold`
+/// panics if any is rejected, while `Unfold::try_unfold` returns `None`, checking values just as
+/// [`TryUncase`] does.
+///
+/// ```
+/// use vesta::{Match, Unfold};
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(unfold, validate = "Range::is_ordered")]
+/// enum Range {
+///     Point(u32),
+///     Between(Box<Range>, Box<Range>),
+/// }
+///
+/// impl Range {
+///     fn start(&self) -> u32 {
+///         match self {
+///             Range::Point(n) => *n,
+///             Range::Between(start, _) => start.start(),
+///         }
+///     }
+///
+///     fn end(&self) -> u32 {
+///         match self {
+///             Range::Point(n) => *n,
+///             Range::Between(_, end) => end.end(),
+///         }
+///     }
+///
+///     fn is_ordered(&self) -> bool {
+///         match self {
+///             Range::Point(_) => true,
+///             Range::Between(start, end) => start.end() <= end.start(),
+///         }
+///     }
+/// }
+///
+/// let split = |(start, end): (u32, u32)| {
+///     if start == end {
+///         RangeStep::Point(start)
+///     } else {
+///         RangeStep::Between((start, start), (start + 1, end))
+///     }
+/// };
+/// assert_eq!(Range::try_unfold((1, 2), split).map(|range| range.end()), Some(2));
+///
+/// let backwards = |(start, end): (u32, u32)| {
+///     if start == end {
+///         RangeStep::Point(start)
+///     } else {
+///         RangeStep::Between((end, end), (start, start))
+///     }
+/// };
+/// assert_eq!(Range::try_unfold((1, 2), backwards), None);
+/// ```
+///
+/// ## `flat_option`
+///
+/// Allow an `Option` of an exhaustive type to be matched in one level, by wrapping it in a
+/// `vesta::option::FlatOption`, whose case 0 is `None`, and whose case `n + 1` is `Some` of case
+/// `n` of the type.
+///
+/// ```
+/// use vesta::{case, option::FlatOption, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(flat_option)]
+/// enum Shape {
+///     Circle(f64),
+///     Square(f64),
+/// }
+///
+/// fn area(shape: Option<Shape>) -> f64 {
+///     case!(FlatOption(shape) {
+///         0 => 0.0,
+///         1(r) => 3.0 * r * r,
+///         2(s) => s * s,
+///     })
+/// }
+///
+/// assert_eq!(area(Some(Shape::Square(2.0))), 4.0);
+/// assert_eq!(area(None), 0.0);
+/// ```
+///
+/// ## `absurd`
+///
+/// Convert an enum with no variants, which can never have a value, to and from
+/// [`Infallible`](std::convert::Infallible), so that errors of either type can be propagated as the
+/// other using `?`. Generic code can eliminate a value of any such type using `vesta::absurd`.
+///
+/// ```
+/// use std::convert::Infallible;
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(absurd)]
+/// enum Never {}
+///
+/// fn parse(input: &str) -> Result<String, Never> {
+///     let parsed: String = input.parse::<String>()?;
+///     Ok(parsed)
+/// }
+///
+/// fn infallible(input: &str) -> Result<String, Infallible> {
+///     Ok(parse(input)?)
+/// }
+///
+/// assert_eq!(infallible("hi").unwrap(), "hi");
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// #[derive(Match)]
+/// #[vesta(absurd)]
+/// // Error: cannot mark `Unit` as absurd, because only enums with no variants are absurd
+/// enum Unit {
+///     Unit,
+/// }
+/// ```
+///
+/// ## `frozen`
+///
+/// Make the tags of the cases of a type part of its public contract, so that they are not
+/// renumbered by accident. Writing `#[vesta(frozen = "...")]` with the fingerprint of the cases as
+/// they were first published checks that the type's cases still begin with those cases, in the
+/// same order, so new cases may only be added after them. It also generates a public constant
+/// `FROZEN_CASES` listing the names of the frozen cases, documented with a table of their tags.
+///
+/// To find the fingerprint to write, leave it empty: the resulting error says what it should be.
+/// When the tags are deliberately changed, the error says what the new fingerprint should be.
+///
+/// Renaming a frozen case changes the fingerprint, unless its old name is kept as an alias as
+/// described in [Renaming cases](#renaming-cases): the fingerprint is checked against the cases
+/// named by their current names, and against the cases named by their earliest names, which is the
+/// first alias of each case that has any. To rename a case more than once, add each newer former
+/// name after the ones before it.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(frozen = "3601494a3930f869")]
+/// pub enum Message {
+///     Ping,
+///     // Renamed after the tags were frozen
+///     #[vesta(alias = "Data")]
+///     Payload(Vec<u8>),
+/// }
+///
+/// assert_eq!(Message::FROZEN_CASES, ["Ping", "Payload"]);
+/// ```
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(frozen = "3601494a3930f869")]
+/// pub enum Message {
+///     Ping,
+///     Data(Vec<u8>),
+///     // Added after the tags above were frozen
+///     Close,
+/// }
+///
+/// assert_eq!(Message::FROZEN_CASES, ["Ping", "Data"]);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// #[derive(Match)]
+/// #[vesta(frozen = "3601494a3930f869")]
+/// pub enum Message {
+///     // Error: the tags of the cases of `Message` have changed since they were frozen
+///     Close,
+///     Ping,
+///     Data(Vec<u8>),
+/// }
+/// ```
+///
+/// ## `tag_base`
+///
+/// Give the cases of a type global tags, offset from their tags by a constant, so that each
+/// subsystem of a large program can be given its own block of tags, unique across the program.
+/// Writing `#[vesta(tag_base = "BASE")]`, where `BASE` is the path of a `usize` constant,
+/// implements [`TagBase`] with `TAG_BASE` being the [`Tag`] of it, so that a value with tag `n` has
+/// the global tag `TAG_BASE + n`, and implements [`GlobalCase`] for each case at its global tag. It
+/// is a compile-time error for the global tags to overflow a `usize`.
+///
+/// The tags themselves are unchanged, since they must run from `0` for the type to be exhaustive,
+/// but the arms of a `case!` can be written with global tags instead, as constant expressions using
+/// the same constant, so that the same names for cases are used wherever they are matched, such as
+/// when decoding messages:
+///
+/// ```
+/// use vesta::{case, Match, Tag, TagBase};
+///
+/// pub const AUTH_BASE: usize = 100;
+/// pub const LOGOUT: usize = AUTH_BASE + 1;
+///
+/// #[derive(Match)]
+/// #[vesta(tag_base = "AUTH_BASE")]
+/// pub enum Auth {
+///     Login(String),
+///     Logout,
+/// }
+///
+/// let request = Auth::Logout;
+/// assert_eq!(request.global_tag(), Some(Tag(LOGOUT)));
+/// assert_eq!(
+///     case!(request {
+///         AUTH_BASE(user) => format!("login {}", user),
+///         LOGOUT => "logout".to_string(),
+///         _ => "unknown".to_string(),
+///     }),
+///     "logout",
+/// );
+///
+/// assert_eq!(Auth::local_tag(Tag(LOGOUT)), Some(Tag(1)));
+/// assert_eq!(Auth::local_tag(Tag(AUTH_BASE + 2)), None);
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// #[derive(Match)]
+/// // Error: the global tags overflow
+/// #[vesta(tag_base = "usize::MAX")]
+/// pub enum Auth {
+///     Login(String),
+///     Logout,
+/// }
+/// ```
+///
+/// ## `ffi`
+///
+/// Implement `vesta::ffi::FfiEnum` for an enum with a `#[repr(C)]` or primitive representation,
+/// such as `#[repr(u8)]`, so that raw values of it which arrive from foreign code can have their
+/// discriminants checked before they are used as the enum. This provides
+/// `FfiEnum::validate_tag`, which gives the tag of the variant with a given discriminant, if any,
+/// and the unsafe `FfiEnum::from_raw_validated`, which converts a raw value to the enum only if its
+/// discriminant is known. Raw values can also be matched directly by wrapping them in a
+/// `vesta::ffi::Unvalidated`, which is [`Nonexhaustive`]: values with unknown discriminants have
+/// no tag.
+///
+/// ```
+/// use std::mem::MaybeUninit;
+/// use vesta::{ffi::FfiEnum, Match};
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(ffi)]
+/// #[repr(u8)]
+/// enum Color {
+///     Red = 1,
+///     Green = 2,
+///     Blue = 4,
+/// }
+///
+/// assert_eq!(Color::validate_tag(4), Some(2));
+/// assert_eq!(Color::validate_tag(3), None);
+///
+/// let raw = MaybeUninit::new(Color::Green);
+/// assert_eq!(unsafe { Color::from_raw_validated(raw) }, Some(Color::Green));
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// #[derive(Match)]
+/// #[vesta(ffi)]
+/// // Error: cannot check the discriminants of `Color` unless it is `#[repr(C)]` or has a
+/// // primitive representation such as `#[repr(u8)]`
+/// enum Color {
+///     Red,
+///     Green,
+///     Blue,
+/// }
+/// ```
+///
+/// ## `case_eq`
+///
+/// Implement `vesta::assert::CaseEq`, so that values can be compared case by case using
+/// `vesta::assert_case_eq!`, which names the cases of values in different cases, and shows a diff
+/// of the payloads of values in the same case. Only the types of the fields need to implement
+/// `PartialEq` and `Debug`, not the type itself.
+///
+/// ```
+/// use vesta::{assert_case_eq, Match};
+///
+/// // Neither `PartialEq` nor `Debug` is implemented for `Request` itself
+/// #[derive(Match)]
+/// #[vesta(case_eq)]
+/// enum Request {
+///     Open(String),
+///     Read(usize, usize),
+/// }
+///
+/// assert_case_eq!(Request::Read(0, 10), Request::Read(0, 10));
+/// ```
+///
+/// ```should_panic
+/// # use vesta::{assert_case_eq, Match};
+/// # #[derive(Match)]
+/// # #[vesta(case_eq)]
+/// # enum Request {
+/// #     Open(String),
+/// #     Read(usize, usize),
+/// # }
+/// // Panics with:
+/// //
+/// // assertion `left == right` failed
+/// //   left: Open(0)
+/// //  right: Read(1)
+/// assert_case_eq!(Request::Open("a".into()), Request::Read(0, 10));
+/// ```
+///
+/// ## `clone_case` and `debug_case`
+///
+/// Implement [`CloneCase`] or [`DebugCase`] for each case of an enum, along with inherent methods
+/// `clone_case::<N>()`, which clones the payload of a value if it has the tag `N`, and
+/// `debug_case::<N>()`, which formats it for debugging in the same way as `#[derive(Debug)]`
+/// formats its variant. Only the fields of case `N` need to implement `Clone` or `Debug` for these
+/// methods to be called with `N`, so code which duplicates or logs just one case doesn't require
+/// every payload in the enum to support it.
+/// If the type is also [`validate`](#validate)d, `clone_case` checks the clone of the value before
+/// handing out its payload, just as [`TryUncase`] would, returning `None` if it is rejected.
+///
+/// ```
+/// use std::net::TcpStream;
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(clone_case, debug_case)]
+/// enum Connection {
+///     Pending(String, u16),
+///     Open(TcpStream),
+/// }
+///
+/// let pending = Connection::Pending("localhost".to_string(), 8080);
+/// assert_eq!(pending.clone_case::<0>(), Some(("localhost".to_string(), 8080)));
+/// assert_eq!(
+///     format!("{:?}", pending.debug_case::<0>().unwrap()),
+///     "Pending(\"localhost\", 8080)",
+/// );
+/// ```
+///
+/// ```compile_fail
+/// # use std::net::TcpStream;
+/// # use vesta::Match;
+/// # #[derive(Match)]
+/// # #[vesta(clone_case, debug_case)]
+/// # enum Connection {
+/// #     Pending(String, u16),
+/// #     Open(TcpStream),
+/// # }
+/// # let pending = Connection::Pending("localhost".to_string(), 8080);
+/// // Error: `TcpStream` is not `Clone`
+/// pending.clone_case::<1>();
+/// ```
+///
+/// ## `type_list`
+///
+/// Implement [`CaseList`] for the type, whose associated type `Cases` lists the payload type of
+/// each case in order of their tags as a nested tuple `(C0, (C1, (C2, ())))`, so that generic code
+/// can walk the cases of the type at the type level. The payload types are those extracted by
+/// [`Case`], so they follow `boxed` and `newtype_variants`. Only exhaustive types can list their
+/// cases, since those of a non-exhaustive type are not all known.
+///
+/// ```
+/// use vesta::{CaseList, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(type_list, boxed(1))]
+/// enum Shape {
+///     Point,
+///     Circle(f64),
+///     Rect(f64, f64),
+/// }
+///
+/// let _: <Shape as CaseList>::Cases = ((), (Box::new(1.0), ((2.0, 3.0), ())));
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(type_list)]
+/// #[non_exhaustive]
+/// enum Shape {
+///     Point,
+///     Circle(f64),
+/// }
+/// ```
+///
+/// ## `apply`
+///
+/// Implement [`Apply`] for the type, so that its values can be handled by a [`CaseFn`], which has
+/// a different output type for each case. Only exhaustive types can be handled this way, since a
+/// `CaseFn` has no handler for the unknown cases of a non-exhaustive type.
+///
+/// ```
+/// use vesta::{
+///     case_fn::{apply, CaseFn, CaseFnAt},
+///     Match,
+/// };
+///
+/// #[derive(Match)]
+/// #[vesta(apply)]
+/// enum Input {
+///     Key(char),
+///     Click(i32, i32),
+/// }
+///
+/// struct Describe;
+///
+/// impl CaseFn<Input> for Describe {
+///     type Unified = String;
+/// }
+///
+/// impl CaseFnAt<Input, 0> for Describe {
+///     type Output = String;
+///
+///     fn call(&mut self, key: char) -> String {
+///         format!("key {}", key)
+///     }
+/// }
+///
+/// impl CaseFnAt<Input, 1> for Describe {
+///     type Output = &'static str;
+///
+///     fn call(&mut self, _: (i32, i32)) -> &'static str {
+///         "click"
+///     }
+/// }
 ///
-/// Syntax for this macro is very similar to the syntax for `match` in Rust, except constructor
-/// names are replaced with their corresponding numerals, as defined by implementations of [`Case`].
+/// assert_eq!(apply(Input::Key('q'), Describe), "key q");
+/// assert_eq!(apply(Input::Click(1, 2), Describe), "click");
+/// ```
 ///
-/// Omitting a parenthesized pattern after a numeral `N` is equivalent to the pattern `N(_)`, i.e.
-/// the pattern matching all values tagged with `N`.
+/// ```compile_fail
+/// use vesta::Match;
 ///
-/// # Examples
+/// #[derive(Match)]
+/// #[vesta(apply)]
+/// #[non_exhaustive]
+/// enum Input {
+///     Key(char),
+/// }
+/// ```
+///
+/// ## `validate`
+///
+/// Writing `#[vesta(validate = "path::to::check")]` implements [`TryUncase`] for each case of the
+/// type, for types which maintain an invariant that not every payload upholds. The function `check`
+/// takes a reference to a value of the type and returns whether it is valid; `try_uncase` rebuilds
+/// the value from its case and keeps it only if it is, giving back the case in an [`UncaseError`]
+/// otherwise. [`Case::uncase`] itself still rebuilds values without checking them, so code which
+/// builds values from untrusted cases should use `try_uncase`, as the `TryFrom` conversion of
+/// [`raw_form`](#raw_form) does when both options are given. The values built by
+/// [`unfold`](#unfold), [`clone_case`](#clone_case-and-debug_case), and
+/// [`map_to(..., validated)`](#map_to) are checked in the same way.
 ///
 /// ```
-/// use vesta::case;
+/// use std::convert::TryFrom;
+/// use vesta::{Match, TryUncase};
 ///
-/// let option = Some("thing");
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(validate = "Span::is_ordered", raw_form)]
+/// enum Span {
+///     Empty,
+///     Range(u32, u32),
+/// }
 ///
-/// case!(option {
-///     0 => assert!(false),
-///     1(s) => assert_eq!(s, "thing"),
-/// });
+/// impl Span {
+///     fn is_ordered(&self) -> bool {
+///         match self {
+///             Span::Empty => true,
+///             Span::Range(start, end) => start <= end,
+///         }
+///     }
+/// }
+///
+/// assert_eq!(<Span as TryUncase<1>>::try_uncase((1, 4)), Ok(Span::Range(1, 4)));
+/// assert!(<Span as TryUncase<1>>::try_uncase((4, 1)).is_err());
+///
+/// let raw = SpanRaw::from(Span::Range(1, 4));
+/// let mut backwards = SpanRaw::from(Span::Range(1, 4));
+/// backwards.payload.range = Some((4, 1));
+/// assert_eq!(Span::try_from(raw).ok(), Some(Span::Range(1, 4)));
+/// assert_eq!(Span::try_from(backwards).ok(), None);
 /// ```
 ///
-/// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
+/// ## `raw_form`
 ///
-/// [`Case`]: https://docs.rs/vesta/latest/vesta/trait.Case.html
+/// Generate a raw form of the type, for storing its tag and payload separately, as in a SQL table
+/// or a columnar store. This is a struct `{Type}Raw<...>` with a `tag` field and a `payload` field,
+/// which is a struct `{Type}RawPayload<...>` with one `Option` field per case, named after the case
+/// in `snake_case`, holding the payload of that case (as extracted by [`Case`]) when it is the case
+/// of the value. The raw form can be converted from the type using `From`, and back using
+/// `TryFrom`, which gives back the raw form unchanged unless its tag is that of a case and only
+/// that case's payload is present.
 ///
-/// [`try_case`]: https://docs.rs/vesta/latest/vesta/trait.CaseExt.html#method.try_case
-#[proc_macro]
-pub fn case(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as CaseInput);
-    match input.compile() {
-        Ok(output) => output.to_token_stream().into(),
-        Err(e) => e.to_compile_error().into(),
-    }
-}
-
-/// Derive `Match` and `Case` for a "foreign" struct or enum, given its declaration.
+/// Writing `#[vesta(raw_form(...))]` applies each item in the list as an attribute to both
+/// generated structs, so that they can, for instance, derive `serde::Serialize` and
+/// `serde::Deserialize` using `raw_form(derive(Serialize, Deserialize))`.
 ///
-/// This is only useful within the `vesta` crate itself, because otherwise it will generate an
-/// orphan implementation.
-#[proc_macro]
-pub fn derive_match(input: TokenStream) -> TokenStream {
-    derive_match_impl(input)
-}
-
-/// Derive correct and efficient instances of [`Match`] and [`Case`] for a given `struct` or `enum`.
+/// ```
+/// use std::convert::TryFrom;
+/// use vesta::Match;
 ///
-/// # Examples
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(raw_form(derive(Debug, PartialEq)))]
+/// enum Reading {
+///     Missing,
+///     Celsius(f64),
+///     RawADC(u16, u8),
+/// }
 ///
+/// let raw = ReadingRaw::from(Reading::RawADC(512, 2));
+/// assert_eq!(raw.tag, 2);
+/// assert_eq!(raw.payload.celsius, None);
+/// assert_eq!(raw.payload.raw_adc, Some((512, 2)));
+/// assert_eq!(Reading::try_from(raw), Ok(Reading::RawADC(512, 2)));
+///
+/// // A tag with the payload of another case is not a valid raw form
+/// let raw = ReadingRaw {
+///     tag: 0,
+///     payload: ReadingRawPayload {
+///         missing: None,
+///         celsius: Some(20.5),
+///         raw_adc: None,
+///     },
+/// };
+/// assert!(Reading::try_from(raw).is_err());
 /// ```
-/// use vesta::{Match, case};
+///
+/// ## `newtype_variants`
+///
+/// Generate a struct `{Variant}Payload` for each variant with more than one field, which has the
+/// same fields as the variant, and use it as the case of the variant in place of a tuple of its
+/// fields. This makes the payloads of such cases nameable, as in the signatures of functions
+/// handling them, and keeps those signatures stable if a tuple variant later gains field names.
+/// The payload struct of a variant with named fields has the same named fields, so such variants,
+/// which otherwise can't have a case, can be matched as well. Each payload struct has only those
+/// generic parameters of the type which its fields mention. Borrowing a case using [`CaseRef`] or
+/// [`CaseMut`] still gives a tuple of references to its fields.
+///
+/// As for `raw_form`, writing `#[vesta(newtype_variants(...))]` applies each item in the list as an
+/// attribute to every payload struct.
+///
+/// ```
+/// use vesta::{case, Match};
 ///
 /// #[derive(Match)]
-/// enum T<'a, P> {
-///     A,
-///     B(i64),
-///     C { field: P },
-///     D(&'a str, bool),
+/// #[vesta(newtype_variants(derive(Debug, PartialEq)))]
+/// enum Command<'a> {
+///     Stop,
+///     Move(i64, i64),
+///     Say { text: &'a str, loud: bool },
 /// }
 ///
-/// fn check<'a>(t: T<'a, usize>) -> bool {
-///     case!(t {
-///         0 => true,
-///         1(0) => true,
-///         1(n) => n != 0,
-///         2(u) if u == 6 => u % 2 == 0,
-///         2 => true,
-///         3(s, true) => s.chars().count() % 2 == 0,
-///         3(s, _) => true,
+/// fn distance(MovePayload(x, y): MovePayload) -> i64 {
+///     x.abs() + y.abs()
+/// }
+///
+/// fn run(command: Command) -> String {
+///     case!(command {
+///         0 => "stopped".to_string(),
+///         1(payload) => format!("moved {}", distance(payload)),
+///         2(SayPayload { text, loud: true }) => text.to_uppercase(),
+///         2(SayPayload { text, .. }) => text.to_string(),
 ///     })
 /// }
 ///
-/// use T::*;
+/// assert_eq!(run(Command::Move(3, -4)), "moved 7");
+/// assert_eq!(run(Command::Say { text: "hi", loud: true }), "HI");
+/// ```
 ///
-/// assert!(check(A));
-/// assert!(check(B(0)));
-/// assert!(check(B(1)));
-/// assert!(check(C { field: 0 }));
-/// assert!(check(C { field: 6 }));
-/// assert!(check(D("hello", false)));
-/// assert!(check(D("world!", true)));
+/// The bounds of a payload struct's parameters are those of the type, less any which mention
+/// parameters the struct doesn't have:
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(newtype_variants)]
+/// enum Bounded<'a, T: 'a> {
+///     Pair(T, T),
+///     Borrowed(&'a T),
+/// }
+///
+/// // `PairPayload` has no lifetime, so it has no bound `T: 'a`
+/// let PairPayload(x, y): PairPayload<u8> = PairPayload(1, 2);
+/// assert_eq!(x + y, 3);
+/// # let _ = Bounded::<u8>::Pair(x, y);
+/// # let _ = Bounded::Borrowed(&x);
+/// ```
+///
+/// ## `map_to`
+///
+/// Writing `#[vesta(map_to(Other))]` generates a method `map_payloads`, which converts a value to
+/// the type `Other` by mapping its payload to the payload of the same case of `Other`. This suits
+/// pairs of types with the same cases but different payloads, such as borrowed and owned versions
+/// of the same message. The mapping is anything implementing [`MapPayload`] from the payload of
+/// each case of the type to the payload of the same case of `Other`, such as a closure when all
+/// the payloads have the same type. Since the conversion goes case by case, adding, removing, or
+/// reordering the cases of either type without the other is a compile-time error, as is changing
+/// the type of a payload without changing the mapping.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(map_to(Fahrenheit))]
+/// enum Celsius {
+///     Indoor(f64),
+///     Outdoor(f64),
+/// }
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// enum Fahrenheit {
+///     Indoor(f64),
+///     Outdoor(f64),
+/// }
+///
+/// let outdoor = Celsius::Outdoor(20.0).map_payloads(|celsius| celsius * 1.8 + 32.0);
+/// assert_eq!(outdoor, Fahrenheit::Outdoor(68.0));
+/// ```
+///
+/// ```compile_fail
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(map_to(Fahrenheit))]
+/// enum Celsius {
+///     Indoor(f64),
+///     Outdoor(f64),
+///     // Error: `Fahrenheit` has no case with tag 2
+///     Space(f64),
+/// }
+///
+/// #[derive(Match)]
+/// enum Fahrenheit {
+///     Indoor(f64),
+///     Outdoor(f64),
+/// }
+/// ```
+///
+/// Since `map_payloads` rebuilds the value of `Other` using [`Case::uncase`], it doesn't check it.
+/// If `Other` is [`validate`](#validate)d, write `#[vesta(map_to(Other, validated))]` instead, to
+/// generate a method `try_map_payloads` which rebuilds it using [`TryUncase`], returning `None` if
+/// `Other` rejects the mapped payload.
+///
+/// ```
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(map_to(Kelvin, validated))]
+/// enum Celsius {
+///     Indoor(f64),
+///     Outdoor(f64),
+/// }
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(validate = "Kelvin::is_physical")]
+/// enum Kelvin {
+///     Indoor(f64),
+///     Outdoor(f64),
+/// }
+///
+/// impl Kelvin {
+///     fn is_physical(&self) -> bool {
+///         match self {
+///             Kelvin::Indoor(kelvin) | Kelvin::Outdoor(kelvin) => *kelvin >= 0.0,
+///         }
+///     }
+/// }
+///
+/// let to_kelvin = |celsius| celsius + 273.15;
+/// assert_eq!(
+///     Celsius::Indoor(20.0).try_map_payloads(to_kelvin),
+///     Some(Kelvin::Indoor(293.15)),
+/// );
+/// assert_eq!(Celsius::Outdoor(-300.0).try_map_payloads(to_kelvin), None);
+/// ```
+///
+/// ## `helper_vis`
+///
+/// Writing `#[vesta(helper_vis = "...")]` sets the visibility of generated helper items, which is
+/// otherwise the visibility of the type. For instance, `helper_vis = "pub(crate)"` keeps them out
+/// of a crate's public API, and `helper_vis = ""` makes them private.
+///
+/// ```
+/// mod commands {
+///     use vesta::Match;
+///
+///     #[derive(Match)]
+///     #[vesta(router, module = "command_impls", helper_vis = "pub(crate)")]
+///     pub enum Command {
+///         Stop,
+///         Go(u64),
+///     }
+/// }
+///
+/// use commands::{Command, CommandRouter};
+///
+/// fn main() {
+///     let router = CommandRouter::builder()
+///         .on_0(|()| 0)
+///         .on_1(|speed| speed)
+///         .build();
+///     assert_eq!(router.route(Command::Go(3)), 3);
+/// }
+/// ```
+///
+/// ```compile_fail
+/// mod commands {
+///     use vesta::Match;
+///
+///     #[derive(Match)]
+///     #[vesta(router, helper_vis = "")]
+///     pub enum Command {
+///         Stop,
+///         Go(u64),
+///     }
+/// }
+///
+/// // The router is private to the `commands` module
+/// use commands::CommandRouter;
+/// # fn main() {}
 /// ```
 ///
 /// [`Match`]: https://docs.rs/vesta/latest/vesta/trait.Match.html
 ///
 /// [`Case`]: https://docs.rs/vesta/latest/vesta/trait.Case.html
-#[proc_macro_derive(Match)]
+///
+/// [`CaseRef`]: https://docs.rs/vesta/latest/vesta/trait.CaseRef.html
+///
+/// [`CaseMut`]: https://docs.rs/vesta/latest/vesta/trait.CaseMut.html
+///
+/// [`Nonexhaustive`]: https://docs.rs/vesta/latest/vesta/struct.Nonexhaustive.html
+///
+/// [`stable_expansion`]: https://docs.rs/vesta-syntax/latest/vesta_syntax/fn.stable_expansion.html
+///
+/// [`MatchMeta`]: https://docs.rs/vesta/latest/vesta/trait.MatchMeta.html
+///
+/// [`Case::uncase`]: https://docs.rs/vesta/latest/vesta/trait.Case.html#tymethod.uncase
+///
+/// [`MapPayload`]: https://docs.rs/vesta/latest/vesta/trait.MapPayload.html
+///
+/// [`CloneCase`]: https://docs.rs/vesta/latest/vesta/trait.CloneCase.html
+///
+/// [`DebugCase`]: https://docs.rs/vesta/latest/vesta/trait.DebugCase.html
+///
+/// [`CaseList`]: https://docs.rs/vesta/latest/vesta/trait.CaseList.html
+///
+/// [`DropLog`]: https://docs.rs/vesta/latest/vesta/drop_check/struct.DropLog.html
+///
+/// [`Tag`]: https://docs.rs/vesta/latest/vesta/struct.Tag.html
+///
+/// [`TagBase`]: https://docs.rs/vesta/latest/vesta/trait.TagBase.html
+///
+/// [`GlobalCase`]: https://docs.rs/vesta/latest/vesta/trait.GlobalCase.html
+///
+/// [`TryUncase`]: https://docs.rs/vesta/latest/vesta/trait.TryUncase.html
+///
+/// [`UncaseError`]: https://docs.rs/vesta/latest/vesta/struct.UncaseError.html
+///
+/// [`Apply`]: https://docs.rs/vesta/latest/vesta/case_fn/trait.Apply.html
+///
+/// [`CaseFn`]: https://docs.rs/vesta/latest/vesta/case_fn/trait.CaseFn.html
+///
+/// [`MatchMeta::tag_from_name`]:
+/// https://docs.rs/vesta/latest/vesta/trait.MatchMeta.html#method.tag_from_name
+///
+/// [`MatchMeta::CASE_ALIASES`]:
+/// https://docs.rs/vesta/latest/vesta/trait.MatchMeta.html#associatedconstant.CASE_ALIASES
+#[proc_macro_derive(Match, attributes(vesta))]
 pub fn derive_match_derive(input: TokenStream) -> TokenStream {
     derive_match_impl(input)
 }
@@ -122,267 +2691,448 @@ pub fn derive_match_derive(input: TokenStream) -> TokenStream {
 fn derive_match_impl(input: TokenStream) -> TokenStream {
     let DeriveInput {
         ident,
+        vis,
         generics,
-        data,
+        mut data,
         attrs,
-        ..
     } = parse_macro_input!(input as DeriveInput);
-    // Determine if the enum is exhaustive
-    let mut exhaustive = true;
-    for attr in attrs {
-        if let Some(ident) = attr.path.get_ident() {
-            if ident == "non_exhaustive" {
-                exhaustive = false;
+    let options = match Options::from_attrs(&attrs) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    // Everything generated from the types of the fields must be able to name them outside the
+    // type's own impls, where `Self` means something else or nothing at all
+    replace_self(&mut data, &ident, &generics);
+
+    // The declaration of the enum in the order it was written, in case we need to check the
+    // discriminants of its variants, which follow that order rather than the order of their tags
+    let ffi_data = match &data {
+        Data::Enum(data_enum) if options.ffi => Some(data_enum.clone()),
+        _ => None,
+    };
+
+    // Number the variants which only exist under some `cfg` last, so the others keep their tags
+    let conditional_variants = order_conditional_variants(&mut data);
+
+    // Determine if the enum is exhaustive, which it never is while it has variants which don't
+    // always exist
+    let non_exhaustive = if conditional_variants {
+        NonExhaustive::Always
+    } else {
+        NonExhaustive::from_attrs(&attrs)
+    };
+
+    // Count the cases, so we can generate any extra items that need to know them
+    let num_cases = match &data {
+        Data::Struct(_) => 1,
+        Data::Enum(DataEnum { variants, .. }) => variants.len(),
+        Data::Union(_) => 0,
+    };
+
+    // Only the cases which exist can be boxed
+    let mut boxed = BTreeSet::new();
+    for lit in &options.boxed {
+        match lit.base10_parse::<usize>() {
+            Ok(n) if n < num_cases => {
+                let _ = boxed.insert(n);
+            }
+            Ok(n) => {
+                return Error::new(
+                    lit.span(),
+                    format!(
+                        "cannot box case {} of `{}`, which has {} cases",
+                        n, ident, num_cases
+                    ),
+                )
+                .to_compile_error()
+                .into()
             }
+            Err(e) => return e.to_compile_error().into(),
         }
     }
 
-    match data {
-        Data::Struct(s) => derive_match_struct(ident, generics, s),
-        Data::Enum(e) => derive_match_enum(exhaustive, ident, generics, e),
-        Data::Union(_) => Error::new(
-            Span::call_site(),
-            "Cannot derive `Match` for a union, since unions lack a tag",
-        )
-        .to_compile_error()
-        .into(),
+    // The payload of each case of an enum, in case we need to check their sizes
+    let payloads: Vec<(Ident, Type)> = match &data {
+        Data::Enum(DataEnum { variants, .. }) => variants
+            .iter()
+            .map(|variant| {
+                let types = variant.fields.iter().map(|field| &field.ty);
+                (variant.ident.clone(), parse_quote!((#(#types,)*)))
+            })
+            .collect(),
+        Data::Struct(_) | Data::Union(_) => Vec::new(),
+    };
+
+    // The types of the fields of every case, in case we need to compare them case by case
+    let mut field_types: Vec<Type> = Vec::new();
+    if options.case_eq {
+        let fields: Vec<&Field> = match &data {
+            Data::Enum(DataEnum { variants, .. }) => variants
+                .iter()
+                .flat_map(|variant| &variant.fields)
+                .collect(),
+            Data::Struct(DataStruct { fields, .. }) => fields.iter().collect(),
+            Data::Union(_) => Vec::new(),
+        };
+        let mut seen = BTreeSet::new();
+        for field in fields {
+            if seen.insert(field.ty.to_token_stream().to_string()) {
+                field_types.push(field.ty.clone());
+            }
+        }
     }
-}
 
-/// Extract an ordered sequence of field types from a list of fields as `()`, a single `T`, or a
-/// tuple, or return `None` if there are more than one named field.
-fn ordered_fields_types(fields: Fields) -> Option<Punctuated<Type, Token![,]>> {
-    let types = match fields {
-        Fields::Named(FieldsNamed { named, .. }) if named.len() > 1 => return None,
-        Fields::Named(FieldsNamed { named: fields, .. })
-        | Fields::Unnamed(FieldsUnnamed {
-            unnamed: fields, ..
-        }) => fields.into_iter().map(|f| f.ty).collect(),
-        Fields::Unit => vec![parse_quote!(())],
+    // The names of the cases, for the report of which cases are matched
+    let case_names: Vec<Ident> = match &data {
+        Data::Enum(DataEnum { variants, .. }) => variants
+            .iter()
+            .map(|variant| variant.ident.clone())
+            .collect(),
+        Data::Struct(_) | Data::Union(_) => vec![ident.clone()],
     };
-    Some(Punctuated::from_iter(types.into_iter()))
-}
+    let usage = TokenStream::from(register_usage(&ident, &case_names));
 
-/// Extract the field names of a named struct, or count them if they are unnamed.
-fn field_names(fields: Fields) -> Result<Punctuated<Ident, Token![,]>, usize> {
-    let fields = match fields {
-        Fields::Named(FieldsNamed { named: fields, .. })
-        | Fields::Unnamed(FieldsUnnamed {
-            unnamed: fields, ..
-        }) => fields,
-        Fields::Unit => Punctuated::new(),
+    // And what they used to be called, any errors in which are reported by the derived impls
+    let case_aliases: Vec<Vec<LitStr>> = match &data {
+        Data::Enum(DataEnum { variants, .. }) => variants
+            .iter()
+            .map(|variant| case_aliases(&variant.attrs).unwrap_or_default())
+            .collect(),
+        Data::Struct(_) | Data::Union(_) => vec![Vec::new()],
     };
-    let len = fields.len();
-    fields
-        .into_iter()
-        .map(|Field { ident, .. }| ident)
-        .collect::<Option<_>>()
-        .ok_or(len)
-}
 
-/// Implement `Case<#n>` for the type `ident` with generics `generics`, constructor `constructor`
-/// (this is equal to `ident` for structs, and equal to `ident::constructor` for enums), and fields
-/// `fields`.
-fn case_impl(
-    n: usize,
-    ident: Ident,
-    generics: Generics,
-    constructor: Path,
-    fields: Fields,
-) -> Option<Item> {
-    let vesta_path = vesta_path();
-    let case_types = ordered_fields_types(fields.clone())?;
-    let this_ident = Ident::new("this", Span::mixed_site());
-    let (case_body, uncase_body, try_case_body) = match field_names(fields) {
-        // In the case of unnamed fields...
-        Err(params) => {
-            let names: Punctuated<Ident, Token![,]> = (0usize..)
-                .map(|i| format_ident!("x_{}", i))
-                .take(params)
-                .collect();
-            (
-                quote!({
-                    if let #constructor(#names) = #this_ident {
-                        (#names)
-                    } else {
-                        #vesta_path::unreachable()
-                    }
-                }),
-                quote!({
-                    let (#names) = case;
-                    #constructor(#names)
-                }),
-                quote!({
-                    if let #constructor(#names) = #this_ident {
-                        ::std::result::Result::Ok((#names))
-                    } else {
-                        ::std::result::Result::Err(#this_ident)
-                    }
-                }),
-            )
+    // The declaration of the enum, in case we need to generate its step type for unfolding
+    let unfold_data = match &data {
+        Data::Enum(data_enum) if options.unfold => Some(data_enum.clone()),
+        _ => None,
+    };
+
+    // Likewise, in case we need to clone or format the payloads of its variants one by one
+    let case_wise_data = match &data {
+        Data::Enum(data_enum) if options.clone_case || options.debug_case => {
+            Some(data_enum.clone())
         }
-        // In the case of named fields...
-        Ok(field_names) => (
-            quote!({
-                if let #constructor { #field_names } = #this_ident {
-                    (#field_names)
-                } else {
-                    #vesta_path::unreachable()
-                }
-            }),
-            quote!({
-                let (#field_names) = case;
-                #constructor { #field_names }
-            }),
-            quote!({
-                if let #constructor { #field_names } = #this_ident {
-                    ::std::result::Result::Ok((#field_names))
-                } else {
-                    ::std::result::Result::Err(#this_ident)
-                }
-            }),
-        ),
+        _ => None,
+    };
+
+    // Likewise, in case we need to generate the payload structs of its variants, which are used as
+    // their cases
+    let newtype_data = match &data {
+        Data::Enum(data_enum) if options.newtype_variants.is_some() => Some(data_enum.clone()),
+        _ => None,
     };
+    let payload_structs = newtype_data
+        .as_ref()
+        .map(|data_enum| payload_structs(&generics, data_enum))
+        .unwrap_or_default();
 
-    let where_clause = &generics.where_clause;
-    Some(parse_quote! {
-        #[allow(unused_qualifications)]
-        impl #generics #vesta_path::Case<#n> for #ident #generics #where_clause {
-            type Case = ( #case_types );
-            unsafe fn case(#this_ident: Self) -> Self::Case #case_body
-            fn uncase(case: Self::Case) -> Self #uncase_body
-            fn try_case(#this_ident: Self) -> ::std::result::Result<Self::Case, Self> #try_case_body
+    let impl_options = ImplOptions {
+        check_tags: options.check_tags,
+        boxed,
+        payload_structs,
+    };
+    let is_enum = matches!(data, Data::Enum(_));
+    let mut output: TokenStream = match data {
+        Data::Struct(s) => {
+            derive_match_struct(ident.clone(), generics.clone(), s, &impl_options).into()
         }
-    })
-}
+        Data::Enum(e) => derive_match_enum(
+            &non_exhaustive,
+            ident.clone(),
+            generics.clone(),
+            e,
+            &impl_options,
+        )
+        .into(),
+        Data::Union(_) => {
+            return Error::new(Span::call_site(), messages::union(&ident))
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    // Helper items are declared `pub` inside a generated module, and then re-exported from it with
+    // their intended visibility
+    let helper_vis = options.helper_vis.unwrap_or(vis);
+    let item_vis = if options.module.is_some() {
+        parse_quote!(pub)
+    } else {
+        helper_vis.clone()
+    };
+    let mut helpers = Vec::new();
 
-/// Derive `Match` for a `struct`
-fn derive_match_struct(
-    ident: Ident,
-    generics: Generics,
-    DataStruct { fields, .. }: DataStruct,
-) -> TokenStream {
-    let fields_span = fields.span();
-    if let Some(case_impl) = case_impl(
-        0,
-        ident.clone(),
-        generics.clone(),
-        ident.clone().into(),
-        fields,
-    ) {
-        let vesta_path = vesta_path();
-        let where_clause = &generics.where_clause;
-        TokenStream::from(quote! {
-            #[allow(unused_qualifications)]
-            unsafe impl #generics #vesta_path::Match for #ident #generics #where_clause {
-                type Range = #vesta_path::Exhaustive<1>;
-
-                fn tag(&self) -> ::std::option::Option<::std::primitive::usize> {
-                    ::std::option::Option::Some(0)
-                }
+    if options.router {
+        helpers.extend(router_idents(&ident));
+        output.extend(TokenStream::from(match non_exhaustive {
+            NonExhaustive::Never if num_cases > 0 => {
+                derive_router(&item_vis, &ident, &generics, num_cases)
             }
+            NonExhaustive::Never => Error::new(
+                ident.span(),
+                format!(
+                    "cannot generate a router for `{}`, because it has no cases to route",
+                    ident
+                ),
+            )
+            .to_compile_error(),
+            NonExhaustive::Always => Error::new(
+                ident.span(),
+                format!(
+                    "cannot generate a router for the non-exhaustive type `{}`",
+                    ident
+                ),
+            )
+            .to_compile_error(),
+        }));
+    }
 
-            #case_impl
-        })
-    } else {
-        Error::new(
-            fields_span,
-            format!(
-                "cannot derive `Match` for the struct `{i}` with more than one named field\n\
-            consider making `{i}` a tuple struct, or a wrapper for another type with named fields",
-                i = ident
-            ),
-        )
-        .to_compile_error()
-        .into()
+    if let Some(attrs) = &options.raw_form {
+        helpers.extend(raw_form_idents(&ident));
+        output.extend(TokenStream::from(derive_raw_form(
+            &item_vis,
+            &ident,
+            &generics,
+            &case_names,
+            attrs,
+            options.validate.is_some(),
+        )));
     }
-}
 
-/// Derive `Match` for an `enum`
-fn derive_match_enum(
-    exhaustive: bool,
-    ident: Ident,
-    generics: Generics,
-    DataEnum { variants, .. }: DataEnum,
-) -> TokenStream {
-    let vesta_path = vesta_path();
-
-    // Count the number of variants
-    let num_variants = variants.len();
-
-    // Construct the `Match` impl
-    let mut tag_arms: Vec<Arm> = variants
-        .iter()
-        .enumerate()
-        .map(
-            |(
-                i,
-                Variant {
-                    ident: constructor, ..
-                },
-            )| parse_quote!(#ident::#constructor { .. } => ::std::option::Option::Some(#i)),
-        )
-        .collect();
+    if let Some(attrs) = &options.newtype_variants {
+        output.extend(TokenStream::from(if let Some(data_enum) = &newtype_data {
+            helpers.extend(
+                impl_options
+                    .payload_structs
+                    .values()
+                    .map(|payload_struct| payload_struct.ident.clone()),
+            );
+            derive_newtype_variants(
+                &item_vis,
+                &ident,
+                &generics,
+                data_enum,
+                &impl_options.payload_structs,
+                attrs,
+            )
+        } else {
+            Error::new(
+                ident.span(),
+                "only the variants of enums can have payload structs",
+            )
+            .to_compile_error()
+        }));
+    }
 
-    // Only if non-exhaustive, push this fall-through arm
-    if !exhaustive {
-        tag_arms.push(parse_quote! {
-            _ => ::std::option::Option::None
-        });
+    if options.unfold {
+        output.extend(TokenStream::from(if let Some(data_enum) = &unfold_data {
+            helpers.push(unfold_ident(&ident));
+            derive_unfold(
+                &item_vis,
+                &ident,
+                &generics,
+                data_enum,
+                options.validate.as_ref(),
+            )
+        } else {
+            Error::new(ident.span(), "only enums can be unfolded").to_compile_error()
+        }));
     }
 
-    // Range of the instance
-    let range = if exhaustive {
-        quote!(#vesta_path::Exhaustive<#num_variants>)
-    } else {
-        quote!(#vesta_path::Nonexhaustive)
-    };
+    if let Some(frozen) = &options.frozen {
+        output.extend(TokenStream::from(derive_frozen(
+            &ident,
+            &generics,
+            &case_names,
+            &case_aliases,
+            frozen,
+        )));
+    }
 
-    // Output stream starts with the `Match` impl
-    let where_clause = &generics.where_clause;
-    let mut output = quote! {
-        #[allow(unused_qualifications)]
-        unsafe impl #generics #vesta_path::Match for #ident #generics #where_clause {
-            type Range = #range;
-
-            fn tag(&self) -> ::std::option::Option<::std::primitive::usize> {
-                match *self {
-                    #(#tag_arms),*
-                }
-            }
-        }
-    };
+    if let Some(base) = &options.tag_base {
+        output.extend(TokenStream::from(derive_tag_base(
+            &ident, &generics, num_cases, base,
+        )));
+    }
+
+    if let Some(check) = &options.validate {
+        output.extend(TokenStream::from(derive_try_uncase(
+            &ident, &generics, num_cases, check,
+        )));
+    }
+
+    if let Some((target, validated)) = &options.map_to {
+        output.extend(TokenStream::from(derive_map_payloads(
+            &ident, &generics, num_cases, target, *validated,
+        )));
+    }
+
+    if options.apply {
+        output.extend(TokenStream::from(
+            if let NonExhaustive::Never = non_exhaustive {
+                derive_apply(&ident, &generics, num_cases)
+            } else {
+                Error::new(
+                    ident.span(),
+                    format!(
+                        "cannot handle every case of the non-exhaustive type `{}`",
+                        ident
+                    ),
+                )
+                .to_compile_error()
+            },
+        ));
+    }
+
+    if options.type_list {
+        output.extend(TokenStream::from(
+            if let NonExhaustive::Never = non_exhaustive {
+                derive_type_list(&ident, &generics, num_cases)
+            } else {
+                Error::new(
+                    ident.span(),
+                    format!(
+                        "cannot list the cases of the non-exhaustive type `{}`",
+                        ident
+                    ),
+                )
+                .to_compile_error()
+            },
+        ));
+    }
+
+    if options.flat_option {
+        output.extend(TokenStream::from(
+            if let NonExhaustive::Never = non_exhaustive {
+                derive_flat_option(&ident, &generics, num_cases)
+            } else {
+                Error::new(
+                    ident.span(),
+                    format!(
+                        "cannot flatten options of the non-exhaustive type `{}`",
+                        ident
+                    ),
+                )
+                .to_compile_error()
+            },
+        ));
+    }
+
+    if options.absurd {
+        output.extend(TokenStream::from(match non_exhaustive {
+            NonExhaustive::Never if is_enum && num_cases == 0 => derive_absurd(&ident, &generics),
+            NonExhaustive::Never => Error::new(
+                ident.span(),
+                format!(
+                    "cannot mark `{}` as absurd, because only enums with no variants are absurd",
+                    ident
+                ),
+            )
+            .to_compile_error(),
+            NonExhaustive::Always => Error::new(
+                ident.span(),
+                format!(
+                    "cannot mark the non-exhaustive type `{}` as absurd, because it may gain variants",
+                    ident
+                ),
+            )
+            .to_compile_error(),
+        }));
+    }
+
+    if options.ffi {
+        output.extend(TokenStream::from(if let Some(data_enum) = &ffi_data {
+            derive_ffi(&ident, &generics, &attrs, data_enum, &case_names)
+        } else {
+            Error::new(
+                ident.span(),
+                "only the discriminants of enums can be checked",
+            )
+            .to_compile_error()
+        }));
+    }
+
+    if options.case_eq {
+        output.extend(TokenStream::from(derive_case_eq(
+            &ident,
+            &generics,
+            num_cases,
+            &field_types,
+        )));
+    }
 
-    // Construct each `Case` impl
-    let case_impls = variants.into_iter().enumerate().map(
-        |(
-            n,
-            Variant {
-                ident: constructor,
-                fields,
-                ..
+    if options.clone_case {
+        output.extend(TokenStream::from(
+            if let Some(data_enum) = &case_wise_data {
+                derive_clone_case(&ident, &generics, data_enum, options.validate.as_ref())
+            } else {
+                Error::new(
+                    ident.span(),
+                    "only the cases of enums can be cloned one by one",
+                )
+                .to_compile_error()
             },
-        )| {
-            let fields_span = fields.span();
-            if let Some(case_impl) = case_impl(
-                n,
-                ident.clone(),
-                generics.clone(),
-                parse_quote!(#ident::#constructor),
-                fields,
-            ) {
-                quote!(#case_impl)
+        ));
+    }
+
+    if options.debug_case {
+        output.extend(TokenStream::from(
+            if let Some(data_enum) = &case_wise_data {
+                derive_debug_case(&ident, &generics, data_enum)
             } else {
                 Error::new(
-                    fields_span,
-                    format!("cannot derive `Match` for the enum variant `{i}::{c}` with more than one named field\n\
-                    consider making `{i}::{c}` a tuple variant, or a wrapper for another type with named fields", i = ident, c = constructor),
+                    ident.span(),
+                    "only the cases of enums can be formatted one by one",
                 )
                 .to_compile_error()
+            },
+        ));
+    }
+
+    if options.strum {
+        output.extend(TokenStream::from(if is_enum {
+            derive_strum_check(&ident, &generics, num_cases)
+        } else {
+            Error::new(
+                ident.span(),
+                "`strum` consistency can only be checked for enums",
+            )
+            .to_compile_error()
+        }));
+    }
+
+    if options.warn_large {
+        output.extend(TokenStream::from(if is_enum {
+            derive_size_check(&ident, &generics, &payloads)
+        } else {
+            Error::new(
+                ident.span(),
+                "the sizes of cases can only be checked for enums",
+            )
+            .to_compile_error()
+        }));
+    }
+
+    if let Some(module) = options.module {
+        let output = proc_macro2::TokenStream::from(output);
+        let helpers = if helpers.is_empty() {
+            None
+        } else {
+            Some(quote!(#helper_vis use self::#module::{#(#helpers),*};))
+        };
+        let usage = proc_macro2::TokenStream::from(usage);
+        return TokenStream::from(quote! {
+            #[doc(hidden)]
+            #[allow(unused_imports)]
+            mod #module {
+                use super::*;
+                #output
             }
-        },
-    );
+            #helpers
+            #usage
+        });
+    }
 
-    output.extend(case_impls);
-    TokenStream::from(output)
+    output.extend(usage);
+    output
 }
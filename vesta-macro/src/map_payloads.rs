@@ -0,0 +1,108 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Generics, Ident, Type};
+
+use vesta_syntax::{audited_case, vesta_path};
+
+/// Generate a method `map_payloads` for the type `ident` with `num_cases` cases, which converts a
+/// value to the type `target` by mapping its payload to the payload of the same case of `target`.
+/// The target is required to have the same range as the type, and the mapping to implement
+/// `MapPayload` from each case of the type to the same case of the target, so that a case missing
+/// from either side is a compile-time error.
+///
+/// If the target is `validated`, the method is named `try_map_payloads` instead, and rebuilds the
+/// value of the target using `TryUncase` rather than `Case`, returning `None` if it is rejected.
+pub fn derive_map_payloads(
+    ident: &Ident,
+    generics: &Generics,
+    num_cases: usize,
+    target: &Type,
+    validated: bool,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let map_ident = Ident::new("map", Span::mixed_site());
+    let tags: Vec<usize> = (0..num_cases).collect();
+    let map_payload_bounds = tags.iter().map(|n| {
+        quote! {
+            #vesta_path::MapPayload<
+                <Self as #vesta_path::Case<#n>>::Case,
+                <#target as #vesta_path::Case<#n>>::Case,
+            >
+        }
+    });
+    let target_name = quote!(#target).to_string().replace(' ', "");
+    let (method, output, doc) = if validated {
+        (
+            Ident::new("try_map_payloads", Span::call_site()),
+            quote!(::std::option::Option<#target>),
+            format!(
+                "Convert this value to a `{}` in the same case, using `map` to map its payload, \
+                or `None` if the `{}` rejects the mapped payload, as generated by \
+                `#[vesta(map_to(..., validated))]`.",
+                target_name, target_name
+            ),
+        )
+    } else {
+        (
+            Ident::new("map_payloads", Span::call_site()),
+            quote!(#target),
+            format!(
+                "Convert this value to a `{}` in the same case, using `map` to map its payload, as \
+                generated by `#[vesta(map_to(...))]`.",
+                target_name
+            ),
+        )
+    };
+    // Map the payload of each case, and rebuild the value of the target from it, checking it if the
+    // target is validated
+    let arms = tags.iter().map(|n| {
+        let extract_case = audited_case(
+            quote!(&self),
+            n,
+            quote!(#vesta_path::Case::<#n>::case(self)),
+        );
+        let mapped = quote! {
+            #vesta_path::MapPayload::<
+                <Self as #vesta_path::Case<#n>>::Case,
+                <#target as #vesta_path::Case<#n>>::Case,
+            >::map_payload(
+                &mut #map_ident,
+                // The tag is `n`, so the case can be extracted
+                unsafe #extract_case,
+            )
+        };
+        if validated {
+            quote! {
+                ::std::result::Result::ok(
+                    <#target as #vesta_path::TryUncase<#n>>::try_uncase(#mapped),
+                )
+            }
+        } else {
+            quote!(<#target as #vesta_path::Case<#n>>::uncase(#mapped))
+        }
+    });
+    let try_uncase_bounds = tags
+        .iter()
+        .filter(|_| validated)
+        .map(|n| quote!(#target: #vesta_path::TryUncase<#n>,));
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[doc = #doc]
+            #[inline]
+            pub fn #method<__VestaMap>(self, mut #map_ident: __VestaMap) -> #output
+            where
+                #target: #vesta_path::Match<Range = <Self as #vesta_path::Match>::Range>,
+                #(#try_uncase_bounds)*
+                __VestaMap: #(#map_payload_bounds)+*,
+            {
+                match #vesta_path::Match::tag(&self) {
+                    #(::std::option::Option::Some(#tags) => #arms,)*
+                    // The derived tag of a value is always that of one of its cases
+                    _ => unsafe { #vesta_path::unreachable() },
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use std::collections::BTreeMap;
+use syn::{ext::IdentExt, DataEnum, Fields, Generics, Ident, Meta, Visibility};
+
+use vesta_syntax::derive::PayloadStruct;
+
+/// The payload struct for each variant of an enum with more than one field, by tag, each
+/// named `{Variant}Payload` after its variant.
+pub fn payload_structs(generics: &Generics, data: &DataEnum) -> BTreeMap<usize, PayloadStruct> {
+    data.variants
+        .iter()
+        .enumerate()
+        .filter(|(_, variant)| variant.fields.len() > 1)
+        .map(|(n, variant)| {
+            let ident = format_ident!("{}Payload", variant.ident.unraw());
+            (n, PayloadStruct::new(ident, generics, &variant.fields))
+        })
+        .collect()
+}
+
+/// Declare the payload structs of the enum `ident` with generics `generics`, which have the same
+/// fields as their variants, with the same names and documentation, all public. A payload struct of
+/// a tuple variant is a tuple struct.
+///
+/// Each of `attrs` is applied to every payload struct, so that they can derive traits such as
+/// `Debug`.
+pub fn derive_newtype_variants(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    data: &DataEnum,
+    payload_structs: &BTreeMap<usize, PayloadStruct>,
+    attrs: &[Meta],
+) -> TokenStream {
+    let structs = payload_structs.iter().map(|(&n, payload_struct)| {
+        let variant = &data.variants[n];
+        let payload_ident = &payload_struct.ident;
+        let payload_generics = payload_struct.generics(generics);
+        let (_, _, where_clause) = payload_generics.split_for_impl();
+        let params = &payload_generics.params;
+        let doc = format!(
+            "The payload of [`{}::{}`], which is its case {}.\n\n\
+            This is generated by `#[vesta(newtype_variants)]`.",
+            ident,
+            variant.ident.unraw(),
+            n
+        );
+
+        // Keep only the documentation of each field
+        let mut fields = variant.fields.clone();
+        for field in fields.iter_mut() {
+            field.attrs.retain(|attr| attr.path.is_ident("doc"));
+            field.vis = Visibility::Public(syn::VisPublic {
+                pub_token: Default::default(),
+            });
+        }
+        let body = match &fields {
+            Fields::Named(named) => quote!(#where_clause #named),
+            Fields::Unnamed(unnamed) => quote!(#unnamed #where_clause;),
+            Fields::Unit => quote!(;),
+        };
+
+        quote! {
+            #[doc = #doc]
+            #(#[#attrs])*
+            #vis struct #payload_ident <#params> #body
+        }
+    });
+    quote!(#(#structs)*)
+}
@@ -0,0 +1,92 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Generics, Ident, Lifetime};
+
+use vesta_syntax::vesta_path;
+
+/// Generate implementations of `OptionCases` and `CaseInOption` for the exhaustive type `ident`
+/// with `num_cases` cases, so that its options can be matched flatly using `FlatOption`: case 0 of
+/// the option is `None`, and each case `n + 1` is `Some` of case `n` of the type.
+pub fn derive_flat_option(ident: &Ident, generics: &Generics, num_cases: usize) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let ref_lifetime = Lifetime::new("'__vesta", Span::call_site());
+    let this_ident = Ident::new("this", Span::mixed_site());
+    let value_ident = Ident::new("value", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+    let with_none = num_cases + 1;
+
+    // Each case after the first is `Some` of the case before it
+    let some_impls = (0..num_cases).map(|n| {
+        let m = n + 1;
+        quote! {
+            #[allow(unused_qualifications)]
+            unsafe impl #impl_generics #vesta_path::option::CaseInOption<#m> for #ident #ty_generics #where_clause {
+                type Case = <Self as #vesta_path::Case<#n>>::Case;
+                type Ref<#ref_lifetime> = <Self as #vesta_path::CaseRef<#n>>::Ref<#ref_lifetime> where Self: #ref_lifetime;
+                #[inline]
+                unsafe fn case(#this_ident: ::std::option::Option<Self>) -> Self::Case {
+                    match #this_ident {
+                        ::std::option::Option::Some(#value_ident) => #vesta_path::Case::<#n>::case(#value_ident),
+                        ::std::option::Option::None => #vesta_path::unreachable(),
+                    }
+                }
+                #[inline]
+                fn uncase(#case_ident: Self::Case) -> ::std::option::Option<Self> {
+                    ::std::option::Option::Some(#vesta_path::Case::<#n>::uncase(#case_ident))
+                }
+                #[inline]
+                fn try_case(
+                    #this_ident: ::std::option::Option<Self>,
+                ) -> ::std::result::Result<Self::Case, ::std::option::Option<Self>> {
+                    match #this_ident {
+                        ::std::option::Option::Some(#value_ident) => {
+                            #vesta_path::Case::<#n>::try_case(#value_ident)
+                                .map_err(::std::option::Option::Some)
+                        }
+                        ::std::option::Option::None => ::std::result::Result::Err(::std::option::Option::None),
+                    }
+                }
+                #[inline]
+                unsafe fn case_ref(#this_ident: &::std::option::Option<Self>) -> Self::Ref<'_> {
+                    match #this_ident {
+                        ::std::option::Option::Some(#value_ident) => #vesta_path::CaseRef::<#n>::case_ref(#value_ident),
+                        ::std::option::Option::None => #vesta_path::unreachable(),
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[allow(unused_qualifications)]
+        unsafe impl #impl_generics #vesta_path::option::OptionCases for #ident #ty_generics #where_clause {
+            type WithNone = #vesta_path::Exhaustive<#with_none>;
+        }
+
+        #[allow(unused_qualifications, clippy::unused_unit)]
+        unsafe impl #impl_generics #vesta_path::option::CaseInOption<0> for #ident #ty_generics #where_clause {
+            type Case = ();
+            type Ref<#ref_lifetime> = () where Self: #ref_lifetime;
+            #[inline]
+            unsafe fn case(_: ::std::option::Option<Self>) -> Self::Case {}
+            #[inline]
+            fn uncase((): Self::Case) -> ::std::option::Option<Self> {
+                ::std::option::Option::None
+            }
+            #[inline]
+            fn try_case(
+                #this_ident: ::std::option::Option<Self>,
+            ) -> ::std::result::Result<Self::Case, ::std::option::Option<Self>> {
+                match #this_ident {
+                    ::std::option::Option::None => ::std::result::Result::Ok(()),
+                    ::std::option::Option::Some(_) => ::std::result::Result::Err(#this_ident),
+                }
+            }
+            #[inline]
+            unsafe fn case_ref(_: &::std::option::Option<Self>) -> Self::Ref<'_> {}
+        }
+
+        #(#some_impls)*
+    }
+}
@@ -0,0 +1,258 @@
+use proc_macro2::{TokenStream, TokenTree};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    Attribute, Error, Ident, LitInt, LitStr, Meta, Path, Token, Type, Visibility,
+};
+
+/// A single item in a `#[vesta(...)]` attribute: either a bare flag like `router`, an assignment
+/// like `module = "name"`, or a list like `boxed(1, 2)`.
+struct OptionItem {
+    name: Ident,
+    value: OptionValue,
+}
+
+/// The value given to an [`OptionItem`], if any.
+enum OptionValue {
+    Flag,
+    Assign(TokenStream),
+    List(TokenStream),
+}
+
+impl Parse for OptionItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            let _: Token![=] = input.parse()?;
+            let mut tokens = TokenStream::new();
+            while !input.is_empty() && !input.peek(Token![,]) {
+                tokens.extend(Some(input.parse::<TokenTree>()?));
+            }
+            OptionValue::Assign(tokens)
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            let _ = parenthesized!(content in input);
+            OptionValue::List(content.parse()?)
+        } else {
+            OptionValue::Flag
+        };
+        Ok(OptionItem { name, value })
+    }
+}
+
+/// Parse all the `#[vesta(...)]` attributes in a list, calling `apply` on each item found.
+fn parse_options(
+    attrs: &[Attribute],
+    mut apply: impl FnMut(&Ident, OptionValue) -> syn::Result<()>,
+) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("vesta")) {
+        let items = attr.parse_args_with(Punctuated::<OptionItem, Token![,]>::parse_terminated)?;
+        for OptionItem { name, value } in items {
+            apply(&name, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Require that an option is a bare flag, returning an error otherwise.
+fn expect_flag(name: &Ident, value: OptionValue) -> syn::Result<()> {
+    match value {
+        OptionValue::Flag => Ok(()),
+        OptionValue::Assign(tokens) | OptionValue::List(tokens) => Err(Error::new_spanned(
+            tokens,
+            format!("`{}` does not take a value", name),
+        )),
+    }
+}
+
+/// Require that an option is assigned a string literal, and parse the contents of that literal.
+fn expect_assign<T: Parse>(name: &Ident, value: OptionValue) -> syn::Result<T> {
+    match value {
+        OptionValue::Assign(tokens) => syn::parse2::<LitStr>(tokens)?.parse(),
+        OptionValue::Flag | OptionValue::List(_) => Err(Error::new(
+            name.span(),
+            format!(
+                "`{}` must be given a value, as in `{} = \"...\"`",
+                name, name
+            ),
+        )),
+    }
+}
+
+/// Require that an option is assigned a string literal, and return that literal.
+fn expect_str(name: &Ident, value: OptionValue) -> syn::Result<LitStr> {
+    match value {
+        OptionValue::Assign(tokens) => syn::parse2(tokens),
+        OptionValue::Flag | OptionValue::List(_) => Err(Error::new(
+            name.span(),
+            format!(
+                "`{}` must be given a value, as in `{} = \"...\"`",
+                name, name
+            ),
+        )),
+    }
+}
+
+/// Require that an option is given a parenthesized list, and parse its comma-separated items.
+fn expect_list<T: Parse>(name: &Ident, value: OptionValue) -> syn::Result<Vec<T>> {
+    match value {
+        OptionValue::List(tokens) => Ok(Punctuated::<T, Token![,]>::parse_terminated
+            .parse2(tokens)?
+            .into_iter()
+            .collect()),
+        OptionValue::Flag | OptionValue::Assign(_) => Err(Error::new(
+            name.span(),
+            format!("`{}` must be given a list, as in `{}(...)`", name, name),
+        )),
+    }
+}
+
+/// The options for deriving `Match`, given as `#[vesta(...)]` attributes on the type.
+#[derive(Default)]
+pub struct Options {
+    /// Whether to generate a router with a handler for each case.
+    pub router: bool,
+    /// Whether to check that the type's `strum::EnumCount` implementation agrees with its cases.
+    pub strum: bool,
+    /// The name of a module in which to place all the generated items, if any.
+    pub module: Option<Ident>,
+    /// The visibility of generated helper items, if different from that of the type.
+    pub helper_vis: Option<Visibility>,
+    /// Whether to check in debug builds that cases are only extracted from values with their tag.
+    pub check_tags: bool,
+    /// The tags of the cases whose payloads should be boxed when extracted.
+    pub boxed: Vec<LitInt>,
+    /// Whether to warn when the payload of one case is much larger than the others.
+    pub warn_large: bool,
+    /// Whether to implement `Unfold`, along with a step type for it.
+    pub unfold: bool,
+    /// Whether to allow options of the type to be matched flatly using `FlatOption`.
+    pub flat_option: bool,
+    /// The fingerprint of the cases whose tags are frozen, if any.
+    pub frozen: Option<LitStr>,
+    /// Whether to convert the type, which must have no cases, to and from `Infallible`.
+    pub absurd: bool,
+    /// Whether to check the discriminants of raw values of the type, which must be `#[repr(C)]` or
+    /// have a primitive representation, as they arrive from foreign code.
+    pub ffi: bool,
+    /// Whether to implement `CaseEq`, comparing values case by case.
+    pub case_eq: bool,
+    /// Whether to implement `CloneCase` for each case, cloning its payload alone.
+    pub clone_case: bool,
+    /// Whether to implement `DebugCase` for each case, formatting its payload alone.
+    pub debug_case: bool,
+    /// Whether to implement `CaseList`, listing the payload types of the cases.
+    pub type_list: bool,
+    /// Whether to implement `Apply`, handling each case by a `CaseFn`.
+    pub apply: bool,
+    /// The attributes to apply to the generated raw form of the type, if it is to be generated.
+    pub raw_form: Option<Vec<Meta>>,
+    /// The constant by which to offset the tags of the type to get its global tags, if any.
+    pub tag_base: Option<Path>,
+    /// The attributes to apply to the generated payload structs of the cases with more than one
+    /// field, if they are to be generated.
+    pub newtype_variants: Option<Vec<Meta>>,
+    /// The type to which the payloads of the type can be mapped case by case, if any, and whether
+    /// that type validates the values rebuilt from its cases, so that they must be rebuilt using
+    /// `TryUncase`.
+    pub map_to: Option<(Type, bool)>,
+    /// The function checking the invariants of values rebuilt from their cases by `TryUncase`, if
+    /// any.
+    pub validate: Option<Path>,
+}
+
+impl Options {
+    /// Parse the options from the attributes on the type.
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Options> {
+        let mut options = Options::default();
+        parse_options(attrs, |name, value| {
+            if name == "router" {
+                expect_flag(name, value)?;
+                options.router = true;
+            } else if name == "strum" {
+                expect_flag(name, value)?;
+                options.strum = true;
+            } else if name == "check_tags" {
+                expect_flag(name, value)?;
+                options.check_tags = true;
+            } else if name == "boxed" {
+                options.boxed.extend(expect_list(name, value)?);
+            } else if name == "warn_large" {
+                expect_flag(name, value)?;
+                options.warn_large = true;
+            } else if name == "unfold" {
+                expect_flag(name, value)?;
+                options.unfold = true;
+            } else if name == "flat_option" {
+                expect_flag(name, value)?;
+                options.flat_option = true;
+            } else if name == "absurd" {
+                expect_flag(name, value)?;
+                options.absurd = true;
+            } else if name == "ffi" {
+                expect_flag(name, value)?;
+                options.ffi = true;
+            } else if name == "case_eq" {
+                expect_flag(name, value)?;
+                options.case_eq = true;
+            } else if name == "clone_case" {
+                expect_flag(name, value)?;
+                options.clone_case = true;
+            } else if name == "debug_case" {
+                expect_flag(name, value)?;
+                options.debug_case = true;
+            } else if name == "type_list" {
+                expect_flag(name, value)?;
+                options.type_list = true;
+            } else if name == "apply" {
+                expect_flag(name, value)?;
+                options.apply = true;
+            } else if name == "raw_form" {
+                options.raw_form = Some(match value {
+                    OptionValue::Flag => Vec::new(),
+                    value => expect_list(name, value)?,
+                });
+            } else if name == "newtype_variants" {
+                options.newtype_variants = Some(match value {
+                    OptionValue::Flag => Vec::new(),
+                    value => expect_list(name, value)?,
+                });
+            } else if name == "frozen" {
+                options.frozen = Some(expect_str(name, value)?);
+            } else if name == "validate" {
+                options.validate = Some(expect_assign(name, value)?);
+            } else if name == "tag_base" {
+                options.tag_base = Some(expect_assign(name, value)?);
+            } else if name == "map_to" {
+                let span = name.span();
+                let targets = expect_list::<Type>(name, value)?;
+                options.map_to =
+                    Some(match targets.as_slice() {
+                        [target] => (target.clone(), false),
+                        [target, Type::Path(flag)]
+                            if flag.qself.is_none() && flag.path.is_ident("validated") =>
+                        {
+                            (target.clone(), true)
+                        }
+                        _ => return Err(Error::new(
+                            span,
+                            "`map_to` must be given one type, optionally followed by `validated`, \
+                            as in `map_to(Type)` or `map_to(Type, validated)`",
+                        )),
+                    });
+            } else if name == "module" {
+                options.module = Some(expect_assign(name, value)?);
+            } else if name == "helper_vis" {
+                options.helper_vis = Some(expect_assign(name, value)?);
+            } else {
+                return Err(Error::new(
+                    name.span(),
+                    format!("unknown `vesta` option `{}`", name),
+                ));
+            }
+            Ok(())
+        })?;
+        Ok(options)
+    }
+}
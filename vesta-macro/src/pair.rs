@@ -0,0 +1,257 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    spanned::Spanned,
+    Attribute, Error, Expr, Ident, LitInt, Pat, Token,
+};
+
+use vesta_syntax::{audited_case, messages, vesta_path};
+
+/// The input to `case_pair!`: a pair of scrutinees and the arms matching on their tags together.
+pub struct PairInput {
+    first: Expr,
+    second: Expr,
+    arms: Vec<PairArm>,
+    span: Span,
+}
+
+/// A single arm of a `case_pair!`, i.e. `(0(x), 1) if x > 0 => ...`.
+struct PairArm {
+    attrs: Vec<Attribute>,
+    first: PairTag,
+    second: PairTag,
+    guard: Option<Expr>,
+    body: Expr,
+}
+
+/// One half of the pattern of a [`PairArm`]: either `_`, or a tag with an optional pattern for its
+/// case.
+enum PairTag {
+    Wild,
+    Tag(usize, Option<Pat>),
+}
+
+impl PairTag {
+    /// Whether this matches the given tag.
+    fn matches(&self, tag: usize) -> bool {
+        match self {
+            PairTag::Wild => true,
+            PairTag::Tag(t, _) => *t == tag,
+        }
+    }
+
+    /// The pattern for the case of this tag, or `_` if none was given.
+    fn pattern(&self) -> TokenStream {
+        match self {
+            PairTag::Tag(_, Some(pat)) => quote!(#pat),
+            PairTag::Tag(_, None) | PairTag::Wild => quote!(_),
+        }
+    }
+}
+
+impl Parse for PairTag {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![_]) {
+            let _: Token![_] = input.parse()?;
+            return Ok(PairTag::Wild);
+        }
+        let lit: LitInt = input.parse()?;
+        let tag = lit.base10_parse()?;
+        let pat = if input.peek(syn::token::Paren) {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(PairTag::Tag(tag, pat))
+    }
+}
+
+impl Parse for PairArm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let (first, second) = if input.peek(Token![_]) {
+            let _: Token![_] = input.parse()?;
+            (PairTag::Wild, PairTag::Wild)
+        } else {
+            let content;
+            let _ = parenthesized!(content in input);
+            let first = content.parse()?;
+            let _: Token![,] = content.parse()?;
+            let second = content.parse()?;
+            if !content.is_empty() {
+                return Err(content.error("expected a pair of tags"));
+            }
+            (first, second)
+        };
+        let guard = if input.peek(Token![if]) {
+            let _: Token![if] = input.parse()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let _: Token![=>] = input.parse()?;
+        let body: Expr = input.parse()?;
+
+        // Like in `match`, the comma is optional after a block-like body, or after the last arm
+        let block_like = matches!(
+            body,
+            Expr::Block(_)
+                | Expr::If(_)
+                | Expr::Match(_)
+                | Expr::Loop(_)
+                | Expr::While(_)
+                | Expr::ForLoop(_)
+                | Expr::Unsafe(_)
+        );
+        if input.peek(Token![,]) {
+            let _: Token![,] = input.parse()?;
+        } else if !block_like && !input.is_empty() {
+            return Err(input.error("expected `,` following `case_pair!` arm"));
+        }
+
+        Ok(PairArm {
+            attrs,
+            first,
+            second,
+            guard,
+            body,
+        })
+    }
+}
+
+impl Parse for PairInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let scrutinee = Expr::parse_without_eager_brace(input)?;
+        let span = scrutinee.span();
+        let (first, second) = match scrutinee {
+            Expr::Tuple(tuple) if tuple.elems.len() == 2 => {
+                let mut elems = tuple.elems.into_iter();
+                (elems.next().unwrap(), elems.next().unwrap())
+            }
+            other => {
+                return Err(Error::new_spanned(
+                    other,
+                    "expected a pair of scrutinees, as in `(a, b)`",
+                ))
+            }
+        };
+        let content;
+        let _ = braced!(content in input);
+        let mut arms = Vec::new();
+        while !content.is_empty() {
+            arms.push(content.parse()?);
+        }
+        Ok(PairInput {
+            first,
+            second,
+            arms,
+            span,
+        })
+    }
+}
+
+impl PairInput {
+    /// Compile the arms into a single match on the packed pair of tags, with an inner match on the
+    /// pair of cases for each pair of tags.
+    pub fn expand(self) -> Result<TokenStream, Error> {
+        let vesta_path = vesta_path();
+        let first_ident = Ident::new("first", Span::mixed_site());
+        let second_ident = Ident::new("second", Span::mixed_site());
+        let PairInput {
+            first,
+            second,
+            arms,
+            span,
+        } = self;
+
+        // The number of cases of each scrutinee is one more than the largest tag mentioned for it,
+        // which is checked by asserting that its type is exhaustive for exactly that many cases
+        let num_cases = |tag: fn(&PairArm) -> &PairTag| {
+            arms.iter()
+                .filter_map(|arm| match tag(arm) {
+                    PairTag::Tag(t, _) => Some(t + 1),
+                    PairTag::Wild => None,
+                })
+                .max()
+                .unwrap_or(0)
+        };
+        let num_first = num_cases(|arm| &arm.first);
+        let num_second = num_cases(|arm| &arm.second);
+        if num_first == 0 || num_second == 0 {
+            return Err(Error::new(
+                span,
+                "every case of both scrutinees must be mentioned in some arm of `case_pair!`",
+            ));
+        }
+
+        // Generate one outer arm for each pair of tags, containing all the arms which apply to it
+        let mut missing = Vec::new();
+        let mut outer_arms = Vec::new();
+        for a in 0..num_first {
+            for b in 0..num_second {
+                let inner_arms = arms
+                    .iter()
+                    .filter(|arm| arm.first.matches(a) && arm.second.matches(b))
+                    .map(|arm| {
+                        let PairArm {
+                            attrs,
+                            first,
+                            second,
+                            guard,
+                            body,
+                        } = arm;
+                        let first = first.pattern();
+                        let second = second.pattern();
+                        let guard = guard.as_ref().map(|guard| quote!(if #guard));
+                        quote! {
+                            #(#attrs)*
+                            (#first, #second) #guard => #body,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if inner_arms.is_empty() {
+                    missing.push(format!("`({}, {})`", a, b));
+                    continue;
+                }
+                let packed = a * num_second + b;
+                let first_case = audited_case(
+                    quote!(&#first_ident),
+                    a,
+                    quote!(#vesta_path::Case::<#a>::case(#first_ident)),
+                );
+                let second_case = audited_case(
+                    quote!(&#second_ident),
+                    b,
+                    quote!(#vesta_path::Case::<#b>::case(#second_ident)),
+                );
+                outer_arms.push(quote! {
+                    #packed => match unsafe { (#first_case, #second_case) } {
+                        #(#inner_arms)*
+                    }
+                });
+            }
+        }
+        if !missing.is_empty() {
+            return Err(Error::new(
+                span,
+                messages::missing_cases(missing.join(", ")),
+            ));
+        }
+
+        Ok(quote! {
+            {
+                let #first_ident = #first;
+                let #second_ident = #second;
+                #vesta_path::assert_exhaustive::<_, #num_first>(&#first_ident);
+                #vesta_path::assert_exhaustive::<_, #num_second>(&#second_ident);
+                #[allow(unused_parens)]
+                match #vesta_path::pack_tags(&#first_ident, &#second_ident) {
+                    #(#outer_arms)*
+                    _ => unsafe { #vesta_path::unreachable() },
+                }
+            }
+        })
+    }
+}
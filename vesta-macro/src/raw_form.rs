@@ -0,0 +1,207 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{ext::IdentExt, Error, Generics, Ident, Meta, Visibility};
+
+use vesta_syntax::{audited_case, vesta_path};
+
+/// The names of the items generated by [`derive_raw_form`] for the type `ident`.
+pub fn raw_form_idents(ident: &Ident) -> [Ident; 2] {
+    [
+        format_ident!("{}Raw", ident),
+        format_ident!("{}RawPayload", ident),
+    ]
+}
+
+/// Convert a name in `CamelCase` to `snake_case`, treating a run of capitals as one word, so that
+/// `HTTPRequest` becomes `http_request`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_lowercase)
+            {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+/// Generate the raw form of the type `ident`, whose cases have the given names: a struct
+/// `{ident}Raw` holding the tag of a value and a struct `{ident}RawPayload`, which has one
+/// optional field per case, named after the case in `snake_case`, holding the payload of that case
+/// if it is the value's case. The conversion to the raw form always succeeds, and the conversion
+/// back succeeds exactly when the tag is that of a case, and only that case's payload is present.
+///
+/// Each of `attrs` is applied to both generated structs, so that they can derive traits such as
+/// `serde::Serialize`. If the type is `validated`, the conversion back also requires its payload to
+/// pass `TryUncase`, and gives back the raw form unchanged if it does not.
+pub fn derive_raw_form(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    case_names: &[Ident],
+    attrs: &[Meta],
+    validated: bool,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let [raw_ident, payload_ident] = raw_form_idents(ident);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let value_ident = Ident::new("value", Span::mixed_site());
+    let payload_value_ident = Ident::new("payload", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+    let tag_ident = Ident::new("tag", Span::mixed_site());
+
+    // The name of the field for each case, which must all be distinct
+    let mut fields: Vec<Ident> = Vec::new();
+    for name in case_names {
+        let snake = snake_case(&name.unraw().to_string());
+        let field = syn::parse_str::<Ident>(&snake)
+            .unwrap_or_else(|_| Ident::new_raw(&snake, Span::call_site()));
+        if let Some(other) = fields.iter().position(|other| *other == field) {
+            return Error::new(
+                name.span(),
+                format!(
+                    "cannot generate the raw form of `{}`, because the cases `{}` and `{}` would \
+                    both be stored in the field `{}`",
+                    ident, case_names[other], name, snake
+                ),
+            )
+            .to_compile_error();
+        }
+        fields.push(field);
+    }
+
+    let tags: Vec<usize> = (0..case_names.len()).collect();
+    let field_docs = tags.iter().zip(case_names).map(|(n, name)| {
+        format!(
+            "The payload of `{}::{}`, if the tag is `{}`.",
+            ident,
+            name.unraw(),
+            n
+        )
+    });
+    let raw_doc = format!(
+        "The raw form of [`{}`], with its tag and payload stored separately.\n\n\
+        This is generated by `#[vesta(raw_form)]`. It can be converted from `{}` using `From`, \
+        and back using `TryFrom`, which gives back the raw form unchanged unless its tag is that of \
+        a case of `{}` and only that case's payload is present.",
+        ident, ident, ident
+    );
+    let payload_doc = format!(
+        "The payload of the raw form of [`{}`], with one field per case, only one of which is \
+        present in a valid raw form.\n\nThis is generated by `#[vesta(raw_form)]`.",
+        ident
+    );
+
+    // Each arm of the conversion back requires the payload of exactly one case
+    let try_from_arms = tags.iter().map(|&n| {
+        let field_patterns = fields.iter().enumerate().map(|(m, field)| {
+            if m == n {
+                quote!(#field: ::std::option::Option::Some(#case_ident))
+            } else {
+                quote!(#field: ::std::option::Option::None)
+            }
+        });
+        let uncase = if validated {
+            let field_patterns = field_patterns.clone();
+            quote! {
+                match #vesta_path::TryUncase::<#n>::try_uncase(#case_ident) {
+                    ::std::result::Result::Ok(#value_ident) => ::std::result::Result::Ok(#value_ident),
+                    ::std::result::Result::Err(#case_ident) => {
+                        let #case_ident = #vesta_path::UncaseError::into_case(#case_ident);
+                        ::std::result::Result::Err(#raw_ident {
+                            tag: #n,
+                            payload: #payload_ident { #(#field_patterns),* },
+                        })
+                    }
+                }
+            }
+        } else {
+            quote!(::std::result::Result::Ok(#vesta_path::Case::<#n>::uncase(#case_ident)))
+        };
+        quote! {
+            #raw_ident {
+                tag: #n,
+                payload: #payload_ident { #(#field_patterns),* },
+            } => #uncase,
+        }
+    });
+
+    let extract_cases = tags.iter().map(|n| {
+        audited_case(
+            quote!(&#value_ident),
+            n,
+            quote!(#vesta_path::Case::<#n>::case(#value_ident)),
+        )
+    });
+
+    quote! {
+        #[doc = #raw_doc]
+        #(#[#attrs])*
+        #vis struct #raw_ident #impl_generics #where_clause {
+            /// The tag of the value.
+            pub tag: ::std::primitive::usize,
+            /// The payload of the value, under its case.
+            pub payload: #payload_ident #ty_generics,
+        }
+
+        #[doc = #payload_doc]
+        #(#[#attrs])*
+        #vis struct #payload_ident #impl_generics #where_clause {
+            #(
+                #[doc = #field_docs]
+                pub #fields: ::std::option::Option<
+                    <#ident #ty_generics as #vesta_path::Case<#tags>>::Case
+                >,
+            )*
+        }
+
+        #[allow(unused_qualifications, unused_mut, unused_variables)]
+        impl #impl_generics ::std::convert::From<#ident #ty_generics>
+            for #raw_ident #ty_generics #where_clause
+        {
+            fn from(#value_ident: #ident #ty_generics) -> Self {
+                let mut #payload_value_ident = #payload_ident {
+                    #(#fields: ::std::option::Option::None),*
+                };
+                let #tag_ident = match #vesta_path::Match::tag(&#value_ident) {
+                    #(::std::option::Option::Some(#tags) => {
+                        // The tag is `n`, so the case can be extracted
+                        #payload_value_ident.#fields =
+                            ::std::option::Option::Some(unsafe #extract_cases);
+                        #tags
+                    })*
+                    // The derived tag of a value is always that of one of its cases
+                    _ => unsafe { #vesta_path::unreachable() },
+                };
+                #raw_ident {
+                    tag: #tag_ident,
+                    payload: #payload_value_ident,
+                }
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics ::std::convert::TryFrom<#raw_ident #ty_generics>
+            for #ident #ty_generics #where_clause
+        {
+            type Error = #raw_ident #ty_generics;
+
+            fn try_from(
+                #value_ident: #raw_ident #ty_generics,
+            ) -> ::std::result::Result<Self, Self::Error> {
+                match #value_ident {
+                    #(#try_from_arms)*
+                    #value_ident => ::std::result::Result::Err(#value_ident),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    ConstParam, GenericParam, Generics, Ident, Lifetime, LifetimeDef, TypeParam, Visibility,
+};
+
+use vesta_syntax::vesta_path;
+
+/// The names of the items generated by [`derive_router`] for the type `ident`.
+pub fn router_idents(ident: &Ident) -> [Ident; 3] {
+    [
+        format_ident!("{}Router", ident),
+        format_ident!("{}RouterBuilder", ident),
+        format_ident!("{}StaticRouter", ident),
+    ]
+}
+
+/// Generate a router for the type `ident`, which has `num_cases` cases, all of them exhaustive.
+///
+/// The router is a struct named `{ident}Router` holding one boxed handler per case, constructed
+/// using a builder named `{ident}RouterBuilder` whose `build` method only exists once every
+/// handler has been given. Alongside it, a struct named `{ident}StaticRouter` holds one function
+/// pointer per case, given all at once to its `const` constructor, so that it can be a `static`.
+pub fn derive_router(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    num_cases: usize,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let [router_ident, builder_ident, static_ident] = router_idents(ident);
+    let handler_lifetime = Lifetime::new("'__vesta_handler", Span::call_site());
+    let output_ident = Ident::new("__VestaOutput", Span::call_site());
+    let value_ident = Ident::new("value", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+
+    // The router has all the generics of the type, as well as a lifetime for the handlers and a
+    // type for their output
+    let mut router_generics = generics.clone();
+    router_generics
+        .params
+        .insert(0, syn::parse_quote!(#handler_lifetime));
+    router_generics
+        .params
+        .push(syn::parse_quote!(#output_ident));
+    let (impl_generics, router_ty_generics, where_clause) = router_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    // The static router has no lifetime for its handlers, which are function pointers
+    let mut static_generics = generics.clone();
+    static_generics
+        .params
+        .push(syn::parse_quote!(#output_ident));
+    let (static_impl_generics, static_ty_generics, static_where_clause) =
+        static_generics.split_for_impl();
+    // The builder's own parameters come after these, so none of them may have a default
+    let mut router_params = router_generics.params.clone();
+    for param in router_params.iter_mut() {
+        match param {
+            GenericParam::Type(param) => {
+                param.eq_token = None;
+                param.default = None;
+            }
+            GenericParam::Const(param) => {
+                param.eq_token = None;
+                param.default = None;
+            }
+            GenericParam::Lifetime(_) => {}
+        }
+    }
+    let router_args = router_generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(TypeParam { ident, .. })
+            | GenericParam::Const(ConstParam { ident, .. }) => quote!(#ident),
+            GenericParam::Lifetime(LifetimeDef { lifetime, .. }) => quote!(#lifetime),
+        })
+        .collect::<Vec<_>>();
+
+    let tags: Vec<usize> = (0..num_cases).collect();
+    let tag_literals = tags
+        .iter()
+        .map(|&n| Literal::usize_unsuffixed(n))
+        .collect::<Vec<_>>();
+    let indices = tags
+        .iter()
+        .map(|&n| syn::Index::from(n))
+        .collect::<Vec<_>>();
+    let handler_types = tags
+        .iter()
+        .map(|n| {
+            quote! {
+                ::std::boxed::Box<
+                    dyn ::std::ops::Fn(
+                        <#ident #ty_generics as #vesta_path::Case<#n>>::Case
+                    ) -> #output_ident + #handler_lifetime
+                >
+            }
+        })
+        .collect::<Vec<_>>();
+    let fn_types = tags
+        .iter()
+        .map(|n| {
+            quote! {
+                fn(<#ident #ty_generics as #vesta_path::Case<#n>>::Case) -> #output_ident
+            }
+        })
+        .collect::<Vec<_>>();
+    let handler_args = tags
+        .iter()
+        .map(|n| format_ident!("on_{}", n))
+        .collect::<Vec<_>>();
+    let handler_params = tags
+        .iter()
+        .map(|n| format_ident!("H{}", n))
+        .collect::<Vec<_>>();
+    let missing = tags
+        .iter()
+        .map(|_| quote!(#vesta_path::router::Missing))
+        .collect::<Vec<_>>();
+
+    // One method on the builder per case, each available only while that case is missing
+    let setters = tags.iter().map(|&n| {
+        let method = format_ident!("on_{}", n);
+        let handler_type = &handler_types[n];
+        let before = handler_params.iter().enumerate().map(|(i, h)| {
+            if i == n {
+                quote!(#vesta_path::router::Missing)
+            } else {
+                quote!(#h)
+            }
+        });
+        let after = handler_params.iter().enumerate().map(|(i, h)| {
+            if i == n {
+                quote!(#handler_type)
+            } else {
+                quote!(#h)
+            }
+        });
+        let other_params = handler_params
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != n)
+            .map(|(_, h)| h);
+        let fields = indices.iter().enumerate().map(|(i, index)| {
+            if i == n {
+                quote!(::std::boxed::Box::new(handler))
+            } else {
+                quote!(self.handlers.#index)
+            }
+        });
+        let doc = format!("Set the handler for case `{}`.", n);
+        quote! {
+            #[allow(unused_qualifications)]
+            impl<#router_params, #(#other_params),*> #builder_ident<#(#router_args,)* #(#before),*>
+            #where_clause
+            {
+                #[doc = #doc]
+                pub fn #method(
+                    self,
+                    handler: impl ::std::ops::Fn(
+                        <#ident #ty_generics as #vesta_path::Case<#n>>::Case
+                    ) -> #output_ident + #handler_lifetime,
+                ) -> #builder_ident<#(#router_args,)* #(#after),*> {
+                    #builder_ident {
+                        handlers: (#(#fields,)*),
+                        marker: ::std::marker::PhantomData,
+                    }
+                }
+            }
+        }
+    });
+
+    let router_doc = format!(
+        "A table of handlers, one for each case of [`{i}`], built using [`{i}RouterBuilder`].",
+        i = ident
+    );
+    let static_doc = format!(
+        "A table of function pointers handling each case of [`{}`], which can be constructed in a \
+        `const` context and stored in a `static`.",
+        ident
+    );
+    let builder_doc = format!(
+        "A builder for [`{i}Router`], which can only be built once every case has a handler.",
+        i = ident
+    );
+
+    quote! {
+        #[doc = #router_doc]
+        #[allow(unused_qualifications, clippy::type_complexity)]
+        #vis struct #router_ident #impl_generics #where_clause {
+            handlers: (#(#handler_types,)*),
+        }
+
+        #[doc = #builder_doc]
+        #[allow(unused_qualifications, clippy::type_complexity)]
+        #vis struct #builder_ident<#router_params, #(#handler_params),*> #where_clause {
+            handlers: (#(#handler_params,)*),
+            marker: ::std::marker::PhantomData<(#(#handler_types,)*)>,
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics #router_ident #router_ty_generics #where_clause {
+            /// Start building a router, with no handlers yet.
+            pub fn builder() -> #builder_ident<#(#router_args,)* #(#missing),*> {
+                #builder_ident {
+                    handlers: (#(#missing,)*),
+                    marker: ::std::marker::PhantomData,
+                }
+            }
+
+            /// Dispatch a value to the handler for its case.
+            pub fn route(&self, #value_ident: #ident #ty_generics) -> #output_ident {
+                #vesta_path::case!(#value_ident {
+                    #(#tag_literals(#case_ident) => (self.handlers.#indices)(#case_ident),)*
+                })
+            }
+        }
+
+        #[doc = #static_doc]
+        #[allow(unused_qualifications, clippy::type_complexity)]
+        #vis struct #static_ident #static_impl_generics #static_where_clause {
+            handlers: (#(#fn_types,)*),
+        }
+
+        #[allow(unused_qualifications, clippy::too_many_arguments)]
+        impl #static_impl_generics #static_ident #static_ty_generics #static_where_clause {
+            /// Construct a router from one handler for each case, in order of their tags.
+            pub const fn new(#(#handler_args: #fn_types),*) -> Self {
+                #static_ident {
+                    handlers: (#(#handler_args,)*),
+                }
+            }
+
+            /// Dispatch a value to the handler for its case.
+            pub fn route(&self, #value_ident: #ident #ty_generics) -> #output_ident {
+                #vesta_path::case!(#value_ident {
+                    #(#tag_literals(#case_ident) => (self.handlers.#indices)(#case_ident),)*
+                })
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #static_impl_generics ::std::clone::Clone
+            for #static_ident #static_ty_generics #static_where_clause
+        {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #static_impl_generics ::std::marker::Copy
+            for #static_ident #static_ty_generics #static_where_clause
+        {
+        }
+
+        #(#setters)*
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics #builder_ident<#(#router_args,)* #(#handler_types),*> #where_clause {
+            /// Finish building the router, now that every case has a handler.
+            pub fn build(self) -> #router_ident #router_ty_generics {
+                #router_ident {
+                    handlers: self.handlers,
+                }
+            }
+        }
+    }
+}
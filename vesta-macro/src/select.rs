@@ -0,0 +1,134 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    parse_quote,
+    spanned::Spanned,
+    token::Brace,
+    Error, Expr, Ident, Token,
+};
+
+use vesta_syntax::{close_span, vesta_path, CaseArm, CaseInput};
+
+/// The input to `select_case!`: a list of receivers, each with its own arms, and an optional arm to
+/// run when the selected channel is disconnected.
+pub struct SelectInput {
+    receivers: Vec<Receiver>,
+    closed: Option<Expr>,
+}
+
+/// A single `recv(rx) { ... }` block of a `select_case!`.
+struct Receiver {
+    receiver: Expr,
+    brace_token: Brace,
+    close_span: Span,
+    arms: Vec<CaseArm>,
+}
+
+impl Parse for SelectInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut receivers = Vec::new();
+        let mut closed = None;
+        while !input.is_empty() {
+            let keyword: Ident = input.parse()?;
+            if keyword == "recv" {
+                let receiver;
+                let _ = parenthesized!(receiver in input);
+                let receiver = receiver.parse()?;
+                let close_span = close_span(input);
+                let content;
+                let brace_token = braced!(content in input);
+                let arms = content.call(CaseArm::parse_all)?;
+                receivers.push(Receiver {
+                    receiver,
+                    brace_token,
+                    close_span,
+                    arms,
+                });
+            } else if keyword == "closed" && closed.is_none() {
+                let _: Token![=>] = input.parse()?;
+                closed = Some(input.parse()?);
+            } else {
+                return Err(Error::new(
+                    keyword.span(),
+                    "expected `recv(...) { ... }` or a single `closed => ...` arm",
+                ));
+            }
+            if input.peek(Token![,]) {
+                let _: Token![,] = input.parse()?;
+            }
+        }
+        Ok(SelectInput { receivers, closed })
+    }
+}
+
+impl SelectInput {
+    /// Compile each receiver's arms as a `case!` on the received value, wrapped in a
+    /// `crossbeam_channel::select!` over all the receivers.
+    pub fn expand(self) -> Result<TokenStream, Error> {
+        let vesta_path = vesta_path();
+        let message_ident = Ident::new("message", Span::mixed_site());
+        let value_ident = Ident::new("value", Span::mixed_site());
+
+        let closed = match self.closed {
+            Some(closed) => closed.into_token_stream(),
+            None => quote!(::std::panic!(
+                "channel disconnected in `vesta::select_case!`"
+            )),
+        };
+
+        let operations = self
+            .receivers
+            .into_iter()
+            .map(
+                |Receiver {
+                     receiver,
+                     brace_token,
+                     close_span,
+                     arms,
+                 }| {
+                    let case = CaseInput {
+                        attrs: Vec::new(),
+                        ref_token: None,
+                        cow_token: None,
+                        scrutinee: parse_quote!(#value_ident),
+                        output_ty: None,
+                        brace_token,
+                        close_span,
+                        arms,
+                    }
+                    .compile()
+                    .map_err(|e| respan_first(e, receiver.span()))?;
+                    Ok(quote! {
+                        recv(#receiver) -> #message_ident => match #message_ident {
+                            ::std::result::Result::Ok(#value_ident) => #case,
+                            ::std::result::Result::Err(_) => #closed,
+                        },
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(quote! {
+            #vesta_path::crossbeam_channel::select! {
+                #(#operations)*
+            }
+        })
+    }
+}
+
+/// Report the first of some errors at the given span, keeping the spans of the rest. This reports
+/// missing cases at the receiver rather than the generated scrutinee, while leaving any suggestions
+/// where they are.
+fn respan_first(errors: Error, span: Span) -> Error {
+    let mut errors = errors.into_iter();
+    let mut respanned = match errors.next() {
+        Some(first) => Error::new(span, first),
+        None => return Error::new(span, "unknown error"),
+    };
+    for error in errors {
+        respanned.combine(error);
+    }
+    respanned
+}
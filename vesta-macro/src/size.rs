@@ -0,0 +1,80 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{Error, Generics, Ident, Type};
+
+/// The size in bytes above which the payload of a case may be reported as large.
+const LARGE_SIZE: usize = 128;
+
+/// How many times larger than every other payload a payload must be to be reported as large.
+const LARGE_FACTOR: usize = 4;
+
+/// Generate a check for each case of the enum `ident` with the given constructors and payload
+/// types, which produces a warning if its payload is much larger than those of all the other cases.
+/// Cases which are `boxed` are checked like any other, since that only boxes their payloads once
+/// extracted, and leaves the layout of the type as it is.
+///
+/// Since sizes are only known after macro expansion, each check selects one of two otherwise
+/// identical functions by whether its case is large, and the one for large cases is deprecated, so
+/// that calling it produces a warning pointing at the case.
+pub fn derive_size_check(
+    ident: &Ident,
+    generics: &Generics,
+    payloads: &[(Ident, Type)],
+) -> TokenStream {
+    // A free constant can't mention the type's parameters, so we can't check generic types
+    if !generics.params.is_empty() {
+        return Error::new(
+            ident.span(),
+            format!(
+                "cannot check the sizes of the cases of the generic type `{}`",
+                ident
+            ),
+        )
+        .to_compile_error();
+    }
+
+    let check_ident = Ident::new("__VestaLargeCase", Span::call_site());
+    let checks = payloads
+        .iter()
+        .enumerate()
+        .map(|(n, (constructor, payload))| {
+            let size_ident = Ident::new("size", Span::mixed_site());
+            let larger_than_others = payloads
+                .iter()
+                .enumerate()
+                .filter(|(m, _)| *m != n)
+                .map(|(_, (_, other))| {
+                    quote!(&& #size_ident >= #LARGE_FACTOR * ::std::mem::size_of::<#other>())
+                });
+            // Deprecation warnings are not shown in derived code, so the path to the function
+            // must appear to come from the case itself
+            let check_fn = format_ident!("case_{}", n, span = constructor.span());
+            let check_ident = Ident::new(&check_ident.to_string(), constructor.span());
+            let note = format!(
+                "the payload of `{}::{}` is at least {} times as large as that of any other case; \
+                consider boxing its fields in the variant itself",
+                ident, constructor, LARGE_FACTOR
+            );
+            quote_spanned! {constructor.span()=>
+                impl #check_ident<false> {
+                    const fn #check_fn() {}
+                }
+                impl #check_ident<true> {
+                    #[deprecated(note = #note)]
+                    const fn #check_fn() {}
+                }
+                #check_ident::<{
+                    let #size_ident = ::std::mem::size_of::<#payload>();
+                    #size_ident >= #LARGE_SIZE #(#larger_than_others)*
+                }>::#check_fn();
+            }
+        });
+
+    quote! {
+        #[allow(unused_qualifications, dead_code)]
+        const _: () = {
+            struct #check_ident<const LARGE: bool>;
+            #(#checks)*
+        };
+    }
+}
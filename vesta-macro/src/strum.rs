@@ -0,0 +1,34 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, Generics, Ident};
+
+use vesta_syntax::vesta_path;
+
+/// Generate a compile-time assertion that the `strum::EnumCount` implementation for the enum
+/// `ident` agrees with its `num_cases` cases.
+pub fn derive_strum_check(ident: &Ident, generics: &Generics, num_cases: usize) -> TokenStream {
+    // A free constant can't mention the type's parameters, so we can't check generic types
+    if !generics.params.is_empty() {
+        return Error::new(
+            ident.span(),
+            format!(
+                "cannot check `strum` consistency for the generic type `{}`",
+                ident
+            ),
+        )
+        .to_compile_error();
+    }
+
+    let vesta_path = vesta_path();
+    let message = format!(
+        "`strum::EnumCount::COUNT` for `{}` does not match its {} cases",
+        ident, num_cases
+    );
+    quote! {
+        #[allow(unused_qualifications)]
+        const _: () = ::std::assert!(
+            <#ident as #vesta_path::strum::EnumCount>::COUNT == #num_cases,
+            #message
+        );
+    }
+}
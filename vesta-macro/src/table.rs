@@ -0,0 +1,55 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    token::Brace,
+    Error, Ident, Token,
+};
+
+use vesta_syntax::{CaseArm, CaseInput};
+
+/// The input to `case_table!`: an optional `ref`, followed by the arms of a `case!`.
+pub struct TableInput {
+    ref_token: Option<Token![ref]>,
+    arms: Vec<CaseArm>,
+}
+
+impl Parse for TableInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ref_token = input.parse()?;
+        let arms = input.call(CaseArm::parse_all)?;
+        Ok(TableInput { ref_token, arms })
+    }
+}
+
+impl TableInput {
+    /// Compile the arms into a closure which matches on its argument using those arms.
+    pub fn expand(self) -> Result<TokenStream, Error> {
+        let TableInput { ref_token, arms } = self;
+        let span = Span::call_site();
+        let scrutinee_ident = Ident::new("scrutinee", Span::mixed_site());
+
+        // When matching by reference, the argument is already a reference, so we match on the
+        // value behind it
+        let scrutinee = if ref_token.is_some() {
+            parse_quote!(*#scrutinee_ident)
+        } else {
+            parse_quote!(#scrutinee_ident)
+        };
+        let output = CaseInput {
+            attrs: Vec::new(),
+            ref_token,
+            cow_token: None,
+            scrutinee,
+            output_ty: None,
+            brace_token: Brace(span),
+            close_span: span,
+            arms,
+        }
+        .compile()?
+        .into_token_stream();
+
+        Ok(quote!(|#scrutinee_ident| #output))
+    }
+}
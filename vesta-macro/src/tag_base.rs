@@ -0,0 +1,65 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Generics, Ident, Path};
+
+use vesta_syntax::vesta_path;
+
+/// Generate the global tags of the type `ident` with `num_cases` cases, which are its tags offset
+/// by the constant `base`: an implementation of `TagBase`, and an implementation of `GlobalCase`
+/// for each case, at its global tag. The sum of the base and the number of cases is checked not to
+/// overflow at compile time.
+pub fn derive_tag_base(
+    ident: &Ident,
+    generics: &Generics,
+    num_cases: usize,
+    base: &Path,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let global_ident = Ident::new("global_tag", Span::mixed_site());
+    let tag_ident = Ident::new("tag", Span::mixed_site());
+    let this_ident = Ident::new("this", Span::mixed_site());
+
+    // Each case is also the case at its global tag, which is checked by the audit, if enabled,
+    // against its tag, since `case!` doesn't know that when it matches on global tags
+    let global_cases = (0..num_cases).map(|n| {
+        quote! {
+            #[allow(unused_qualifications)]
+            impl #impl_generics #vesta_path::GlobalCase<{ #base + #n }> for #ident #ty_generics
+            #where_clause
+            {
+                type Case = <Self as #vesta_path::Case<#n>>::Case;
+
+                #[inline(always)]
+                unsafe fn global_case(#this_ident: Self) -> Self::Case {
+                    #vesta_path::__audit_case!(&#this_ident, #n);
+                    #vesta_path::Case::<#n>::case(#this_ident)
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::TagBase for #ident #ty_generics #where_clause {
+            const TAG_BASE: #vesta_path::Tag = #vesta_path::Tag(#base);
+
+            #[inline]
+            fn local_tag(
+                #global_ident: #vesta_path::Tag,
+            ) -> ::std::option::Option<#vesta_path::Tag> {
+                match #global_ident.offset_from(<Self as #vesta_path::TagBase>::TAG_BASE) {
+                    ::std::option::Option::Some(#tag_ident) if #tag_ident < #num_cases => {
+                        ::std::option::Option::Some(#vesta_path::Tag(#tag_ident))
+                    }
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        #(#global_cases)*
+
+        // The global tags of all the cases must fit in a `usize`
+        const _: ::std::primitive::usize = #base + #num_cases;
+    }
+}
@@ -0,0 +1,22 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Generics, Ident};
+
+use vesta_syntax::vesta_path;
+
+/// Implement `CaseList` for the exhaustive type `ident` with `num_cases` cases, listing the case
+/// type of each of its tags in order as a nested tuple ending in `()`.
+pub fn derive_type_list(ident: &Ident, generics: &Generics, num_cases: usize) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let cases = (0..num_cases).rev().fold(
+        quote!(()),
+        |tail, n| quote!((<Self as #vesta_path::Case<#n>>::Case, #tail)),
+    );
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::CaseList for #ident #ty_generics #where_clause {
+            type Cases = #cases;
+        }
+    }
+}
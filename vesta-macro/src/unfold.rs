@@ -0,0 +1,258 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, DataEnum, GenericArgument, Generics, Ident, Index, Member, Path, PathArguments,
+    Type, Visibility,
+};
+
+use vesta_syntax::{
+    derive::{derive_match_enum, ImplOptions, NonExhaustive},
+    vesta_path,
+};
+
+/// The name of the step type generated by [`derive_unfold`] for the type `ident`.
+pub fn unfold_ident(ident: &Ident) -> Ident {
+    format_ident!("{}Step", ident)
+}
+
+/// Generate an implementation of `Unfold` for the enum `ident`, along with its step type, an enum
+/// named `{ident}Step` which is the same as the enum except that each of its recursive parts is
+/// replaced by a seed. The step type also implements `Match` and `Case`, so steps can be built by
+/// tag using `Case::uncase`.
+///
+/// If the type is validated by `check`, each value built is checked: `from_step` panics if it is
+/// rejected, while `try_from_step` returns `None`.
+pub fn derive_unfold(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    data: &DataEnum,
+    check: Option<&Path>,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let step_ident = unfold_ident(ident);
+    let seed_ident = Ident::new("__VestaSeed", Span::call_site());
+    let unfold_fn = Ident::new("unfold", Span::mixed_site());
+    let step_value = Ident::new("step", Span::mixed_site());
+    let value_ident = Ident::new("value", Span::mixed_site());
+
+    // The step type has all the generics of the type, as well as the type of its seeds
+    let mut step_generics = generics.clone();
+    step_generics.params.push(parse_quote!(#seed_ident));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let (_, step_ty_generics, _) = step_generics.split_for_impl();
+    let step_params = &step_generics.params;
+    let step_where_clause = &step_generics.where_clause;
+
+    // Replace each recursive field with a seed, and work out how to build the field from it
+    let mut step_data = data.clone();
+    let mut arms = Vec::new();
+    let mut try_arms = Vec::new();
+    for variant in step_data.variants.iter_mut() {
+        let constructor = &variant.ident;
+        // Each field is bound to a fresh name, whatever it is called
+        let names: Vec<Ident> = (0..variant.fields.len())
+            .map(|i| Ident::new(&format!("x_{}", i), Span::mixed_site()))
+            .collect();
+        let members: Vec<Member> = variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index::from(i)),
+            })
+            .collect();
+        let mut values = Vec::new();
+        let mut try_values = Vec::new();
+        for (field, name) in variant.fields.iter_mut().zip(&names) {
+            let name_expr = quote!(#name);
+            let step = |fallible| {
+                step_field(
+                    &field.ty,
+                    ident,
+                    &seed_ident,
+                    name_expr.clone(),
+                    &unfold_fn,
+                    fallible,
+                )
+            };
+            match (step(false), step(true)) {
+                (Some((step_ty, value)), Some((_, try_value))) => {
+                    field.ty = step_ty;
+                    values.push(value);
+                    try_values.push(try_value);
+                }
+                _ => {
+                    values.push(name_expr.clone());
+                    try_values.push(name_expr);
+                }
+            }
+        }
+        arms.push(quote! {
+            #step_ident::#constructor { #(#members: #names),* } => #ident::#constructor {
+                #(#members: #values),*
+            }
+        });
+        try_arms.push(quote! {
+            #step_ident::#constructor { #(#members: #names),* } => #ident::#constructor {
+                #(#members: #try_values),*
+            }
+        });
+    }
+
+    // Check the value built, if the type is validated
+    let (from_step_check, try_from_step_check) = match check {
+        Some(check) => {
+            let message = format!(
+                "the unfolded `{}` was rejected by `{}`, so it must be built using \
+                `Unfold::try_unfold`",
+                ident,
+                quote!(#check).to_string().replace(' ', "")
+            );
+            (
+                quote!(::std::assert!(#check(&#value_ident), #message);),
+                quote! {
+                    if !#check(&#value_ident) {
+                        return ::std::option::Option::None;
+                    }
+                },
+            )
+        }
+        None => (TokenStream::new(), TokenStream::new()),
+    };
+
+    // The step type is matchable, just like the type itself
+    let step_variants = &step_data.variants;
+    let step_match = derive_match_enum(
+        &NonExhaustive::Never,
+        step_ident.clone(),
+        step_generics.clone(),
+        step_data.clone(),
+        &ImplOptions::default(),
+    );
+    let doc = format!(
+        "A single layer of [`{}`], with seeds of type `{}` in place of its recursive parts, for \
+        use with `vesta::Unfold`.",
+        ident, seed_ident
+    );
+
+    quote! {
+        #[doc = #doc]
+        #[allow(missing_debug_implementations, missing_copy_implementations)]
+        #vis enum #step_ident<#step_params> #step_where_clause {
+            #step_variants
+        }
+
+        #step_match
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::Unfold for #ident #ty_generics #where_clause {
+            type Step<#seed_ident> = #step_ident #step_ty_generics;
+
+            fn from_step<#seed_ident>(
+                #step_value: Self::Step<#seed_ident>,
+                #unfold_fn: &mut impl ::std::ops::FnMut(#seed_ident) -> Self,
+            ) -> Self {
+                let #value_ident = match #step_value {
+                    #(#arms,)*
+                };
+                #from_step_check
+                #value_ident
+            }
+
+            fn try_from_step<#seed_ident>(
+                #step_value: Self::Step<#seed_ident>,
+                #unfold_fn: &mut impl ::std::ops::FnMut(
+                    #seed_ident,
+                ) -> ::std::option::Option<Self>,
+            ) -> ::std::option::Option<Self> {
+                let #value_ident = match #step_value {
+                    #(#try_arms,)*
+                };
+                #try_from_step_check
+                ::std::option::Option::Some(#value_ident)
+            }
+        }
+    }
+}
+
+/// If the type `ty` of a field of the type `ident` contains `ident` itself, directly or within a
+/// `Box`, `Vec`, or `Option`, return the type of the field in the step type, with `seed` in place
+/// of `ident`, and an expression building the field from `value`, its value in the step type,
+/// using the function `unfold`. If the build is `fallible`, `unfold` returns an `Option`, and the
+/// expression returns `None` from the enclosing function if any part of the field can't be built.
+fn step_field(
+    ty: &Type,
+    ident: &Ident,
+    seed: &Ident,
+    value: TokenStream,
+    unfold: &Ident,
+    fallible: bool,
+) -> Option<(Type, TokenStream)> {
+    let path = match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => &type_path.path,
+        _ => return None,
+    };
+    let last = path.segments.last()?;
+    if path.segments.len() == 1 && (last.ident == *ident || last.ident == "Self") {
+        let unfolded = if fallible {
+            quote!(#unfold(#value)?)
+        } else {
+            quote!(#unfold(#value))
+        };
+        return Some((parse_quote!(#seed), unfolded));
+    }
+
+    // Otherwise, look inside the single type argument of a `Box`, `Vec`, or `Option`
+    let inner = match &last.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => inner,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let element = Ident::new("element", Span::mixed_site());
+    if last.ident == "Box" {
+        let (step_ty, inner_value) = step_field(inner, ident, seed, value, unfold, fallible)?;
+        Some((step_ty, quote!(::std::boxed::Box::new(#inner_value))))
+    } else if last.ident == "Vec" {
+        let (step_ty, inner_value) =
+            step_field(inner, ident, seed, quote!(#element), unfold, fallible)?;
+        // When fallible, each element is built inside a closure returning an `Option`, and the
+        // first failure ends the whole collection
+        let elements = if fallible {
+            quote! {
+                ::std::iter::IntoIterator::into_iter(#value)
+                    .map(|#element| ::std::option::Option::Some(#inner_value))
+                    .collect::<::std::option::Option<_>>()?
+            }
+        } else {
+            quote! {
+                ::std::iter::IntoIterator::into_iter(#value)
+                    .map(|#element| #inner_value)
+                    .collect()
+            }
+        };
+        Some((parse_quote!(::std::vec::Vec<#step_ty>), elements))
+    } else if last.ident == "Option" {
+        let (step_ty, inner_value) =
+            step_field(inner, ident, seed, quote!(#element), unfold, fallible)?;
+        // When fallible, the element can't be built inside a closure, which would capture `?`
+        let element_value = if fallible {
+            quote! {
+                match #value {
+                    ::std::option::Option::Some(#element) => {
+                        ::std::option::Option::Some(#inner_value)
+                    }
+                    ::std::option::Option::None => ::std::option::Option::None,
+                }
+            }
+        } else {
+            quote!(#value.map(|#element| #inner_value))
+        };
+        Some((parse_quote!(::std::option::Option<#step_ty>), element_value))
+    } else {
+        None
+    }
+}
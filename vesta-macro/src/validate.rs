@@ -0,0 +1,48 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Generics, Ident, Path};
+
+use vesta_syntax::{audited_case, vesta_path};
+
+/// Implement `TryUncase` for each of the `num_cases` cases of the type `ident`, rebuilding the
+/// value using `Case::uncase` and then keeping it only if `check`, a function from a reference to
+/// the value to `bool`, accepts it. A rejected value is taken apart again to give back its case.
+pub fn derive_try_uncase(
+    ident: &Ident,
+    generics: &Generics,
+    num_cases: usize,
+    check: &Path,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let case_ident = Ident::new("case", Span::mixed_site());
+    let value_ident = Ident::new("value", Span::mixed_site());
+
+    let impls = (0..num_cases).map(|n| {
+        let extract_case = audited_case(
+            quote!(&#value_ident),
+            n,
+            quote!(#vesta_path::Case::<#n>::case(#value_ident)),
+        );
+        quote! {
+            #[allow(unused_qualifications)]
+            impl #impl_generics #vesta_path::TryUncase<#n> for #ident #ty_generics #where_clause {
+                #[inline]
+                fn try_uncase(
+                    #case_ident: Self::Case,
+                ) -> ::std::result::Result<Self, #vesta_path::UncaseError<Self::Case>> {
+                    let #value_ident: Self = #vesta_path::Case::<#n>::uncase(#case_ident);
+                    if #check(&#value_ident) {
+                        ::std::result::Result::Ok(#value_ident)
+                    } else {
+                        ::std::result::Result::Err(#vesta_path::UncaseError::new(
+                            // The value was just built from case `n`, so it has the tag `n`
+                            unsafe #extract_case,
+                        ))
+                    }
+                }
+            }
+        }
+    });
+    quote!(#(#impls)*)
+}
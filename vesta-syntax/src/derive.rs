@@ -0,0 +1,838 @@
+//! Generating the implementations of `Match`, `Case`, and `CaseRef` for a struct or enum, as done
+//! by `vesta`'s `Match` derive macro.
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::FromIterator,
+};
+use syn::{
+    ext::IdentExt, parse::ParseStream, parse_quote, punctuated::Punctuated, spanned::Spanned, Arm,
+    Attribute, Data, DataEnum, DataStruct, Error, Field, Fields, FieldsNamed, FieldsUnnamed,
+    GenericParam, Generics, Ident, Index, Lifetime, LitStr, Member, Path, Token, Type, Variant,
+};
+
+use crate::{messages, stable_expansion, vesta_path};
+
+/// Whether a type is `#[non_exhaustive]`. By the time a derive macro sees the type, any
+/// `#[cfg_attr(...)]` attributes on it have been expanded, so this never depends on the
+/// configuration.
+pub enum NonExhaustive {
+    /// The type is exhaustive.
+    Never,
+    /// The type is non-exhaustive.
+    Always,
+}
+
+impl NonExhaustive {
+    /// Determine whether a type is non-exhaustive from its attributes.
+    pub fn from_attrs(attrs: &[Attribute]) -> NonExhaustive {
+        if attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("non_exhaustive"))
+        {
+            NonExhaustive::Always
+        } else {
+            NonExhaustive::Never
+        }
+    }
+}
+
+/// Options changing the implementations of `Case` generated for a type, as requested by
+/// `#[vesta(...)]` attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImplOptions {
+    /// Whether to check the tag of each value whose case is extracted or reconstructed, in debug
+    /// builds, as requested by `#[vesta(check_tags)]`.
+    pub check_tags: bool,
+    /// The tags of the cases whose payloads are boxed when extracted, as requested by
+    /// `#[vesta(boxed(...))]`.
+    pub boxed: BTreeSet<usize>,
+    /// The structs holding the payloads of the cases with more than one field, by tag, which are
+    /// used as those cases in place of tuples, as requested by `#[vesta(newtype_variants)]`.
+    pub payload_structs: BTreeMap<usize, PayloadStruct>,
+}
+
+/// A struct holding the payload of a single case, with the same fields as its variant, which is
+/// used as the case in place of a tuple of those fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadStruct {
+    /// The name of the struct.
+    pub ident: Ident,
+    /// The positions among the generic parameters of the type of those which the struct has, which
+    /// are those mentioned by its fields.
+    pub params: Vec<usize>,
+}
+
+impl PayloadStruct {
+    /// The struct named `ident` holding the given fields, of a type with the given generics.
+    pub fn new(ident: Ident, generics: &Generics, fields: &Fields) -> PayloadStruct {
+        let mut mentioned = BTreeSet::new();
+        for field in fields {
+            mentioned_idents(field.ty.to_token_stream(), &mut mentioned);
+        }
+        let params = generics
+            .params
+            .iter()
+            .enumerate()
+            .filter(|(_, param)| mentioned.contains(&generic_param_ident(param).to_string()))
+            .map(|(n, _)| n)
+            .collect();
+        PayloadStruct { ident, params }
+    }
+
+    /// The generics of the struct: its parameters, with those of their bounds which mention only
+    /// those parameters, and likewise the predicates of the where clause of the type.
+    pub fn generics(&self, generics: &Generics) -> Generics {
+        let mut params: Punctuated<GenericParam, Token![,]> = self
+            .params
+            .iter()
+            .map(|&n| generics.params[n].clone())
+            .collect();
+        let included: BTreeSet<String> = params
+            .iter()
+            .map(|param| generic_param_ident(param).to_string())
+            .collect();
+        let excluded: BTreeSet<String> = generics
+            .params
+            .iter()
+            .map(|param| generic_param_ident(param).to_string())
+            .filter(|param| !included.contains(param))
+            .collect();
+        let keep = |tokens: TokenStream| {
+            let mut mentioned = BTreeSet::new();
+            mentioned_idents(tokens, &mut mentioned);
+            mentioned.is_disjoint(&excluded)
+        };
+        for param in params.iter_mut() {
+            match param {
+                GenericParam::Type(param) => {
+                    param.bounds = param
+                        .bounds
+                        .iter()
+                        .filter(|bound| keep(bound.to_token_stream()))
+                        .cloned()
+                        .collect();
+                    if param.bounds.is_empty() {
+                        param.colon_token = None;
+                    }
+                }
+                GenericParam::Lifetime(param) => {
+                    param.bounds = param
+                        .bounds
+                        .iter()
+                        .filter(|bound| keep(bound.to_token_stream()))
+                        .cloned()
+                        .collect();
+                    if param.bounds.is_empty() {
+                        param.colon_token = None;
+                    }
+                }
+                GenericParam::Const(_) => {}
+            }
+        }
+        let where_clause = generics.where_clause.as_ref().map(|where_clause| {
+            let mut where_clause = where_clause.clone();
+            where_clause.predicates = where_clause
+                .predicates
+                .into_iter()
+                .filter(|predicate| keep(predicate.to_token_stream()))
+                .collect();
+            where_clause
+        });
+        Generics {
+            lt_token: Some(Default::default()),
+            params,
+            gt_token: Some(Default::default()),
+            where_clause,
+        }
+    }
+
+    /// The struct as a type, with the parameters of the type given the same generics.
+    pub fn ty(&self, generics: &Generics) -> Type {
+        let ident = &self.ident;
+        let generics = self.generics(generics);
+        let (_, ty_generics, _) = generics.split_for_impl();
+        parse_quote!(#ident #ty_generics)
+    }
+}
+
+/// The identifier of a generic parameter, without the `'` of a lifetime.
+fn generic_param_ident(param: &GenericParam) -> &Ident {
+    match param {
+        GenericParam::Type(param) => &param.ident,
+        GenericParam::Lifetime(param) => &param.lifetime.ident,
+        GenericParam::Const(param) => &param.ident,
+    }
+}
+
+/// Collect every identifier in a token stream, including those of lifetimes.
+fn mentioned_idents(tokens: TokenStream, idents: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                let _ = idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => mentioned_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+/// Move the variants of an enum which only exist under some `cfg` after all the others, keeping the
+/// order in which each kind was written, so that the tags of the variants which always exist are
+/// the same in every configuration. Returns whether there are any such variants in the
+/// configuration being compiled, in which case the enum must be treated as non-exhaustive.
+///
+/// The compiler removes the variants whose `cfg` predicates are false before the derive sees them,
+/// but leaves the `cfg` attributes on the others, which is how they are recognized here.
+pub fn order_conditional_variants(data: &mut Data) -> bool {
+    let variants = match data {
+        Data::Enum(DataEnum { variants, .. }) => variants,
+        Data::Struct(_) | Data::Union(_) => return false,
+    };
+    let is_conditional =
+        |variant: &Variant| variant.attrs.iter().any(|attr| attr.path.is_ident("cfg"));
+    if !variants.iter().any(is_conditional) {
+        return false;
+    }
+    let (conditional, unconditional): (Vec<Variant>, Vec<Variant>) =
+        variants.iter().cloned().partition(is_conditional);
+    *variants = unconditional.into_iter().chain(conditional).collect();
+    true
+}
+
+/// Replace every `Self` in the types of the fields of the type `ident` with generics `generics` by
+/// the type itself, so that the types of its fields can be named outside of its own impls, such as
+/// in the payload structs generated for its cases, or in its associated types when they are used by
+/// generic code. A `Self` followed by `::` becomes `<Type>::`, so that it is still a valid path.
+pub fn replace_self(data: &mut Data, ident: &Ident, generics: &Generics) {
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let self_ty = quote!(#ident #ty_generics);
+    let fields: Box<dyn Iterator<Item = &mut Field>> = match data {
+        Data::Struct(DataStruct { fields, .. }) => Box::new(fields.iter_mut()),
+        Data::Enum(DataEnum { variants, .. }) => Box::new(
+            variants
+                .iter_mut()
+                .flat_map(|variant| variant.fields.iter_mut()),
+        ),
+        Data::Union(_) => return,
+    };
+    for field in fields {
+        let tokens = field.ty.to_token_stream();
+        if !mentioned_self(tokens.clone()) {
+            continue;
+        }
+        // If the replacement somehow isn't a type, leave the field for the compiler to explain
+        if let Ok(ty) = syn::parse2(replaced_self(tokens, &self_ty)) {
+            field.ty = ty;
+        }
+    }
+}
+
+/// Whether a token stream mentions `Self`.
+fn mentioned_self(tokens: TokenStream) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "Self",
+        TokenTree::Group(group) => mentioned_self(group.stream()),
+        _ => false,
+    })
+}
+
+/// Replace every `Self` in a token stream with `self_ty`, spanned like the `Self` it replaces.
+fn replaced_self(tokens: TokenStream, self_ty: &TokenStream) -> TokenStream {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut replaced = TokenStream::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "Self" => {
+                let span = ident.span();
+                let respanned = self_ty.clone().into_iter().map(|mut token| {
+                    token.set_span(span);
+                    token
+                });
+                let self_ty = TokenStream::from_iter(respanned);
+                let is_path = matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == ':');
+                if is_path {
+                    replaced.extend(quote_spanned!(span=> <#self_ty>));
+                } else {
+                    replaced.extend(self_ty);
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut new_group =
+                    Group::new(group.delimiter(), replaced_self(group.stream(), self_ty));
+                new_group.set_span(group.span());
+                replaced.extend(Some(TokenTree::Group(new_group)));
+            }
+            token => replaced.extend(Some(token)),
+        }
+    }
+    replaced
+}
+
+/// Extract an ordered sequence of field types from a list of fields as `()`, a single `T`, or a
+/// tuple, or return `None` if there are more than one named field.
+fn ordered_fields_types(fields: Fields) -> Option<Punctuated<Type, Token![,]>> {
+    let types = match fields {
+        Fields::Named(FieldsNamed { named, .. }) if named.len() > 1 => return None,
+        Fields::Named(FieldsNamed { named: fields, .. })
+        | Fields::Unnamed(FieldsUnnamed {
+            unnamed: fields, ..
+        }) => fields.into_iter().map(|f| f.ty).collect(),
+        Fields::Unit => vec![parse_quote!(())],
+    };
+    Some(Punctuated::from_iter(types))
+}
+
+/// Implement `Case<#n>`, `CaseRef<#n>`, and `CaseMut<#n>` for the type `ident` with generics
+/// `generics`, constructor `constructor` (this is equal to `ident` for structs, and equal to
+/// `ident::constructor` for enums), and fields `fields`, as modified by `options`.
+fn case_impl(
+    n: usize,
+    ident: Ident,
+    generics: Generics,
+    constructor: Path,
+    fields: Fields,
+    options: &ImplOptions,
+) -> Option<TokenStream> {
+    let vesta_path = vesta_path();
+    let payload_struct = options.payload_structs.get(&n);
+    let unboxed_type = match payload_struct {
+        Some(payload_struct) => payload_struct.ty(&generics).into_token_stream(),
+        None => {
+            let case_types = ordered_fields_types(fields.clone())?;
+            quote!(( #case_types ))
+        }
+    };
+    let ref_lifetime = Lifetime::new("'__vesta", Span::call_site());
+    let ref_types: Punctuated<Type, Token![,]> = fields
+        .iter()
+        .map(|Field { ty, .. }| -> Type { parse_quote!(&#ref_lifetime #ty) })
+        .collect();
+    let mut_types: Punctuated<Type, Token![,]> = fields
+        .iter()
+        .map(|Field { ty, .. }| -> Type { parse_quote!(&#ref_lifetime mut #ty) })
+        .collect();
+    let this_ident = Ident::new("this", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+
+    // The pattern binding all the fields of the constructor, and the tuple of all those bindings;
+    // the fields are bound to fresh names rather than their own, so that a constant in scope with
+    // the same name as a field can't turn the binding into a comparison
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+    let names: Vec<Ident> = (0..fields.len())
+        .map(|i| Ident::new(&format!("x_{}", i), Span::mixed_site()))
+        .collect();
+    let pattern = quote!(#constructor { #(#members: #names),* });
+    let bindings = quote!((#(#names),*));
+
+    // The case itself gathers the same bindings into its payload struct, if it has one
+    let case_bindings = match payload_struct {
+        Some(PayloadStruct { ident, .. }) => quote!(#ident { #(#members: #names),* }),
+        None => bindings.clone(),
+    };
+
+    // The checks of the tag before extracting a case and after reconstructing one, if requested
+    let check_tag = |value: &Ident, method: &str| {
+        if options.check_tags {
+            Some(quote! {
+                #vesta_path::debug_assert_tag::<Self>(&#value, #n, #method);
+            })
+        } else {
+            None
+        }
+    };
+    let check_case = check_tag(&this_ident, "Case::case");
+    let check_case_ref = check_tag(&this_ident, "CaseRef::case_ref");
+    let check_case_mut = check_tag(&this_ident, "CaseMut::case_mut");
+    let check_uncase = check_tag(&this_ident, "Case::uncase");
+
+    // A boxed case is boxed when extracted, and unboxed when reconstructed
+    let (case_type, boxed_bindings, unboxed_case) = if options.boxed.contains(&n) {
+        (
+            quote!(::std::boxed::Box<#unboxed_type>),
+            quote!(::std::boxed::Box::new(#case_bindings)),
+            quote!(*#case_ident),
+        )
+    } else {
+        (unboxed_type, case_bindings.clone(), quote!(#case_ident))
+    };
+
+    // The overrides of the provided methods, which only make them faster, are left out of the
+    // stable form of the expansion
+    let overrides = !stable_expansion();
+    let try_case = overrides.then(|| {
+        quote! {
+            #[inline]
+            fn try_case(#this_ident: Self) -> ::std::result::Result<Self::Case, Self> {
+                if let #pattern = #this_ident {
+                    ::std::result::Result::Ok(#boxed_bindings)
+                } else {
+                    ::std::result::Result::Err(#this_ident)
+                }
+            }
+        }
+    });
+    let try_case_ref = overrides.then(|| {
+        quote! {
+            #[inline]
+            fn try_case_ref(#this_ident: &Self) -> ::std::option::Option<Self::Ref<'_>> {
+                if let #pattern = #this_ident {
+                    ::std::option::Option::Some(#bindings)
+                } else {
+                    ::std::option::Option::None
+                }
+            }
+        }
+    });
+    let try_case_mut = overrides.then(|| {
+        quote! {
+            #[inline]
+            fn try_case_mut(#this_ident: &mut Self) -> ::std::option::Option<Self::Mut<'_>> {
+                if let #pattern = #this_ident {
+                    ::std::option::Option::Some(#bindings)
+                } else {
+                    ::std::option::Option::None
+                }
+            }
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Some(quote! {
+        #[allow(unused_qualifications, unused_parens, clippy::unused_unit)]
+        impl #impl_generics #vesta_path::Case<#n> for #ident #ty_generics #where_clause {
+            type Case = #case_type;
+            #[inline]
+            unsafe fn case(#this_ident: Self) -> Self::Case {
+                #check_case
+                if let #pattern = #this_ident {
+                    #boxed_bindings
+                } else {
+                    #vesta_path::unreachable()
+                }
+            }
+            #[inline]
+            fn uncase(#case_ident: Self::Case) -> Self {
+                let #case_bindings = #unboxed_case;
+                let #this_ident = #pattern;
+                #check_uncase
+                #this_ident
+            }
+            #try_case
+        }
+
+        #[allow(unused_qualifications, unused_parens, clippy::unused_unit)]
+        impl #impl_generics #vesta_path::CaseRef<#n> for #ident #ty_generics #where_clause {
+            type Ref<#ref_lifetime> = ( #ref_types ) where Self: #ref_lifetime;
+            #[inline]
+            unsafe fn case_ref(#this_ident: &Self) -> Self::Ref<'_> {
+                #check_case_ref
+                if let #pattern = #this_ident {
+                    #bindings
+                } else {
+                    #vesta_path::unreachable()
+                }
+            }
+            #try_case_ref
+        }
+
+        #[allow(unused_qualifications, unused_parens, clippy::unused_unit)]
+        impl #impl_generics #vesta_path::CaseMut<#n> for #ident #ty_generics #where_clause {
+            type Mut<#ref_lifetime> = ( #mut_types ) where Self: #ref_lifetime;
+            #[inline]
+            unsafe fn case_mut(#this_ident: &mut Self) -> Self::Mut<'_> {
+                #check_case_mut
+                if let #pattern = #this_ident {
+                    #bindings
+                } else {
+                    #vesta_path::unreachable()
+                }
+            }
+            #try_case_mut
+        }
+    })
+}
+
+/// Override `Match::tag_exhaustive` for an exhaustive type, given the expression computing the tag
+/// of `self`, unless the stable form of the expansion is asked for.
+fn tag_exhaustive_fn(tag: TokenStream) -> Option<TokenStream> {
+    if stable_expansion() {
+        return None;
+    }
+    let vesta_path = vesta_path();
+    let range_ident = Ident::new("__VESTA_N", Span::call_site());
+    Some(quote! {
+        #[inline]
+        fn tag_exhaustive<const #range_ident: ::std::primitive::usize>(
+            &self,
+        ) -> ::std::primitive::usize
+        where
+            Self: #vesta_path::Match<Range = #vesta_path::Exhaustive<#range_ident>>,
+        {
+            #tag
+        }
+    })
+}
+
+/// Implement `MatchMeta` for the type `ident` with generics `generics`, whose cases are named
+/// `cases`, in order of their tags, and were formerly named `aliases`, in the same order.
+fn meta_impl<'a>(
+    ident: &Ident,
+    generics: &Generics,
+    cases: impl IntoIterator<Item = &'a Ident>,
+    aliases: &[Vec<LitStr>],
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = ident.unraw().to_string();
+    let case_names = cases.into_iter().map(|case| case.unraw().to_string());
+
+    // Only list the former names of the cases up to the last one which has any
+    let renamed = aliases
+        .iter()
+        .rposition(|aliases| !aliases.is_empty())
+        .map_or(0, |last| last + 1);
+    let case_aliases = if renamed > 0 {
+        let aliases = aliases[..renamed]
+            .iter()
+            .map(|aliases| quote!(&[#(#aliases),*]));
+        Some(quote! {
+            const CASE_ALIASES: &'static [&'static [&'static ::std::primitive::str]] =
+                &[#(#aliases),*];
+        })
+    } else {
+        None
+    };
+
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics #vesta_path::MatchMeta for #ident #ty_generics #where_clause {
+            const NAME: &'static ::std::primitive::str = #name;
+            const CASE_NAMES: &'static [&'static ::std::primitive::str] = &[#(#case_names),*];
+            #case_aliases
+        }
+    }
+}
+
+/// Parse the former names of a variant, given by `#[vesta(alias = "...")]` attributes on it, which
+/// may each list any number of them. Any other options in the same attributes are skipped, since
+/// they are parsed alongside the variant's other options.
+pub fn case_aliases(attrs: &[Attribute]) -> Result<Vec<LitStr>, Error> {
+    let mut aliases = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("vesta")) {
+        attr.parse_args_with(|input: ParseStream| {
+            while !input.is_empty() {
+                let name = input.call(Ident::parse_any)?;
+                if name == "alias" {
+                    let _: Token![=] = input.parse()?;
+                    aliases.push(input.parse()?);
+                } else {
+                    // Skip the value of any other option, up to the next comma
+                    while !input.is_empty() && !input.peek(Token![,]) {
+                        let _: TokenTree = input.parse()?;
+                    }
+                }
+                if !input.is_empty() {
+                    let _: Token![,] = input.parse()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(aliases)
+}
+
+/// Check that no case of the type `ident` can be looked up by more than one name, given the names
+/// of the cases and their former names.
+fn check_aliases(ident: &Ident, cases: &[&Ident], aliases: &[Vec<LitStr>]) -> Result<(), Error> {
+    let mut names: BTreeMap<String, usize> = cases
+        .iter()
+        .enumerate()
+        .map(|(n, case)| (case.unraw().to_string(), n))
+        .collect();
+    for (n, aliases) in aliases.iter().enumerate() {
+        for alias in aliases {
+            if let Some(other) = names.insert(alias.value(), n) {
+                return Err(Error::new(
+                    alias.span(),
+                    format!(
+                        "the former name `{}` of `{}::{}` is already the name of `{}::{}`, or one \
+                        of its former names",
+                        alias.value(),
+                        ident,
+                        cases[n].unraw(),
+                        ident,
+                        cases[other].unraw(),
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Derive `Match`, `MatchMeta`, `Case`, `CaseRef`, and `CaseMut` for a `struct`, given its name,
+/// generics, fields, and the options for its implementations of `Case`.
+pub fn derive_match_struct(
+    ident: Ident,
+    generics: Generics,
+    DataStruct { fields, .. }: DataStruct,
+    options: &ImplOptions,
+) -> TokenStream {
+    let fields_span = fields.span();
+    if let Some(case_impl) = case_impl(
+        0,
+        ident.clone(),
+        generics.clone(),
+        ident.clone().into(),
+        fields,
+        options,
+    ) {
+        let vesta_path = vesta_path();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let meta_impl = meta_impl(&ident, &generics, Some(&ident), &[]);
+        let tag_exhaustive = tag_exhaustive_fn(quote!(0));
+        quote! {
+            #[allow(unused_qualifications)]
+            unsafe impl #impl_generics #vesta_path::Match for #ident #ty_generics #where_clause {
+                type Range = #vesta_path::Exhaustive<1>;
+
+                #[inline]
+                fn tag(&self) -> ::std::option::Option<::std::primitive::usize> {
+                    ::std::option::Option::Some(0)
+                }
+
+                #tag_exhaustive
+            }
+
+            #meta_impl
+
+            #case_impl
+        }
+    } else {
+        Error::new(fields_span, messages::named_fields_struct(&ident)).to_compile_error()
+    }
+}
+
+/// Derive `Match`, `MatchMeta`, `Case`, `CaseRef`, and `CaseMut` for an `enum`, given its name,
+/// generics, and variants, when it is non-exhaustive, and the options for its implementations of
+/// `Case`.
+pub fn derive_match_enum(
+    non_exhaustive: &NonExhaustive,
+    ident: Ident,
+    generics: Generics,
+    DataEnum { variants, .. }: DataEnum,
+    options: &ImplOptions,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+
+    // Count the number of variants
+    let num_variants = variants.len();
+
+    // Construct the `Match` impl, which differs depending on whether the enum is exhaustive
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let match_impl = |exhaustive: bool| {
+        let mut tag_arms: Vec<Arm> = variants
+            .iter()
+            .enumerate()
+            .map(
+                |(
+                    i,
+                    Variant {
+                        ident: constructor, ..
+                    },
+                )| parse_quote!(#ident::#constructor { .. } => ::std::option::Option::Some(#i)),
+            )
+            .collect();
+
+        // Only if non-exhaustive, push this fall-through arm
+        if !exhaustive {
+            tag_arms.push(parse_quote! {
+                _ => ::std::option::Option::None
+            });
+        }
+
+        // Range of the instance
+        let range = if exhaustive {
+            quote!(#vesta_path::Exhaustive<#num_variants>)
+        } else {
+            quote!(#vesta_path::Nonexhaustive)
+        };
+
+        // Only if exhaustive, read the tag directly rather than unwrapping it
+        let tag_exhaustive = if exhaustive {
+            let exhaustive_arms = variants.iter().enumerate().map(
+                |(
+                    i,
+                    Variant {
+                        ident: constructor, ..
+                    },
+                )| quote!(#ident::#constructor { .. } => #i),
+            );
+            tag_exhaustive_fn(quote! {
+                match *self {
+                    #(#exhaustive_arms),*
+                }
+            })
+        } else {
+            None
+        };
+
+        quote! {
+            #[allow(unused_qualifications)]
+            unsafe impl #impl_generics #vesta_path::Match for #ident #ty_generics #where_clause {
+                type Range = #range;
+
+                #[inline]
+                fn tag(&self) -> ::std::option::Option<::std::primitive::usize> {
+                    match *self {
+                        #(#tag_arms),*
+                    }
+                }
+
+                #tag_exhaustive
+            }
+        }
+    };
+
+    // Output stream starts with the `Match` impl
+    let mut output = match non_exhaustive {
+        NonExhaustive::Never => match_impl(true),
+        NonExhaustive::Always => match_impl(false),
+    };
+
+    // A non-exhaustive enum can also be matched exhaustively through `OrUnknown`
+    if let NonExhaustive::Always = non_exhaustive {
+        output.extend(or_unknown_impls(&ident, &generics, num_variants));
+    }
+
+    // Name the type and its cases, including what its cases used to be called
+    let cases: Vec<&Ident> = variants.iter().map(|variant| &variant.ident).collect();
+    let aliases = variants
+        .iter()
+        .map(|variant| case_aliases(&variant.attrs))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|aliases| check_aliases(&ident, &cases, &aliases).map(|()| aliases));
+    match aliases {
+        Ok(aliases) => output.extend(meta_impl(&ident, &generics, cases, &aliases)),
+        Err(error) => output.extend(error.to_compile_error()),
+    }
+
+    // Construct each `Case` impl
+    let case_impls = variants.into_iter().enumerate().map(
+        |(
+            n,
+            Variant {
+                ident: constructor,
+                fields,
+                ..
+            },
+        )| {
+            let fields_span = fields.span();
+            if let Some(case_impl) = case_impl(
+                n,
+                ident.clone(),
+                generics.clone(),
+                parse_quote!(#ident::#constructor),
+                fields,
+                options,
+            ) {
+                quote!(#case_impl)
+            } else {
+                Error::new(
+                    fields_span,
+                    messages::named_fields_variant(&ident, &constructor),
+                )
+                .to_compile_error()
+            }
+        },
+    );
+
+    output.extend(case_impls);
+    output
+}
+
+/// Implement `KnownCases` and `CaseOrUnknown` for the non-exhaustive type `ident` with generics
+/// `generics` and `known` known cases, so that it can be wrapped in an `OrUnknown`.
+fn or_unknown_impls(ident: &Ident, generics: &Generics, known: usize) -> TokenStream {
+    let vesta_path = vesta_path();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let ref_lifetime = Lifetime::new("'__vesta", Span::call_site());
+    let this_ident = Ident::new("this", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+    let with_unknown = known + 1;
+
+    // Each known case is the same as the corresponding case of the type itself
+    let known_impls = (0..known).map(|n| {
+        quote! {
+            #[allow(unused_qualifications)]
+            unsafe impl #impl_generics #vesta_path::unknown::CaseOrUnknown<#n> for #ident #ty_generics #where_clause {
+                type Case = <Self as #vesta_path::Case<#n>>::Case;
+                type Ref<#ref_lifetime> = <Self as #vesta_path::CaseRef<#n>>::Ref<#ref_lifetime> where Self: #ref_lifetime;
+                #[inline]
+                unsafe fn case(#this_ident: Self) -> Self::Case {
+                    #vesta_path::Case::<#n>::case(#this_ident)
+                }
+                #[inline]
+                fn uncase(#case_ident: Self::Case) -> Self {
+                    #vesta_path::Case::<#n>::uncase(#case_ident)
+                }
+                #[inline]
+                unsafe fn case_ref(#this_ident: &Self) -> Self::Ref<'_> {
+                    #vesta_path::CaseRef::<#n>::case_ref(#this_ident)
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[allow(unused_qualifications)]
+        unsafe impl #impl_generics #vesta_path::unknown::KnownCases for #ident #ty_generics #where_clause {
+            const KNOWN: ::std::primitive::usize = #known;
+            type WithUnknown = #vesta_path::Exhaustive<#with_unknown>;
+        }
+
+        #(#known_impls)*
+
+        #[allow(unused_qualifications)]
+        unsafe impl #impl_generics #vesta_path::unknown::CaseOrUnknown<#known> for #ident #ty_generics #where_clause {
+            type Case = #vesta_path::unknown::Unknown<Self>;
+            type Ref<#ref_lifetime> = #vesta_path::unknown::Unknown<&#ref_lifetime Self> where Self: #ref_lifetime;
+            #[inline]
+            unsafe fn case(#this_ident: Self) -> Self::Case {
+                #vesta_path::unknown::unknown(#this_ident)
+            }
+            #[inline]
+            fn uncase(#case_ident: Self::Case) -> Self {
+                #case_ident.into_value()
+            }
+            #[inline]
+            unsafe fn case_ref(#this_ident: &Self) -> Self::Ref<'_> {
+                #vesta_path::unknown::unknown_ref(#this_ident)
+            }
+        }
+    }
+}
+
+/// Register a type in the report of which cases are matched, given its name and the names of its
+/// cases. This does nothing unless the `usage` feature of `vesta` is enabled.
+pub fn register_usage<'a>(
+    ident: &Ident,
+    cases: impl IntoIterator<Item = &'a Ident>,
+) -> TokenStream {
+    let vesta_path = vesta_path();
+    let cases = cases.into_iter();
+    quote! {
+        #vesta_path::__usage_type!(#ident, [#(#cases),*]);
+    }
+}
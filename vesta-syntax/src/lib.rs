@@ -1,20 +1,34 @@
-use itertools::Itertools;
-use proc_macro2::Span;
+use proc_macro2::{Spacing, Span, TokenTree};
 use proc_macro_crate::FoundCrate;
 use quote::{format_ident, quote, quote_spanned, ToTokens};
 use std::{
     collections::{BTreeMap, BTreeSet},
     env,
+    rc::Rc,
 };
 use syn::{
-    braced, parenthesized,
-    parse::{Parse, ParseStream},
-    parse_quote,
+    braced,
+    buffer::Cursor,
+    ext::IdentExt,
+    parenthesized,
+    parse::{Parse, ParseStream, Parser},
+    parse_quote, parse_quote_spanned,
     spanned::Spanned,
     token::{Brace, Paren, Underscore},
-    Arm, Attribute, Error, Expr, Ident, LitInt, Pat, PatWild, Path, Token,
+    visit::{self, Visit},
+    Arm, Attribute, Error, Expr, ExprAsync, ExprBreak, ExprClosure, ExprContinue, ExprForLoop,
+    ExprLoop, ExprWhile, Ident, Item, Lifetime, LitInt, Macro, Pat, PatWild, Path, Token, Type,
 };
 
+pub mod derive;
+pub mod messages;
+pub mod schema;
+
+/// The custom keywords of the `case!` syntax.
+pub mod kw {
+    syn::custom_keyword!(cow);
+}
+
 /// Get the absolute path to `vesta`, from within the package itself, the doc tests, or any other
 /// package. This means we can use these proc macros from inside `vesta` with no issue.
 pub fn vesta_path() -> Path {
@@ -30,43 +44,361 @@ pub fn vesta_path() -> Path {
     }
 }
 
+/// Wrap the unchecked extraction `extract` of the case with tag `tag` from the value which
+/// `value_ref` refers to, so that the tag of the value is checked first if the `audit-unsafe`
+/// feature of `vesta` is enabled.
+pub fn audited_case(
+    value_ref: impl ToTokens,
+    tag: impl ToTokens,
+    extract: impl ToTokens,
+) -> proc_macro2::TokenStream {
+    let vesta_path = vesta_path();
+    quote!({
+        #vesta_path::__audit_case!(#value_ref, #tag);
+        #extract
+    })
+}
+
 /// The input syntax to `vesta`'s `case!` macro. This implements [`Parse`].
 #[derive(Clone)]
 pub struct CaseInput {
+    /// The attributes on the whole invocation, such as `#[deny_tags(...)]`.
+    pub attrs: Vec<Attribute>,
+    /// The `ref` token before the scrutinee, if the scrutinee is to be matched by reference.
+    pub ref_token: Option<Token![ref]>,
+    /// The `cow` keyword before the scrutinee, if the scrutinee is to be matched by reference and
+    /// each case bound as a [`Cow`](std::borrow::Cow) borrowed from it.
+    pub cow_token: Option<kw::cow>,
     /// The scrutinee of the `case!` macro: the thing upon which we are matching.
     pub scrutinee: Expr,
+    /// The type of the whole `case!`, if given as `-> Type` after the scrutinee.
+    pub output_ty: Option<Type>,
     /// The brace token wrapping all the cases.
     pub brace_token: Brace,
+    /// The span of the closing brace, where missing arms would be added.
+    pub close_span: Span,
     /// The cases, as input by the user.
     pub arms: Vec<CaseArm>,
 }
 
 impl Parse for CaseInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let scrutinee = Expr::parse_without_eager_brace(input)?;
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ref_token: Option<Token![ref]> = input.parse()?;
+        // A scrutinee may itself be named `cow`, so the keyword is only taken as such when it's
+        // followed by the start of another expression
+        let cow_token = if ref_token.is_none()
+            && input.peek(kw::cow)
+            && (input.peek2(Ident::peek_any)
+                || input.peek2(syn::Lit)
+                || input.peek2(Token![&])
+                || input.peek2(Token![*]))
+        {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        // A `->` would be parsed as the start of a subtraction, so the scrutinee ends before the
+        // first one outside any delimiters, and is followed by the type of the whole `case!`
+        let (scrutinee, output_ty) = if arrow_after(input.cursor()) {
+            let tokens = input.step(|cursor| {
+                let mut tokens = proc_macro2::TokenStream::new();
+                let mut rest = *cursor;
+                while !is_arrow(rest) {
+                    match rest.token_tree() {
+                        Some((token, next)) => {
+                            tokens.extend(Some(token));
+                            rest = next;
+                        }
+                        None => break,
+                    }
+                }
+                Ok((tokens, rest))
+            })?;
+            let scrutinee = Expr::parse_without_eager_brace.parse2(tokens)?;
+            let _: Token![->] = input.parse()?;
+            (scrutinee, Some(input.call(Type::without_plus)?))
+        } else {
+            (Expr::parse_without_eager_brace(input)?, None)
+        };
+        let close_span = close_span(input);
         let content;
         let brace_token = braced!(content in input);
-        let mut arms = Vec::new();
-        while !content.is_empty() {
-            arms.push(content.call(CaseArm::parse)?);
-        }
+        let arms = content.call(CaseArm::parse_all)?;
         Ok(CaseInput {
+            attrs,
+            ref_token,
+            cow_token,
             scrutinee,
+            output_ty,
             arms,
             brace_token,
+            close_span,
         })
     }
 }
 
+/// The arguments of a `#[deny_tags(...)]` attribute on a `case!`, i.e. `3, 4 => expr`: the tags
+/// which no arm may mention, and optionally the expression to evaluate when the scrutinee has one
+/// of them.
+struct DenyTags {
+    tags: Vec<LitInt>,
+    body: Option<Expr>,
+}
+
+impl Parse for DenyTags {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut tags = vec![input.parse()?];
+        while input.peek(Token![,]) {
+            let _: Token![,] = input.parse()?;
+            if input.is_empty() || input.peek(Token![=>]) {
+                break;
+            }
+            tags.push(input.parse()?);
+        }
+        let body = if input.is_empty() {
+            None
+        } else {
+            let _: Token![=>] = input.parse()?;
+            Some(input.parse()?)
+        };
+        Ok(DenyTags { tags, body })
+    }
+}
+
+/// Whether there is a `->` anywhere after the cursor, outside any delimiters.
+fn arrow_after(mut cursor: Cursor) -> bool {
+    while !is_arrow(cursor) {
+        match cursor.token_tree() {
+            Some((_, next)) => cursor = next,
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Whether the cursor is at a `->`.
+fn is_arrow(cursor: Cursor) -> bool {
+    match cursor.punct() {
+        Some((minus, next)) if minus.as_char() == '-' && minus.spacing() == Spacing::Joint => {
+            matches!(next.punct(), Some((greater, _)) if greater.as_char() == '>')
+        }
+        _ => false,
+    }
+}
+
+/// Whether the cursor is at the tag of an arm written as a constant expression giving a global tag:
+/// anything before the arm's pattern, guard, or `=>` other than a lone integer literal or `_`.
+fn starts_global_tag(cursor: Cursor) -> bool {
+    if ends_tag(cursor) {
+        return false;
+    }
+    let next = match (cursor.literal(), cursor.ident()) {
+        (Some((_, next)), _) => next,
+        (_, Some((ident, next))) if ident == "_" => next,
+        _ => return true,
+    };
+    !ends_tag(next) && !next.eof() && !matches!(next.ident(), Some((ident, _)) if ident == "where")
+}
+
+/// Whether the cursor is at the end of the tag of an arm: the start of its pattern, its guard, or
+/// its `=>`.
+fn ends_tag(cursor: Cursor) -> bool {
+    if cursor.group(proc_macro2::Delimiter::Parenthesis).is_some() {
+        return true;
+    }
+    if let Some((ident, _)) = cursor.ident() {
+        return ident == "if";
+    }
+    match cursor.punct() {
+        Some((equals, next)) if equals.as_char() == '=' && equals.spacing() == Spacing::Joint => {
+            matches!(next.punct(), Some((greater, _)) if greater.as_char() == '>')
+        }
+        _ => false,
+    }
+}
+
+/// Parse the rest of an arm whose tag has been consumed and which has no pattern, i.e. its guard,
+/// if any, its `=>`, its body, and its comma, as [`Arm`] would parse them after a pattern. The
+/// pattern of the result is `_`.
+fn arm_without_pattern(input: ParseStream) -> syn::Result<Arm> {
+    let guard = if input.peek(Token![if]) {
+        Some((input.parse()?, Box::new(input.parse()?)))
+    } else {
+        None
+    };
+    let fat_arrow_token = input.parse()?;
+    // As in a `match`, a body which is a block needs no comma after it
+    let (body, comma) = if input.peek(Brace) {
+        (Expr::Block(input.parse()?), input.parse()?)
+    } else {
+        let body = input.parse()?;
+        let comma = if input.is_empty() {
+            input.parse()?
+        } else {
+            Some(input.parse()?)
+        };
+        (body, comma)
+    };
+    Ok(Arm {
+        attrs: Vec::new(),
+        pat: Pat::Wild(PatWild {
+            attrs: Vec::new(),
+            underscore_token: Underscore::default(),
+        }),
+        guard,
+        fat_arrow_token,
+        body: Box::new(body),
+        comma,
+    })
+}
+
+/// Get the span of the closing delimiter of the group at the start of the input, so errors can
+/// point at it, or the call site if the input doesn't start with a group.
+pub fn close_span(input: ParseStream) -> Span {
+    // Parsing a `Group` loses the spans of its delimiters, so take the token tree directly instead
+    match input.cursor().token_tree() {
+        Some((proc_macro2::TokenTree::Group(group), _)) => group.span_close(),
+        _ => Span::call_site(),
+    }
+}
+
 /// A single arm of a `case!`, i.e. `1(x, Some(y)) => x + y,`. This implements [`Parse`].
+///
+/// The parts of the arm are available as separate fields, mirroring those of [`Arm`], so that they
+/// can be inspected or transformed before the arm is compiled. Converting a [`CaseArm`] into an
+/// [`Arm`] gives the arm as it is matched against the extracted case.
+///
+/// # Examples
+///
+/// ```
+/// use quote::quote;
+/// use syn::{parse_quote, Arm};
+/// use vesta_syntax::CaseArm;
+///
+/// let mut case_arm: CaseArm = parse_quote!(1(x) if x > 0 => x + 1,);
+/// assert_eq!(case_arm.tag, Some(1));
+/// assert!(case_arm.guard_expr().is_some());
+///
+/// // Wrap the body in some instrumentation
+/// let body = &case_arm.body;
+/// case_arm.body = parse_quote!({ count_calls(); #body });
+///
+/// let arm: Arm = case_arm.into();
+/// assert_eq!(
+///     quote!(#arm).to_string(),
+///     quote!((x) if x > 0 => { count_calls(); x + 1 },).to_string(),
+/// );
+///
+/// // The tag may also be a constant expression giving a global tag
+/// let case_arm: CaseArm = parse_quote!(AUTH_BASE + 1 => "logout",);
+/// assert_eq!(case_arm.tag, None);
+/// let global_tag = case_arm.global_tag.unwrap();
+/// assert_eq!(quote!(#global_tag).to_string(), quote!(AUTH_BASE + 1).to_string());
+/// ```
 #[derive(Clone)]
 pub struct CaseArm {
-    /// The tag for this case, or `None` if the case was a catch-all `_` case.
+    /// The attributes on the arm.
+    pub attrs: Vec<Attribute>,
+    /// The tag for this case, or `None` if the case was a catch-all `_` case, or if its tag was
+    /// given by `global_tag`.
     pub tag: Option<usize>,
+    /// The constant expression giving the global tag for this case, if it was written as one rather
+    /// than as a literal tag, i.e. the `AUTH_BASE + 1` in `AUTH_BASE + 1(x) => ...`.
+    pub global_tag: Option<Expr>,
     /// The span for the tag.
     pub tag_span: Span,
-    /// The [`Arm`] for the case, i.e. the pattern following the tag, its `=>`, and its body.
-    pub arm: Arm,
+    /// The pattern for the case, including its parentheses, i.e. the `(x, Some(y))` in
+    /// `1(x, Some(y)) => ...`. This is `_` if no pattern was given, as in `1 => ...` or `_ => ...`.
+    /// For a default arm matching only some tags, this is the pattern for the tag, i.e. the `(t)`
+    /// in `_(t) if t > 10 => ...`.
+    pub pat: Pat,
+    /// The guard of the arm, if any, i.e. the `if x > 0` in `1(x) if x > 0 => ...`.
+    pub guard: Option<(Token![if], Box<Expr>)>,
+    /// The `=>` token.
+    pub fat_arrow_token: Token![=>],
+    /// The body of the arm.
+    pub body: Box<Expr>,
+    /// The comma following the arm, if any.
+    pub comma: Option<Token![,]>,
+    /// The guard shared by this arm and the others in its group, if it was written within a
+    /// `N(...) where ... { ... }` group. Arms from the same group share the same [`Rc`].
+    pub shared_guard: Option<Rc<SharedGuard>>,
+}
+
+impl CaseArm {
+    /// Construct a [`CaseArm`] for the given tag from an [`Arm`] whose pattern matches the case.
+    fn from_arm(
+        tag: Option<usize>,
+        tag_span: Span,
+        arm: Arm,
+        shared_guard: Option<Rc<SharedGuard>>,
+    ) -> CaseArm {
+        let Arm {
+            attrs,
+            pat,
+            guard,
+            fat_arrow_token,
+            body,
+            comma,
+        } = arm;
+        CaseArm {
+            attrs,
+            tag,
+            global_tag: None,
+            tag_span,
+            pat,
+            guard,
+            fat_arrow_token,
+            body,
+            comma,
+            shared_guard,
+        }
+    }
+
+    /// The condition of the arm's guard, if it has one.
+    pub fn guard_expr(&self) -> Option<&Expr> {
+        self.guard.as_ref().map(|(_, guard)| &**guard)
+    }
+
+    /// A mutable reference to the condition of the arm's guard, if it has one.
+    pub fn guard_expr_mut(&mut self) -> Option<&mut Expr> {
+        self.guard.as_mut().map(|(_, guard)| &mut **guard)
+    }
+}
+
+impl From<CaseArm> for Arm {
+    fn from(case_arm: CaseArm) -> Arm {
+        let CaseArm {
+            attrs,
+            pat,
+            guard,
+            fat_arrow_token,
+            body,
+            comma,
+            ..
+        } = case_arm;
+        Arm {
+            attrs,
+            pat,
+            guard,
+            fat_arrow_token,
+            body,
+            comma,
+        }
+    }
+}
+
+/// A guard shared by all the arms of a group for a single tag, i.e. the `N(u) where *u > 0` in
+/// `N(u) where *u > 0 { (u) => ..., _ => ... }`.
+#[derive(Clone)]
+pub struct SharedGuard {
+    /// The pattern binding a reference to the case, for use in the guard, if any was given.
+    pub pat: Option<Pat>,
+    /// The `where` token introducing the guard.
+    pub where_token: Token![where],
+    /// The guard itself.
+    pub guard: Expr,
 }
 
 impl Parse for CaseArm {
@@ -79,7 +411,51 @@ impl Parse for CaseArm {
         // Parse outer attributes
         let attrs = input.call(Attribute::parse_outer)?;
 
-        if input.peek(Token![_]) {
+        if starts_global_tag(input.cursor()) {
+            // If the tag is a constant expression giving a global tag, such as `AUTH_BASE + 1`,
+            // the tag is `None`, and we *consume* the expression, then parse the rest of the arm
+            // as for a literal tag
+            let tokens = input.step(|cursor| {
+                let mut tokens = proc_macro2::TokenStream::new();
+                let mut rest = *cursor;
+                while !ends_tag(rest) {
+                    match rest.token_tree() {
+                        Some((token, next)) => {
+                            tokens.extend(Some(token));
+                            rest = next;
+                        }
+                        None => break,
+                    }
+                }
+                Ok((tokens, rest))
+            })?;
+            let global_tag: Expr = syn::parse2(tokens)?;
+            let tag_span = global_tag.span();
+            let mut case_arm = if input.peek(Paren) {
+                let pat;
+                parenthesized!(pat in input.fork());
+                if pat.is_empty() {
+                    return Err(pat.error("expected pattern"));
+                }
+                CaseArm::from_arm(None, tag_span, input.parse()?, None)
+            } else {
+                CaseArm::from_arm(None, tag_span, input.call(arm_without_pattern)?, None)
+            };
+            case_arm.global_tag = Some(global_tag);
+            case_arm.attrs.extend(attrs);
+            return Ok(case_arm);
+        } else if input.peek(Token![_]) && input.peek2(Paren) {
+            // If of the form `_(...) => ...`, the tag is `None`, and we *consume* the `_` token,
+            // then parse an `Arm` whose pattern matches the tag of the scrutinee
+            tag = None;
+            tag_span = input.parse::<Token![_]>()?.span();
+            let pat;
+            parenthesized!(pat in input.fork());
+            if pat.is_empty() {
+                return Err(pat.error("expected pattern"));
+            }
+            arm = input.parse::<Arm>()?;
+        } else if input.peek(Token![_]) {
             // If wildcard pattern, the tag is `None`, parse an arm also with a wildcard pattern
             tag = None;
             tag_span = input.fork().parse::<Token![_]>()?.span();
@@ -118,8 +494,96 @@ impl Parse for CaseArm {
         // Add the previously-parsed outer attributes to the arm
         arm.attrs.extend(attrs);
 
-        Ok(CaseArm { tag, tag_span, arm })
+        Ok(CaseArm::from_arm(tag, tag_span, arm, None))
+    }
+}
+
+impl CaseArm {
+    /// Parse a sequence of arms until the end of the input, expanding each group of arms with a
+    /// shared guard, i.e. `N(u) where *u > 0 { (u) => ..., _ => ... }`, into its individual arms.
+    pub fn parse_all(input: ParseStream) -> syn::Result<Vec<CaseArm>> {
+        let mut arms = Vec::new();
+        while !input.is_empty() {
+            if starts_shared_guard(input) {
+                arms.extend(input.call(parse_shared_guard)?);
+            } else {
+                arms.push(input.call(CaseArm::parse)?);
+            }
+        }
+        Ok(arms)
+    }
+}
+
+/// Determine whether the input starts with a group of arms with a shared guard, by looking for a
+/// `where` after the tag and its optional parenthesized pattern.
+fn starts_shared_guard(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.call(Attribute::parse_outer).is_ok()
+        && fork.parse::<LitInt>().is_ok()
+        && (!fork.peek(Paren) || fork.parse::<proc_macro2::Group>().is_ok())
+        && fork.peek(Token![where])
+}
+
+/// Parse a group of arms with a shared guard, i.e. `N(u) where *u > 0 { (u) => ..., _ => ... }`,
+/// into its individual arms, each of which refers to the same [`SharedGuard`].
+fn parse_shared_guard(input: ParseStream) -> syn::Result<Vec<CaseArm>> {
+    let attrs = input.call(Attribute::parse_outer)?;
+    let lit = input.parse::<LitInt>()?;
+    let tag = lit.base10_parse::<usize>()?;
+    let tag_span = lit.span();
+    let pat = if input.peek(Paren) {
+        let content;
+        let _ = parenthesized!(content in input);
+        Some(content.parse()?)
+    } else {
+        None
+    };
+    let where_token = input.parse()?;
+    let guard = Expr::parse_without_eager_brace(input)?;
+    let shared_guard = Rc::new(SharedGuard {
+        pat,
+        where_token,
+        guard,
+    });
+
+    // Each arm within the group is either `(...) => ...` or `_ => ...`, both for the tag `N`
+    let content;
+    let _ = braced!(content in input);
+    let mut arms = Vec::new();
+    while !content.is_empty() {
+        let inner_attrs = content.call(Attribute::parse_outer)?;
+        if content.peek(Paren) {
+            let pat;
+            parenthesized!(pat in content.fork());
+            if pat.is_empty() {
+                return Err(pat.error("expected pattern"));
+            }
+        } else if !content.peek(Token![_]) {
+            return Err(content.error("expected `(pattern) => ...` or `_ => ...`"));
+        }
+        let mut arm = content.parse::<Arm>()?;
+        // The last arm in the group may omit its comma, but it won't be last once compiled
+        let _ = arm.comma.get_or_insert_with(Default::default);
+        arm.attrs.extend(attrs.iter().cloned());
+        arm.attrs.extend(inner_attrs);
+        arms.push(CaseArm::from_arm(
+            Some(tag),
+            tag_span,
+            arm,
+            Some(shared_guard.clone()),
+        ));
+    }
+    if arms.is_empty() {
+        return Err(Error::new(
+            tag_span,
+            "expected at least one arm after shared guard",
+        ));
+    }
+
+    if input.peek(Token![,]) {
+        let _: Token![,] = input.parse()?;
     }
+    Ok(arms)
 }
 
 impl CaseInput {
@@ -127,35 +591,382 @@ impl CaseInput {
     /// if it is missing cases.
     pub fn compile(self) -> Result<CaseOutput, Error> {
         let CaseInput {
+            attrs,
+            ref_token,
+            cow_token,
             scrutinee,
+            output_ty,
             arms,
             brace_token,
+            close_span,
         } = self;
 
+        // Arms whose tags are global tags are compiled on their own, since their tags aren't known
+        // until the constants giving them are evaluated
+        if arms.iter().any(|arm| arm.global_tag.is_some()) {
+            return compile_global(
+                attrs,
+                ref_token,
+                cow_token,
+                scrutinee,
+                output_ty,
+                brace_token,
+                arms,
+            );
+        }
+
+        // Collect the tags denied by `#[deny_tags(...)]`, along with the arm to use for each, what
+        // to do when no arm for a tag matches, given by `#[on_fallthrough]`, the counters which
+        // observe each value matched, given by `#[profile(...)]`, whether guards are checked
+        // before the case is extracted, given by `#[borrow_guards]`, and the type of the table to
+        // compile the arms into, given by `#[lookup_table(...)]`, whether to warn about guarded
+        // arms interleaved with arms for other tags, given by `#[warn_interleaved]`, whether the
+        // arms must dispatch on the tag alone, given by `#[fast_path]`, and what to do if the
+        // scrutinee's `Match` implementation breaks its invariants, given by `#[on_invariant(...)]`
+        let mut denied: BTreeMap<usize, (Span, Arm)> = BTreeMap::new();
+        let mut fallthrough = None;
+        let mut profile = Vec::new();
+        let mut borrow_guards = false;
+        let mut lookup_table: Option<(Attribute, Type)> = None;
+        let mut warn_interleaved = false;
+        let mut fast_path: Option<Attribute> = None;
+        let mut on_invariant = None;
+        for attr in attrs {
+            if attr.path.is_ident("deny_tags") {
+                let DenyTags { tags, body } = attr.parse_args()?;
+                for lit in tags {
+                    let tag = lit.base10_parse::<usize>()?;
+                    let span = lit.span();
+                    let body = body.clone().unwrap_or_else(|| {
+                        let message = format!("case `{}` is denied here", tag);
+                        parse_quote_spanned!(span=> ::std::panic!(#message))
+                    });
+                    let arm = parse_quote_spanned!(span=> _ => #body,);
+                    denied.insert(tag, (span, arm));
+                }
+            } else if attr.path.is_ident("on_fallthrough") {
+                if fallthrough.is_some() {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "duplicate `#[on_fallthrough]` attribute",
+                    ));
+                }
+                fallthrough = Some(if attr.tokens.is_empty() {
+                    Fallthrough::Panic
+                } else {
+                    Fallthrough::Handle(attr.parse_args()?)
+                });
+            } else if attr.path.is_ident("profile") {
+                profile.push(attr.parse_args()?);
+            } else if attr.path.is_ident("borrow_guards") {
+                if !attr.tokens.is_empty() {
+                    return Err(Error::new_spanned(
+                        attr.tokens,
+                        "`#[borrow_guards]` takes no arguments",
+                    ));
+                }
+                if ref_token.is_some() || cow_token.is_some() {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "`#[borrow_guards]` has no effect when matching by reference, because \
+                        guards never move the case then",
+                    ));
+                }
+                borrow_guards = true;
+            } else if attr.path.is_ident("lookup_table") {
+                if lookup_table.is_some() {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "duplicate `#[lookup_table(...)]` attribute",
+                    ));
+                }
+                let ty = attr.parse_args()?;
+                lookup_table = Some((attr, ty));
+            } else if attr.path.is_ident("warn_interleaved") {
+                if !attr.tokens.is_empty() {
+                    return Err(Error::new_spanned(
+                        attr.tokens,
+                        "`#[warn_interleaved]` takes no arguments",
+                    ));
+                }
+                warn_interleaved = true;
+            } else if attr.path.is_ident("fast_path") {
+                if !attr.tokens.is_empty() {
+                    return Err(Error::new_spanned(
+                        attr.tokens,
+                        "`#[fast_path]` takes no arguments",
+                    ));
+                }
+                fast_path = Some(attr);
+            } else if attr.path.is_ident("on_invariant") {
+                if on_invariant.is_some() {
+                    return Err(Error::new_spanned(
+                        attr,
+                        "duplicate `#[on_invariant(...)]` attribute",
+                    ));
+                }
+                let strategy: Ident = attr.parse_args()?;
+                on_invariant = Some(if strategy == "panic" {
+                    OnInvariant::Panic
+                } else if strategy == "abort" {
+                    OnInvariant::Abort
+                } else if strategy == "unreachable" {
+                    OnInvariant::Unreachable
+                } else {
+                    return Err(Error::new(
+                        strategy.span(),
+                        "expected `panic`, `abort`, or `unreachable`",
+                    ));
+                });
+            } else {
+                return Err(Error::new_spanned(
+                    attr,
+                    "unknown attribute on `case!`: expected `#[deny_tags(...)]`, \
+                    `#[on_fallthrough]`, `#[profile(...)]`, `#[borrow_guards]`, \
+                    `#[lookup_table(...)]`, `#[warn_interleaved]`, `#[fast_path]`, or \
+                    `#[on_invariant(...)]`",
+                ));
+            }
+        }
+
         let mut cases: BTreeMap<usize, Vec<(Span, Arm)>> = BTreeMap::new();
+        let mut guarded: BTreeMap<usize, Vec<GuardedArms>> = BTreeMap::new();
         let mut default: Option<(Span, Arm)> = None;
+        let mut partial_defaults: Vec<(Span, Arm)> = Vec::new();
         let mut unreachable: Vec<CaseArm> = Vec::new();
         let mut all_tags = BTreeSet::new();
+        let mut max_guarded_tag: Option<usize> = None;
+        let mut debug_expansion = false;
+
+        // The arms for each tag which come after arms for some other tag, in order to warn about
+        // the first of them for each tag whose arms have guards
+        let mut previous_tag: Option<usize> = None;
+        let mut interleaved_arms: BTreeMap<usize, Span> = BTreeMap::new();
+        let mut guarded_tags: BTreeSet<usize> = BTreeSet::new();
+        let mut seen_tags: BTreeSet<usize> = BTreeSet::new();
+        let mut tag_order: Vec<usize> = Vec::new();
 
         // Read each case arm into the appropriate location
-        for case_arm in arms {
+        for mut case_arm in arms {
+            // Strip any `#[debug_expansion]` attribute from the arm, remembering that we saw it
+            case_arm.attrs.retain(|attr| {
+                let is_debug = attr.path.is_ident("debug_expansion");
+                debug_expansion |= is_debug;
+                !is_debug
+            });
+
+            // No arm may mention a denied tag
+            if let Some(tag) = case_arm.tag.filter(|tag| denied.contains_key(tag)) {
+                return Err(Error::new(
+                    case_arm.tag_span,
+                    format!("case `{}` is denied here by `#[deny_tags(...)]`", tag),
+                ));
+            }
+
+            // Default arms which only match some tags must come after all the arms for specific
+            // tags, since they are tried after them
+            if let (Some(_), Some((span, _))) = (case_arm.tag, partial_defaults.first()) {
+                let mut error = Error::new(
+                    case_arm.tag_span,
+                    "arms for specific tags must come before any guarded `_` or `_(...)` arms",
+                );
+                error.combine(Error::new(*span, "the first such arm is here"));
+                return Err(error);
+            }
+
+            if let (Some(tag), None) = (case_arm.tag, &default) {
+                if case_arm.guard.is_some() || case_arm.shared_guard.is_some() {
+                    guarded_tags.insert(tag);
+                }
+                if previous_tag != Some(tag) && !seen_tags.insert(tag) {
+                    let _ = interleaved_arms.entry(tag).or_insert(case_arm.tag_span);
+                }
+                previous_tag = Some(tag);
+            }
+
             if default.is_none() {
                 if let Some(tag) = case_arm.tag {
+                    if !tag_order.contains(&tag) {
+                        tag_order.push(tag);
+                    }
+                }
+                if let (Some(tag), Some(shared_guard)) =
+                    (case_arm.tag, case_arm.shared_guard.clone())
+                {
+                    // Arms with a shared guard don't cover their tag, since the guard may fail, and
+                    // must come first, since their payload is moved if the guard succeeds
+                    if cases.contains_key(&tag) {
+                        return Err(Error::new(
+                            case_arm.tag_span,
+                            "arms with a shared guard must come before all other arms for their tag",
+                        ));
+                    }
+                    max_guarded_tag = max_guarded_tag.max(Some(tag));
+                    let groups = guarded.entry(tag).or_default();
+                    match groups.last_mut() {
+                        Some(group) if Rc::ptr_eq(&group.shared_guard, &shared_guard) => {
+                            group.arms.push((case_arm.tag_span, case_arm.into()))
+                        }
+                        _ => groups.push(GuardedArms {
+                            shared_guard,
+                            arms: vec![(case_arm.tag_span, case_arm.into())],
+                        }),
+                    }
+                } else if let Some(tag) = case_arm.tag {
                     all_tags.insert(tag);
                     cases
                         .entry(tag)
-                        .or_insert_with(Vec::new)
-                        .push((case_arm.tag_span, case_arm.arm));
+                        .or_default()
+                        .push((case_arm.tag_span, case_arm.into()));
+                } else if case_arm.guard.is_some() || !matches!(case_arm.pat, Pat::Wild(_)) {
+                    partial_defaults.push((case_arm.tag_span, case_arm.into()));
                 } else {
-                    default = Some((case_arm.tag_span, case_arm.arm));
+                    default = Some((case_arm.tag_span, case_arm.into()));
                 }
             } else {
                 unreachable.push(case_arm);
             }
         }
 
+        // A lookup table has exactly one entry per tag, so each arm must cover its whole tag
+        // without looking at the case, and nothing may run after the tag is read
+        if let Some((attr, _)) = &lookup_table {
+            let unsupported = |span: Span, what: &str| {
+                let mut error = Error::new(
+                    span,
+                    format!("{} can't be compiled into a lookup table", what),
+                );
+                error.combine(Error::new_spanned(
+                    attr,
+                    "help: each arm must be `N => ...` or `_ => ...`, with no guard",
+                ));
+                Err(error)
+            };
+            if let Some((span, _)) = denied.values().next() {
+                return unsupported(*span, "a denied tag");
+            }
+            if let Some((span, _)) = guarded
+                .values()
+                .flatten()
+                .flat_map(|group| &group.arms)
+                .next()
+            {
+                return unsupported(*span, "a group of arms with a shared guard");
+            }
+            if let Some((span, _)) = partial_defaults.first() {
+                return unsupported(*span, "a guarded `_` or `_(...)` arm");
+            }
+            for arms in cases.values() {
+                let (span, arm) = &arms[0];
+                let whole_case = match &arm.pat {
+                    Pat::Wild(_) => true,
+                    Pat::Tuple(tuple) => {
+                        tuple.elems.len() == 1
+                            && !tuple.elems.trailing_punct()
+                            && matches!(tuple.elems[0], Pat::Wild(_))
+                    }
+                    _ => false,
+                };
+                if !whole_case || arm.guard.is_some() {
+                    return unsupported(*span, "an arm with a pattern or guard");
+                }
+                if let Some((span, _)) = arms.get(1) {
+                    return unsupported(*span, "a second arm for the same tag");
+                }
+            }
+        }
+
+        // A fast path dispatches on the tag alone, so each tag must have exactly one arm, which
+        // must match its whole case without checking anything else
+        if let Some(attr) = &fast_path {
+            let slow = |span: Span, what: &str| {
+                let mut error = Error::new(
+                    span,
+                    format!("{} can't be dispatched on the tag alone", what),
+                );
+                error.combine(Error::new_spanned(
+                    attr,
+                    "help: required by `#[fast_path]`, which allows one unguarded arm per tag, \
+                    with a pattern which can't fail",
+                ));
+                Err(error)
+            };
+            if let Some((span, _)) = guarded
+                .values()
+                .flatten()
+                .flat_map(|group| &group.arms)
+                .next()
+            {
+                return slow(*span, "a group of arms with a shared guard");
+            }
+            if let Some((span, _)) = partial_defaults.first() {
+                return slow(*span, "a guarded `_` or `_(...)` arm");
+            }
+            for arms in cases.values() {
+                let (span, arm) = &arms[0];
+                if arm.guard.is_some() {
+                    return slow(*span, "an arm with a guard");
+                }
+                match refutable_part(&arm.pat) {
+                    Some(pat @ Pat::Macro(_)) => {
+                        return slow(
+                            pat.span(),
+                            "a pattern macro, which may expand to a pattern that fails to match,",
+                        )
+                    }
+                    Some(pat) => return slow(pat.span(), "a pattern which may fail to match"),
+                    None => {}
+                }
+                if let Some((span, _)) = arms.get(1) {
+                    return slow(*span, "a second arm for the same tag");
+                }
+            }
+        }
+
+        // Each denied tag is covered by its own arm, which takes precedence over any default
+        for (tag, (span, arm)) in denied {
+            all_tags.insert(tag);
+            if !tag_order.contains(&tag) {
+                tag_order.push(tag);
+            }
+            cases.entry(tag).or_default().push((span, arm));
+        }
+
+        // When guards are checked before extracting the case, a value whose last arm's guard fails
+        // is never extracted, so it can only be handled by the default arm or the fall-through
+        // handler, and one of them must exist
+        let handles_fallthrough =
+            default.is_some() || matches!(fallthrough, Some(Fallthrough::Handle(_)));
+        if borrow_guards && !handles_fallthrough {
+            for (tag, arms) in &cases {
+                if let Some((span, Arm { guard: Some(_), .. })) = arms.last() {
+                    return Err(Error::new(
+                        *span,
+                        format!(
+                            "non-exhaustive patterns: case `{}` is not covered when the guard of \
+                            its last arm fails; add an unguarded arm for it, a `_ => ...` arm, or \
+                            `#[on_fallthrough(...)]`",
+                            tag
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Regrouping the arms by tag never changes which guards are evaluated for a value, or in
+        // what order, but interleaved guarded arms may read as though it does
+        let interleaved = if warn_interleaved {
+            interleaved_arms
+                .into_iter()
+                .filter(|(tag, _)| guarded_tags.contains(tag))
+                .map(|(tag, span)| (span, tag))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         // Compute the missing cases, if any were skipped when there was not a default
-        let max_tag: Option<usize> = all_tags.iter().rev().next().cloned();
+        let max_tag: Option<usize> = all_tags.iter().next_back().cloned().max(max_guarded_tag);
         let missing_cases = if let Some(max_tag) = max_tag {
             if default.is_none() {
                 (0..=max_tag)
@@ -170,11 +981,25 @@ impl CaseInput {
 
         if missing_cases.is_empty() {
             Ok(CaseOutput {
+                ref_token,
+                cow_token,
                 scrutinee,
+                output_ty,
                 brace_token,
                 cases,
+                guarded,
+                tag_order,
+                global_cases: Vec::new(),
                 default,
+                partial_defaults,
                 unreachable,
+                fallthrough,
+                profile,
+                borrow_guards,
+                lookup_table: lookup_table.map(|(_, ty)| ty),
+                interleaved,
+                on_invariant,
+                debug_expansion,
             })
         } else {
             // Construct the list of missing cases as a nice string
@@ -195,31 +1020,386 @@ impl CaseInput {
                 patterns.push_str(&format!("`{}`", tag));
                 previous = true;
             }
-            let message = format!("non-exhaustive patterns: {} not covered", patterns);
-            Err(Error::new(scrutinee.span(), message))
+            let mut error = Error::new(scrutinee.span(), messages::missing_cases(&patterns));
+
+            // Suggest how to fix it, at the place where the fix would go
+            let arms = if missing_cases.len() == 1 {
+                "an arm"
+            } else {
+                "arms"
+            };
+            error.combine(Error::new(
+                close_span,
+                messages::missing_cases_help(arms, &patterns),
+            ));
+            Err(error)
+        }
+    }
+}
+
+/// Compile the arms of a `case!` whose tags are global tags, written as constant expressions, into
+/// a [`CaseOutput`] with only `global_cases` and a default case. These can't be mixed with arms for
+/// literal tags, nor checked for exhaustiveness, so they must be followed by a `_` arm, and none of
+/// the attributes on `case!` apply to them.
+fn compile_global(
+    attrs: Vec<Attribute>,
+    ref_token: Option<Token![ref]>,
+    cow_token: Option<kw::cow>,
+    scrutinee: Expr,
+    output_ty: Option<Type>,
+    brace_token: Brace,
+    arms: Vec<CaseArm>,
+) -> Result<CaseOutput, Error> {
+    if let Some(attr) = attrs.first() {
+        return Err(Error::new_spanned(
+            attr,
+            "attributes on `case!` can't be used with arms for global tags",
+        ));
+    }
+    if let Some(ref_token) = ref_token {
+        return Err(Error::new_spanned(
+            ref_token,
+            "arms for global tags can only match by value",
+        ));
+    }
+    if let Some(cow_token) = cow_token {
+        return Err(Error::new_spanned(
+            cow_token,
+            "arms for global tags can only match by value",
+        ));
+    }
+
+    let mut global_cases: Vec<(Expr, Vec<(Span, Arm)>)> = Vec::new();
+    let mut default: Option<(Span, Arm)> = None;
+    let mut debug_expansion = false;
+    for mut case_arm in arms {
+        case_arm.attrs.retain(|attr| {
+            let is_debug = attr.path.is_ident("debug_expansion");
+            debug_expansion |= is_debug;
+            !is_debug
+        });
+        if default.is_some() {
+            return Err(Error::new(
+                case_arm.tag_span,
+                "unreachable arm after the `_` arm",
+            ));
+        }
+        match case_arm.global_tag.take() {
+            Some(global_tag) => {
+                // Arms with the same expression for their tag are grouped together, like arms for
+                // the same literal tag
+                let key = global_tag.to_token_stream().to_string();
+                let span = case_arm.tag_span;
+                match global_cases
+                    .iter_mut()
+                    .find(|(tag, _)| tag.to_token_stream().to_string() == key)
+                {
+                    Some((_, arms)) => arms.push((span, case_arm.into())),
+                    None => global_cases.push((global_tag, vec![(span, case_arm.into())])),
+                }
+            }
+            None if case_arm.tag.is_some() => {
+                return Err(Error::new(
+                    case_arm.tag_span,
+                    "arms for literal tags can't be mixed with arms for global tags",
+                ));
+            }
+            None if case_arm.guard.is_some() || !matches!(case_arm.pat, Pat::Wild(_)) => {
+                return Err(Error::new(
+                    case_arm.tag_span,
+                    "only a plain `_` arm can be used with arms for global tags",
+                ));
+            }
+            None => default = Some((case_arm.tag_span, case_arm.into())),
         }
     }
+    if default.is_none() {
+        return Err(Error::new(
+            brace_token.span,
+            "arms for global tags can't be checked for exhaustiveness, so they must be followed by \
+            a `_` arm",
+        ));
+    }
+
+    Ok(CaseOutput {
+        ref_token: None,
+        cow_token: None,
+        scrutinee,
+        output_ty,
+        brace_token,
+        cases: BTreeMap::new(),
+        guarded: BTreeMap::new(),
+        tag_order: Vec::new(),
+        global_cases,
+        default,
+        partial_defaults: Vec::new(),
+        unreachable: Vec::new(),
+        fallthrough: None,
+        profile: Vec::new(),
+        borrow_guards: false,
+        lookup_table: None,
+        interleaved: Vec::new(),
+        on_invariant: None,
+        debug_expansion,
+    })
 }
 
 /// The output of `vesta`'s `case!` macro, in a representation suitable for turning back into tokens
 /// via [`ToTokens`].
+///
+/// The tokens are a block ending in a single match on the tag. Where the only arm for a tag always
+/// matches, its body is the last expression of the block in the arm for the tag, after `let`s
+/// binding the case, rather than nested in another match, so that IDEs can analyze it as they
+/// would any other code.
+///
+/// # Examples
+///
+/// ```
+/// use quote::{quote, ToTokens};
+/// use syn::{parse_quote, Expr, ExprBlock, Stmt};
+/// use vesta_syntax::CaseInput;
+///
+/// let input: CaseInput = parse_quote!(option {
+///     0 => "none".len(),
+///     1(s) => s.len(),
+/// });
+/// let tokens = input.compile().unwrap().into_token_stream();
+/// let expansion: ExprBlock = syn::parse2(tokens).unwrap();
+///
+/// let arms = match expansion.block.stmts.last() {
+///     Some(Stmt::Expr(Expr::Match(tag_match))) => &tag_match.arms,
+///     _ => panic!("the expansion should end in a match on the tag"),
+/// };
+/// let bodies: Vec<String> = arms
+///     .iter()
+///     .filter_map(|arm| match &*arm.body {
+///         Expr::Block(block) => match block.block.stmts.last() {
+///             Some(Stmt::Expr(body)) => Some(body.to_token_stream().to_string()),
+///             _ => None,
+///         },
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(
+///     bodies,
+///     [quote!("none".len()).to_string(), quote!(s.len()).to_string()],
+/// );
+/// ```
 #[derive(Clone)]
 pub struct CaseOutput {
+    /// The `ref` token before the scrutinee, if the scrutinee is to be matched by reference.
+    pub ref_token: Option<Token![ref]>,
+    /// The `cow` keyword before the scrutinee, if the scrutinee is to be matched by reference and
+    /// each case bound as a [`Cow`](std::borrow::Cow) borrowed from it.
+    pub cow_token: Option<kw::cow>,
     /// The scrutinee of the `case!`.
     pub scrutinee: Expr,
+    /// The type of the whole `case!`, if given as `-> Type` after the scrutinee, which every arm
+    /// is inferred to have.
+    pub output_ty: Option<Type>,
     /// The brace token wrapping the whole of the cases.
     pub brace_token: Brace,
     /// The reachable cases, organized by which tag they belong to, ordered within each tag by the
     /// order they were listed in the original input.
     pub cases: BTreeMap<usize, Vec<(Span, Arm)>>,
+    /// The groups of arms with a shared guard, organized by which tag they belong to, ordered
+    /// within each tag by the order they were listed in the original input. These are tried before
+    /// the arms in `cases` for the same tag.
+    pub guarded: BTreeMap<usize, Vec<GuardedArms>>,
+    /// The tags which have reachable arms, in `cases` or `guarded`, in the order of their first
+    /// arms in the original input. Their arms are tried in this order, so that the tags written
+    /// first are checked first.
+    pub tag_order: Vec<usize>,
+    /// The arms whose tags are global tags, written as constant expressions, grouped by their tag
+    /// expressions in the order in which each was first listed in the original input. When there
+    /// are any, there are no arms for literal tags, and there is a default case.
+    pub global_cases: Vec<(Expr, Vec<(Span, Arm)>)>,
     /// The default case `_ => ...`, if there was any.
     pub default: Option<(Span, Arm)>,
+    /// The default cases which only match some tags, i.e. `_ if ... => ...` and
+    /// `_(t) if ... => ...`, in the order they were listed in the original input. These are tried,
+    /// in order, before the default case. The pattern of each, if not `_`, matches the tag.
+    pub partial_defaults: Vec<(Span, Arm)>,
     /// All the unreachable arms, for which we emit code so as to generate warnings.
     pub unreachable: Vec<CaseArm>,
+    /// What to do when arms for a tag are tried but none of them match, instead of falling through
+    /// to the default case, as requested by an `#[on_fallthrough]` attribute on the invocation.
+    pub fallthrough: Option<Fallthrough>,
+    /// The counters which observe each value matched, as requested by `#[profile(...)]` attributes
+    /// on the invocation.
+    pub profile: Vec<Expr>,
+    /// Whether the guards of arms outside shared-guard groups are checked against a reference to
+    /// the case, before it is extracted, as requested by a `#[borrow_guards]` attribute on the
+    /// invocation.
+    pub borrow_guards: bool,
+    /// The type of the entries of the static table to compile the arms into, to be indexed by
+    /// tag, as requested by a `#[lookup_table(...)]` attribute on the invocation.
+    pub lookup_table: Option<Type>,
+    /// The first arm for each tag with guarded arms which comes after arms for other tags, to be
+    /// warned about, as requested by a `#[warn_interleaved]` attribute on the invocation.
+    pub interleaved: Vec<(Span, usize)>,
+    /// What to do if the scrutinee's `Match` implementation gives a tag outside its range, as
+    /// requested by an `#[on_invariant(...)]` attribute on the invocation, or `None` to use
+    /// `vesta::unreachable`, which panics unless built with `--cfg release`.
+    pub on_invariant: Option<OnInvariant>,
+    /// Whether to print the expansion to standard error, as requested by a `#[debug_expansion]`
+    /// attribute on any arm. This also happens for every expansion when the
+    /// `VESTA_DEBUG_EXPANSION` environment variable is set to anything other than `0`.
+    pub debug_expansion: bool,
+}
+
+/// What to do when arms for a tag are tried but none of them match, because their guards or
+/// patterns fail, as given by an `#[on_fallthrough]` attribute on a `case!`.
+#[derive(Clone)]
+pub enum Fallthrough {
+    /// Panic in debug builds, and fall through to the default case otherwise, as requested by
+    /// `#[on_fallthrough]`.
+    Panic,
+    /// Evaluate this expression instead of the default case, as requested by
+    /// `#[on_fallthrough(...)]`.
+    Handle(Box<Expr>),
+}
+
+/// What to do if the `Match` implementation of the scrutinee of a `case!` breaks its invariants, as
+/// given by an `#[on_invariant(...)]` attribute on a `case!`.
+#[derive(Clone, Copy)]
+pub enum OnInvariant {
+    /// Panic with a message giving the tag, as requested by `#[on_invariant(panic)]`.
+    Panic,
+    /// Abort the process, as requested by `#[on_invariant(abort)]`.
+    Abort,
+    /// Assume that it never happens, as requested by `#[on_invariant(unreachable)]`.
+    Unreachable,
+}
+
+/// A group of arms for a single tag which share a guard, as they appear in a [`CaseOutput`].
+#[derive(Clone)]
+pub struct GuardedArms {
+    /// The guard shared by all the arms.
+    pub shared_guard: Rc<SharedGuard>,
+    /// The arms themselves, in the order they were listed in the original input.
+    pub arms: Vec<(Span, Arm)>,
+}
+
+impl CaseOutput {
+    /// The expansion as formatted Rust source, wrapped in a function named `expansion`, as printed
+    /// by `#[debug_expansion]`.
+    ///
+    /// This is meant for snapshot tests of expansions. Tools which expand macros using the
+    /// compiler, such as `cargo expand`, also expand the macros of the standard library used within
+    /// the expansion and format the result using the toolchain's own `rustfmt`, so their output
+    /// changes from one toolchain to the next. This text is formatted using the version of
+    /// `prettyplease` this crate depends on, with the macros within the expansion left as they were
+    /// written, so it doesn't depend on the toolchain. It does still change when the expansion
+    /// does, when `prettyplease` is updated to a newer `0.1` release, whose formatting may differ,
+    /// and with the path to `vesta` given by [`vesta_path`], which follows any rename of `vesta` in
+    /// the `Cargo.toml` of the package being built. Snapshots should be updated along with any of
+    /// these, rather than expected to stay the same across them.
+    ///
+    /// Unlike the expansion itself, this is never printed to standard error, whether or not
+    /// `#[debug_expansion]` or `VESTA_DEBUG_EXPANSION` asks for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use syn::parse_quote;
+    /// use vesta_syntax::CaseInput;
+    ///
+    /// let input: CaseInput = parse_quote!(option {
+    ///     0 => 0,
+    ///     1(n) => n + 1,
+    /// });
+    /// let pretty = input.compile().unwrap().pretty();
+    /// assert!(pretty.starts_with("fn expansion() {\n"));
+    /// assert!(pretty.contains("n + 1"));
+    ///
+    /// // The same input always gives the same text
+    /// let again: CaseInput = parse_quote!(option {
+    ///     0 => 0,
+    ///     1(n) => n + 1,
+    /// });
+    /// assert_eq!(again.compile().unwrap().pretty(), pretty);
+    /// ```
+    ///
+    /// The body of the only arm for a case is written directly in the arm of the match on the tag,
+    /// with its pattern bound by `let`, if the pattern always matches and the arm has no guard, so
+    /// that tools like rust-analyzer can follow it easily:
+    ///
+    /// ```
+    /// # use syn::parse_quote;
+    /// # use vesta_syntax::CaseInput;
+    /// let input: CaseInput = parse_quote!(option {
+    ///     0 => 0,
+    ///     1(n) => n + 1,
+    /// });
+    /// assert_eq!(
+    ///     input.compile().unwrap().pretty(),
+    ///     r#"
+    /// fn expansion() {
+    ///     let value = option;
+    ///     ::vesta::assert_case::<_, 0usize>(&value);
+    ///     ::vesta::assert_case::<_, 1usize>(&value);
+    ///     ::vesta::__usage_site!(& value, [0usize, 1usize], false);
+    ///     let tag = ::std::option::Option::Some(
+    ///         ::vesta::Match::tag_exhaustive::<2usize>(&value),
+    ///     );
+    ///     #[allow(unused_parens)]
+    ///     match tag {
+    ///         #[allow(clippy::let_unit_value)]
+    ///         ::std::option::Option::Some(0usize) => {
+    ///             let case = unsafe {
+    ///                 {
+    ///                     ::vesta::__audit_case!(& value, 0usize);
+    ///                     ::vesta::Case::<0usize>::case(value)
+    ///                 }
+    ///             };
+    ///             let _ = case;
+    ///             0
+    ///         }
+    ///         #[allow(clippy::let_unit_value)]
+    ///         ::std::option::Option::Some(1usize) => {
+    ///             let case = unsafe {
+    ///                 {
+    ///                     ::vesta::__audit_case!(& value, 1usize);
+    ///                     ::vesta::Case::<1usize>::case(value)
+    ///                 }
+    ///             };
+    ///             let (n) = case;
+    ///             n + 1
+    ///         }
+    ///         _ => unsafe { ::vesta::unreachable() }
+    ///     }
+    /// }
+    /// "#[1..],
+    /// );
+    /// ```
+    pub fn pretty(&self) -> String {
+        pretty_expansion(&self.expand())
+    }
 }
 
 impl ToTokens for CaseOutput {
     fn to_tokens(&self, stream: &mut proc_macro2::TokenStream) {
+        let expansion = self.expand();
+        if self.debug_expansion || debug_expansion_env() {
+            eprintln!(
+                "note: expansion of `case!({}{} {{ ... }})`:\n{}",
+                match (self.ref_token, self.cow_token) {
+                    (Some(_), _) => "ref ",
+                    (None, Some(_)) => "cow ",
+                    (None, None) => "",
+                },
+                self.scrutinee.to_token_stream(),
+                pretty_expansion(&expansion),
+            );
+        }
+        stream.extend(expansion)
+    }
+}
+
+impl CaseOutput {
+    /// The expansion, without printing it even if asked to.
+    fn expand(&self) -> proc_macro2::TokenStream {
         let vesta_path = crate::vesta_path();
 
         // Generate hygienic idents named "value" and "tag"
@@ -227,20 +1407,125 @@ impl ToTokens for CaseOutput {
         let tag_ident = Ident::new("tag", Span::mixed_site());
 
         let CaseOutput {
+            ref_token,
+            cow_token,
             scrutinee,
+            output_ty,
             brace_token,
             cases,
+            guarded,
+            tag_order,
+            global_cases,
             default,
+            partial_defaults,
             unreachable,
+            fallthrough,
+            profile,
+            borrow_guards,
+            lookup_table,
+            interleaved,
+            on_invariant,
+            debug_expansion: _,
         } = self;
 
         // Get the span for all the cases
         let cases_span = brace_token.span;
 
+        if !global_cases.is_empty() {
+            return expand_global(
+                scrutinee,
+                output_ty.as_ref(),
+                cases_span,
+                global_cases,
+                default,
+            );
+        }
+
+        // When matching by reference, the value is already a reference, and cases are extracted
+        // using `CaseRef` rather than `Case`, then wrapped in a `Cow` if asked for
+        let by_ref = ref_token.is_some() || cow_token.is_some();
+        let value_ref = if by_ref {
+            quote!(#value_ident)
+        } else {
+            quote!(&#value_ident)
+        };
+        let extract_case = |tag: &usize, span: Span| {
+            let value_ident = Ident::new("value", Span::mixed_site().located_at(span));
+            if cow_token.is_some() {
+                audited_case(
+                    &value_ident,
+                    tag,
+                    quote_spanned!(span=> #vesta_path::CowCase::cow_case(
+                        #vesta_path::CaseRef::<#tag>::case_ref(#value_ident)
+                    )),
+                )
+            } else if ref_token.is_some() {
+                audited_case(
+                    &value_ident,
+                    tag,
+                    quote_spanned!(span=> #vesta_path::CaseRef::<#tag>::case_ref(#value_ident)),
+                )
+            } else {
+                audited_case(
+                    quote!(&#value_ident),
+                    tag,
+                    quote_spanned!(span=> #vesta_path::Case::<#tag>::case(#value_ident)),
+                )
+            }
+        };
+
+        // With `#[borrow_guards]`, every arm for a tag up to and including its last guarded arm is
+        // checked against a reference to the case before the case is extracted
+        let checked_len = |inner_cases: &[(Span, Arm)]| {
+            if *borrow_guards {
+                inner_cases
+                    .iter()
+                    .rposition(|(_, arm)| arm.guard.is_some())
+                    .map_or(0, |last| last + 1)
+            } else {
+                0
+            }
+        };
+
+        // Assert that each arm's case can be extracted, spanned at the arm, so that a missing
+        // implementation of `Case` or `CaseRef` is reported there, unless the stable form of the
+        // expansion is asked for
+        let assert_case = if cow_token.is_some() {
+            quote!(assert_case_cow)
+        } else if ref_token.is_some() {
+            quote!(assert_case_ref)
+        } else {
+            quote!(assert_case)
+        };
+        let bound_checks = cases
+            .iter()
+            .flat_map(|(tag, arms)| arms.iter().map(move |(span, _)| (tag, span)))
+            .chain(guarded.iter().flat_map(|(tag, groups)| {
+                groups
+                    .iter()
+                    .flat_map(|group| &group.arms)
+                    .map(move |(span, _)| (tag, span))
+            }))
+            .chain(unreachable.iter().filter_map(|case_arm| {
+                case_arm.tag.as_ref().map(|tag| (tag, &case_arm.tag_span))
+            }))
+            .map(|(tag, span)| {
+                quote_spanned!(*span=> #vesta_path::#assert_case::<_, #tag>(#value_ref);)
+            })
+            .chain(cases.iter().flat_map(|(tag, arms)| {
+                let (vesta_path, value_ref) = (&vesta_path, &value_ref);
+                arms[..checked_len(arms)].iter().map(move |(span, _)| {
+                    quote_spanned!(*span=> #vesta_path::assert_case_ref::<_, #tag>(#value_ref);)
+                })
+            }))
+            .filter(|_| !stable_expansion())
+            .collect::<Vec<_>>();
+
         // Compute the max tag ever mentioned
         let mut max_tag = None;
         cases
             .keys()
+            .chain(guarded.keys())
             .chain(
                 unreachable
                     .iter()
@@ -261,8 +1546,95 @@ impl ToTokens for CaseOutput {
             Some(max_tag.map(|t| t + 1).unwrap_or(0))
         };
 
-        // Generate all the reachable outer arms
-        let active_arms = cases.iter().map(|(tag, inner_cases)| {
+        // Generate the arm handling the case where no arm tried for a tag matches, as requested,
+        // given the pattern which matches the tag
+        let fallthrough_arm = |tag: &usize, pat: proc_macro2::TokenStream| match fallthrough {
+            None => None,
+            // Without a default arm to fall through to, it must panic in every build, and its arm
+            // must be unguarded to count towards the match being exhaustive
+            Some(Fallthrough::Panic) if default.is_none() => {
+                let message = format!("no arm for case `{}` matched", tag);
+                Some(quote! {
+                    #[allow(unreachable_patterns)]
+                    #pat => ::std::panic!(#message),
+                })
+            }
+            Some(Fallthrough::Panic) => {
+                let message = format!("no arm for case `{}` matched", tag);
+                Some(quote! {
+                    #[allow(unreachable_patterns)]
+                    #pat if ::std::cfg!(debug_assertions) => ::std::panic!(#message),
+                })
+            }
+            Some(Fallthrough::Handle(handler)) => Some(quote! {
+                #[allow(unreachable_patterns)]
+                #pat => #handler,
+            }),
+        };
+
+        // Turn a default arm into an arm of the outer match on the tag, where the pattern of an arm
+        // like `_(t) if t > 10 => ...` matches the tag of a value which has one
+        let tag_arm = |arm: &Arm| {
+            let mut arm = match &arm.pat {
+                Pat::Wild(_) => arm.clone(),
+                pat => Arm {
+                    pat: parse_quote_spanned!(pat.span()=> ::std::option::Option::Some(#pat)),
+                    ..arm.clone()
+                },
+            };
+            let _ = arm.comma.get_or_insert_with(Default::default);
+            arm
+        };
+        let partial_default_arms: Vec<Arm> = partial_defaults
+            .iter()
+            .map(|(_, arm)| tag_arm(arm))
+            .collect();
+
+        // The default arm is written only once, after a labeled block which every arm for a tag
+        // that doesn't match jumps out of, unless one of the arms uses an unlabeled `break` or
+        // `continue` of its own, which can't be written inside a labeled block; then it is copied
+        // into the match for each tag instead
+        let mut jumps = UnlabeledJump::default();
+        for (_, arm) in cases
+            .values()
+            .flatten()
+            .chain(partial_defaults)
+            .chain(default)
+        {
+            jumps.visit_arm(arm);
+        }
+        for GuardedArms { shared_guard, arms } in guarded.values().flatten() {
+            jumps.visit_expr(&shared_guard.guard);
+            for (_, arm) in arms {
+                jumps.visit_arm(arm);
+            }
+        }
+        for case_arm in unreachable {
+            jumps.visit_arm(&Arm::from(case_arm.clone()));
+        }
+        if let Some(Fallthrough::Handle(handler)) = fallthrough {
+            jumps.visit_expr(handler);
+        }
+        let default_label = if default.is_some() && !jumps.found {
+            Some(Lifetime::new("'default", Span::mixed_site()))
+        } else {
+            None
+        };
+
+        // When the default arm comes after the labeled block, a case extracted for a tag whose arms
+        // don't match is moved out of the block into a variable of its own, declared before it, so
+        // that it is still dropped after the body of the default arm; each group of arms for a tag
+        // extracts the case into a different variable, since the cases differ in type
+        let holder_ident = |tag: &usize, group: usize| {
+            Ident::new(&format!("case_{}_{}", tag, group), Span::mixed_site())
+        };
+
+        // Generate an outer arm for some of the inner arms for a tag, guarded by a shared guard if
+        // one is given, as the `group`th group of arms for the tag
+        let outer_arm = |tag: &usize,
+                         group: usize,
+                         inner_cases: &[(Span, Arm)],
+                         shared_guard: Option<&SharedGuard>| {
             let inner_arms = inner_cases.iter().map(|(_, arm)| arm);
 
             // The pattern for the outer match on the tag, with a good span
@@ -270,77 +1642,667 @@ impl ToTokens for CaseOutput {
                 .iter()
                 .map(|(span, _)| span)
                 .cloned()
-                .fold1(|s, t| s.join(t).unwrap_or(s))
+                .reduce(|s, t| s.join(t).unwrap_or(s))
                 .unwrap_or_else(Span::call_site);
             let pat = quote_spanned!(tag_span=> ::std::option::Option::Some(#tag));
 
-            // The default arm, if one exists, is allowed to be unreachable but always inserted in
-            // the inner match if it exists
-            let default_arm = default.iter().map(|(_, arm)| {
-                quote! {
+            // The shared guard borrows the case without moving it, so it can be checked before the
+            // case is extracted
+            let guard = shared_guard.map(|SharedGuard { pat, guard, .. }| match pat {
+                Some(pat) => {
+                    let case_ref = audited_case(
+                        &value_ref,
+                        tag,
+                        quote!(#vesta_path::CaseRef::<#tag>::case_ref(#value_ref)),
+                    );
+                    quote! {
+                        if {
+                            let #pat = unsafe #case_ref;
+                            #guard
+                        }
+                    }
+                }
+                None => quote!(if #guard),
+            });
+
+            // If none of the inner arms match, this is handled as requested before falling
+            // through to the default arm
+            let failure_arm = fallthrough_arm(tag, quote!(_));
+
+            // The default arm, if one exists, is allowed to be unreachable but always reached
+            // from the inner match, either by jumping to its only copy, or by a copy of it
+            // inserted here, after trying the default arms for only some tags
+            let default_arm = default.iter().map(|(_, arm)| match &default_label {
+                Some(label) => quote! {
+                    #[allow(unreachable_patterns)]
+                    _ => break #label,
+                },
+                None if partial_default_arms.is_empty() => quote! {
                     #[allow(unreachable_patterns)]
                     #arm
-                }
+                },
+                None => quote! {
+                    #[allow(unreachable_patterns)]
+                    _ => match #tag_ident {
+                        #(#partial_default_arms)*
+                        #arm
+                    }
+                },
             });
 
-            quote! {
-                #pat => match unsafe {
-                    #vesta_path::Case::<#tag>::case(#value_ident)
-                } {
+            let extract = extract_case(tag, tag_span);
+
+            // A lone arm which always matches is bound by `let` rather than an inner match, so
+            // its body sits directly in the outer arm, where tools like rust-analyzer handle it
+            // best; the case is bound first so that anything it doesn't move is still dropped
+            // after the body, as it would be by a match
+            if let Some(arm) = lone_arm(inner_cases, shared_guard) {
+                let case_ident = Ident::new("case", Span::mixed_site());
+                let (arm_pat, body) = (&arm.pat, &arm.body);
+                return quote_spanned! {tag_span=>
+                    #[allow(clippy::let_unit_value)]
+                    #pat => {
+                        let #case_ident = unsafe { #extract };
+                        let #arm_pat = #case_ident;
+                        #body
+                    }
+                };
+            }
+
+            if default_label.is_some() {
+                let holder = holder_ident(tag, group);
+                return quote_spanned! {tag_span=>
+                    #pat #guard => {
+                        #holder = unsafe { #extract };
+                        match #holder {
+                            #(#inner_arms)*
+                            #failure_arm
+                            #(#default_arm)*
+                        }
+                    }
+                };
+            }
+
+            quote_spanned! {tag_span=>
+                #pat #guard => match unsafe { #extract } {
                     #(#inner_arms)*
+                    #failure_arm
                     #(#default_arm)*
                 }
             }
+        };
+
+        // Generate an outer arm for a single inner arm for a tag, which is only chosen if the arm
+        // matches a reference to the case, so that the case is not extracted if its guard fails
+        let checked_arm = |tag: &usize, (span, arm): &(Span, Arm)| {
+            let span = *span;
+            let pat = &arm.pat;
+            let guard = arm
+                .guard
+                .as_ref()
+                .map(|(if_token, guard)| quote!(#if_token #guard));
+            // Bindings which are only used in the guard are unused once it has been removed
+            let mut unguarded_arm = Arm {
+                guard: None,
+                ..arm.clone()
+            };
+            if arm.guard.is_some() {
+                unguarded_arm
+                    .attrs
+                    .push(parse_quote!(#[allow(unused_variables)]));
+            }
+            let case_ref = audited_case(
+                &value_ref,
+                tag,
+                quote_spanned!(span=> #vesta_path::CaseRef::<#tag>::case_ref(#value_ref)),
+            );
+            let extract = extract_case(tag, span);
+            let message = format!("case `{}` matched by reference, but not by value", tag);
+            quote_spanned! {span=>
+                ::std::option::Option::Some(#tag) if match unsafe { #case_ref } {
+                    #[allow(unused_variables, unused_mut)]
+                    #pat #guard => true,
+                    _ => false,
+                } => match unsafe { #extract } {
+                    #unguarded_arm
+                    #[allow(unreachable_patterns)]
+                    _ => ::std::unreachable!(#message),
+                }
+            }
+        };
+
+        // Generate all the reachable outer arms, in the order in which their tags were first
+        // written, trying the guarded groups for each tag before its other arms
+        let active_arms = tag_order.iter().flat_map(|tag| {
+            let groups = guarded.get(tag).map_or(&[][..], Vec::as_slice);
+            let guarded_arms = groups.iter().enumerate().map(
+                move |(group, GuardedArms { shared_guard, arms })| {
+                    outer_arm(tag, group, arms, Some(shared_guard))
+                },
+            );
+            let inner_cases = cases.get(tag).map_or(&[][..], Vec::as_slice);
+            let (checked_cases, unchecked_cases) = inner_cases.split_at(checked_len(inner_cases));
+            let checked_arms = checked_cases.iter().map(move |arm| checked_arm(tag, arm));
+            let unguarded_arms = if unchecked_cases.is_empty() {
+                None
+            } else {
+                Some(outer_arm(tag, groups.len(), unchecked_cases, None))
+            };
+            // If every arm for the tag has a shared guard, or is checked before the case is
+            // extracted, the failure of all of them is handled here
+            let failure_arm = if unguarded_arms.is_some() {
+                None
+            } else {
+                fallthrough_arm(tag, quote!(::std::option::Option::Some(#tag)))
+            };
+            guarded_arms
+                .chain(checked_arms)
+                .chain(unguarded_arms)
+                .chain(failure_arm)
+                .collect::<Vec<_>>()
         });
 
-        // Generate the exhaustive fall-through case, if one is necessary
-        let exhaustive_arm = exhaustive_cases.iter().map(|num_cases| {
+        // The code for paths which are only reached if the `Match` implementation is wrong
+        let invariant_violation = match on_invariant {
+            None => quote!(unsafe { #vesta_path::unreachable() }),
+            Some(OnInvariant::Panic) => quote! {
+                ::std::panic!(
+                    "invariant violation in `vesta::Match` implementation: `case!` was given the \
+                    tag {:?}, which is outside the range of the type",
+                    #tag_ident,
+                )
+            },
+            Some(OnInvariant::Abort) => quote!(::std::process::abort()),
+            Some(OnInvariant::Unreachable) => {
+                quote!(unsafe { ::std::hint::unreachable_unchecked() })
+            }
+        };
+
+        // Generate the exhaustive fall-through case, if one is necessary; it is unreachable, since
+        // the tag is then read using `tag_exhaustive`, which requires the type to be exhaustive
+        let exhaustive_arm = exhaustive_cases.iter().map(|_| {
             quote! {
-                _ => {
-                    #vesta_path::assert_exhaustive::<_, #num_cases>(&#value_ident);
-                    unsafe { #vesta_path::unreachable() }
-                }
+                _ => #invariant_violation
             }
         });
+        // When an invariant violation is to be handled as requested, a missing tag must reach the
+        // handler too, so the tag is read as usual and the type is checked to be exhaustive apart
+        let read_tag = match exhaustive_cases {
+            Some(num_cases) if on_invariant.is_some() => quote! {
+                {
+                    #vesta_path::assert_exhaustive::<_, #num_cases>(#value_ref);
+                    #vesta_path::Match::tag(#value_ref)
+                }
+            },
+            Some(num_cases) => quote! {
+                ::std::option::Option::Some(
+                    #vesta_path::Match::tag_exhaustive::<#num_cases>(#value_ref),
+                )
+            },
+            None => quote!(#vesta_path::Match::tag(#value_ref)),
+        };
 
         // Generate all the unreachable arms, for maximum warning reporting
-        let unreachable_arms = unreachable
+        let unreachable_arms = unreachable.iter().map(|case_arm| match &case_arm.tag {
+            Some(tag) => {
+                let tag_span = &case_arm.tag_span;
+                let mut arm = Arm::from(case_arm.clone());
+                let _ = arm.comma.get_or_insert_with(Default::default);
+                let extract = extract_case(tag, *tag_span);
+                quote_spanned! { *tag_span=>
+                ::std::option::Option::Some(#tag) => match unsafe { #extract } {
+                    #arm
+                    // We need to make this pattern match complete so that this type-checks, but
+                    // the only reason we're generating code at all is for warnings, so here we
+                    // say the next arm is unreachable: it *is* unreachable, because this whole
+                    // match expression is unreachable. This is only a valid assumption because
+                    // all the arms for which this is generated are unreachable.
+                    _ => #invariant_violation
+                }
+                }
+            }
+            None => {
+                let arm = tag_arm(&Arm::from(case_arm.clone()));
+                quote!(#arm)
+            }
+        });
+
+        // Glue all the arms together
+        // The default arms for only some tags may be unreachable in the outer match, when they are
+        // only reached after the arms for a tag fail to match
+        // If the default arm comes after the match, so do they
+        let outer_partial_default_arms = partial_default_arms
             .iter()
-            .map(|CaseArm { tag, arm, tag_span }| match tag {
-                Some(tag) => quote_spanned! { *tag_span=>
-                    ::std::option::Option::Some(#tag) => match unsafe {
-                        #vesta_path::Case::<#tag>::case(#value_ident)
-                    } {
-                        #arm
-                        // We need to make this pattern match complete so that this type-checks, but
-                        // the only reason we're generating code at all is for warnings, so here we
-                        // say the next arm is unreachable: it *is* unreachable, because this whole
-                        // match expression is unreachable. This is only a valid assumption because
-                        // all the arms for which this is generated are unreachable.
-                        _ => unsafe { #vesta_path::unreachable() }
+            .filter(|_| default_label.is_none())
+            .map(|arm| {
+                quote! {
+                    #[allow(unreachable_patterns)]
+                    #arm
+                }
+            });
+        // When the default arm comes after the match, the outer match jumps to it in its place, so
+        // that the arms after it are still reported as unreachable
+        let outer_default_arm = default.iter().map(|(_, arm)| match &default_label {
+            Some(label) => {
+                let pat = &arm.pat;
+                quote!(#pat => break #label,)
+            }
+            // Unlike in the inner matches, we don't `#[allow(unreachable)]` the default
+            None => quote!(#arm),
+        });
+        let arms = active_arms
+            .chain(outer_partial_default_arms)
+            .chain(exhaustive_arm.chain(outer_default_arm.chain(unreachable_arms)));
+
+        let binding = if by_ref {
+            quote!(#[allow(unused_parens)] let #value_ident = &(#scrutinee);)
+        } else {
+            quote!(let #value_ident = #scrutinee;)
+        };
+
+        // Register the tags matched here in the report of which cases are matched, if it's enabled
+        let matched_tags: BTreeSet<&usize> = cases.keys().chain(guarded.keys()).collect();
+        let has_default = default.is_some();
+        let register_usage = quote! {
+            #vesta_path::__usage_site!(#value_ref, [#(#matched_tags),*], #has_default);
+        };
+
+        // Let each counter observe the value, before it is taken apart
+        let observe = profile
+            .iter()
+            .map(|counter| quote_spanned!(counter.span()=> (#counter).observe(#value_ref);));
+
+        // A profiled site also counts the tags of its own arms, so that it can suggest an order
+        let observe_site = if profile.is_empty() {
+            None
+        } else {
+            let counts_ident = Ident::new("COUNTS", Span::mixed_site());
+            let site_ident = Ident::new("SITE", Span::mixed_site());
+            let num_counts = tag_order.len() + 1;
+            Some(quote! {
+                {
+                    static #counts_ident: [::std::sync::atomic::AtomicU64; #num_counts] =
+                        [const { ::std::sync::atomic::AtomicU64::new(0) }; #num_counts];
+                    static #site_ident: #vesta_path::profile::Site = #vesta_path::profile::Site::new(
+                        ::std::file!(),
+                        ::std::line!(),
+                        ::std::column!(),
+                        &[#(#tag_order),*],
+                        &#counts_ident,
+                    );
+                    #site_ident.observe(#value_ref);
+                }
+            })
+        };
+
+        // Warn about each interleaved arm using a deprecated function, which must appear to come
+        // from the arm itself for the warning to be shown, unless the stable form of the expansion
+        // is asked for
+        let warn_interleaved =
+            interleaved
+                .iter()
+                .filter(|_| !stable_expansion())
+                .map(|(span, tag)| {
+                    let warning_ident = Ident::new("__VestaInterleaved", *span);
+                    let note = format!(
+                "this arm for case `{}` comes after arms for other cases, but is tried right after \
+                the earlier arms for case `{}`; consider moving it next to them",
+                tag, tag
+            );
+                    quote_spanned! {*span=>
+                        struct #warning_ident;
+                        impl #warning_ident {
+                            #[deprecated(note = #note)]
+                            fn interleaved_arm() {}
+                        }
+                        #warning_ident::interleaved_arm();
                     }
-                },
-                None => quote!(#arm),
+                });
+
+        // The match on the tag, followed by the only copy of the default arm, if it isn't copied
+        let tag_match = match (&default_label, default) {
+            (Some(default_label), Some((_, default_arm))) => {
+                let case_label = Lifetime::new("'case", Span::mixed_site());
+                let result_ident = Ident::new("result", Span::mixed_site());
+                // Every group of arms for a tag which is matched after extracting the case needs a
+                // variable to hold it
+                let holders = tag_order.iter().flat_map(|tag| {
+                    let groups = guarded.get(tag).map_or(0, Vec::len);
+                    let inner_cases = cases.get(tag).map_or(&[][..], Vec::as_slice);
+                    let unchecked_cases = &inner_cases[checked_len(inner_cases)..];
+                    let unguarded =
+                        !unchecked_cases.is_empty() && lone_arm(unchecked_cases, None).is_none();
+                    (0..groups)
+                        .chain(Some(groups).filter(|_| unguarded))
+                        .map(move |group| holder_ident(tag, group))
+                });
+                quote! {
+                    #case_label: {
+                        #(
+                            #[allow(unused_mut)]
+                            let mut #holders;
+                        )*
+                        #default_label: {
+                            #[allow(unused_parens)]
+                            let #result_ident = match #tag_ident {
+                                #(#arms)*
+                            };
+                            #[allow(unreachable_code)]
+                            break #case_label #result_ident;
+                        }
+                        match #tag_ident {
+                            #(#partial_default_arms)*
+                            #default_arm
+                        }
+                    }
+                }
+            }
+            _ => quote! {
+                #[allow(unused_parens)]
+                match #tag_ident {
+                    #(#arms)*
+                }
+            },
+        };
+
+        // The expansion is spanned at the braces written by the user, rather than coming from the
+        // macro, so that lints about the value of the whole expression, like `unused_must_use`, are
+        // still reported wherever it is discarded
+        let expansion = if let Some(table_ty) = lookup_table {
+            // Every tag up to the largest one mentioned has an entry in the table, taken from its
+            // arm or the default arm, and the type must have exactly that many cases
+            let table_ident = Ident::new("TABLE", Span::mixed_site());
+            let len = max_tag.map_or(0, |tag| tag + 1);
+            let lookup = if on_invariant.is_some() {
+                let index_ident = Ident::new("index", Span::mixed_site());
+                quote! {
+                    {
+                        #vesta_path::assert_exhaustive::<_, #len>(#value_ref);
+                        let #tag_ident = #vesta_path::Match::tag(#value_ref);
+                        match #tag_ident {
+                            ::std::option::Option::Some(#index_ident) if #index_ident < #len => {
+                                #table_ident[#index_ident]
+                            }
+                            _ => #invariant_violation,
+                        }
+                    }
+                }
+            } else {
+                quote!(#table_ident[#vesta_path::Match::tag_exhaustive::<#len>(#value_ref)])
+            };
+            let entries = (0..len).map(|tag| {
+                match cases
+                    .get(&tag)
+                    .and_then(|arms| arms.first())
+                    .or(default.as_ref())
+                {
+                    Some((_, arm)) => arm.body.to_token_stream(),
+                    // Missing cases are reported by `compile`, so this is never generated
+                    None => quote!(::std::unreachable!()),
+                }
             });
+            quote_spanned!(cases_span=> {
+                #binding
+                #register_usage
+                #(#observe)*
+                #observe_site
+                static #table_ident: [#table_ty; #len] = [#(#entries),*];
+                #lookup
+            })
+        } else {
+            quote_spanned!(cases_span=> {
+                #binding
+                #(#bound_checks)*
+                #({ #warn_interleaved })*
+                #register_usage
+                #(#observe)*
+                #observe_site
+                let #tag_ident = #read_tag;
+                #tag_match
+            })
+        };
 
-        // Glue all the arms together
-        let arms = active_arms.chain(
-            exhaustive_arm.chain(
-                default
-                    .iter()
-                    // Unlike in the inner matches, we don't `#[allow(unreachable)]` the default
-                    .map(|(_, arm)| quote!(#arm))
-                    .chain(unreachable_arms),
-            ),
-        );
-
-        stream.extend(quote_spanned!(cases_span=> {
-            let #value_ident = #scrutinee;
-            let #tag_ident = #vesta_path::Match::tag(&#value_ident);
-            #[allow(unused_parens)]
-            match #tag_ident {
-                #(#arms)*
-            }
-        }))
+        // Give the whole expansion the type written, if any, by binding it, so that the type of
+        // each arm can be inferred from it
+        match output_ty {
+            Some(ty) => {
+                let result_ident = Ident::new("result", Span::mixed_site());
+                quote_spanned!(cases_span=> {
+                    let #result_ident: #ty = #expansion;
+                    #result_ident
+                })
+            }
+            None => expansion,
+        }
+    }
+}
+
+/// Expand the arms of a `case!` for global tags, which match on the global tag of the scrutinee,
+/// given by `TagBase`, and extract each case using `GlobalCase`. Each tag expression is evaluated
+/// as a constant, so that it can be used as a pattern and as the parameter of `GlobalCase`. If no
+/// arm for the global tag of the scrutinee matches, the default arm is evaluated.
+fn expand_global(
+    scrutinee: &Expr,
+    output_ty: Option<&Type>,
+    cases_span: Span,
+    global_cases: &[(Expr, Vec<(Span, Arm)>)],
+    default: &Option<(Span, Arm)>,
+) -> proc_macro2::TokenStream {
+    let vesta_path = crate::vesta_path();
+    let value_ident = Ident::new("value", Span::mixed_site());
+    let case_ident = Ident::new("case", Span::mixed_site());
+    let result_ident = Ident::new("result", Span::mixed_site());
+    let case_label = Lifetime::new("'case", Span::mixed_site());
+    let default_label = Lifetime::new("'default", Span::mixed_site());
+
+    let tag_idents: Vec<Ident> = (0..global_cases.len())
+        .map(|n| Ident::new(&format!("GLOBAL_TAG_{}", n), Span::mixed_site()))
+        .collect();
+    let tag_consts = global_cases
+        .iter()
+        .zip(&tag_idents)
+        .map(|((global_tag, _), tag_ident)| {
+            quote_spanned! {global_tag.span()=>
+                #[allow(unused_braces)]
+                const #tag_ident: ::std::primitive::usize = #global_tag;
+            }
+        });
+    let arms = global_cases
+        .iter()
+        .zip(&tag_idents)
+        .map(|((global_tag, inner_arms), tag_ident)| {
+            let inner_arms = inner_arms.iter().map(|(_, arm)| arm);
+            let span = global_tag.span();
+            let value_ident = Ident::new("value", Span::mixed_site().located_at(span));
+            let extract = quote_spanned! {span=>
+                #vesta_path::GlobalCase::<{ #tag_ident }>::global_case(#value_ident)
+            };
+            quote! {
+                ::std::option::Option::Some(#vesta_path::Tag(#tag_ident)) => {
+                    // It is safe to extract the case because its global tag has just been checked
+                    let #case_ident = unsafe { #extract };
+                    match #case_ident {
+                        #(#inner_arms)*
+                        #[allow(unreachable_patterns)]
+                        _ => break #default_label,
+                    }
+                }
+            }
+        });
+    let default_body = default.iter().map(|(_, arm)| &arm.body);
+
+    let expansion = quote_spanned!(cases_span=> {
+        let #value_ident = #scrutinee;
+        #(#tag_consts)*
+        #case_label: {
+            #default_label: {
+                #[allow(unused_parens)]
+                let #result_ident = match #vesta_path::TagBase::global_tag(&#value_ident) {
+                    #(#arms)*
+                    _ => break #default_label,
+                };
+                #[allow(unreachable_code)]
+                break #case_label #result_ident;
+            }
+            #(#default_body)*
+        }
+    });
+
+    match output_ty {
+        Some(ty) => quote_spanned!(cases_span=> {
+            let #result_ident: #ty = #expansion;
+            #result_ident
+        }),
+        None => expansion,
+    }
+}
+
+/// The first part of a pattern which may fail to match, if any. This is conservative: patterns
+/// naming types, such as structs, are assumed to be refutable, since they may name enum variants,
+/// as are identifiers starting with a capital, which may name constants or unit variants.
+fn refutable_part(pat: &Pat) -> Option<&Pat> {
+    match pat {
+        Pat::Wild(_) | Pat::Rest(_) => None,
+        Pat::Ident(ident) if ident.ident.to_string().starts_with(char::is_uppercase) => Some(pat),
+        Pat::Ident(ident) => match &ident.subpat {
+            Some((_, subpat)) => refutable_part(subpat),
+            None => None,
+        },
+        Pat::Tuple(tuple) => tuple.elems.iter().find_map(refutable_part),
+        Pat::Reference(reference) => refutable_part(&reference.pat),
+        Pat::Type(typed) => refutable_part(&typed.pat),
+        _ => Some(pat),
+    }
+}
+
+/// The only arm for a tag, if it always matches and has no shared guard, so that it can be bound by
+/// `let` rather than by an inner match.
+fn lone_arm<'a>(
+    inner_cases: &'a [(Span, Arm)],
+    shared_guard: Option<&SharedGuard>,
+) -> Option<&'a Arm> {
+    match inner_cases {
+        [(_, arm)]
+            if shared_guard.is_none()
+                && arm.guard.is_none()
+                && arm.attrs.is_empty()
+                && refutable_part(&arm.pat).is_none() =>
+        {
+            Some(arm)
+        }
+        _ => None,
+    }
+}
+
+/// A search for a `break` or `continue` without a label which would leave the arms of a `case!`,
+/// as opposed to one leaving a loop or closure written inside them.
+#[derive(Default)]
+struct UnlabeledJump {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for UnlabeledJump {
+    fn visit_expr_break(&mut self, expr: &'ast ExprBreak) {
+        self.found |= expr.label.is_none();
+        visit::visit_expr_break(self, expr);
+    }
+
+    fn visit_expr_continue(&mut self, expr: &'ast ExprContinue) {
+        self.found |= expr.label.is_none();
+    }
+
+    // Only the iterator of a `for` loop is outside the loop
+    fn visit_expr_for_loop(&mut self, expr: &'ast ExprForLoop) {
+        self.visit_expr(&expr.expr);
+    }
+
+    fn visit_expr_loop(&mut self, _: &'ast ExprLoop) {}
+
+    fn visit_expr_while(&mut self, _: &'ast ExprWhile) {}
+
+    fn visit_expr_closure(&mut self, _: &'ast ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _: &'ast ExprAsync) {}
+
+    fn visit_item(&mut self, _: &'ast Item) {}
+
+    // The arguments of a macro aren't parsed, so any jump in them is assumed to leave the arms
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        self.found |= unlabeled_jump(mac.tokens.clone());
+    }
+}
+
+/// Whether a token stream contains a `break` or `continue` without a label. This is conservative,
+/// since one within a loop or closure of its own could be written inside a labeled block.
+fn unlabeled_jump(tokens: proc_macro2::TokenStream) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "break" || ident == "continue" => {
+                match tokens.peek() {
+                    Some(TokenTree::Punct(punct)) if punct.as_char() == '\'' => {}
+                    _ => return true,
+                }
+            }
+            TokenTree::Group(group) if unlabeled_jump(group.stream()) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether the `VESTA_STABLE_EXPANSION` environment variable is set to anything other than `0`,
+/// asking the macros for the stable form of their expansions, for snapshot tests of them using
+/// tools like `macrotest` or `insta`.
+///
+/// The stable form leaves out everything in the expansions of `case!` and the `Match` derive which
+/// only affects diagnostics or performance, and which is therefore liable to change from one
+/// release of `vesta` to the next: the static checks that each arm's case can be extracted, the
+/// warnings requested by `#[warn_interleaved]`, and the derived overrides of provided methods,
+/// such as `Case::try_case` and `Match::tag_exhaustive`, which fall back to their default
+/// implementations. What remains only changes when the meaning of the generated code does. Since
+/// the checks are left out, the errors for invalid arms are less helpful in this form, so it
+/// should only be used for the builds which take the snapshots.
+///
+/// Cargo doesn't know that the expansions depend on this variable, so the crates whose expansions
+/// are snapshotted must be rebuilt, e.g. by `cargo clean -p`, after it is changed.
+///
+/// # Examples
+///
+/// ```
+/// use quote::ToTokens;
+/// use syn::parse_quote;
+/// use vesta_syntax::{stable_expansion, CaseInput};
+///
+/// let input: CaseInput = parse_quote!(option {
+///     0 => 0,
+///     1(n) => n + 1,
+/// });
+///
+/// std::env::set_var("VESTA_STABLE_EXPANSION", "1");
+/// assert!(stable_expansion());
+/// let stable = input.clone().compile().unwrap().into_token_stream().to_string();
+/// assert!(!stable.contains("assert_case"));
+///
+/// std::env::set_var("VESTA_STABLE_EXPANSION", "0");
+/// assert!(!stable_expansion());
+/// let usual = input.compile().unwrap().into_token_stream().to_string();
+/// assert!(usual.contains("assert_case"));
+/// ```
+pub fn stable_expansion() -> bool {
+    env::var_os("VESTA_STABLE_EXPANSION").is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Whether the `VESTA_DEBUG_EXPANSION` environment variable asks for all expansions to be printed.
+fn debug_expansion_env() -> bool {
+    env::var_os("VESTA_DEBUG_EXPANSION").is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Format a block expression as readable Rust source, falling back to its unformatted tokens if it
+/// somehow can't be parsed.
+fn pretty_expansion(expansion: &proc_macro2::TokenStream) -> String {
+    match syn::parse2(quote!(fn expansion() #expansion)) {
+        Ok(file) => prettyplease::unparse(&file),
+        Err(_) => expansion.to_string(),
     }
 }
@@ -0,0 +1,220 @@
+//! The wording of the diagnostics reported by `vesta`'s macros, which can be overridden by a
+//! message catalog.
+//!
+//! Each diagnostic has a key, and a default template in English, in which arguments are written
+//! `{name}`. With the `message-catalog` feature enabled, the `VESTA_MESSAGE_CATALOG` environment
+//! variable may give the path of a catalog file, whose templates are used instead for the keys it
+//! lists. Each line of the catalog is either blank, a comment starting with `#`, or of the form
+//! `key = template`, where `\n` in the template stands for a line break:
+//!
+//! ```text
+//! # Our own wording for missing arms
+//! missing_cases = cas non couverts : {cases}
+//! missing_cases_help = aide : ajoutez un bras `_ => ...`, ou {arms} pour {cases}
+//! ```
+//!
+//! The keys, and the arguments available to their templates, are those of the functions in this
+//! module which construct each message:
+//!
+//! | Key                      | Arguments         |
+//! |--------------------------|-------------------|
+//! | [`missing_cases`]        | `cases`           |
+//! | [`missing_cases_help`]   | `arms`, `cases`   |
+//! | [`named_fields_struct`]  | `type`            |
+//! | [`named_fields_variant`] | `type`, `variant` |
+//! | [`union`]                | `type`            |
+//!
+//! The catalog is read by the compiler while expanding the macros, so after changing it or the
+//! environment variable, the crates using the macros must be rebuilt from scratch (for instance
+//! after `cargo clean -p my-crate`) for the new wording to appear. If the catalog can't be read,
+//! the default wording is used, along with a note saying why.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta_syntax::messages::Message;
+//!
+//! let message = Message::new("union", "cannot derive `Match` for the union `{type}`")
+//!     .arg("type", "Bits");
+//! assert_eq!(message.to_string(), "cannot derive `Match` for the union `Bits`");
+//! ```
+
+use std::fmt;
+
+/// A diagnostic, given by its key in the message catalog, its default template, and its arguments.
+#[derive(Debug, Clone)]
+pub struct Message {
+    key: &'static str,
+    template: &'static str,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    /// A message with the given key and default template, and no arguments yet.
+    pub fn new(key: &'static str, template: &'static str) -> Self {
+        Message {
+            key,
+            template,
+            args: Vec::new(),
+        }
+    }
+
+    /// Give a value to the argument written `{name}` in the template.
+    pub fn arg(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let template = catalog::template(self.key);
+        let template = template.as_deref().unwrap_or(self.template);
+
+        // Substitute each `{name}` with its argument, leaving any unknown name as it was written
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            f.write_str(&rest[..start])?;
+            let after = &rest[start + 1..];
+            let arg = after.find('}').and_then(|end| {
+                let name = &after[..end];
+                self.args
+                    .iter()
+                    .find(|(arg, _)| *arg == name)
+                    .map(|(_, value)| (value, &after[end + 1..]))
+            });
+            match arg {
+                Some((value, after)) => {
+                    f.write_str(value)?;
+                    rest = after;
+                }
+                None => {
+                    f.write_str("{")?;
+                    rest = after;
+                }
+            }
+        }
+        f.write_str(rest)?;
+
+        if let Some(error) = catalog::error() {
+            write!(
+                f,
+                "\nnote: the message catalog could not be read: {}",
+                error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the message catalog named by `VESTA_MESSAGE_CATALOG` could not be read, if the
+/// `message-catalog` feature is enabled and it couldn't.
+pub fn catalog_error() -> Option<&'static str> {
+    catalog::error()
+}
+
+/// The error for a `case!` which does not cover the given cases.
+pub fn missing_cases(cases: impl fmt::Display) -> Message {
+    Message::new(
+        "missing_cases",
+        "non-exhaustive patterns: {cases} not covered",
+    )
+    .arg("cases", cases)
+}
+
+/// The suggestion of how to cover the cases missing from a `case!`, where `arms` is either "an arm"
+/// or "arms".
+pub fn missing_cases_help(arms: &str, cases: impl fmt::Display) -> Message {
+    Message::new(
+        "missing_cases_help",
+        "help: add a `_ => ...` arm, or {arms} for {cases}, before this closing brace",
+    )
+    .arg("arms", arms)
+    .arg("cases", cases)
+}
+
+/// The error for deriving `Match` for a struct with more than one named field.
+pub fn named_fields_struct(ty: impl fmt::Display) -> Message {
+    Message::new(
+        "named_fields_struct",
+        "cannot derive `Match` for the struct `{type}` with more than one named field\n\
+        consider making `{type}` a tuple struct, or a wrapper for another type with named fields",
+    )
+    .arg("type", ty)
+}
+
+/// The error for deriving `Match` for an enum with a variant with more than one named field.
+pub fn named_fields_variant(ty: impl fmt::Display, variant: impl fmt::Display) -> Message {
+    Message::new(
+        "named_fields_variant",
+        "cannot derive `Match` for the enum variant `{type}::{variant}` with more than one named \
+        field\nconsider making `{type}::{variant}` a tuple variant, or a wrapper for another type \
+        with named fields, or generating such a type using `#[vesta(newtype_variants)]`",
+    )
+    .arg("type", ty)
+    .arg("variant", variant)
+}
+
+/// The error for deriving `Match` for a union.
+pub fn union(ty: impl fmt::Display) -> Message {
+    Message::new(
+        "union",
+        "Cannot derive `Match` for a union, since unions lack a tag",
+    )
+    .arg("type", ty)
+}
+
+#[cfg(feature = "message-catalog")]
+mod catalog {
+    use std::{collections::HashMap, env, fs, sync::OnceLock};
+
+    /// The catalog named by `VESTA_MESSAGE_CATALOG`, or why it couldn't be read, read at most once
+    /// per compiler process.
+    fn catalog() -> &'static Result<HashMap<String, String>, String> {
+        static CATALOG: OnceLock<Result<HashMap<String, String>, String>> = OnceLock::new();
+        CATALOG.get_or_init(|| match env::var_os("VESTA_MESSAGE_CATALOG") {
+            None => Ok(HashMap::new()),
+            Some(path) => fs::read_to_string(&path)
+                .map_err(|error| format!("{}: {}", path.to_string_lossy(), error))
+                .and_then(|contents| parse(&contents)),
+        })
+    }
+
+    /// Parse the lines of a catalog.
+    fn parse(contents: &str) -> Result<HashMap<String, String>, String> {
+        let mut templates = HashMap::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, template)) => {
+                    let template = template.trim().replace("\\n", "\n");
+                    let _ = templates.insert(key.trim().to_string(), template);
+                }
+                None => return Err(format!("line {}: expected `key = template`", number + 1)),
+            }
+        }
+        Ok(templates)
+    }
+
+    pub(super) fn template(key: &str) -> Option<String> {
+        catalog().as_ref().ok()?.get(key).cloned()
+    }
+
+    pub(super) fn error() -> Option<&'static str> {
+        catalog().as_ref().err().map(String::as_str)
+    }
+}
+
+#[cfg(not(feature = "message-catalog"))]
+mod catalog {
+    pub(super) fn template(_: &str) -> Option<String> {
+        None
+    }
+
+    pub(super) fn error() -> Option<&'static str> {
+        None
+    }
+}
@@ -0,0 +1,194 @@
+//! Generating `vesta`-ready types from a simple schema, for use from build scripts and other code
+//! generators.
+//!
+//! Code generators for protocols and interface descriptions can describe each tagged union as a
+//! [`Schema`], and use [`generate`] to produce both the definition of the corresponding enum and
+//! the same implementations of `Match`, `Case`, and `CaseRef` that `#[derive(Match)]` would
+//! produce, without depending on the derive macro or reimplementing it.
+//!
+//! With the `serde` feature enabled, [`Schema`] and [`SchemaCase`] can be deserialized, so schemas
+//! can be read from JSON or any other format `serde` supports. A build script might do:
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use vesta_syntax::schema::{generate, Schema};
+//!
+//! let json = r#"{
+//!     "name": "Reply",
+//!     "visibility": "pub",
+//!     "cases": [
+//!         { "name": "Ok", "tag": 0, "payload": ["Vec<u8>"] },
+//!         { "name": "NotFound", "tag": 1 }
+//!     ]
+//! }"#;
+//! let schema: Schema = serde_json::from_str(json).unwrap();
+//! let code = generate(&schema).unwrap();
+//! // ... then write `code` to a file in `OUT_DIR`, and `include!` it in the crate
+//! # assert!(code.to_string().starts_with("pub enum Reply"));
+//! # }
+//! ```
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    punctuated::Punctuated, token, DataEnum, Error, Fields, FieldsUnnamed, Generics, Ident, Type,
+    Variant, Visibility,
+};
+
+use crate::derive::{derive_match_enum, register_usage, ImplOptions, NonExhaustive};
+
+/// A description of a tagged union, from which an enum can be [`generate`]d.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Schema {
+    /// The name of the enum.
+    pub name: String,
+    /// The visibility of the enum, such as `"pub"` or `"pub(crate)"`, or `""` for private.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: String,
+    /// The cases of the enum, in any order. Their tags must be exactly `0` to `N - 1`.
+    pub cases: Vec<SchemaCase>,
+}
+
+/// A description of a single case of a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SchemaCase {
+    /// The name of the enum variant for this case.
+    pub name: String,
+    /// The tag of this case.
+    pub tag: usize,
+    /// The types of the fields of the variant, as Rust source, such as `"u32"` or `"Vec<String>"`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub payload: Vec<String>,
+}
+
+/// Generate the definition of an enum from a [`Schema`], along with its implementations of
+/// `Match`, `Case`, and `CaseRef`.
+///
+/// The variants are declared in order of their tags, with unnamed fields of the given payload
+/// types. The generated code refers to the `vesta` crate as a dependency of the package being
+/// built, just like the derive macro.
+///
+/// # Errors
+///
+/// Returns an error if a name is not a valid identifier, a payload is not a valid type, or the tags
+/// are not exactly `0` to `N - 1`.
+///
+/// # Examples
+///
+/// ```
+/// use vesta_syntax::schema::{generate, Schema, SchemaCase};
+///
+/// let schema = Schema {
+///     name: "Shape".to_string(),
+///     visibility: "pub".to_string(),
+///     cases: vec![
+///         SchemaCase {
+///             name: "Circle".to_string(),
+///             tag: 1,
+///             payload: vec!["f64".to_string()],
+///         },
+///         SchemaCase {
+///             name: "Point".to_string(),
+///             tag: 0,
+///             payload: vec![],
+///         },
+///     ],
+/// };
+///
+/// let code = generate(&schema).unwrap().to_string();
+/// assert!(code.starts_with("pub enum Shape { Point , Circle (f64) , }"));
+/// assert!(code.contains("Case < 1usize > for Shape"));
+///
+/// let mut bad = schema.clone();
+/// bad.cases[0].tag = 2;
+/// assert!(generate(&bad).is_err());
+///
+/// // A schema with no cases gives an enum with no variants
+/// let empty = Schema {
+///     name: "Never".to_string(),
+///     visibility: "pub".to_string(),
+///     cases: vec![],
+/// };
+/// let code = generate(&empty).unwrap();
+/// assert!(code.to_string().starts_with("pub enum Never { }"));
+/// let _: syn::File = syn::parse2(code).unwrap();
+/// ```
+pub fn generate(schema: &Schema) -> Result<TokenStream, Error> {
+    let span = Span::call_site();
+    let ident: Ident = syn::parse_str(&schema.name)?;
+    let vis: Visibility = syn::parse_str(&schema.visibility)?;
+
+    // Put the cases in order of their tags, making sure every tag is used exactly once
+    let mut cases: Vec<&SchemaCase> = schema.cases.iter().collect();
+    cases.sort_by_key(|case| case.tag);
+    for (expected, case) in cases.iter().enumerate() {
+        if case.tag != expected {
+            return Err(Error::new(
+                span,
+                format!(
+                    "the tags of the cases of `{}` must be exactly 0 to {}, but case `{}` has tag {}",
+                    schema.name,
+                    cases.len().saturating_sub(1),
+                    case.name,
+                    case.tag
+                ),
+            ));
+        }
+    }
+
+    let variants = cases
+        .into_iter()
+        .map(|case| {
+            let payload = case
+                .payload
+                .iter()
+                .map(|ty| {
+                    Ok(syn::Field {
+                        attrs: Vec::new(),
+                        vis: Visibility::Inherited,
+                        ident: None,
+                        colon_token: None,
+                        ty: syn::parse_str::<Type>(ty)?,
+                    })
+                })
+                .collect::<Result<Punctuated<_, _>, Error>>()?;
+            let fields = if payload.is_empty() {
+                Fields::Unit
+            } else {
+                Fields::Unnamed(FieldsUnnamed {
+                    paren_token: token::Paren(span),
+                    unnamed: payload,
+                })
+            };
+            Ok(Variant {
+                attrs: Vec::new(),
+                ident: syn::parse_str(&case.name)?,
+                fields,
+                discriminant: None,
+            })
+        })
+        .collect::<Result<Punctuated<_, _>, Error>>()?;
+
+    let data = DataEnum {
+        enum_token: Default::default(),
+        brace_token: Default::default(),
+        variants,
+    };
+    let definition_variants = data.variants.iter();
+    let usage = register_usage(&ident, data.variants.iter().map(|variant| &variant.ident));
+    let impls = derive_match_enum(
+        &NonExhaustive::Never,
+        ident.clone(),
+        Generics::default(),
+        data.clone(),
+        &ImplOptions::default(),
+    );
+    Ok(quote! {
+        #vis enum #ident { #(#definition_variants,)* }
+        #impls
+        #usage
+    })
+}
@@ -0,0 +1,133 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+use std::collections::BTreeSet;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute, Expr, Ident, ItemMod, LitInt, Token, Type,
+};
+
+use vesta_syntax::{vesta_path, CaseArm, CaseInput};
+
+/// The arguments to `assert_case!`: a value, and the tag it should have.
+pub struct AssertCaseInput {
+    pub value: Expr,
+    pub tag: LitInt,
+}
+
+impl Parse for AssertCaseInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let value = input.parse()?;
+        let _: Token![,] = input.parse()?;
+        let tag = input.parse()?;
+        let _: Option<Token![,]> = input.parse()?;
+        Ok(AssertCaseInput { value, tag })
+    }
+}
+
+/// The input to `case_table!`, as far as coverage is concerned: an optional `ref`, followed by the
+/// arms of a `case!`.
+struct TableArms(Vec<CaseArm>);
+
+impl Parse for TableArms {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let _: Option<Token![ref]> = input.parse()?;
+        Ok(TableArms(input.call(CaseArm::parse_all)?))
+    }
+}
+
+/// Collect the tags with a dedicated arm in any `case!` or `case_table!`, or asserted by any
+/// `assert_case!`, anywhere in the tokens, including within other macro invocations.
+///
+/// Invocations which don't parse are skipped, since the macros themselves will report them.
+pub fn tested_tags(tokens: TokenStream, tags: &mut BTreeSet<usize>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        if let TokenTree::Group(group) = token {
+            // A macro invocation is a name, followed by `!`, followed by its arguments
+            if let (Some(TokenTree::Ident(name)), Some(TokenTree::Punct(bang))) = (
+                i.checked_sub(2).map(|j| &tokens[j]),
+                i.checked_sub(1).map(|j| &tokens[j]),
+            ) {
+                if bang.as_char() == '!' {
+                    invocation_tags(name, group.stream(), tags);
+                }
+            }
+            tested_tags(group.stream(), tags);
+        }
+    }
+}
+
+/// Collect the tags tested by a single macro invocation, if it is one of the macros we know.
+fn invocation_tags(name: &Ident, arguments: TokenStream, tags: &mut BTreeSet<usize>) {
+    let arms = if name == "case" {
+        syn::parse2::<CaseInput>(arguments).map(|input| input.arms)
+    } else if name == "case_table" {
+        syn::parse2::<TableArms>(arguments).map(|TableArms(arms)| arms)
+    } else if name == "assert_case" {
+        if let Ok(AssertCaseInput { tag, .. }) = syn::parse2(arguments) {
+            tags.extend(tag.base10_parse::<usize>());
+        }
+        return;
+    } else {
+        return;
+    };
+    tags.extend(arms.into_iter().flatten().filter_map(|arm| arm.tag));
+}
+
+/// The arguments to `#[all_cases_tested(...)]`: the types whose cases must all be tested.
+pub struct AllCasesTestedArgs {
+    pub types: Punctuated<Type, Token![,]>,
+}
+
+impl Parse for AllCasesTestedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(AllCasesTestedArgs {
+            types: input.parse_terminated(Type::parse)?,
+        })
+    }
+}
+
+/// Generate compile-time checks that every case of each type has a tag in `tags`, which were
+/// collected from the module `module`.
+pub fn coverage_checks(types: &[Type], module: &ItemMod, tags: &BTreeSet<usize>) -> TokenStream {
+    let vesta_path = vesta_path();
+    let module_ident = &module.ident;
+
+    // The checks only exist when the module itself does
+    let cfgs: Vec<&Attribute> = module
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg"))
+        .collect();
+
+    // Any tag up to the one after the largest tested tag might be missing; if there are no cases
+    // beyond those, the checks for the missing ones are vacuous
+    let limit = tags.iter().next_back().map_or(0, |max| max + 1);
+    let untested: Vec<usize> = (0..=limit).filter(|tag| !tags.contains(tag)).collect();
+
+    let checks = types.iter().map(|ty| {
+        let num_cases = quote!(<#ty as #vesta_path::MatchMeta>::CASE_NAMES.len());
+        let ty_name = quote!(#ty).to_string();
+        let missing = untested.iter().map(|tag| {
+            let message = format!(
+                "case {} of `{}` is never matched by an arm in `{}`",
+                tag, ty_name, module_ident
+            );
+            quote_spanned! {ty.span()=>
+                #(#cfgs)*
+                #[allow(unused_qualifications)]
+                const _: () = ::std::assert!(#num_cases <= #tag, #message);
+            }
+        });
+        quote_spanned! {ty.span()=>
+            #(#cfgs)*
+            #[allow(unused_qualifications)]
+            const _: fn(&#ty) = #vesta_path::assert_exhaustive::<#ty, { #num_cases }>;
+            #(#missing)*
+        }
+    });
+
+    quote!(#(#checks)*)
+}
@@ -0,0 +1,337 @@
+//! This crate defines the [`case_test`](macro@case_test) attribute macro, which generates one test
+//! for each case of a type matched with the [Vesta](https://crates.io/crates/vesta) crate, and the
+//! [`all_cases_tested`](macro@all_cases_tested) attribute macro, which checks that a test module
+//! has an arm for every case of a type.
+//!
+//! Add this crate as a dev-dependency alongside `vesta` to use it.
+
+#![warn(missing_docs)]
+#![warn(missing_copy_implementations, missing_debug_implementations)]
+#![warn(unused_qualifications, unused_results)]
+#![warn(future_incompatible)]
+#![warn(unused)]
+// Documentation configuration
+#![forbid(broken_intra_doc_links)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote, ToTokens};
+use std::collections::BTreeSet;
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Error, Ident, ItemFn, ItemMod, Token, Type,
+};
+
+use vesta_syntax::vesta_path;
+
+mod coverage;
+use coverage::{coverage_checks, tested_tags, AllCasesTestedArgs, AssertCaseInput};
+
+/// The arguments to `#[case_test(...)]`: the type, the names of its cases, and an optional
+/// `arbitrary` flag.
+struct CaseTestArgs {
+    ty: Type,
+    names: Punctuated<Ident, Token![,]>,
+    arbitrary: bool,
+}
+
+impl Parse for CaseTestArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        let content;
+        let _ = braced!(content in input);
+        let names = content.parse_terminated(Ident::parse)?;
+        let mut arbitrary = false;
+        if input.peek(Token![,]) {
+            let _: Token![,] = input.parse()?;
+            if !input.is_empty() {
+                let flag: Ident = input.parse()?;
+                if flag != "arbitrary" {
+                    return Err(Error::new(flag.span(), "expected `arbitrary`"));
+                }
+                arbitrary = true;
+            }
+        }
+        Ok(CaseTestArgs {
+            ty,
+            names,
+            arbitrary,
+        })
+    }
+}
+
+/// Generate one test for each case of a type, each of which calls the annotated function with a
+/// representative value of that case.
+///
+/// The attribute is written `#[case_test(Type { Name0, Name1, ... })]`, listing a name for each
+/// case of `Type` in the order of their tags. The type must be exhaustive, and the number of names
+/// must match its number of cases, or compilation fails. For each case `N` with name `Name`, a test
+/// called `{function}_{name}` is generated, which constructs a value of case `N` by passing the
+/// [`Default`] value of its payload to `Case::<N>::uncase`, then calls the annotated function with
+/// it. This way, each case shows up by name in the test report.
+///
+/// If the payloads don't implement [`Default`], write `#[case_test(Type { ... }, arbitrary)]` to
+/// construct them using [`arbitrary`](https://docs.rs/arbitrary) instead, from a fixed buffer of
+/// zeros. This requires a dependency on `arbitrary` in the crate using the attribute.
+///
+/// The annotated function itself is left as it is, and must take a single argument of type `Type`.
+///
+/// # Examples
+///
+/// ```
+/// use vesta::Match;
+/// use vesta_test_macros::case_test;
+///
+/// #[derive(Match)]
+/// enum Message {
+///     Ping,
+///     Data(Vec<u8>),
+///     Close { code: u16 },
+/// }
+///
+/// fn handle(message: Message) -> usize {
+///     match message {
+///         Message::Ping => 0,
+///         Message::Data(bytes) => bytes.len(),
+///         Message::Close { code } => code as usize,
+///     }
+/// }
+///
+/// // Generates the tests `handles_ping`, `handles_data`, and `handles_close`
+/// #[case_test(Message { Ping, Data, Close })]
+/// fn handles(message: Message) {
+///     assert_eq!(handle(message), 0);
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::Match;
+/// # use vesta_test_macros::case_test;
+/// # #[derive(Match)]
+/// # enum Message {
+/// #     Ping,
+/// #     Data(Vec<u8>),
+/// #     Close { code: u16 },
+/// # }
+/// // The `Close` case has no name, so it would have no test
+/// #[case_test(Message { Ping, Data })]
+/// fn handles(message: Message) {}
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn case_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let CaseTestArgs {
+        ty,
+        names,
+        arbitrary,
+    } = parse_macro_input!(attr as CaseTestArgs);
+    let function = parse_macro_input!(item as ItemFn);
+    let vesta_path = vesta_path();
+    let function_ident = &function.sig.ident;
+    let num_cases = names.len();
+
+    let tests = names.iter().enumerate().map(|(n, name)| {
+        let test_ident = format_ident!(
+            "{}_{}",
+            function_ident,
+            snake_case(&name.to_string()),
+            span = name.span()
+        );
+        let payload = if arbitrary {
+            quote! {
+                ::arbitrary::Arbitrary::arbitrary(
+                    &mut ::arbitrary::Unstructured::new(&[0; 4096]),
+                )
+                .expect("could not construct an arbitrary payload")
+            }
+        } else {
+            quote!(::std::default::Default::default())
+        };
+        quote! {
+            #[test]
+            #[allow(unused_qualifications)]
+            fn #test_ident() {
+                #function_ident(<#ty as #vesta_path::Case<#n>>::uncase(#payload))
+            }
+        }
+    });
+
+    // Check that there is exactly one name for each case of the type
+    let count_check = quote! {
+        #[allow(unused_qualifications)]
+        const _: fn(&#ty) = #vesta_path::assert_exhaustive::<#ty, #num_cases>;
+    };
+
+    TokenStream::from(quote! {
+        #function
+        #count_check
+        #(#tests)*
+    })
+}
+
+/// Assert that a value has the case with the given tag, as determined by its
+/// [`tag`](https://docs.rs/vesta/latest/vesta/trait.Match.html#tymethod.tag).
+///
+/// The value is only borrowed, and the type of the value must have a case with the tag, or
+/// compilation fails. Along with the arms of `case!` and `case_table!`, the tags asserted count
+/// towards the cases tested by a module with [`#[all_cases_tested(...)]`](macro@all_cases_tested).
+///
+/// # Panics
+///
+/// If the value doesn't have the case with the given tag.
+///
+/// # Examples
+///
+/// ```
+/// use vesta_test_macros::assert_case;
+///
+/// let result: Result<u8, String> = Err("oops".into());
+/// assert_case!(result, 1);
+/// ```
+///
+/// ```should_panic
+/// # use vesta_test_macros::assert_case;
+/// assert_case!(Some(1), 0);
+/// ```
+#[proc_macro]
+pub fn assert_case(input: TokenStream) -> TokenStream {
+    let AssertCaseInput { value, tag } = parse_macro_input!(input as AssertCaseInput);
+    let vesta_path = vesta_path();
+    let value_string = value.to_token_stream().to_string();
+    let value_ident = Ident::new("value", Span::mixed_site());
+    TokenStream::from(quote! {
+        #[allow(unused_qualifications)]
+        {
+            let #value_ident = &#value;
+            #vesta_path::assert_case_ref::<_, #tag>(#value_ident);
+            ::std::assert!(
+                #vesta_path::Match::tag(#value_ident) == ::std::option::Option::Some(#tag),
+                "expected `{}` to have case {}, but its tag is {:?}",
+                #value_string,
+                #tag,
+                #vesta_path::Match::tag(#value_ident),
+            );
+        }
+    })
+}
+
+/// Check at compile time that an inline test module has a dedicated arm for every case of each of
+/// the given types.
+///
+/// The attribute is written `#[all_cases_tested(Type, ...)]` on a module. It collects the tags of
+/// the arms of every `case!` and `case_table!` in the module, along with the tags asserted by
+/// every [`assert_case!`], and fails compilation if any case of each type is missing from them. A
+/// `_` arm doesn't count, since it doesn't exercise any case in particular. The types must be
+/// exhaustive, and must implement
+/// [`MatchMeta`](https://docs.rs/vesta/latest/vesta/trait.MatchMeta.html), as the
+/// [`Match`](https://docs.rs/vesta/latest/vesta/derive.Match.html) derive does.
+///
+/// The types matched by each `case!` are inferred, so they can't be known when the attribute runs.
+/// Instead, the tags from every invocation in the module count towards every type given, so it's
+/// best to keep the tests for each type in their own module. (In the examples below, the functions
+/// would be marked `#[test]`.)
+///
+/// # Examples
+///
+/// ```
+/// use vesta::{case, Match};
+/// use vesta_test_macros::{all_cases_tested, assert_case};
+///
+/// #[derive(Match)]
+/// pub enum Message {
+///     Ping,
+///     Data(Vec<u8>),
+///     Close { code: u16 },
+/// }
+///
+/// #[all_cases_tested(Message)]
+/// mod message_tests {
+///     use super::*;
+///
+///     fn sizes() {
+///         let size = |message| case!(message {
+///             0 => 0,
+///             1(data) => Vec::len(&data),
+///             _ => 0,
+///         });
+///         assert_eq!(size(Message::Data(vec![1])), 1);
+///     }
+///
+///     fn closes() {
+///         assert_case!(Message::Close { code: 0 }, 2);
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// # use vesta::{case, Match};
+/// # use vesta_test_macros::all_cases_tested;
+/// # #[derive(Match)]
+/// # pub enum Message {
+/// #     Ping,
+/// #     Data(Vec<u8>),
+/// #     Close { code: u16 },
+/// # }
+/// // Error: case 2 of `Message` is never matched by an arm in `message_tests`
+/// #[all_cases_tested(Message)]
+/// mod message_tests {
+///     use super::*;
+///
+///     fn pings() {
+///         assert!(case!(Message::Ping {
+///             0 => true,
+///             1(_) => false,
+///             _ => false,
+///         }));
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn all_cases_tested(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let AllCasesTestedArgs { types } = parse_macro_input!(attr as AllCasesTestedArgs);
+    let module = parse_macro_input!(item as ItemMod);
+    let mut tags = BTreeSet::new();
+    match &module.content {
+        Some((_, items)) => {
+            for item in items {
+                tested_tags(item.to_token_stream(), &mut tags);
+            }
+        }
+        None => {
+            return Error::new(
+                module.ident.span(),
+                "cases can only be checked in an inline module, as in `mod tests { ... }`",
+            )
+            .to_compile_error()
+            .into()
+        }
+    }
+    let types: Vec<Type> = types.into_iter().collect();
+    let checks = coverage_checks(&types, &module, &tags);
+    TokenStream::from(quote! {
+        #module
+        #checks
+    })
+}
+
+/// Convert a `CamelCase` name into `snake_case`, for use in the name of a test.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -0,0 +1,45 @@
+use vesta::{Match, MatchMeta};
+use vesta_test_macros::case_test;
+
+#[derive(Match)]
+enum Message {
+    Ping,
+    Data(Vec<u8>),
+    Close { code: u16 },
+}
+
+fn handle(message: Message) -> usize {
+    match message {
+        Message::Ping => 0,
+        Message::Data(bytes) => bytes.len(),
+        Message::Close { code } => code as usize,
+    }
+}
+
+// Run by `cargo test` as `handles_ping`, `handles_data`, and `handles_close`
+#[case_test(Message { Ping, Data, Close })]
+fn handles(message: Message) {
+    assert_eq!(handle(message), 0);
+}
+
+// Each case is constructed from the default value of its payload
+#[case_test(Message { Ping, Data, Close })]
+fn constructs(message: Message) {
+    match message {
+        Message::Ping => {}
+        Message::Data(bytes) => assert!(bytes.is_empty()),
+        Message::Close { code } => assert_eq!(code, 0),
+    }
+}
+
+#[case_test(Option<u8> { None, Some })]
+fn foreign(option: Option<u8>) {
+    assert_eq!(option.unwrap_or_default(), 0);
+}
+
+#[test]
+fn one_test_per_case() {
+    let tests: [fn(); 3] = [handles_ping, handles_data, handles_close];
+    assert_eq!(tests.len(), Message::CASE_NAMES.len());
+    let _: [fn(); 2] = [foreign_none, foreign_some];
+}
@@ -0,0 +1,147 @@
+//! Benchmarks comparing matching on types with a niche, such as `Option<&T>` and
+//! `Option<NonZeroU32>`, using `case!` and `try_case` against a hand-written `match`.
+//!
+//! All three approaches should perform identically, because the generated `try_case` and `tag`
+//! compile down to the same single comparison as the `match`. Likewise, matching on an option of an
+//! enum in one level with `FlatOption` should perform identically to nested `match`es, which check
+//! the niche of the option in the enum's discriminant.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::num::NonZeroU32;
+use vesta::{case, option::FlatOption, CaseExt, Match};
+
+/// An enum with a niche in its discriminant, for `Option<Step>` to use.
+#[derive(Match, Clone, Copy)]
+#[vesta(flat_option)]
+enum Step {
+    Forward(u32),
+    Back(u32),
+}
+
+/// A mix of present and absent values, so that neither branch is trivially predicted.
+fn inputs<T>(some: impl Fn(u32) -> T, none: T) -> Vec<T>
+where
+    T: Clone,
+{
+    (1..=1024)
+        .map(|i| if i % 3 == 0 { none.clone() } else { some(i) })
+        .collect()
+}
+
+fn option_ref(c: &mut Criterion) {
+    let values: Vec<u32> = (1..=1024).collect();
+    let inputs = inputs(|i| Some(&values[i as usize - 1]), None);
+    let mut group = c.benchmark_group("Option<&u32>");
+
+    group.bench_function("match", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| match x {
+                Some(x) => sum.wrapping_add(*x),
+                None => sum,
+            })
+        })
+    });
+
+    group.bench_function("case!", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| {
+                case!(x {
+                    1(x) => sum.wrapping_add(*x),
+                    0 => sum,
+                })
+            })
+        })
+    });
+
+    group.bench_function("try_case", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .iter()
+                .fold(0u32, |sum, &x| match x.try_case::<1>() {
+                    Ok(x) => sum.wrapping_add(*x),
+                    Err(_) => sum,
+                })
+        })
+    });
+
+    group.finish();
+}
+
+fn option_non_zero(c: &mut Criterion) {
+    let inputs = inputs(NonZeroU32::new, None);
+    let mut group = c.benchmark_group("Option<NonZeroU32>");
+
+    group.bench_function("match", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| match x {
+                Some(x) => sum.wrapping_add(x.get()),
+                None => sum,
+            })
+        })
+    });
+
+    group.bench_function("case!", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| {
+                case!(x {
+                    1(x) => sum.wrapping_add(x.get()),
+                    0 => sum,
+                })
+            })
+        })
+    });
+
+    group.bench_function("try_case", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .iter()
+                .fold(0u32, |sum, &x| match x.try_case::<1>() {
+                    Ok(x) => sum.wrapping_add(x.get()),
+                    Err(_) => sum,
+                })
+        })
+    });
+
+    group.finish();
+}
+
+fn option_flat(c: &mut Criterion) {
+    let inputs = inputs(
+        |i| {
+            Some(if i % 2 == 0 {
+                Step::Forward(i)
+            } else {
+                Step::Back(i)
+            })
+        },
+        None,
+    );
+    let mut group = c.benchmark_group("Option<Step>");
+
+    group.bench_function("match", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| match x {
+                Some(Step::Forward(x)) => sum.wrapping_add(x),
+                Some(Step::Back(x)) => sum.wrapping_sub(x),
+                None => sum,
+            })
+        })
+    });
+
+    group.bench_function("case!", |b| {
+        b.iter(|| {
+            black_box(&inputs).iter().fold(0u32, |sum, &x| {
+                case!(FlatOption(x) {
+                    1(x) => sum.wrapping_add(x),
+                    2(x) => sum.wrapping_sub(x),
+                    0 => sum,
+                })
+            })
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, option_ref, option_non_zero, option_flat);
+criterion_main!(benches);
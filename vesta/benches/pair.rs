@@ -0,0 +1,100 @@
+//! Benchmarks comparing double dispatch on a pair of values using `case_pair!`, which matches once
+//! on their packed tags, against nested uses of `case!`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use vesta::{case, case_pair, Match};
+
+#[derive(Match, Clone, Copy)]
+enum Shape {
+    Point,
+    Circle(u32),
+    Square(u32),
+    Rect(u32, u32),
+}
+
+/// A deterministic mix of all the pairs of shapes.
+fn inputs() -> Vec<(Shape, Shape)> {
+    let shapes = [
+        Shape::Point,
+        Shape::Circle(3),
+        Shape::Square(4),
+        Shape::Rect(2, 5),
+    ];
+    (0..1024u32)
+        .map(|i| {
+            let i = i.wrapping_mul(2_654_435_761);
+            (
+                shapes[(i >> 7) as usize % 4],
+                shapes[(i >> 13) as usize % 4],
+            )
+        })
+        .collect()
+}
+
+fn nested(a: Shape, b: Shape) -> u32 {
+    case!(a {
+        0 => 0,
+        1(r) => case!(b {
+            0 => r,
+            1(s) => r + s,
+            2(s) => r * s,
+            3(w, h) => r + w * h,
+        }),
+        2(s) => case!(b {
+            0 => s,
+            1(r) => s * r,
+            2(t) => s + t,
+            3(w, h) => s * w + h,
+        }),
+        3(w, h) => case!(b {
+            0 => w,
+            1(r) => h + r,
+            2(s) => w * s,
+            3(x, y) => w * x + h * y,
+        }),
+    })
+}
+
+fn packed(a: Shape, b: Shape) -> u32 {
+    case_pair!((a, b) {
+        (0, _) => 0,
+        (1(r), 0) => r,
+        (1(r), 1(s)) => r + s,
+        (1(r), 2(s)) => r * s,
+        (1(r), 3(w, h)) => r + w * h,
+        (2(s), 0) => s,
+        (2(s), 1(r)) => s * r,
+        (2(s), 2(t)) => s + t,
+        (2(s), 3(w, h)) => s * w + h,
+        (3(w, _), 0) => w,
+        (3(_, h), 1(r)) => h + r,
+        (3(w, _), 2(s)) => w * s,
+        (3(w, h), 3(x, y)) => w * x + h * y,
+    })
+}
+
+fn double_dispatch(c: &mut Criterion) {
+    let inputs = inputs();
+    let mut group = c.benchmark_group("double dispatch");
+
+    group.bench_function("nested case!", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .iter()
+                .fold(0u32, |sum, &(x, y)| sum.wrapping_add(nested(x, y)))
+        })
+    });
+
+    group.bench_function("case_pair!", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .iter()
+                .fold(0u32, |sum, &(x, y)| sum.wrapping_add(packed(x, y)))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, double_dispatch);
+criterion_main!(benches);
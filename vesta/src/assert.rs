@@ -0,0 +1,250 @@
+//! Asserting that two values are in the same case with equal payloads, with failure messages which
+//! name the cases involved and show how the payloads differ.
+//!
+//! [`assert_case_eq!`] is like `assert_eq!`, but it only needs each payload to implement
+//! [`PartialEq`] and [`Debug`], rather than the whole type. When the values are in different cases,
+//! it says which cases they are; when they are in the same case, it shows a line-by-line diff of
+//! their payloads. It works for any type implementing [`CaseEq`], which the
+//! [`Match`](macro@crate::Match) derive implements when given `#[vesta(case_eq)]`.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{assert_case_eq, Match};
+//!
+//! #[derive(Match)]
+//! #[vesta(case_eq)]
+//! enum Shape {
+//!     Point,
+//!     Circle { radius: f64 },
+//!     Polygon(Vec<(i32, i32)>),
+//! }
+//!
+//! assert_case_eq!(Shape::Circle { radius: 1.0 }, Shape::Circle { radius: 1.0 });
+//! ```
+//!
+#![cfg_attr(feature = "macros", doc = "```should_panic")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! # use vesta::{assert_case_eq, Match};
+//! # #[derive(Match)]
+//! # #[vesta(case_eq)]
+//! # enum Shape {
+//! #     Point,
+//! #     Circle { radius: f64 },
+//! #     Polygon(Vec<(i32, i32)>),
+//! # }
+//! // Panics with:
+//! //
+//! // assertion `left == right` failed: the payloads of case `Polygon(2)` of `Shape` differ
+//! //   [
+//! //       (
+//! //           0,
+//! //           0,
+//! //       ),
+//! //       (
+//! // -         1,
+//! // +         2,
+//! //           0,
+//! //       ),
+//! //   ]
+//! assert_case_eq!(
+//!     Shape::Polygon(vec![(0, 0), (1, 0)]),
+//!     Shape::Polygon(vec![(0, 0), (2, 0)]),
+//! );
+//! ```
+//!
+//! [`assert_case_eq!`]: crate::assert_case_eq
+
+use std::fmt::{self, Debug, Write};
+
+use crate::{DisplayCase, MatchMeta};
+
+/// A type whose values can be compared case by case, as described in the [module
+/// documentation](self). This is implemented by the [`Match`](macro@crate::Match) derive when given
+/// `#[vesta(case_eq)]`, for as long as the payloads of every case implement [`PartialEq`] and
+/// [`Debug`].
+pub trait CaseEq: MatchMeta {
+    /// Compare this value to another, case by case.
+    fn compare_cases(&self, other: &Self) -> CaseComparison;
+}
+
+/// The result of comparing two values case by case, using [`CaseEq`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CaseComparison {
+    /// The values are in the same case, with equal payloads.
+    Equal,
+    /// The values are in different cases, or at least one of them is in an unknown case.
+    DifferentCases,
+    /// The values are in the same case, with different payloads, formatted here using `{:#?}`.
+    DifferentPayloads {
+        /// The payload of the first value.
+        left: String,
+        /// The payload of the second value.
+        right: String,
+    },
+}
+
+/// Compare the payloads of two values in the same case, for implementing [`CaseEq`].
+pub fn compare_payloads<P: PartialEq + Debug>(left: P, right: P) -> CaseComparison {
+    if left == right {
+        CaseComparison::Equal
+    } else {
+        CaseComparison::DifferentPayloads {
+            left: format!("{:#?}", left),
+            right: format!("{:#?}", right),
+        }
+    }
+}
+
+impl<T: PartialEq + Debug> CaseEq for Option<T> {
+    fn compare_cases(&self, other: &Self) -> CaseComparison {
+        match (self, other) {
+            (None, None) => CaseComparison::Equal,
+            (Some(left), Some(right)) => compare_payloads(left, right),
+            _ => CaseComparison::DifferentCases,
+        }
+    }
+}
+
+impl<T: PartialEq + Debug, E: PartialEq + Debug> CaseEq for Result<T, E> {
+    fn compare_cases(&self, other: &Self) -> CaseComparison {
+        match (self, other) {
+            (Ok(left), Ok(right)) => compare_payloads(left, right),
+            (Err(left), Err(right)) => compare_payloads(left, right),
+            _ => CaseComparison::DifferentCases,
+        }
+    }
+}
+
+/// Assert that two values are in the same case, with equal payloads, as described in the [module
+/// documentation](crate::assert).
+///
+/// Like `assert_eq!`, this takes its arguments by reference, and may be given a message to add to
+/// the panic message, with arguments formatted as by `format!`.
+///
+/// # Panics
+///
+/// If the values are in different cases, or in the same case with different payloads, or if either
+/// is in an unknown case.
+///
+/// # Examples
+///
+/// ```should_panic
+/// use vesta::assert_case_eq;
+///
+/// let expected: Result<u8, String> = Ok(1);
+/// // Panics with:
+/// //
+/// // assertion `left == right` failed: response 7
+/// //   left: Ok(0)
+/// //  right: Err(1)
+/// assert_case_eq!(expected, Err("oops".to_string()), "response {}", 7);
+/// ```
+#[macro_export]
+macro_rules! assert_case_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if let ::std::result::Result::Err(message) =
+                    $crate::assert::check_case_eq(left, right, ::std::option::Option::None)
+                {
+                    ::std::panic!("{}", message);
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if let ::std::result::Result::Err(message) = $crate::assert::check_case_eq(
+                    left,
+                    right,
+                    ::std::option::Option::Some(::std::format_args!($($arg)+)),
+                ) {
+                    ::std::panic!("{}", message);
+                }
+            }
+        }
+    };
+}
+
+/// Compare two values case by case, returning the panic message of [`assert_case_eq!`] if they
+/// differ.
+///
+/// [`assert_case_eq!`]: crate::assert_case_eq
+#[doc(hidden)]
+pub fn check_case_eq<T: CaseEq + ?Sized>(
+    left: &T,
+    right: &T,
+    message: Option<fmt::Arguments>,
+) -> Result<(), String> {
+    let comparison = left.compare_cases(right);
+    if comparison == CaseComparison::Equal {
+        return Ok(());
+    }
+
+    let mut output = String::from("assertion `left == right` failed");
+    let _ = match message {
+        Some(message) => write!(output, ": {}", message),
+        None => Ok(()),
+    };
+    let _ = match comparison {
+        CaseComparison::Equal => Ok(()),
+        CaseComparison::DifferentCases => write!(
+            output,
+            "\n  left: {}\n right: {}",
+            DisplayCase(left),
+            DisplayCase(right)
+        ),
+        CaseComparison::DifferentPayloads {
+            left: left_payload,
+            right: right_payload,
+        } => {
+            let _ = write!(
+                output,
+                "{}the payloads of case `{}` of `{}` differ",
+                if message.is_some() { "\n" } else { ": " },
+                DisplayCase(left),
+                T::NAME
+            );
+            write_diff(&mut output, &left_payload, &right_payload)
+        }
+    };
+    Err(output)
+}
+
+/// Write a line-by-line diff of two strings, marking lines only in the first with `-`, lines only
+/// in the second with `+`, and lines in both with a space.
+fn write_diff(output: &mut String, left: &str, right: &str) -> fmt::Result {
+    let left: Vec<&str> = left.lines().collect();
+    let right: Vec<&str> = right.lines().collect();
+
+    // The length of the longest common subsequence of each pair of suffixes of the lines
+    let mut common = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            common[i][j] = if left[i] == right[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            write!(output, "\n  {}", left[i])?;
+            i += 1;
+            j += 1;
+        } else if j == right.len() || (i < left.len() && common[i + 1][j] >= common[i][j + 1]) {
+            write!(output, "\n- {}", left[i])?;
+            i += 1;
+        } else {
+            write!(output, "\n+ {}", right[j])?;
+            j += 1;
+        }
+    }
+    Ok(())
+}
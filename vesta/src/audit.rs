@@ -0,0 +1,234 @@
+//! An audit of every `unsafe` extraction of a case by the code which the macros generate, for
+//! confirming that none of them would ever extract a case from a value with a different tag.
+//!
+//! This module is only available when the `audit-unsafe` feature is enabled. With it, wherever the
+//! code generated by [`case!`], [`case_pair!`](crate::case_pair), or the options of
+//! [`#[derive(Match)]`](macro@crate::Match) extracts a case from a value using the unchecked
+//! [`Case::case`](crate::Case::case) or [`CaseRef::case_ref`](crate::CaseRef::case_ref), it first
+//! checks the tag of the value again, and counts the check. Every such site is registered in
+//! [`UNSAFE_SITES`], which is gathered at link time so that it covers the whole program, including
+//! its dependencies.
+//!
+//! A site whose check fails would be undefined behavior without the audit, so the failure is
+//! recorded in the audit log and then the program panics rather than extracting the case. Running
+//! an integration suite with the feature enabled and finding that [`report`] has no failures, and
+//! that every site has run, is evidence that the unchecked extractions are safe to trust in release
+//! builds.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta::{audit, case};
+//!
+//! let sum = |value: Option<u32>| case!(value { 0 => 0, 1(n) => n });
+//! assert_eq!(sum(Some(3)), 3);
+//!
+//! let report = audit::report();
+//! assert!(report.failures.is_empty());
+//! assert!(report.checks() >= 1);
+//! ```
+//!
+//! A hand-written implementation of [`Match`] whose tag changes from one call to the next, which
+//! [`Match`] forbids, is caught before a case is extracted using a tag which no longer holds:
+//!
+//! ```
+//! use std::{cell::Cell, panic};
+//! use vesta::{audit, case, Case, Exhaustive, Match};
+//!
+//! struct Flaky(Cell<usize>);
+//!
+//! unsafe impl Match for Flaky {
+//!     type Range = Exhaustive<2>;
+//!
+//!     // Wrong: the tag of a value must not change unless the value does
+//!     fn tag(&self) -> Option<usize> {
+//!         let tag = self.0.get();
+//!         self.0.set(1 - tag);
+//!         Some(tag)
+//!     }
+//! }
+//!
+//! impl Case<0> for Flaky {
+//!     type Case = ();
+//!     unsafe fn case(_: Self) {}
+//!     fn uncase(_: ()) -> Self {
+//!         Flaky(Cell::new(0))
+//!     }
+//! }
+//!
+//! impl Case<1> for Flaky {
+//!     type Case = ();
+//!     unsafe fn case(_: Self) {}
+//!     fn uncase(_: ()) -> Self {
+//!         Flaky(Cell::new(1))
+//!     }
+//! }
+//!
+//! let result = panic::catch_unwind(|| case!(Flaky(Cell::new(0)) { 0 => "zero", 1 => "one" }));
+//! assert!(result.is_err());
+//!
+//! let report = audit::report();
+//! assert_eq!(report.failures.len(), 1);
+//! assert_eq!(report.failures[0].site.tag, 0);
+//! assert_eq!(report.failures[0].found, Some(1));
+//! ```
+//!
+//! [`case!`]: crate::case
+
+use std::{
+    any, fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::Match;
+
+/// Re-exported so that the code generated by the macros can refer to it.
+#[doc(hidden)]
+pub use linkme;
+
+/// All the sites in the program which extract a case without checking its tag.
+#[linkme::distributed_slice]
+#[linkme(crate = linkme)]
+pub static UNSAFE_SITES: [Site];
+
+/// Every failed check, in the order in which they happened.
+static FAILURES: Mutex<Vec<Failure>> = Mutex::new(Vec::new());
+
+/// A site in the program which extracts a case without checking its tag, as registered in
+/// [`UNSAFE_SITES`].
+#[derive(Debug)]
+pub struct Site {
+    /// The file containing the site.
+    pub file: &'static str,
+    /// The line of the site.
+    pub line: u32,
+    /// The column of the site.
+    pub column: u32,
+    /// The tag of the case which the site extracts.
+    pub tag: usize,
+    checks: AtomicU64,
+}
+
+impl Site {
+    /// Register a site, as done by the generated code.
+    #[doc(hidden)]
+    pub const fn new(file: &'static str, line: u32, column: u32, tag: usize) -> Site {
+        Site {
+            file,
+            line,
+            column,
+            tag,
+            checks: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of times the tag has been checked at this site, which is the number of times the
+    /// site has run.
+    pub fn checks(&self) -> u64 {
+        self.checks.load(Ordering::Relaxed)
+    }
+
+    /// Check that the value has the tag of the case about to be extracted from it, as done by the
+    /// generated code, recording a failure in the audit log and panicking if it does not.
+    #[doc(hidden)]
+    #[inline]
+    pub fn check<T: Match + ?Sized>(&'static self, value: &T) {
+        let _ = self.checks.fetch_add(1, Ordering::Relaxed);
+        let found = value.tag();
+        if found != Some(self.tag) {
+            let failure = Failure {
+                site: self,
+                type_name: any::type_name::<T>(),
+                found,
+            };
+            let message = failure.to_string();
+            FAILURES
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(failure);
+            panic!("{}", message);
+        }
+    }
+}
+
+/// A check which failed, because a case was about to be extracted from a value with another tag.
+#[derive(Debug, Clone, Copy)]
+pub struct Failure {
+    /// The site at which the check failed.
+    pub site: &'static Site,
+    /// The full name of the type of the value, as returned by [`std::any::type_name`].
+    pub type_name: &'static str,
+    /// The tag which the value actually had.
+    pub found: Option<usize>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "case {} extracted from a value of type `{}` with ",
+            self.site.tag, self.type_name
+        )?;
+        match self.found {
+            Some(tag) => write!(f, "tag {}", tag)?,
+            None => f.write_str("an unknown tag")?,
+        }
+        write!(
+            f,
+            " at {}:{}:{}",
+            self.site.file, self.site.line, self.site.column
+        )
+    }
+}
+
+/// The state of the audit at some moment, as returned by [`report`].
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Every site in the program which extracts a case without checking its tag.
+    pub sites: &'static [Site],
+    /// Every failed check so far, in the order in which they happened.
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// The total number of checks made so far, at every site.
+    pub fn checks(&self) -> u64 {
+        self.sites.iter().map(Site::checks).sum()
+    }
+
+    /// The sites which have never run, and so have never been checked.
+    pub fn unchecked(&self) -> impl Iterator<Item = &'static Site> {
+        self.sites.iter().filter(|site| site.checks() == 0)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} checks at {} of {} sites, {} failed",
+            self.checks(),
+            self.sites.len() - self.unchecked().count(),
+            self.sites.len(),
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            writeln!(f, "failed: {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Report the state of the audit: every audited site and every failed check so far.
+pub fn report() -> Report {
+    Report {
+        sites: &UNSAFE_SITES,
+        failures: FAILURES
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone(),
+    }
+}
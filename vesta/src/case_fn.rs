@@ -0,0 +1,140 @@
+//! Handling each case of a value with a different result type, by a handler which defines a method
+//! and an output type for every case.
+//!
+//! Every arm of a [`case!`] must have the same type, which is the type of the whole expression. A
+//! [`CaseFn`] instead handles the `N`th case of a value using its implementation of
+//! [`CaseFnAt<T, N>`](CaseFnAt), each of which has its own [`Output`](CaseFnAt::Output) type. These
+//! outputs can be used directly, wherever the case is known statically, or unified into a single
+//! [`Unified`](CaseFn::Unified) type by converting each of them [`Into`] it, as [`apply`] does
+//! when dispatching on the tag of a value.
+//!
+//! [`apply`] works for any type implementing [`Apply`], which is implemented here for [`Option`]
+//! and [`Result`], and which [`#[derive(Match)]`](macro@crate::Match) implements for exhaustive
+//! types given `#[vesta(apply)]`.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{
+//!     case_fn::{apply, CaseFn, CaseFnAt},
+//!     Match,
+//! };
+//!
+//! #[derive(Match)]
+//! #[vesta(apply)]
+//! enum Reading {
+//!     Temperature(f64),
+//!     Count(u32),
+//! }
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Summary {
+//!     Warm(bool),
+//!     Label(String),
+//! }
+//!
+//! impl From<bool> for Summary {
+//!     fn from(warm: bool) -> Self {
+//!         Summary::Warm(warm)
+//!     }
+//! }
+//!
+//! impl From<String> for Summary {
+//!     fn from(label: String) -> Self {
+//!         Summary::Label(label)
+//!     }
+//! }
+//!
+//! struct Summarize;
+//!
+//! impl CaseFn<Reading> for Summarize {
+//!     type Unified = Summary;
+//! }
+//!
+//! impl CaseFnAt<Reading, 0> for Summarize {
+//!     type Output = bool;
+//!
+//!     fn call(&mut self, celsius: f64) -> bool {
+//!         celsius > 20.0
+//!     }
+//! }
+//!
+//! impl CaseFnAt<Reading, 1> for Summarize {
+//!     type Output = String;
+//!
+//!     fn call(&mut self, count: u32) -> String {
+//!         format!("{} events", count)
+//!     }
+//! }
+//!
+//! // Where the case is known, its own output type is available
+//! let warm: bool = CaseFnAt::<Reading, 0>::call(&mut Summarize, 25.0);
+//! assert!(warm);
+//!
+//! // Otherwise, the outputs are unified
+//! assert_eq!(apply(Reading::Count(3), Summarize), Summary::Label("3 events".to_string()));
+//! ```
+//!
+//! [`case!`]: crate::case
+
+use crate::{Case, Match};
+
+/// A handler for every case of values of type `T`, with a different output type for each case, as
+/// described in the [module documentation](self). The handler for the `N`th case is its
+/// implementation of [`CaseFnAt<T, N>`](CaseFnAt).
+pub trait CaseFn<T> {
+    /// The type into which the output for every case is converted when the case is only known
+    /// at runtime.
+    type Unified;
+}
+
+/// The handler of the `N`th case of values of type `T`, as part of a [`CaseFn`].
+pub trait CaseFnAt<T: Case<N>, const N: usize>: CaseFn<T> {
+    /// The output of handling the `N`th case.
+    type Output: Into<Self::Unified>;
+
+    /// Handle the payload of the `N`th case.
+    fn call(&mut self, case: T::Case) -> Self::Output;
+}
+
+/// A type whose values can be handled by a [`CaseFn`] with a handler for each of its cases,
+/// dispatching on their tags. This is implemented by [`#[derive(Match)]`](macro@crate::Match) for
+/// exhaustive types given `#[vesta(apply)]`.
+pub trait Apply<F: CaseFn<Self>>: Match + Sized {
+    /// Handle the case of this value using `f`, converting the output into the unified type.
+    fn apply(self, f: F) -> F::Unified;
+}
+
+/// Handle the case of a value using `f`, converting the output for that case into the unified
+/// type, as described in the [module documentation](self).
+#[inline]
+pub fn apply<T: Apply<F>, F: CaseFn<T>>(value: T, f: F) -> F::Unified {
+    value.apply(f)
+}
+
+impl<T, F> Apply<F> for Option<T>
+where
+    F: CaseFn<Self> + CaseFnAt<Self, 0> + CaseFnAt<Self, 1>,
+{
+    #[inline]
+    fn apply(self, mut f: F) -> F::Unified {
+        match self {
+            None => CaseFnAt::<Self, 0>::call(&mut f, ()).into(),
+            Some(value) => CaseFnAt::<Self, 1>::call(&mut f, value).into(),
+        }
+    }
+}
+
+impl<T, E, F> Apply<F> for Result<T, E>
+where
+    F: CaseFn<Self> + CaseFnAt<Self, 0> + CaseFnAt<Self, 1>,
+{
+    #[inline]
+    fn apply(self, mut f: F) -> F::Unified {
+        match self {
+            Ok(value) => CaseFnAt::<Self, 0>::call(&mut f, value).into(),
+            Err(error) => CaseFnAt::<Self, 1>::call(&mut f, error).into(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Tools for testing when the payloads of cases are dropped.
+//!
+//! Extracting a case with [`Case::case`](crate::Case::case) moves the payload out of the matched
+//! value, so an incorrect implementation of [`Case`](crate::Case) or [`Match`](crate::Match) can
+//! drop a payload twice, or never drop it at all. A [`DropLog`] hands out [`Tracked`] values which
+//! record when they are dropped, so tests can check that every payload is dropped exactly once, and
+//! in the expected order.
+//!
+//! The guarantees which [`case!`](crate::case) itself makes about dropping are described in its
+//! documentation, under "Drop order". The examples there use this module, and are checked by
+//! running the doctests under [Miri](https://github.com/rust-lang/miri), which also catches any
+//! double drop or use after free that safe code could not observe:
+//!
+//! ```text
+//! cargo +nightly miri test --doc --all-features
+//! ```
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{case, drop_check::DropLog};
+//!
+//! let log = DropLog::new();
+//! let value = Some((log.tracked("left"), log.tracked("right")));
+//! case!(value {
+//!     1((left, _)) => {
+//!         drop(left);
+//!         log.assert_dropped(&["left"]);
+//!     }
+//!     0 => unreachable!(),
+//! });
+//! log.assert_dropped(&["left", "right"]);
+//! ```
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// A log of the [`Tracked`] values which have been dropped, in the order they were dropped.
+///
+/// Cloning a [`DropLog`] gives another handle to the same log.
+#[derive(Clone, Default)]
+pub struct DropLog {
+    dropped: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl fmt::Debug for DropLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DropLog")
+            .field("dropped", &*self.dropped.borrow())
+            .finish()
+    }
+}
+
+impl DropLog {
+    /// Create a new, empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a value which records `name` in this log when it is dropped.
+    ///
+    /// Each name should be used for only one [`Tracked`] value per log, so that dropping the same
+    /// value twice can be told apart from dropping two different values.
+    pub fn tracked(&self, name: &'static str) -> Tracked {
+        Tracked {
+            name,
+            log: self.clone(),
+        }
+    }
+
+    /// The names of the values dropped so far, in the order they were dropped.
+    pub fn dropped(&self) -> Vec<&'static str> {
+        self.dropped.borrow().clone()
+    }
+
+    /// Assert that exactly the values with the given names have been dropped so far, each of them
+    /// once, in the given order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value was dropped twice, or if the values dropped so far differ from `names`.
+    #[track_caller]
+    pub fn assert_dropped(&self, names: &[&'static str]) {
+        let dropped = self.dropped.borrow();
+        for (i, name) in dropped.iter().enumerate() {
+            assert!(
+                !dropped[..i].contains(name),
+                "`{}` was dropped more than once (dropped: {:?})",
+                name,
+                dropped,
+            );
+        }
+        assert_eq!(
+            &dropped[..],
+            names,
+            "the dropped values differ from those expected"
+        );
+    }
+}
+
+/// A value which records its name in a [`DropLog`] when it is dropped.
+pub struct Tracked {
+    name: &'static str,
+    log: DropLog,
+}
+
+impl Tracked {
+    /// The name of this value in its [`DropLog`].
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Debug for Tracked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Tracked").field(&self.name).finish()
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.log.dropped.borrow_mut().push(self.name);
+    }
+}
@@ -0,0 +1,591 @@
+//! Iterator adaptors for building and taking apart many values one case at a time.
+//!
+//! Most message-processing pipelines either build a stream of values which all have the same case,
+//! or pick out the payloads of one case from a stream of values. The extension trait
+//! [`CaseIterExt`] provides an adaptor for each: [`uncase_each`](CaseIterExt::uncase_each) (and its
+//! shorthand [`collect_cases`](CaseIterExt::collect_cases)), which injects each payload into the
+//! matched type with [`Case::uncase`], and [`filter_case`](CaseIterExt::filter_case), which keeps
+//! only the values with a given tag and yields their payloads using [`Case::try_case`]. When the
+//! other values are needed too, [`partition_case`](CaseIterExt::partition_case) splits them off
+//! from the payloads in the same pass.
+//!
+//! Values which are only borrowed can be split up by case in the same way, using
+//! [`route_mut`](CaseIterExt::route_mut), which sorts mutable references to them into a
+//! [`CaseMap`]. The mutable references to the cases of different tags can then be taken from it
+//! and used at the same time, which the borrow checker would not allow if they were each found by
+//! filtering the same slice.
+//!
+//! Sequences of borrowed values can be read as the transitions between their cases, using
+//! [`windows_case`](CaseIterExt::windows_case), which yields a [`Transition`] for each pair of
+//! adjacent values, holding both of their tags and able to borrow both of their cases at once. This
+//! suits counting how often one case follows another, or checking that a sequence of messages
+//! follows a protocol.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{iter::CaseIterExt, Match};
+//!
+//! #[derive(Match, Debug, PartialEq)]
+//! enum Message {
+//!     Ping,
+//!     Data(Vec<u8>),
+//! }
+//!
+//! let messages: Vec<Message> = vec![vec![1], vec![2, 3]].into_iter().collect_cases::<_, 1, _>();
+//! assert_eq!(messages, [Message::Data(vec![1]), Message::Data(vec![2, 3])]);
+//!
+//! let lengths: Vec<usize> = vec![Message::Data(vec![1]), Message::Ping, Message::Data(vec![])]
+//!     .into_iter()
+//!     .filter_case::<1>()
+//!     .map(|data| data.len())
+//!     .collect();
+//! assert_eq!(lengths, [1, 0]);
+//! ```
+
+use std::{
+    fmt,
+    iter::{FromIterator, FusedIterator},
+    marker::PhantomData,
+};
+
+use crate::{Case, CaseExt, CaseMut, CaseRef, Exhaustive, Match, Tag};
+
+/// An extension trait for iterators, providing adaptors which build or take apart values by case,
+/// as described in the [module documentation](self).
+pub trait CaseIterExt: Iterator {
+    /// Inject each item into the type `T` as its `N`th case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::iter::CaseIterExt;
+    ///
+    /// let mut options = (1..=2).uncase_each::<Option<_>, 1>();
+    /// assert_eq!(options.next(), Some(Some(1)));
+    /// assert_eq!(options.next(), Some(Some(2)));
+    /// assert_eq!(options.next(), None);
+    /// ```
+    #[inline(always)]
+    fn uncase_each<T, const N: usize>(self) -> UncaseEach<Self, T, N>
+    where
+        Self: Sized,
+        T: Case<N, Case = Self::Item>,
+    {
+        UncaseEach {
+            iter: self,
+            marker: PhantomData,
+        }
+    }
+
+    /// Inject each item into the type `T` as its `N`th case, and collect the results.
+    ///
+    /// This is shorthand for [`uncase_each`](CaseIterExt::uncase_each) followed by
+    /// [`collect`](Iterator::collect).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::iter::CaseIterExt;
+    ///
+    /// let results = vec!["a", "b"].into_iter().collect_cases::<Result<(), _>, 1, Vec<_>>();
+    /// assert_eq!(results, [Err("a"), Err("b")]);
+    /// ```
+    #[inline(always)]
+    fn collect_cases<T, const N: usize, C>(self) -> C
+    where
+        Self: Sized,
+        T: Case<N, Case = Self::Item>,
+        C: FromIterator<T>,
+    {
+        self.uncase_each::<T, N>().collect()
+    }
+
+    /// Keep only the items whose [`tag`](crate::Match::tag) is `N`, yielding their `N`th cases.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::iter::CaseIterExt;
+    ///
+    /// let results = vec![Ok(1), Err("no"), Ok(2)];
+    /// let errors: Vec<_> = results.into_iter().filter_case::<1>().collect();
+    /// assert_eq!(errors, ["no"]);
+    /// ```
+    #[inline(always)]
+    fn filter_case<const N: usize>(self) -> FilterCase<Self, N>
+    where
+        Self: Sized,
+        Self::Item: Case<N>,
+    {
+        FilterCase { iter: self }
+    }
+
+    /// Split the items into the payloads of those whose [`tag`](crate::Match::tag) is `N`, and
+    /// all the other items, in one pass, keeping their order.
+    ///
+    /// This is like [`partition`](Iterator::partition), except that the items of case `N` are
+    /// taken apart using [`Case::try_case`] on the way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vesta::iter::CaseIterExt;
+    ///
+    /// let results = vec![Ok(1), Err("no"), Ok(2), Err("never")];
+    /// let (errors, others) = results.into_iter().partition_case::<1>();
+    /// assert_eq!(errors, ["no", "never"]);
+    /// assert_eq!(others, [Ok(1), Ok(2)]);
+    /// ```
+    fn partition_case<const N: usize>(self) -> (Vec<<Self::Item as Case<N>>::Case>, Vec<Self::Item>)
+    where
+        Self: Sized,
+        Self::Item: Case<N>,
+    {
+        let mut cases = Vec::new();
+        let mut others = Vec::new();
+        for item in self {
+            match Case::<N>::try_case(item) {
+                Ok(case) => cases.push(case),
+                Err(item) => others.push(item),
+            }
+        }
+        (cases, others)
+    }
+
+    /// Sort mutable references to values of the exhaustive type `T` into a [`CaseMap`] by case,
+    /// so that the mutable references to different cases can be used at the same time.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "macros", doc = "```")]
+    #[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+    /// use vesta::{iter::CaseIterExt, Match};
+    ///
+    /// #[derive(Match, Debug, PartialEq)]
+    /// enum Sample {
+    ///     Temperature(f64),
+    ///     Humidity(u8),
+    /// }
+    ///
+    /// let mut batch = vec![
+    ///     Sample::Temperature(20.5),
+    ///     Sample::Humidity(40),
+    ///     Sample::Temperature(21.0),
+    /// ];
+    ///
+    /// let mut by_case = batch.iter_mut().route_mut();
+    /// let temperatures: Vec<&mut f64> = by_case.take::<0>();
+    /// let humidities: Vec<&mut u8> = by_case.take::<1>();
+    /// for temperature in temperatures {
+    ///     *temperature += 273.15;
+    /// }
+    /// for humidity in humidities {
+    ///     *humidity /= 2;
+    /// }
+    ///
+    /// assert_eq!(batch[0], Sample::Temperature(293.65));
+    /// assert_eq!(batch[1], Sample::Humidity(20));
+    /// ```
+    fn route_mut<'a, T, const N: usize>(self) -> CaseMap<'a, T, N>
+    where
+        Self: Sized + Iterator<Item = &'a mut T>,
+        T: Match<Range = Exhaustive<N>> + 'a,
+    {
+        let mut map = CaseMap::new();
+        map.route_mut(self);
+        map
+    }
+
+    /// Yield the [`Transition`] between each pair of adjacent values, which are borrowed from the
+    /// iterator, in order.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "macros", doc = "```")]
+    #[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+    /// use vesta::{iter::CaseIterExt, Match, Tag};
+    ///
+    /// #[derive(Match)]
+    /// enum Event {
+    ///     Connect(u16),
+    ///     Send(Vec<u8>),
+    ///     Disconnect,
+    /// }
+    ///
+    /// let log = [
+    ///     Event::Connect(80),
+    ///     Event::Send(vec![1, 2]),
+    ///     Event::Send(vec![3]),
+    ///     Event::Disconnect,
+    /// ];
+    ///
+    /// // How often does a send follow a send?
+    /// let repeats = log.iter().windows_case().filter(|step| step.is::<1, 1>()).count();
+    /// assert_eq!(repeats, 1);
+    ///
+    /// // Nothing may happen after disconnecting
+    /// assert!(log.iter().windows_case().all(|step| step.tags().0 != Some(Tag(2))));
+    ///
+    /// // Both payloads of a transition can be borrowed at once
+    /// let first_send = log.iter().windows_case().find_map(|step| step.cases::<0, 1>());
+    /// assert_eq!(first_send, Some((&80, &vec![1, 2])));
+    ///
+    /// // The adaptor can be cloned part way through, although `Event` is not `Clone`
+    /// let mut steps = log.iter().windows_case();
+    /// steps.next();
+    /// assert_eq!(steps.clone().count(), 2);
+    /// assert_eq!(steps.count(), 2);
+    /// ```
+    #[inline(always)]
+    fn windows_case<'a, T>(self) -> WindowsCase<'a, Self, T>
+    where
+        Self: Sized + Iterator<Item = &'a T>,
+        T: Match + ?Sized + 'a,
+    {
+        WindowsCase {
+            iter: self,
+            previous: None,
+        }
+    }
+}
+
+impl<I: Iterator + ?Sized> CaseIterExt for I {}
+
+/// An iterator injecting each item of another iterator into the type `T` as its `N`th case.
+///
+/// This is created by [`CaseIterExt::uncase_each`].
+#[derive(Debug, Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct UncaseEach<I, T, const N: usize> {
+    iter: I,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<I, T, const N: usize> Iterator for UncaseEach<I, T, N>
+where
+    I: Iterator,
+    T: Case<N, Case = I::Item>,
+{
+    type Item = T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(T::uncase)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    #[inline(always)]
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, T) -> B,
+    {
+        self.iter.fold(init, |acc, case| f(acc, T::uncase(case)))
+    }
+}
+
+impl<I, T, const N: usize> DoubleEndedIterator for UncaseEach<I, T, N>
+where
+    I: DoubleEndedIterator,
+    T: Case<N, Case = I::Item>,
+{
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back().map(T::uncase)
+    }
+}
+
+impl<I, T, const N: usize> ExactSizeIterator for UncaseEach<I, T, N>
+where
+    I: ExactSizeIterator,
+    T: Case<N, Case = I::Item>,
+{
+}
+
+impl<I, T, const N: usize> FusedIterator for UncaseEach<I, T, N>
+where
+    I: FusedIterator,
+    T: Case<N, Case = I::Item>,
+{
+}
+
+/// An iterator yielding the `N`th cases of the items of another iterator whose
+/// [`tag`](crate::Match::tag) is `N`, and skipping all the others.
+///
+/// This is created by [`CaseIterExt::filter_case`].
+#[derive(Debug, Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct FilterCase<I, const N: usize> {
+    iter: I,
+}
+
+impl<I, const N: usize> Iterator for FilterCase<I, N>
+where
+    I: Iterator,
+    I::Item: Case<N>,
+{
+    type Item = <I::Item as Case<N>>::Case;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|item| Case::<N>::try_case(item).ok())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+
+    #[inline(always)]
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.iter
+            .fold(init, |acc, item| match Case::<N>::try_case(item) {
+                Ok(case) => f(acc, case),
+                Err(_) => acc,
+            })
+    }
+}
+
+impl<I, const N: usize> DoubleEndedIterator for FilterCase<I, N>
+where
+    I: DoubleEndedIterator,
+    I::Item: Case<N>,
+{
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter
+            .by_ref()
+            .rev()
+            .find_map(|item| Case::<N>::try_case(item).ok())
+    }
+}
+
+impl<I, const N: usize> FusedIterator for FilterCase<I, N>
+where
+    I: FusedIterator,
+    I::Item: Case<N>,
+{
+}
+
+/// An iterator yielding the [`Transition`] between each pair of adjacent values borrowed from
+/// another iterator.
+///
+/// This is created by [`CaseIterExt::windows_case`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct WindowsCase<'a, I, T: ?Sized> {
+    iter: I,
+    previous: Option<Transition<'a, T>>,
+}
+
+// These are written by hand, since the values are only borrowed, and so need not be `Clone` or
+// `Debug` themselves
+impl<I: Clone, T: ?Sized> Clone for WindowsCase<'_, I, T> {
+    fn clone(&self) -> Self {
+        WindowsCase {
+            iter: self.iter.clone(),
+            previous: self.previous,
+        }
+    }
+}
+
+impl<I: fmt::Debug, T: ?Sized> fmt::Debug for WindowsCase<'_, I, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WindowsCase")
+            .field("iter", &self.iter)
+            .field(
+                "previous_tags",
+                &self.previous.map(|previous| previous.tags()),
+            )
+            .finish()
+    }
+}
+
+impl<'a, I, T> Iterator for WindowsCase<'a, I, T>
+where
+    I: Iterator<Item = &'a T>,
+    T: Match + ?Sized + 'a,
+{
+    type Item = Transition<'a, T>;
+
+    fn next(&mut self) -> Option<Transition<'a, T>> {
+        let (from, from_tag) = match self.previous {
+            Some(previous) => (previous.to, previous.to_tag),
+            None => {
+                let from = self.iter.next()?;
+                (from, from.typed_tag())
+            }
+        };
+        let to = self.iter.next()?;
+        let transition = Transition {
+            from,
+            to,
+            from_tag,
+            to_tag: to.typed_tag(),
+        };
+        self.previous = Some(transition);
+        Some(transition)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        if self.previous.is_some() {
+            (lower, upper)
+        } else {
+            (
+                lower.saturating_sub(1),
+                upper.map(|upper| upper.saturating_sub(1)),
+            )
+        }
+    }
+}
+
+impl<'a, I, T> FusedIterator for WindowsCase<'a, I, T>
+where
+    I: FusedIterator<Item = &'a T>,
+    T: Match + ?Sized + 'a,
+{
+}
+
+/// A pair of adjacent values, as yielded by [`CaseIterExt::windows_case`], along with their tags.
+pub struct Transition<'a, T: ?Sized> {
+    /// The earlier of the two values.
+    pub from: &'a T,
+    /// The later of the two values.
+    pub to: &'a T,
+    from_tag: Option<Tag>,
+    to_tag: Option<Tag>,
+}
+
+impl<'a, T: ?Sized> Transition<'a, T> {
+    /// The [`tag`](crate::Match::tag)s of the earlier and the later value.
+    #[inline(always)]
+    pub fn tags(&self) -> (Option<Tag>, Option<Tag>) {
+        (self.from_tag, self.to_tag)
+    }
+
+    /// Whether this is a transition from a value with the tag `A` to one with the tag `B`.
+    #[inline(always)]
+    pub fn is<const A: usize, const B: usize>(&self) -> bool {
+        self.tags() == (Some(Tag(A)), Some(Tag(B)))
+    }
+
+    /// If this is a transition from case `A` to case `B`, borrow both of those cases.
+    pub fn cases<const A: usize, const B: usize>(
+        &self,
+    ) -> Option<(<T as CaseRef<A>>::Ref<'a>, <T as CaseRef<B>>::Ref<'a>)>
+    where
+        T: CaseRef<A> + CaseRef<B>,
+    {
+        // The tags are checked again, since a type with interior mutability could change case
+        // while borrowed
+        if self.is::<A, B>() {
+            Some((
+                CaseRef::<A>::try_case_ref(self.from)?,
+                CaseRef::<B>::try_case_ref(self.to)?,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for Transition<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Transition<'_, T> {}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Transition<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Transition")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+/// Mutable references to values of the exhaustive type `T`, which has `N` cases, sorted by case.
+///
+/// This is created by [`CaseIterExt::route_mut`], or by [`CaseMap::new`] and then filled using
+/// [`CaseMap::route_mut`]. Once sorted, the values can only be reached through their cases, using
+/// [`take`](CaseMap::take), so that none of them can change case while in the map.
+pub struct CaseMap<'a, T, const N: usize> {
+    cases: [Vec<&'a mut T>; N],
+}
+
+impl<'a, T, const N: usize> CaseMap<'a, T, N>
+where
+    T: Match<Range = Exhaustive<N>>,
+{
+    /// A new, empty map.
+    pub fn new() -> Self {
+        CaseMap {
+            cases: std::array::from_fn(|_| Vec::new()),
+        }
+    }
+
+    /// Sort more mutable references into the map by case, after those already in it.
+    pub fn route_mut(&mut self, values: impl IntoIterator<Item = &'a mut T>) {
+        for value in values {
+            if let Some(tag) = value.tag() {
+                // The tag is always less than `N`, because the type is exhaustive
+                self.cases[tag].push(value);
+            }
+        }
+    }
+
+    /// The number of values in the map with the given tag.
+    ///
+    /// # Panics
+    ///
+    /// If `tag` is not less than `N`.
+    pub fn count(&self, tag: usize) -> usize {
+        self.cases[tag].len()
+    }
+
+    /// Remove the values with the tag `K` from the map, returning mutable references to their
+    /// cases, in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// If `K` is not less than `N`, which is only possible if `T` implements [`CaseMut<K>`] for a
+    /// tag it never has.
+    pub fn take<const K: usize>(&mut self) -> Vec<<T as CaseMut<K>>::Mut<'a>>
+    where
+        T: CaseMut<K>,
+    {
+        std::mem::take(&mut self.cases[K])
+            .into_iter()
+            // It is safe to call `case_mut` because every value was added with the tag `K`, and has
+            // been exclusively borrowed by the map since, so its tag can't have changed
+            .map(|value| unsafe { CaseMut::<K>::case_mut(value) })
+            .collect()
+    }
+}
+
+impl<'a, T, const N: usize> Default for CaseMap<'a, T, N>
+where
+    T: Match<Range = Exhaustive<N>>,
+{
+    fn default() -> Self {
+        CaseMap::new()
+    }
+}
+
+impl<'a, T, const N: usize> fmt::Debug for CaseMap<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts: Vec<usize> = self.cases.iter().map(Vec::len).collect();
+        f.debug_struct("CaseMap").field("counts", &counts).finish()
+    }
+}
@@ -8,14 +8,42 @@
 //! >
 //! > **Vesta** is a crate for extensibly *matching cases* in Rust.
 //!
-//! By implementing [`Match`](Match@trait) and [`Case`] for some type (or better yet, correctly
-//! deriving them using the [`Match`](Match@macro) derive macro), you can pattern-match on that type
+//! By implementing [`Match`](trait@Match) and [`Case`] for some type (or better yet, correctly
+//! deriving them using the [`Match`](macro@Match) derive macro), you can pattern-match on that type
 //! using the [`case!`] macro almost like using the `match` keyword built into Rust.
 //!
 //! However, Vesta's [`case!`] macro is more general than `match`, because [`Match`] and [`Case`]
 //! are traits! This means you can enable pattern-matching for types which are not literally
 //! implemented as `enum`s, and you can write code which is generic over any type that is
 //! pattern-matchable.
+//!
+//! # Crate features
+//!
+//! The traits, along with their implementations for types in the standard library, are defined in
+//! the [`vesta-core`](https://crates.io/crates/vesta-core) crate and re-exported here. The macros
+//! are enabled by the `macros` feature, which is on by default; without it, this crate doesn't
+//! depend on `syn`, `quote`, or any other procedural macro machinery. If that's all you need from
+//! it, you may depend on `vesta-core` directly instead.
+//!
+//! The `message-catalog` feature lets the wording of the macros' diagnostics, such as the cases
+//! missing from a [`case!`], be replaced by a catalog of messages, for instance in another
+//! language. The catalog is a file named by the `VESTA_MESSAGE_CATALOG` environment variable when
+//! the macros are expanded; its format and the messages it can override are described in the
+//! [documentation of `vesta-syntax`](https://docs.rs/vesta-syntax/latest/vesta_syntax/messages/).
+//!
+//! The `audit-unsafe` feature makes the code generated by the macros check the tag of a value again
+//! before every unchecked extraction of a case from it, counting the checks, so that a test suite
+//! can confirm that none of them ever fail. The checks and their results are reported by the
+//! `audit` module, which only exists with this feature.
+//!
+//! The `futures` feature implements the traits for the enums returned by the combinators of
+//! [`futures-util`](https://docs.rs/futures-util), such as the [`Either`] output of `select`, as
+//! described in the `futures` module, which only exists with this feature.
+//!
+//! The `lazy-lock` feature lets the state of a `LazyLock` be matched like that of a `OnceLock`, as
+//! described in the `once` module. It needs Rust 1.94, which the rest of this crate doesn't.
+//!
+//! [`Either`]: https://docs.rs/futures-util/latest/futures_util/future/enum.Either.html
 
 #![warn(missing_docs)]
 #![warn(missing_copy_implementations, missing_debug_implementations)]
@@ -25,259 +53,592 @@
 // Documentation configuration
 #![forbid(broken_intra_doc_links)]
 
-pub use vesta_macro::{case, Match};
+pub use vesta_core::{
+    absurd, assert_exhaustive, ffi, fmt_case, once, open, option, result, time, unknown, Case,
+    CaseExt, CaseMut, CaseRef, DisplayCase, Exhaustive, GlobalCase, Match, MatchMeta,
+    Nonexhaustive, Tag, TagBase, TryUncase, UncaseError,
+};
+
+#[doc(hidden)]
+pub use vesta_core::{
+    assert_case, assert_case_cow, assert_case_ref, debug_assert_tag, unreachable, CowCase,
+};
+
+#[cfg(feature = "unstable-ranges")]
+pub use vesta_core::unstable_ranges;
+
+#[cfg(feature = "futures")]
+pub use vesta_core::futures;
+
+#[cfg(feature = "macros")]
+pub use vesta_macro::{case, case_pair, case_table, destructure, match_flags, Match};
 
-/// This module is exported so that the `derive_match!` macro can make reference to `vesta` itself
-/// from within the crate.
+#[cfg(feature = "crossbeam")]
+pub use vesta_macro::select_case;
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use vesta_macro::__doctor;
+
+/// Re-exported so that [`select_case!`] can refer to it, and so that its users can construct
+/// channels without depending on `crossbeam-channel` directly.
+#[cfg(feature = "crossbeam")]
+pub use crossbeam_channel;
+
+use std::fmt;
+
+/// This module is exported so that the macros can make reference to `vesta` itself from within the
+/// crate.
 #[doc(hidden)]
 pub mod vesta {
     pub use super::*;
 }
 
-/// A type which is [`Match`] can be pattern-matched using the [`case!`] macro and the methods of
-/// [`CaseExt`]/[`Case`].
+/// A type which can be built case by case from a seed, by repeatedly choosing a case whose
+/// recursive parts are themselves seeds, to be built in turn.
 ///
-/// In order for a type to be matched, it must implement [`Match`], as well as [`Case`] for each
-/// distinct case it can be matched against.
-pub unsafe trait Match: Sized {
-    /// The range of [`tag`](Match::tag) for this type: either [`Nonexhaustive`], or
-    /// [`Exhaustive<N>`](Exhaustive) for some `N`.
-    ///
-    /// No other types are permissible for this associated type; it is constrained by the sealed
-    /// `Range` trait, which is only implemented for these two options.
-    ///
-    /// # Safety
-    ///
-    /// If the [`Range`](Match::Range) is [`Exhaustive<N>`](Exhaustive), then [`tag`](Match::tag)
-    /// must *never* return `None`. For all `Some(m)` it returns, `m` must be *strictly less than*
-    /// `N`. Undefined behavior may result if this guarantee is violated.
-    type Range: sealed::Range;
+/// This is the dual of matching: rather than taking a value apart one layer at a time, unfolding
+/// builds one up. It is usually derived, using `#[vesta(unfold)]` along with
+/// [`#[derive(Match)]`](macro@Match), which generates the [`Step`](Unfold::Step) type as an enum
+/// named `{Type}Step`.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use vesta::{Match, Unfold};
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// #[vesta(unfold)]
+/// enum Tree {
+///     Leaf(u32),
+///     Node(Box<Tree>, Box<Tree>),
+/// }
+///
+/// // Build a complete tree of the given depth, numbering its leaves by their depth
+/// let tree = Tree::unfold(1, |depth| {
+///     if depth == 0 {
+///         TreeStep::Leaf(depth)
+///     } else {
+///         TreeStep::Node(depth - 1, depth - 1)
+///     }
+/// });
+///
+/// assert_eq!(
+///     tree,
+///     Tree::Node(Box::new(Tree::Leaf(0)), Box::new(Tree::Leaf(0))),
+/// );
+/// ```
+pub trait Unfold: Sized {
+    /// A single layer of `Self`, with seeds of type `S` in place of the values of `Self` it
+    /// contains.
+    type Step<S>;
 
-    /// The tag of this value.
-    ///
-    /// # Safety
-    ///
-    /// If this function returns `Some(n)`, this is a *guarantee* that it is safe to call
-    /// [`case`](Case::case) for this value at the type level tag `N = n`. It is undefined behavior
-    /// for this function to return `Some(n)` if `<Self as Case<N>>::case(self)` would be unsafe.
-    ///
-    /// If the [`Range`](Match::Range) is [`Exhaustive<N>`](Exhaustive), then this function must
-    /// *never* return `None`. For all `Some(m)` it returns, `m` must be *strictly less than* `N`.
-    /// Undefined behavior may result if this guarantee is violated.
+    /// Build a value from a single layer, using `unfold` to build each of its recursive parts from
+    /// their seeds.
     ///
-    /// Only if the [`Range`](Match::Range) is [`Nonexhaustive`] is it safe for this function to
-    /// return `None`. Returning `None` will cause all pattern matches on this value to take the
-    /// default case.
-    ///
-    /// This function should always return the same result. In general, it is impossible to safely
-    /// implement [`Match`] for types with interior mutability, unless that interior mutability has
-    /// no ability to change the tag. When pattern-matching occurs, there is no guarantee that
-    /// `self.tag()` is checked and `self.case()` subsequently called (if applicable) in a single
-    /// atomic action, which may lead to undefined behavior if the tag changes between these two
-    /// moments.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vesta::Match;
+    /// # Panics
     ///
-    /// assert_eq!(Some(0), None::<bool>.tag());
-    /// assert_eq!(Some(1), Some(true).tag());
-    /// ```
-    fn tag(&self) -> Option<usize>;
+    /// If `Self` is validated using `#[vesta(validate = "...")]`, this panics if the value built is
+    /// rejected. Use [`try_from_step`](Unfold::try_from_step) to build such values instead.
+    fn from_step<S>(step: Self::Step<S>, unfold: &mut impl FnMut(S) -> Self) -> Self;
+
+    /// Build a value from a single layer, using `unfold` to try to build each of its recursive
+    /// parts from their seeds, or return `None` if any of them can't be built, or if `Self` is
+    /// validated and rejects the value built.
+    fn try_from_step<S>(
+        step: Self::Step<S>,
+        unfold: &mut impl FnMut(S) -> Option<Self>,
+    ) -> Option<Self>;
+
+    /// Build a value from a seed, using `step` to choose the outermost layer for each seed.
+    fn unfold<S>(seed: S, mut step: impl FnMut(S) -> Self::Step<S>) -> Self {
+        fn unfold_with<T: Unfold, S>(seed: S, step: &mut impl FnMut(S) -> T::Step<S>) -> T {
+            let layer = step(seed);
+            T::from_step(layer, &mut |seed| unfold_with(seed, step))
+        }
+        unfold_with(seed, &mut step)
+    }
+
+    /// Build a value from a seed, using `step` to choose the outermost layer for each seed, or
+    /// return `None` if `Self` is validated and rejects any of the values built along the way.
+    fn try_unfold<S>(seed: S, mut step: impl FnMut(S) -> Self::Step<S>) -> Option<Self> {
+        fn try_unfold_with<T: Unfold, S>(
+            seed: S,
+            step: &mut impl FnMut(S) -> T::Step<S>,
+        ) -> Option<T> {
+            let layer = step(seed);
+            T::try_from_step(layer, &mut |seed| try_unfold_with(seed, step))
+        }
+        try_unfold_with(seed, &mut step)
+    }
 }
 
-/// An extension trait providing methods analogous to those in [`Case`], but which take `self` and
-/// type parameters.<br>💡 Prefer using these to directly calling the methods in [`Case`].
-pub trait CaseExt: Sized {
-    /// If the value's [`tag`](Match::tag) is `N`, return that case.
-    ///
-    /// # Safety
-    ///
-    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
-    /// anything other than `Some(n)`, where `n = N`.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vesta::{Match, CaseExt};
-    ///
-    /// let option = Some("hello");
-    /// assert_eq!(option.tag(), Some(1));
-    /// let string = unsafe { option.case::<1>() };
-    /// assert_eq!(string, "hello");
-    /// ```
+/// An extension trait for applying a handler to a value at the end of a chain of method calls,
+/// rather than wrapping the whole chain in [`case!`].
+///
+/// The handler is usually written using [`case_table!`], which takes the arms of a [`case!`] and
+/// makes a closure matching on its argument using them, but any closure will do.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use vesta::{case_table, Dispatch};
+///
+/// let parsed = "42"
+///     .parse::<u32>()
+///     .dispatch(case_table! {
+///         0(n) => n * 2,
+///         1(_) => 0,
+///     });
+/// assert_eq!(parsed, 84);
+/// ```
+pub trait Dispatch {
+    /// Apply the handler to this value.
     #[inline(always)]
-    unsafe fn case<const N: usize>(self) -> Self::Case
+    fn dispatch<R>(self, handler: impl FnOnce(Self) -> R) -> R
     where
-        Self: Case<N>,
+        Self: Sized,
     {
-        Case::case(self)
+        handler(self)
     }
 
-    /// If the value's [`tag`](Match::tag) is `N`, return that case; otherwise, return `self`.
+    /// Apply the handler to a reference to this value.
     ///
     /// # Examples
     ///
-    /// ```
-    /// use vesta::CaseExt;
+    #[cfg_attr(feature = "macros", doc = "```")]
+    #[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+    /// use vesta::{case_table, Dispatch};
     ///
-    /// let result = Some("hello").try_case::<1>();
-    /// assert_eq!(result, Ok("hello"));
+    /// let length = "hello".dispatch_ref(case_table! {
+    ///     ref
+    ///     0 => 0,
+    ///     1(_, rest) => rest.len() + 1,
+    /// });
+    /// assert_eq!(length, 5);
     /// ```
     #[inline(always)]
-    fn try_case<const N: usize>(self) -> Result<Self::Case, Self>
-    where
-        Self: Case<N>,
-    {
-        Case::try_case(self)
+    fn dispatch_ref<R>(&self, handler: impl FnOnce(&Self) -> R) -> R {
+        handler(self)
     }
+}
 
-    /// The inverse of [`case`](CaseExt::case): inject this case back into the matched type.
-    ///
-    /// This operation must not panic or otherwise fail.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vesta::CaseExt;
-    ///
-    /// let option: Option<_> = "hello".uncase::<_, 1>();
-    /// assert_eq!(option, Some("hello"));
-    /// ```
+impl<T: ?Sized> Dispatch for T {}
+
+/// A mapping from payloads of type `A` to payloads of type `B`, used to map each case of one type
+/// to the same case of another by the `map_payloads` method which `#[vesta(map_to(...))]` generates
+/// along with [`#[derive(Match)]`](macro@Match).
+///
+/// Every closure is a mapping between its argument and result types. When the payloads of
+/// different cases have different types, as when mapping borrowed messages to owned ones, a single
+/// type can implement this trait once for each of them.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use vesta::{MapPayload, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(map_to(Owned))]
+/// enum Borrowed<'a> {
+///     Ping,
+///     Text(&'a str),
+///     Bytes(&'a [u8]),
+/// }
+///
+/// #[derive(Match, Debug, PartialEq)]
+/// enum Owned {
+///     Ping,
+///     Text(String),
+///     Bytes(Vec<u8>),
+/// }
+///
+/// struct ToOwned;
+///
+/// impl MapPayload<(), ()> for ToOwned {
+///     fn map_payload(&mut self, (): ()) {}
+/// }
+///
+/// impl<'a, T: std::borrow::ToOwned + ?Sized> MapPayload<&'a T, T::Owned> for ToOwned {
+///     fn map_payload(&mut self, payload: &'a T) -> T::Owned {
+///         payload.to_owned()
+///     }
+/// }
+///
+/// assert_eq!(Borrowed::Text("hi").map_payloads(ToOwned), Owned::Text("hi".to_string()));
+/// assert_eq!(Borrowed::Bytes(&[1, 2]).map_payloads(ToOwned), Owned::Bytes(vec![1, 2]));
+/// ```
+pub trait MapPayload<A, B> {
+    /// Map the payload of a single case.
+    fn map_payload(&mut self, payload: A) -> B;
+}
+
+impl<A, B, F: FnMut(A) -> B> MapPayload<A, B> for F {
     #[inline(always)]
-    fn uncase<T, const N: usize>(self) -> T
-    where
-        T: Case<N, Case = Self>,
-    {
-        Case::uncase(self)
+    fn map_payload(&mut self, payload: A) -> B {
+        self(payload)
     }
 }
 
-impl<T: Sized> CaseExt for T {}
+/// Cloning the payload of case `N` alone, which requires only the fields of that case to be
+/// [`Clone`], rather than the whole type. This is implemented for each case of an enum by
+/// `#[vesta(clone_case)]` along with [`#[derive(Match)]`](macro@Match), which also generates an
+/// inherent method `clone_case::<N>()` calling it.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use std::fs::File;
+/// use vesta::Match;
+///
+/// #[derive(Match)]
+/// #[vesta(clone_case)]
+/// enum Source {
+///     Text(String),
+///     File(File),
+/// }
+///
+/// let source = Source::Text("hello".to_string());
+/// assert_eq!(source.clone_case::<0>(), Some("hello".to_string()));
+/// ```
+pub trait CloneCase<const N: usize>: Case<N> {
+    /// A clone of the payload of this value, if it has the tag `N`.
+    fn clone_case(&self) -> Option<Self::Case>;
+}
 
-/// Statically assert that the type of the given value is exhaustive for `N`.
+/// Formatting the payload of case `N` alone for debugging, which requires only the fields of that
+/// case to be [`Debug`](fmt::Debug), rather than the whole type. This is implemented for each case
+/// of an enum by `#[vesta(debug_case)]` along with [`#[derive(Match)]`](macro@Match), which also
+/// generates an inherent method `debug_case::<N>()` returning a [`DebugPayload`].
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use std::fs::File;
+/// use vesta::Match;
 ///
-/// This function can only be called if `T: Match<Range = Exhaustive<N>>`. It does nothing
-/// when called.
+/// #[derive(Match)]
+/// #[vesta(debug_case)]
+/// enum Source {
+///     Text(String),
+///     File(File),
+/// }
+///
+/// let source = Source::Text("hello".to_string());
+/// assert_eq!(format!("{:?}", source.debug_case::<0>().unwrap()), "Text(\"hello\")");
+/// assert!(source.debug_case::<1>().is_none());
+/// ```
+pub trait DebugCase<const N: usize>: Match {
+    /// Format the payload of this value in the same way as a derived [`Debug`](fmt::Debug)
+    /// implementation would format its variant, if it has the tag `N`, or return `None` without
+    /// writing anything otherwise.
+    fn fmt_case(&self, f: &mut fmt::Formatter) -> Option<fmt::Result>;
+}
+
+/// An adapter which formats the payload of case `N` of a value for debugging using
+/// [`DebugCase`], as returned by the `debug_case::<N>()` method generated by
+/// `#[vesta(debug_case)]`.
+pub struct DebugPayload<'a, T: ?Sized, const N: usize>(&'a T);
+
+impl<'a, T: DebugCase<N> + ?Sized, const N: usize> DebugPayload<'a, T, N> {
+    /// Format the payload of the value, if it has the tag `N`.
+    pub fn new(value: &'a T) -> Option<Self> {
+        if value.tag() == Some(N) {
+            Some(DebugPayload(value))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: DebugCase<N> + ?Sized, const N: usize> fmt::Debug for DebugPayload<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_case(f).unwrap_or(Ok(()))
+    }
+}
+
+/// The payload types of all the cases of an exhaustive type, as a type-level list.
+///
+/// The list [`Cases`](CaseList::Cases) is a nested tuple `(C0, (C1, (C2, ())))`, holding the
+/// [`Case`] type of each tag in order and ending in `()`, so that generic code can walk it by
+/// implementing a trait for `()` and for `(Head, Tail)`. This is implemented for [`Option`] and
+/// [`Result`], and by `#[vesta(type_list)]` along with [`#[derive(Match)]`](macro@Match).
+///
+/// # Examples
+///
+#[cfg_attr(feature = "macros", doc = "```")]
+#[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+/// use std::mem::size_of;
+/// use vesta::{CaseList, Match};
+///
+/// #[derive(Match)]
+/// #[vesta(type_list)]
+/// enum Reading {
+///     Missing,
+///     Celsius(f64),
+///     RawADC(u16, u8),
+/// }
+///
+/// trait Sizes {
+///     fn sizes() -> Vec<usize>;
+/// }
+///
+/// impl Sizes for () {
+///     fn sizes() -> Vec<usize> {
+///         Vec::new()
+///     }
+/// }
+///
+/// impl<Head, Tail: Sizes> Sizes for (Head, Tail) {
+///     fn sizes() -> Vec<usize> {
+///         let mut sizes = vec![size_of::<Head>()];
+///         sizes.extend(Tail::sizes());
+///         sizes
+///     }
+/// }
+///
+/// assert_eq!(<Reading as CaseList>::Cases::sizes(), [0, 8, 4]);
+/// assert_eq!(<Option<u32> as CaseList>::Cases::sizes(), [0, 4]);
+/// ```
+pub trait CaseList: Match {
+    /// The payload types of the cases, in order of their tags, as `(C0, (C1, ..., ()))`.
+    type Cases;
+}
+
+impl<T> CaseList for Option<T> {
+    type Cases = ((), (T, ()));
+}
+
+impl<T, E> CaseList for Result<T, E> {
+    type Cases = (T, (E, ()));
+}
+
+/// Pack the tags of two values of exhaustive types into a single index, `a * N_B + b`, which is
+/// unique to each pair of tags and less than `N_A * N_B`.
+///
+/// This is used by [`case_pair!`] to match on a pair of tags with a single flat `match`, and can be
+/// used to index tables of per-pair handlers.
 ///
 /// # Examples
 ///
 /// ```
-/// vesta::assert_exhaustive::<_, 2>(&Some(true));
+/// let packed = vesta::pack_tags(&Some(1), &Ok::<_, ()>(2));
+/// assert_eq!(packed, 2);
+///
+/// let packed = vesta::pack_tags(&Some(1), &Err::<(), _>(2));
+/// assert_eq!(packed, 3);
 /// ```
 #[inline(always)]
-pub fn assert_exhaustive<T, const N: usize>(_: &T)
+pub fn pack_tags<A, B, const N_A: usize, const N_B: usize>(a: &A, b: &B) -> usize
 where
-    T: Match<Range = Exhaustive<N>>,
+    A: Match<Range = Exhaustive<N_A>> + ?Sized,
+    B: Match<Range = Exhaustive<N_B>> + ?Sized,
 {
+    match (a.tag(), b.tag()) {
+        (Some(a), Some(b)) => a * N_B + b,
+        // It is safe to assume this is unreachable because the types are exhaustive
+        _ => unsafe { unreachable() },
+    }
 }
 
-/// Mark an unreachable location in generated code.
+/// Check that the macros work in the crate which invokes this, and print a report of how they are
+/// configured for it when it is compiled: how the crate refers to `vesta`, which features of
+/// `vesta` are enabled, whether `vesta` is built with `--cfg release`, and how the environment
+/// variables read by the macros are set.
 ///
-/// # Panics
+/// This is a tool for diagnosing problems with the macros, such as generated code which can't find
+/// `vesta` because it is renamed or only depended upon indirectly, and is not meant to be left in
+/// a crate. The report is printed by the compiler as a warning about the invocation, which expands
+/// to an item that only compiles if the code generated by the macros can refer to `vesta`. If
+/// `vesta` isn't among the dependencies of the crate at all, the report is an error explaining how
+/// to fix it.
 ///
-/// In debug mode, panics immediately when this function is called.
+/// # Examples
 ///
-/// # Safety
+/// ```
+/// vesta::doctor!();
 ///
-/// In release mode, undefined behavior may occur if this function is ever called.
+/// // Prints something like:
+/// //
+/// // warning: use of deprecated associated function `...::report`: `vesta::doctor!()` report for
+/// //          `my_crate`:
+/// //   path:            `::vesta`, found as a dependency of this package, named `vesta`
+/// //   features:        macros
+/// //   unreachable:     panics if reached, since `vesta` is built without `--cfg release`
+/// //   message catalog: not used, since the `message-catalog` feature is disabled
+/// //   expansions:      printed only for a `case!` with a `#[debug_expansion]` arm
+/// //   generated as:    in their usual form, since `VESTA_STABLE_EXPANSION` is not set
+/// //   const generics:  checked by compiling a match on a type with an exhaustive range
+/// ```
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! doctor {
+    () => {
+        $crate::__doctor!();
+    };
+}
+
+/// Whether `vesta` is built with `--cfg release`, for [`doctor!`] to report, since the `cfg`
+/// options of `vesta` aren't passed on to the macros.
+#[cfg(feature = "macros")]
 #[doc(hidden)]
-#[inline(always)]
-pub unsafe fn unreachable<T>() -> T {
-    #[cfg(release)]
-    {
-        core::hint::unreachable_unchecked()
-    }
-    #[cfg(not(release))]
-    {
-        core::unreachable!("invariant violation in `vesta::Match` or `vesta::Case` implementation")
-    }
+pub const __RELEASE: bool = cfg!(release);
+
+/// Which features of `vesta` are enabled, for [`doctor!`] to report, one bit for each, in the order
+/// the macros list them: the macros only see their own features, and these ones only change
+/// `vesta` itself.
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub const __FEATURES: u8 = cfg!(feature = "crossbeam") as u8
+    | (cfg!(feature = "usage") as u8) << 1
+    | (cfg!(feature = "audit-unsafe") as u8) << 2
+    | (cfg!(feature = "unstable-ranges") as u8) << 3
+    | (cfg!(feature = "strum") as u8) << 4
+    | (cfg!(feature = "futures") as u8) << 5;
+
+/// Register a type deriving [`Match`](macro@Match) in [`usage::TYPES`], given its name and the
+/// names of its cases, if the `usage` feature is enabled.
+#[cfg(feature = "usage")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __usage_type {
+    ($name:ident, [$($case:ident),*]) => {
+        const _: () = {
+            #[$crate::usage::linkme::distributed_slice($crate::usage::TYPES)]
+            #[linkme(crate = $crate::usage::linkme)]
+            static RECORD: $crate::usage::TypeRecord = $crate::usage::TypeRecord {
+                module: ::std::module_path!(),
+                name: ::std::stringify!($name),
+                cases: &[$(::std::stringify!($case)),*],
+            };
+        };
+    };
 }
 
-/// A marker type indicating that the [`tag`](Match::tag) for some type will always be *strictly
-/// less than* `N`.
-///
-/// Use this to mark the [`Range`](Match::Range) of exhaustive enumerations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum Exhaustive<const N: usize> {}
+/// Register a type deriving [`Match`](macro@Match), if the `usage` feature is enabled, which it is
+/// not.
+#[cfg(not(feature = "usage"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __usage_type {
+    ($($input:tt)*) => {};
+}
 
-/// A marker type indicating that the [`tag`](Match::tag) for some type is not fixed to some known
-/// upper bound.
-///
-/// Use this to mark the [`Range`](Match::Range) of non-exhaustive enumerations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum Nonexhaustive {}
+/// Register a site matching on a value in [`usage::SITES`], given a reference to the value, the
+/// tags it matches explicitly, and whether it has a `_` arm, if the `usage` feature is enabled.
+#[cfg(feature = "usage")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __usage_site {
+    ($value:expr, [$($tag:expr),*], $default:expr) => {{
+        static TYPE_NAME: ::std::sync::OnceLock<&'static str> = ::std::sync::OnceLock::new();
+        #[$crate::usage::linkme::distributed_slice($crate::usage::SITES)]
+        #[linkme(crate = $crate::usage::linkme)]
+        static SITE: $crate::usage::Site = $crate::usage::Site {
+            file: ::std::file!(),
+            line: ::std::line!(),
+            column: ::std::column!(),
+            tags: &[$($tag),*],
+            default: $default,
+            type_name: &TYPE_NAME,
+        };
+        SITE.record($value);
+    }};
+}
 
-/// An implementation of [`Case`] defines a particular case of a pattern match for a type.<br> ℹ️
-/// Prefer using the methods of [`CaseExt`] to directly calling these methods.
-pub trait Case<const N: usize>: Match {
-    /// The type of the data contained in the `N`th case of the matched type.
-    type Case;
+/// Register a site matching on a value, if the `usage` feature is enabled, which it is not.
+#[cfg(not(feature = "usage"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __usage_site {
+    ($($input:tt)*) => {};
+}
 
-    /// If the value's [`tag`](Match::tag) is `N`, return that case.
-    ///
-    /// # Safety
-    ///
-    /// It is undefined behavior to call this function when [`self.tag()`](Match::tag) would return
-    /// anything other than `Some(n)`, where `n = N`.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vesta::{Match, Case};
-    ///
-    /// let option = Some("hello");
-    /// assert_eq!(option.tag(), Some(1));
-    /// let string = unsafe { <_ as Case<1>>::case(option) };
-    /// assert_eq!(string, "hello");
-    /// ```
-    unsafe fn case(this: Self) -> Self::Case;
+/// Check that a value has the tag of the case about to be extracted from it without checking, and
+/// count the check, registering the site in [`audit::UNSAFE_SITES`], if the `audit-unsafe` feature
+/// is enabled.
+#[cfg(feature = "audit-unsafe")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __audit_case {
+    ($value:expr, $tag:expr) => {{
+        #[$crate::audit::linkme::distributed_slice($crate::audit::UNSAFE_SITES)]
+        #[linkme(crate = $crate::audit::linkme)]
+        static SITE: $crate::audit::Site =
+            $crate::audit::Site::new(::std::file!(), ::std::line!(), ::std::column!(), $tag);
+        SITE.check($value);
+    }};
+}
 
-    /// If the value's [`tag`](Match::tag) is `N`, return that case; otherwise, return `self`.
-    ///
-    /// In its default implementation, this method checks that `self.tag() == N` and then calls
-    /// [`case`](Case::case) only if so.
-    ///
-    /// In the case where this method can be more efficiently implemented than the composition of
-    /// [`tag`](Match::tag) with [`case`](Case::case), this method can be overloaded.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use vesta::Case;
-    ///
-    /// let result = <_ as Case<1>>::try_case(Some("hello"));
-    /// assert_eq!(result, Ok("hello"));
-    /// ```
-    fn try_case(this: Self) -> Result<Self::Case, Self> {
-        if this.tag() == Some(N) {
-            // It is safe to call `self.case()` because we have checked the tag
-            Ok(unsafe { Case::case(this) })
-        } else {
-            Err(this)
-        }
-    }
+/// Check that a value has the tag of the case about to be extracted from it, if the `audit-unsafe`
+/// feature is enabled, which it is not.
+#[cfg(not(feature = "audit-unsafe"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __audit_case {
+    ($($input:tt)*) => {};
+}
 
-    /// The inverse of [`case`](Case::case): inject this case back into the matched type.
-    ///
-    /// This operation must not panic or otherwise fail.
+pub mod assert;
+
+pub mod case_fn;
+
+#[cfg(feature = "audit-unsafe")]
+pub mod audit;
+
+pub mod drop_check;
+
+pub mod iter;
+
+pub mod poll;
+
+pub mod profile;
+
+#[cfg(feature = "usage")]
+pub mod usage;
+
+pub mod router;
+
+#[cfg(feature = "strum")]
+pub mod strum {
+    //! Support for keeping the cases of a type aligned with the
+    //! [`strum`](https://docs.rs/strum) derives `EnumCount` and `EnumIter`, which also encode the
+    //! ordinals of an enum's variants.
+    //!
+    //! This module is only available when the `strum` feature is enabled.
+
+    use crate::Match;
+
+    /// Re-exported so that the code generated by `#[vesta(strum)]` can refer to it.
+    pub use ::strum::{EnumCount, IntoEnumIterator};
+
+    /// Check that iterating over the values of a type using [`IntoEnumIterator`] yields values
+    /// whose tags are `0`, `1`, `2`, ... in order, i.e. that `strum` and `vesta` agree on the
+    /// ordering of its cases.
     ///
     /// # Examples
     ///
     /// ```
-    /// use vesta::Case;
+    /// use vesta::Match;
+    /// use strum::EnumIter;
     ///
-    /// let option: Option<_> = <_ as Case<1>>::uncase("hello");
-    /// assert_eq!(option, Some("hello"));
+    /// #[derive(Match, EnumIter)]
+    /// enum Direction {
+    ///     North,
+    ///     East(u8),
+    ///     South { distance: u8 },
+    /// }
+    ///
+    /// assert!(vesta::strum::tags_aligned::<Direction>());
     /// ```
-    fn uncase(case: Self::Case) -> Self;
-}
-
-mod sealed {
-    pub trait Range {}
-    impl<const N: usize> Range for super::Exhaustive<N> {}
-    impl Range for super::Nonexhaustive {}
+    pub fn tags_aligned<T: IntoEnumIterator + Match>() -> bool {
+        T::iter()
+            .enumerate()
+            .all(|(i, value)| value.tag() == Some(i))
+    }
 }
-
-mod impls;
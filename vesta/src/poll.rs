@@ -0,0 +1,168 @@
+//! Flattened matching on the results of polling futures and streams.
+//!
+//! A [`Poll`] is matched like any other enum, with `Ready` as case 0 and `Pending` as case 1, but
+//! the value inside a ready poll is usually a [`Result`] (when polling a fallible future) or an
+//! [`Option`] (when polling a stream), which would need a second, nested [`case!`]. Wrapping the
+//! poll in a [`FlatPoll`] instead gives it three cases, so it can be matched in one level:
+//!
+//! | Tag | `FlatPoll<Result<T, E>>`  | `FlatPoll<Option<T>>`    |
+//! |-----|---------------------------|--------------------------|
+//! | 0   | `Pending`: `()`           | `Pending`: `()`          |
+//! | 1   | `Ready(Ok(t))`: `T`       | `Ready(Some(t))`: `T`    |
+//! | 2   | `Ready(Err(e))`: `E`      | `Ready(None)`: `()`      |
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use std::task::Poll;
+//! use vesta::{case, poll::FlatPoll};
+//!
+//! fn describe(poll: Poll<Result<u32, String>>) -> String {
+//!     case!(FlatPoll(poll) {
+//!         0 => "pending".to_string(),
+//!         1(n) => format!("ready: {}", n),
+//!         2(e) => format!("failed: {}", e),
+//!     })
+//! }
+//!
+//! assert_eq!(describe(Poll::Pending), "pending");
+//! assert_eq!(describe(Poll::Ready(Ok(1))), "ready: 1");
+//! assert_eq!(describe(Poll::Ready(Err("oops".into()))), "failed: oops");
+//! ```
+//!
+//! [`case!`]: crate::case
+
+use std::task::Poll;
+
+use crate::{unreachable, Case, Exhaustive, Match};
+
+/// A [`Poll`] of a [`Result`] or an [`Option`], matched with three cases rather than two, as
+/// described in the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlatPoll<T>(pub Poll<T>);
+
+impl<T> From<Poll<T>> for FlatPoll<T> {
+    fn from(poll: Poll<T>) -> Self {
+        FlatPoll(poll)
+    }
+}
+
+impl<T> From<FlatPoll<T>> for Poll<T> {
+    fn from(FlatPoll(poll): FlatPoll<T>) -> Self {
+        poll
+    }
+}
+
+unsafe impl<T, E> Match for FlatPoll<Result<T, E>> {
+    type Range = Exhaustive<3>;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        Some(match self.0 {
+            Poll::Pending => 0,
+            Poll::Ready(Ok(_)) => 1,
+            Poll::Ready(Err(_)) => 2,
+        })
+    }
+}
+
+impl<T, E> Case<0> for FlatPoll<Result<T, E>> {
+    type Case = ();
+
+    #[inline]
+    unsafe fn case(_: Self) -> Self::Case {}
+
+    #[inline]
+    fn uncase((): Self::Case) -> Self {
+        FlatPoll(Poll::Pending)
+    }
+}
+
+impl<T, E> Case<1> for FlatPoll<Result<T, E>> {
+    type Case = T;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Poll::Ready(Ok(t)) => t,
+            _ => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        FlatPoll(Poll::Ready(Ok(case)))
+    }
+}
+
+impl<T, E> Case<2> for FlatPoll<Result<T, E>> {
+    type Case = E;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Poll::Ready(Err(e)) => e,
+            _ => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        FlatPoll(Poll::Ready(Err(case)))
+    }
+}
+
+unsafe impl<T> Match for FlatPoll<Option<T>> {
+    type Range = Exhaustive<3>;
+
+    #[inline]
+    fn tag(&self) -> Option<usize> {
+        Some(match self.0 {
+            Poll::Pending => 0,
+            Poll::Ready(Some(_)) => 1,
+            Poll::Ready(None) => 2,
+        })
+    }
+}
+
+impl<T> Case<0> for FlatPoll<Option<T>> {
+    type Case = ();
+
+    #[inline]
+    unsafe fn case(_: Self) -> Self::Case {}
+
+    #[inline]
+    fn uncase((): Self::Case) -> Self {
+        FlatPoll(Poll::Pending)
+    }
+}
+
+impl<T> Case<1> for FlatPoll<Option<T>> {
+    type Case = T;
+
+    #[inline]
+    unsafe fn case(this: Self) -> Self::Case {
+        match this.0 {
+            Poll::Ready(Some(t)) => t,
+            _ => unreachable(),
+        }
+    }
+
+    #[inline]
+    fn uncase(case: Self::Case) -> Self {
+        FlatPoll(Poll::Ready(Some(case)))
+    }
+}
+
+impl<T> Case<2> for FlatPoll<Option<T>> {
+    type Case = ();
+
+    #[inline]
+    unsafe fn case(_: Self) -> Self::Case {}
+
+    #[inline]
+    fn uncase((): Self::Case) -> Self {
+        FlatPoll(Poll::Ready(None))
+    }
+}
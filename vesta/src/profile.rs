@@ -0,0 +1,362 @@
+//! Cheaply counting how often each case of a type is matched, for profiling which cases dominate
+//! in production, so that arms can be ordered accordingly.
+//!
+//! A [`TagCounter`] holds one atomic counter per case of an exhaustive type, and can be placed in a
+//! `static`. Values are counted by calling [`observe`](TagCounter::observe), or by adding a
+//! `#[profile(...)]` attribute to a [`case!`], giving an expression for the counter: every value
+//! matched by that [`case!`] is then observed by the counter before it is taken apart. (Any value
+//! with an `observe` method taking a reference to the scrutinee will do as a counter.)
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{case, profile::TagCounter};
+//!
+//! static RESULTS: TagCounter<Result<u8, String>, 2> = TagCounter::new();
+//!
+//! fn handle(result: Result<u8, String>) -> u8 {
+//!     case!(#[profile(RESULTS)] result {
+//!         0(n) => n,
+//!         1(_) => 0,
+//!     })
+//! }
+//!
+//! handle(Ok(1));
+//! handle(Ok(2));
+//! handle(Err("oops".into()));
+//! assert_eq!(RESULTS.counts(), [2, 1]);
+//! ```
+//!
+//! # Ordering arms
+//!
+//! The arms of a [`case!`] are tried in the order in which their tags are first written, so putting
+//! the arms for the most common tags first can save a few comparisons when the tag isn't dispatched
+//! through a jump table, as for non-exhaustive types. Every [`case!`] with a `#[profile(...)]`
+//! attribute also counts the tags it matches itself, at a [`Site`] which is registered the first
+//! time it runs. At the end of a representative run, [`report`] suggests an order for the arms of
+//! each site, putting the hottest tags first, both for people to read and, using
+//! [`Report::patch`], for tools to apply. The same suggestion can be made from the counts of any
+//! [`TagCounter`] using [`TagCounter::suggest_order`].
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{case, profile::{self, TagCounter}};
+//!
+//! static RESULTS: TagCounter<Result<u8, String>, 2> = TagCounter::new();
+//!
+//! fn handle(result: Result<u8, String>) -> u8 {
+//!     case!(#[profile(RESULTS)] result {
+//!         0(n) => n,
+//!         1(_) => 0,
+//!     })
+//! }
+//!
+//! handle(Ok(1));
+//! handle(Err("oops".into()));
+//! handle(Err("again".into()));
+//!
+//! let report = profile::report();
+//! let order = &report.orders[0];
+//! assert_eq!(order.written, [0, 1]);
+//! assert_eq!(order.suggested, [1, 0]);
+//! assert!(report.patch().ends_with("\t1,0\n"));
+//! println!("{}", report); // e.g. "src/main.rs:7:9: arms for 0 (1x) 1 (2x) should be ordered 1 0"
+//!
+//! assert_eq!(RESULTS.suggest_order(&[0, 1]).suggested, [1, 0]);
+//! ```
+//!
+//! [`case!`]: crate::case
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{Exhaustive, Match};
+
+/// A count of how many values of the exhaustive type `T`, which has `N` cases, have been observed
+/// with each tag, as described in the [module documentation](self).
+pub struct TagCounter<T: ?Sized, const N: usize> {
+    counts: [AtomicU64; N],
+    marker: PhantomData<fn(&T)>,
+}
+
+impl<T, const N: usize> TagCounter<T, N>
+where
+    T: Match<Range = Exhaustive<N>> + ?Sized,
+{
+    /// A new counter, with all its counts zero.
+    pub const fn new() -> Self {
+        TagCounter {
+            counts: [const { AtomicU64::new(0) }; N],
+            marker: PhantomData,
+        }
+    }
+
+    /// Count the tag of this value.
+    #[inline]
+    pub fn observe(&self, value: &T) {
+        if let Some(tag) = value.tag() {
+            // The tag is always less than `N`, because the type is exhaustive
+            let _ = self.counts[tag].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The number of values observed with the given tag so far.
+    ///
+    /// # Panics
+    ///
+    /// If `tag` is not less than `N`.
+    pub fn count(&self, tag: usize) -> u64 {
+        self.counts[tag].load(Ordering::Relaxed)
+    }
+
+    /// The number of values observed with each tag so far.
+    pub fn counts(&self) -> [u64; N] {
+        let mut counts = [0; N];
+        for (count, counter) in counts.iter_mut().zip(&self.counts) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        counts
+    }
+
+    /// Suggest an order for arms written for the given tags, in the given order, putting the tags
+    /// observed most often first.
+    pub fn suggest_order(&self, written: &[usize]) -> ArmOrder {
+        ArmOrder::new(None, written, |tag| {
+            self.counts
+                .get(tag)
+                .map_or(0, |count| count.load(Ordering::Relaxed))
+        })
+    }
+
+    /// Reset all the counts to zero, returning what they were.
+    pub fn reset(&self) -> [u64; N] {
+        let mut counts = [0; N];
+        for (count, counter) in counts.iter_mut().zip(&self.counts) {
+            *count = counter.swap(0, Ordering::Relaxed);
+        }
+        counts
+    }
+}
+
+impl<T, const N: usize> Default for TagCounter<T, N>
+where
+    T: Match<Range = Exhaustive<N>> + ?Sized,
+{
+    fn default() -> Self {
+        TagCounter::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for TagCounter<T, N>
+where
+    T: Match<Range = Exhaustive<N>> + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TagCounter")
+            .field("counts", &self.counts())
+            .finish()
+    }
+}
+
+/// A [`case!`](crate::case) with a `#[profile(...)]` attribute, which counts how many values it has
+/// matched with each of the tags it has arms for, as described in the [module
+/// documentation](self#ordering-arms).
+#[derive(Debug)]
+pub struct Site {
+    /// The file containing the site.
+    pub file: &'static str,
+    /// The line of the site.
+    pub line: u32,
+    /// The column of the site.
+    pub column: u32,
+    /// The tags which have arms at the site, in the order in which they are tried.
+    pub tags: &'static [usize],
+    // One count per tag, followed by the count of all other tags
+    counts: &'static [AtomicU64],
+    registered: AtomicBool,
+}
+
+/// Every site which has run, in the order in which they first ran.
+static SITES: Mutex<Vec<&'static Site>> = Mutex::new(Vec::new());
+
+impl Site {
+    /// A site with the given tags, counted by the given counters, one more than there are tags, as
+    /// done by the generated code.
+    #[doc(hidden)]
+    pub const fn new(
+        file: &'static str,
+        line: u32,
+        column: u32,
+        tags: &'static [usize],
+        counts: &'static [AtomicU64],
+    ) -> Site {
+        Site {
+            file,
+            line,
+            column,
+            tags,
+            counts,
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Count the tag of this value, registering the site the first time it runs, as done by the
+    /// generated code.
+    #[doc(hidden)]
+    #[inline]
+    pub fn observe<T: Match + ?Sized>(&'static self, value: &T) {
+        if !self.registered.load(Ordering::Relaxed) && !self.registered.swap(true, Ordering::AcqRel)
+        {
+            SITES
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(self);
+        }
+        let index = value
+            .tag()
+            .and_then(|tag| self.tags.iter().position(|&other| other == tag))
+            .unwrap_or(self.tags.len());
+        let _ = self.counts[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of values matched at this site with the given tag so far, which is zero for
+    /// tags without arms of their own.
+    pub fn count(&self, tag: usize) -> u64 {
+        self.tags
+            .iter()
+            .position(|&other| other == tag)
+            .map_or(0, |index| self.counts[index].load(Ordering::Relaxed))
+    }
+
+    /// The number of values matched at this site so far whose tags don't have arms of their own,
+    /// and so were matched by a `_` arm.
+    pub fn others(&self) -> u64 {
+        self.counts[self.tags.len()].load(Ordering::Relaxed)
+    }
+
+    /// Suggest an order for the arms of this site, putting the tags matched most often first.
+    pub fn suggest_order(&'static self) -> ArmOrder {
+        ArmOrder::new(Some(self), self.tags, |tag| self.count(tag))
+    }
+}
+
+/// A suggested order for the arms of a [`case!`](crate::case), putting the arms for the tags
+/// matched most often first, as described in the [module documentation](self#ordering-arms).
+///
+/// Only the groups of arms for each tag are reordered: the arms for each tag keep their order, and
+/// `_` arms stay last, so reordering the arms this way never changes what they do.
+#[derive(Debug, Clone)]
+pub struct ArmOrder {
+    /// The site whose arms are ordered, if they belong to a registered [`Site`].
+    pub site: Option<&'static Site>,
+    /// The tags with arms, in the order in which they are written.
+    pub written: Vec<usize>,
+    /// The same tags, with the most often matched first, and those matched equally often in the
+    /// order in which they are written.
+    pub suggested: Vec<usize>,
+    /// The number of values matched with each tag, in the order in which they are written.
+    pub counts: Vec<u64>,
+}
+
+impl ArmOrder {
+    fn new(site: Option<&'static Site>, written: &[usize], count: impl Fn(usize) -> u64) -> Self {
+        let counts: Vec<u64> = written.iter().map(|&tag| count(tag)).collect();
+        let mut ranked: Vec<(usize, u64)> = written
+            .iter()
+            .copied()
+            .zip(counts.iter().copied())
+            .collect();
+        // The sort is stable, so tags matched equally often keep their order
+        ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+        ArmOrder {
+            site,
+            written: written.to_vec(),
+            suggested: ranked.into_iter().map(|(tag, _)| tag).collect(),
+            counts,
+        }
+    }
+
+    /// Whether the arms are already in the suggested order.
+    pub fn is_ordered(&self) -> bool {
+        self.written == self.suggested
+    }
+}
+
+impl fmt::Display for ArmOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(site) = self.site {
+            write!(f, "{}:{}:{}: ", site.file, site.line, site.column)?;
+        }
+        f.write_str("arms for")?;
+        for (tag, count) in self.written.iter().zip(&self.counts) {
+            write!(f, " {} ({}x)", tag, count)?;
+        }
+        if self.is_ordered() {
+            f.write_str(" are hottest first")
+        } else {
+            f.write_str(" should be ordered")?;
+            for tag in &self.suggested {
+                write!(f, " {}", tag)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The suggested orders for the arms of every profiled site which has run, as returned by
+/// [`report`].
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The suggested order for the arms of each site, in the order in which the sites first ran.
+    pub orders: Vec<ArmOrder>,
+}
+
+impl Report {
+    /// A machine-readable list of the arms to reorder: one line for each site whose arms are not
+    /// already in the suggested order, giving the location of the site as `file:line:column`, then
+    /// a tab, then the suggested order of its tags, separated by commas.
+    pub fn patch(&self) -> String {
+        let mut patch = String::new();
+        for order in self.orders.iter().filter(|order| !order.is_ordered()) {
+            if let Some(site) = order.site {
+                let tags: Vec<String> = order.suggested.iter().map(usize::to_string).collect();
+                patch.push_str(&format!(
+                    "{}:{}:{}\t{}\n",
+                    site.file,
+                    site.line,
+                    site.column,
+                    tags.join(",")
+                ));
+            }
+        }
+        patch
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for order in &self.orders {
+            writeln!(f, "{}", order)?;
+        }
+        Ok(())
+    }
+}
+
+/// Suggest an order for the arms of every profiled site which has run so far, from the counts of
+/// the tags matched at each.
+pub fn report() -> Report {
+    let sites = SITES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    Report {
+        orders: sites.into_iter().map(Site::suggest_order).collect(),
+    }
+}
@@ -0,0 +1,190 @@
+//! Support for the routers generated by `#[derive(Match)]` with `#[vesta(router)]`, and for routers
+//! whose handlers can be replaced while they are running.
+//!
+//! A [`DynamicRouter`] starts out with a complete table of handlers, usually written using
+//! [`case_table!`], so that the usual exhaustiveness checking of [`case!`] applies to it.
+//! Afterwards, the handler for any individual tag can be overridden by registering a closure for
+//! it, and later reset back to the table's own arm. Overrides can be registered and reset through a
+//! shared reference, even while other threads are routing values, which makes it possible for
+//! plugins or scripts to take over the handling of particular cases of a protocol in a long-running
+//! service.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "macros", doc = "```")]
+#![cfg_attr(not(feature = "macros"), doc = "```ignore")]
+//! use vesta::{case_table, router::DynamicRouter, Match};
+//!
+//! #[derive(Match)]
+//! enum Request {
+//!     Get(String),
+//!     Delete(String),
+//! }
+//!
+//! let router = DynamicRouter::new(case_table! {
+//!     0(key) => format!("get {}", key),
+//!     1(key) => format!("delete {}", key),
+//! });
+//! assert_eq!(router.route(Request::Delete("a".into())), "delete a");
+//!
+//! router.set::<1>(|key| format!("refusing to delete {}", key));
+//! assert_eq!(router.route(Request::Delete("a".into())), "refusing to delete a");
+//! assert_eq!(router.route(Request::Get("a".into())), "get a");
+//!
+//! assert!(router.reset(1));
+//! assert_eq!(router.route(Request::Delete("a".into())), "delete a");
+//! ```
+//!
+//! [`case!`]: crate::case
+//! [`case_table!`]: crate::case_table
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{Case, Match};
+
+/// A marker for a case of a router builder which does not yet have a handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Missing;
+
+/// A handler registered to override one tag of a [`DynamicRouter`].
+type Override<'h, T, R> = Arc<dyn Fn(T) -> R + Send + Sync + 'h>;
+
+/// A router for values of type `T`, with a complete table of handlers, any of which can be
+/// overridden at runtime by a closure registered for its tag, as described in the [module
+/// documentation](self).
+pub struct DynamicRouter<'h, T, R> {
+    table: Box<dyn Fn(T) -> R + Send + Sync + 'h>,
+    overrides: RwLock<HashMap<usize, Override<'h, T, R>>>,
+}
+
+impl<'h, T: Match, R> DynamicRouter<'h, T, R> {
+    /// A new router which handles every value using the given table, until some of its tags are
+    /// overridden.
+    ///
+    /// The table is usually written using [`case_table!`](crate::case_table), which checks that it
+    /// handles every case of `T`.
+    pub fn new(table: impl Fn(T) -> R + Send + Sync + 'h) -> Self {
+        DynamicRouter {
+            table: Box::new(table),
+            overrides: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Handle this value using the override registered for its tag, if there is one, or otherwise
+    /// using the table.
+    ///
+    /// The override is called without holding any lock, so it may itself register or reset
+    /// overrides on this router.
+    pub fn route(&self, value: T) -> R {
+        let handler = value.tag().and_then(|tag| self.read().get(&tag).cloned());
+        match handler {
+            Some(handler) => handler(value),
+            None => (self.table)(value),
+        }
+    }
+
+    /// Override the handling of the `N`th case, so that its contents are passed to `handler`
+    /// rather than to the table.
+    ///
+    /// Returns `true` if this replaced an earlier override of the same case.
+    pub fn set<const N: usize>(
+        &self,
+        handler: impl Fn(<T as Case<N>>::Case) -> R + Send + Sync + 'h,
+    ) -> bool
+    where
+        T: Case<N>,
+    {
+        // It is safe to call `case` because `route` only calls the override for `N` with values
+        // whose tag is `N`
+        self.insert(
+            N,
+            Arc::new(move |value| handler(unsafe { Case::<N>::case(value) })),
+        )
+    }
+
+    /// Override the handling of every value with the given tag, so that the whole value is passed
+    /// to `handler` rather than to the table.
+    ///
+    /// This is useful when the tag is only known at runtime, such as when it comes from a
+    /// configuration file or a script. Tags which no value of `T` has are accepted, but their
+    /// overrides will never be called.
+    ///
+    /// Returns `true` if this replaced an earlier override of the same tag.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "macros", doc = "```")]
+    #[cfg_attr(not(feature = "macros"), doc = "```ignore")]
+    /// use vesta::{case_table, router::DynamicRouter};
+    ///
+    /// let router = DynamicRouter::new(case_table! {
+    ///     0 => "none".to_string(),
+    ///     1(n) => format!("{}", n),
+    /// });
+    ///
+    /// let tag: usize = "1".parse().unwrap();
+    /// router.set_tag(tag, |option: Option<u8>| format!("{:?}", option));
+    /// assert_eq!(router.route(Some(1)), "Some(1)");
+    /// assert_eq!(router.route(None), "none");
+    /// ```
+    pub fn set_tag(&self, tag: usize, handler: impl Fn(T) -> R + Send + Sync + 'h) -> bool {
+        self.insert(tag, Arc::new(handler))
+    }
+
+    /// Remove the override of the given tag, if there is one, so that values with that tag are
+    /// handled by the table again.
+    ///
+    /// Returns `true` if there was an override to remove.
+    pub fn reset(&self, tag: usize) -> bool {
+        let old = self.write().remove(&tag);
+        old.is_some()
+    }
+
+    /// Remove the overrides of every tag, so that all values are handled by the table again.
+    pub fn reset_all(&self) {
+        let old = std::mem::take(&mut *self.write());
+        drop(old);
+    }
+
+    /// Whether there is currently an override for the given tag.
+    pub fn is_overridden(&self, tag: usize) -> bool {
+        self.read().contains_key(&tag)
+    }
+
+    fn insert(&self, tag: usize, handler: Override<'h, T, R>) -> bool {
+        let old = self.write().insert(tag, handler);
+        old.is_some()
+    }
+
+    // No handler is ever called while the lock is held, so even a poisoned lock holds a consistent
+    // set of overrides
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<usize, Override<'h, T, R>>> {
+        self.overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<usize, Override<'h, T, R>>> {
+        self.overrides
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<'h, T, R> fmt::Debug for DynamicRouter<'h, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let overrides = self
+            .overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut overridden: Vec<usize> = overrides.keys().copied().collect();
+        overridden.sort_unstable();
+        f.debug_struct("DynamicRouter")
+            .field("overridden", &overridden)
+            .finish()
+    }
+}
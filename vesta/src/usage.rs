@@ -0,0 +1,161 @@
+//! A report of which cases of which types are matched anywhere in a program, for finding cases
+//! which are never matched, such as dead variants of a protocol.
+//!
+//! This module is only available when the `usage` feature is enabled. With it, every type deriving
+//! [`Match`](macro@crate::Match) is registered in the list of [`types`], and every [`case!`] (as
+//! well as every [`case_table!`](crate::case_table) and `select_case!`) is registered in the list
+//! of [`sites`], along with the tags it matches explicitly. Both lists are gathered at link time,
+//! so they cover the whole program, including its dependencies.
+//!
+//! The type being matched at a site is usually inferred, so it can't be recorded at compile time.
+//! Instead, each site records the name of its type the first time it runs. This means the report is
+//! most useful at the end of a test suite with good coverage: [`unmatched`] lists the cases of each
+//! registered type which are never explicitly matched at any site which has run, and
+//! [`unresolved`] lists the sites which have never run, and so might match them.
+//!
+//! # Examples
+//!
+//! ```
+//! use vesta::{case, usage, Match};
+//!
+//! #[derive(Match)]
+//! enum Message {
+//!     Ping,
+//!     Data(Vec<u8>),
+//!     Legacy(u32),
+//! }
+//!
+//! fn size(message: Message) -> usize {
+//!     case!(message {
+//!         0 => 0,
+//!         1(data) => data.len(),
+//!         _ => 0,
+//!     })
+//! }
+//!
+//! assert_eq!(size(Message::Ping), 0);
+//!
+//! // The `Legacy` case is never matched explicitly, only by the `_` arm
+//! let unmatched = usage::unmatched();
+//! let (record, tags) = unmatched
+//!     .iter()
+//!     .find(|(record, _)| record.name == "Message")
+//!     .unwrap();
+//! assert_eq!(tags, &[2]);
+//! assert_eq!(record.cases[2], "Legacy");
+//! ```
+//!
+//! [`case!`]: crate::case
+
+use std::{any, sync::OnceLock};
+
+/// Re-exported so that the code generated by the macros can refer to it.
+#[doc(hidden)]
+pub use linkme;
+
+/// All the types deriving [`Match`](macro@crate::Match) in the program.
+#[linkme::distributed_slice]
+#[linkme(crate = linkme)]
+pub static TYPES: [TypeRecord];
+
+/// All the sites in the program which match on the cases of a type.
+#[linkme::distributed_slice]
+#[linkme(crate = linkme)]
+pub static SITES: [Site];
+
+/// A type deriving [`Match`](macro@crate::Match), as registered in [`TYPES`].
+#[derive(Debug, Clone, Copy)]
+pub struct TypeRecord {
+    /// The path of the module in which the type is declared.
+    pub module: &'static str,
+    /// The name of the type.
+    pub name: &'static str,
+    /// The names of the cases of the type, in order of their tags.
+    pub cases: &'static [&'static str],
+}
+
+impl TypeRecord {
+    /// Whether the given full name of a type, as returned by [`std::any::type_name`], refers to
+    /// this type, ignoring its generic parameters.
+    pub fn is_named(&self, type_name: &str) -> bool {
+        let path = type_name.split('<').next().unwrap_or_default();
+        path.starts_with(self.module)
+            && path
+                .strip_suffix(self.name)
+                .is_some_and(|prefix| prefix.ends_with("::"))
+    }
+}
+
+/// A site in the program which matches on the cases of a type, as registered in [`SITES`].
+#[derive(Debug, Clone, Copy)]
+pub struct Site {
+    /// The file containing the site.
+    pub file: &'static str,
+    /// The line of the site.
+    pub line: u32,
+    /// The column of the site.
+    pub column: u32,
+    /// The tags which the site matches explicitly.
+    pub tags: &'static [usize],
+    /// Whether the site has a `_` arm, matching every other tag.
+    pub default: bool,
+    #[doc(hidden)]
+    pub type_name: &'static OnceLock<&'static str>,
+}
+
+impl Site {
+    /// The full name of the type matched at this site, as returned by [`std::any::type_name`], if
+    /// the site has run.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name.get().copied()
+    }
+
+    /// Record the type of the value being matched, as done by the generated code the first time the
+    /// site runs.
+    #[doc(hidden)]
+    #[inline]
+    pub fn record<T: ?Sized>(&self, _: &T) {
+        let _ = self.type_name.get_or_init(any::type_name::<T>);
+    }
+}
+
+/// All the types deriving [`Match`](macro@crate::Match) in the program.
+pub fn types() -> &'static [TypeRecord] {
+    &TYPES
+}
+
+/// All the sites in the program which match on the cases of a type.
+pub fn sites() -> &'static [Site] {
+    &SITES
+}
+
+/// For each registered type with some case which has not been matched explicitly at any site that
+/// has run, that type and the tags of those cases.
+pub fn unmatched() -> Vec<(&'static TypeRecord, Vec<usize>)> {
+    TYPES
+        .iter()
+        .filter_map(|record| {
+            let unmatched: Vec<usize> = (0..record.cases.len())
+                .filter(|tag| {
+                    !SITES.iter().any(|site| {
+                        site.tags.contains(tag)
+                            && site.type_name().is_some_and(|name| record.is_named(name))
+                    })
+                })
+                .collect();
+            if unmatched.is_empty() {
+                None
+            } else {
+                Some((record, unmatched))
+            }
+        })
+        .collect()
+}
+
+/// All the sites which have never run, whose types are therefore unknown.
+pub fn unresolved() -> Vec<&'static Site> {
+    SITES
+        .iter()
+        .filter(|site| site.type_name().is_none())
+        .collect()
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "macros")]
+
+//! Deriving `Match` on types whose tokens come from other macros, which may give them call-site
+//! spans, emit items sharing the names of their fields, or shadow the names of the prelude.
+
+use strum::{EnumDiscriminants, EnumIter, IntoEnumIterator};
+use vesta::{case, Case, CaseExt, Match, MatchMeta};
+
+#[derive(EnumDiscriminants)]
+#[strum_discriminants(derive(Match, EnumIter))]
+#[allow(dead_code)]
+enum Request {
+    Get(String),
+    Put { key: String, value: Vec<u8> },
+    Delete(String),
+}
+
+#[test]
+fn strum_discriminants() {
+    let kind = RequestDiscriminants::from(&Request::Put {
+        key: "key".to_string(),
+        value: vec![1],
+    });
+    assert_eq!(case!(kind { 0 => "get", 1 => "put", 2 => "delete" }), "put");
+    assert_eq!(kind.case_name(), Some("Put"));
+    assert_eq!(
+        RequestDiscriminants::CASE_NAMES,
+        &["Get", "Put", "Delete"][..]
+    );
+
+    // The tags follow the order in which strum lists the discriminants
+    let tags: Vec<_> = RequestDiscriminants::iter()
+        .map(|kind| kind.tag())
+        .collect();
+    assert_eq!(tags, [Some(0), Some(1), Some(2)]);
+}
+
+macro_rules! generate {
+    ($name:ident { $field:ident: $ty:ty = $value:expr }) => {
+        #[allow(non_upper_case_globals)]
+        const $field: $ty = $value;
+
+        #[derive(Match, Debug, PartialEq)]
+        enum $name {
+            Empty,
+            Full { $field: $ty },
+        }
+    };
+}
+
+// A constant with the same name as a field, as binding generators emit
+generate!(Buffer { len: usize = 64 });
+
+#[test]
+fn constant_named_like_field() {
+    let buffer = Buffer::Full { len: 3 };
+    assert_eq!(case!(buffer { 0 => 0, 1(n) => n }), 3);
+    assert_eq!(Buffer::Full { len: 3 }.try_case::<1>().ok(), Some(3));
+    assert_eq!(<Buffer as Case<1>>::uncase(len), Buffer::Full { len: 64 });
+}
+
+macro_rules! generate_shadowed {
+    ($name:ident<$param:ident> { $($variant:ident($ty:ty)),* }) => {
+        #[derive(::vesta::Match)]
+        pub enum $name<$param> {
+            $($variant($ty),)*
+        }
+    };
+}
+
+// The generated code names everything it uses by its full path, so it isn't confused by items
+// which shadow the prelude, or by fields and parameters named like its own locals
+mod shadowed {
+    #![allow(dead_code, non_camel_case_types)]
+
+    pub struct Option;
+    pub struct Some;
+    pub struct None;
+    pub struct Result;
+    pub struct Box;
+    pub struct usize;
+    pub mod std {}
+    pub mod core {}
+
+    generate_shadowed!(Event<value> { this(value), tag(::std::primitive::u8), case(()) });
+}
+
+#[test]
+fn shadowed_prelude() {
+    let event = shadowed::Event::<u32>::this(7);
+    assert_eq!(case!(event { 0(n) => n, 1(n) => n.into(), 2 => 0 }), 7);
+    assert_eq!(shadowed::Event::<u32>::tag(1).case_name(), Some("tag"));
+    assert_eq!(
+        shadowed::Event::<u32>::case(()).try_case_ref::<2>(),
+        Some(&())
+    );
+}